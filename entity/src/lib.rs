@@ -2,6 +2,20 @@
 
 pub mod prelude;
 
+pub mod audit_events;
+pub mod consent_events;
+pub mod email_change_requests;
+pub mod erased_subscribers;
+pub mod idempotency;
+pub mod issue_delivery_queue;
+pub mod list_memberships;
+pub mod lists;
+pub mod newsletter_issue_lists;
+pub mod newsletter_issues;
+pub mod password_reset_tokens;
+pub mod sea_orm_active_enums;
 pub mod subscription_tokens;
 pub mod subscriptions;
+pub mod user_invitations;
+pub mod user_recovery_codes;
 pub mod users;
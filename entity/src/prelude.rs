@@ -1,5 +1,18 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.14
 
+pub use super::audit_events::Entity as AuditEvents;
+pub use super::consent_events::Entity as ConsentEvents;
+pub use super::email_change_requests::Entity as EmailChangeRequests;
+pub use super::erased_subscribers::Entity as ErasedSubscribers;
+pub use super::idempotency::Entity as Idempotency;
+pub use super::issue_delivery_queue::Entity as IssueDeliveryQueue;
+pub use super::list_memberships::Entity as ListMemberships;
+pub use super::lists::Entity as Lists;
+pub use super::newsletter_issue_lists::Entity as NewsletterIssueLists;
+pub use super::newsletter_issues::Entity as NewsletterIssues;
+pub use super::password_reset_tokens::Entity as PasswordResetTokens;
 pub use super::subscription_tokens::Entity as SubscriptionTokens;
 pub use super::subscriptions::Entity as Subscriptions;
+pub use super::user_invitations::Entity as UserInvitations;
+pub use super::user_recovery_codes::Entity as UserRecoveryCodes;
 pub use super::users::Entity as Users;
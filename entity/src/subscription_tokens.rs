@@ -9,10 +9,22 @@ pub struct Model {
     #[sea_orm(primary_key, auto_increment = false, column_type = "Text")]
     pub subscription_token: String,
     pub subscriber_id: Uuid,
+    pub created_at: TimeDateTimeWithTimeZone,
+    pub expires_at: TimeDateTimeWithTimeZone,
+    pub consumed_at: Option<TimeDateTimeWithTimeZone>,
+    pub list_id: Uuid,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(
+        belongs_to = "super::lists::Entity",
+        from = "Column::ListId",
+        to = "super::lists::Column::Id",
+        on_update = "Cascade",
+        on_delete = "Cascade"
+    )]
+    Lists,
     #[sea_orm(
         belongs_to = "super::subscriptions::Entity",
         from = "Column::SubscriberId",
@@ -23,6 +35,12 @@ pub enum Relation {
     Subscriptions,
 }
 
+impl Related<super::lists::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Lists.def()
+    }
+}
+
 impl Related<super::subscriptions::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::Subscriptions.def()
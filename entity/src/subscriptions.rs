@@ -1,5 +1,7 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.14
 
+use super::sea_orm_active_enums::DigestFrequency;
+use super::sea_orm_active_enums::SubscriptionStatus;
 use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
 
@@ -12,20 +14,53 @@ pub struct Model {
     pub email: String,
     pub name: String,
     pub subscribed_at: TimeDateTimeWithTimeZone,
-    #[sea_orm(column_type = "Text")]
-    pub status: String,
+    pub status: SubscriptionStatus,
+    pub digest_frequency: DigestFrequency,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
 pub enum Relation {
+    #[sea_orm(has_many = "super::consent_events::Entity")]
+    ConsentEvents,
+    #[sea_orm(has_many = "super::email_change_requests::Entity")]
+    EmailChangeRequests,
+    #[sea_orm(has_many = "super::list_memberships::Entity")]
+    ListMemberships,
     #[sea_orm(has_many = "super::subscription_tokens::Entity")]
     SubscriptionTokens,
 }
 
+impl Related<super::consent_events::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ConsentEvents.def()
+    }
+}
+
+impl Related<super::email_change_requests::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::EmailChangeRequests.def()
+    }
+}
+
+impl Related<super::list_memberships::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::ListMemberships.def()
+    }
+}
+
 impl Related<super::subscription_tokens::Entity> for Entity {
     fn to() -> RelationDef {
         Relation::SubscriptionTokens.def()
     }
 }
 
+impl Related<super::lists::Entity> for Entity {
+    fn to() -> RelationDef {
+        super::list_memberships::Relation::Lists.def()
+    }
+    fn via() -> Option<RelationDef> {
+        Some(super::list_memberships::Relation::Subscriptions.def().rev())
+    }
+}
+
 impl ActiveModelBehavior for ActiveModel {}
@@ -1,5 +1,6 @@
 //! `SeaORM` Entity. Generated by sea-orm-codegen 0.12.14
 
+use super::sea_orm_active_enums::UserRole;
 use sea_orm::entity::prelude::*;
 use serde::{Deserialize, Serialize};
 
@@ -11,9 +12,59 @@ pub struct Model {
     #[sea_orm(unique)]
     pub username: String,
     pub password_hash: String,
+    pub role: UserRole,
+    pub disabled_at: Option<TimeDateTimeWithTimeZone>,
+    #[sea_orm(column_type = "Text", nullable, unique)]
+    pub email: Option<String>,
+    pub sessions_revoked_at: Option<TimeDateTimeWithTimeZone>,
+    #[sea_orm(column_type = "Text", nullable)]
+    pub totp_secret: Option<String>,
+    pub totp_enabled_at: Option<TimeDateTimeWithTimeZone>,
+    pub totp_last_step: Option<i64>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
-pub enum Relation {}
+pub enum Relation {
+    #[sea_orm(has_many = "super::idempotency::Entity")]
+    Idempotency,
+    #[sea_orm(has_many = "super::newsletter_issues::Entity")]
+    NewsletterIssues,
+    #[sea_orm(has_many = "super::password_reset_tokens::Entity")]
+    PasswordResetTokens,
+    #[sea_orm(has_many = "super::user_invitations::Entity")]
+    UserInvitations,
+    #[sea_orm(has_many = "super::user_recovery_codes::Entity")]
+    UserRecoveryCodes,
+}
+
+impl Related<super::idempotency::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::Idempotency.def()
+    }
+}
+
+impl Related<super::newsletter_issues::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::NewsletterIssues.def()
+    }
+}
+
+impl Related<super::password_reset_tokens::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::PasswordResetTokens.def()
+    }
+}
+
+impl Related<super::user_invitations::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::UserInvitations.def()
+    }
+}
+
+impl Related<super::user_recovery_codes::Entity> for Entity {
+    fn to() -> RelationDef {
+        Relation::UserRecoveryCodes.def()
+    }
+}
 
 impl ActiveModelBehavior for ActiveModel {}
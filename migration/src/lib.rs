@@ -6,6 +6,26 @@ mod m20240302_102407_add_make_status_not_null_in_subscriptions;
 mod m20240302_105101_create_subscriptions_tokens_table;
 mod m20240307_150649_create_users_table;
 mod m20240309_120745_seed_user;
+mod m20240316_101522_create_idempotency_table;
+mod m20240323_141205_create_newsletter_issues_table;
+mod m20240323_141733_create_issue_delivery_queue_table;
+mod m20240330_093144_add_author_and_counts_to_newsletter_issues;
+mod m20240406_101214_add_scheduling_to_newsletter_issues;
+mod m20240413_094512_add_expiry_to_subscription_tokens;
+mod m20240420_083015_add_consumed_at_to_subscription_tokens;
+mod m20240427_090211_create_consent_events_table;
+mod m20240504_104733_create_erased_subscribers_table;
+mod m20240511_091530_make_subscription_status_an_enum;
+mod m20240518_093412_create_lists_tables;
+mod m20240525_101733_add_subscriber_preferences;
+mod m20240601_094217_add_roles_to_users;
+mod m20240608_103921_add_user_invitations;
+mod m20240615_091204_create_password_reset_tokens_table;
+mod m20240622_100512_add_two_factor_to_users;
+mod m20240629_093318_create_audit_events_table;
+mod m20240706_094518_hash_password_reset_tokens;
+mod m20240713_090341_normalize_subscriber_emails;
+mod m20240720_093027_make_issue_status_an_enum;
 
 pub struct Migrator;
 
@@ -19,6 +39,26 @@ impl MigratorTrait for Migrator {
             Box::new(m20240302_105101_create_subscriptions_tokens_table::Migration),
             Box::new(m20240307_150649_create_users_table::Migration),
             Box::new(m20240309_120745_seed_user::Migration),
+            Box::new(m20240316_101522_create_idempotency_table::Migration),
+            Box::new(m20240323_141205_create_newsletter_issues_table::Migration),
+            Box::new(m20240323_141733_create_issue_delivery_queue_table::Migration),
+            Box::new(m20240330_093144_add_author_and_counts_to_newsletter_issues::Migration),
+            Box::new(m20240406_101214_add_scheduling_to_newsletter_issues::Migration),
+            Box::new(m20240413_094512_add_expiry_to_subscription_tokens::Migration),
+            Box::new(m20240420_083015_add_consumed_at_to_subscription_tokens::Migration),
+            Box::new(m20240427_090211_create_consent_events_table::Migration),
+            Box::new(m20240504_104733_create_erased_subscribers_table::Migration),
+            Box::new(m20240511_091530_make_subscription_status_an_enum::Migration),
+            Box::new(m20240518_093412_create_lists_tables::Migration),
+            Box::new(m20240525_101733_add_subscriber_preferences::Migration),
+            Box::new(m20240601_094217_add_roles_to_users::Migration),
+            Box::new(m20240608_103921_add_user_invitations::Migration),
+            Box::new(m20240615_091204_create_password_reset_tokens_table::Migration),
+            Box::new(m20240622_100512_add_two_factor_to_users::Migration),
+            Box::new(m20240629_093318_create_audit_events_table::Migration),
+            Box::new(m20240706_094518_hash_password_reset_tokens::Migration),
+            Box::new(m20240713_090341_normalize_subscriber_emails::Migration),
+            Box::new(m20240720_093027_make_issue_status_an_enum::Migration),
         ]
     }
 }
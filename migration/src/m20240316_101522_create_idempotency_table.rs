@@ -0,0 +1,70 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Idempotency::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(Idempotency::UserId).uuid().not_null())
+                    .col(
+                        ColumnDef::new(Idempotency::IdempotencyKey)
+                            .text()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(Idempotency::ResponseStatusCode).small_integer())
+                    .col(ColumnDef::new(Idempotency::ResponseHeaders).json_binary())
+                    .col(ColumnDef::new(Idempotency::ResponseBody).binary())
+                    .col(
+                        ColumnDef::new(Idempotency::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .primary_key(
+                        Index::create()
+                            .col(Idempotency::UserId)
+                            .col(Idempotency::IdempotencyKey),
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .from_tbl(Idempotency::Table)
+                            .from_col(Idempotency::UserId)
+                            .to_tbl(Users::Table)
+                            .to_col(Users::UserId)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(Idempotency::Table).to_owned())
+            .await
+    }
+}
+
+#[allow(clippy::enum_variant_names)]
+#[derive(DeriveIden)]
+enum Idempotency {
+    Table,
+    UserId,
+    IdempotencyKey,
+    ResponseStatusCode,
+    ResponseHeaders,
+    ResponseBody,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    Table,
+    UserId,
+}
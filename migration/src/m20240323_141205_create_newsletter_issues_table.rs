@@ -0,0 +1,57 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(NewsletterIssues::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(NewsletterIssues::NewsletterIssueId)
+                            .uuid()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(NewsletterIssues::Title).text().not_null())
+                    .col(
+                        ColumnDef::new(NewsletterIssues::TextContent)
+                            .text()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(NewsletterIssues::HtmlContent)
+                            .text()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(NewsletterIssues::PublishedAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(NewsletterIssues::Table).to_owned())
+            .await
+    }
+}
+
+#[allow(clippy::enum_variant_names)]
+#[derive(DeriveIden)]
+enum NewsletterIssues {
+    Table,
+    NewsletterIssueId,
+    Title,
+    TextContent,
+    HtmlContent,
+    PublishedAt,
+}
@@ -0,0 +1,75 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(IssueDeliveryQueue::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(IssueDeliveryQueue::NewsletterIssueId)
+                            .uuid()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(IssueDeliveryQueue::SubscriberEmail)
+                            .text()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(IssueDeliveryQueue::NRetries)
+                            .small_integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .col(
+                        ColumnDef::new(IssueDeliveryQueue::ExecuteAfter)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .primary_key(
+                        Index::create()
+                            .col(IssueDeliveryQueue::NewsletterIssueId)
+                            .col(IssueDeliveryQueue::SubscriberEmail),
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .from_tbl(IssueDeliveryQueue::Table)
+                            .from_col(IssueDeliveryQueue::NewsletterIssueId)
+                            .to_tbl(NewsletterIssues::Table)
+                            .to_col(NewsletterIssues::NewsletterIssueId)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(IssueDeliveryQueue::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum IssueDeliveryQueue {
+    Table,
+    NewsletterIssueId,
+    SubscriberEmail,
+    NRetries,
+    ExecuteAfter,
+}
+
+#[derive(DeriveIden)]
+enum NewsletterIssues {
+    Table,
+    NewsletterIssueId,
+}
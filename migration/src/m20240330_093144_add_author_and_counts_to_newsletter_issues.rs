@@ -0,0 +1,76 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(NewsletterIssues::Table)
+                    .add_column(ColumnDef::new(NewsletterIssues::AuthorId).uuid())
+                    .add_column(
+                        ColumnDef::new(NewsletterIssues::NRecipients)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .add_column(
+                        ColumnDef::new(NewsletterIssues::NDelivered)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .add_column(
+                        ColumnDef::new(NewsletterIssues::NFailed)
+                            .integer()
+                            .not_null()
+                            .default(0),
+                    )
+                    .add_foreign_key(
+                        TableForeignKey::new()
+                            .name("fk_newsletter_issues_author_id")
+                            .from_tbl(NewsletterIssues::Table)
+                            .from_col(NewsletterIssues::AuthorId)
+                            .to_tbl(Users::Table)
+                            .to_col(Users::UserId)
+                            .on_delete(ForeignKeyAction::SetNull)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(NewsletterIssues::Table)
+                    .drop_foreign_key(Alias::new("fk_newsletter_issues_author_id"))
+                    .drop_column(NewsletterIssues::AuthorId)
+                    .drop_column(NewsletterIssues::NRecipients)
+                    .drop_column(NewsletterIssues::NDelivered)
+                    .drop_column(NewsletterIssues::NFailed)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum NewsletterIssues {
+    Table,
+    AuthorId,
+    NRecipients,
+    NDelivered,
+    NFailed,
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    Table,
+    UserId,
+}
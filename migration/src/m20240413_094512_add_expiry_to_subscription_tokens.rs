@@ -0,0 +1,58 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Tokens issued before this migration get a fresh lifetime.
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(SubscriptionTokens::Table)
+                    .add_column(
+                        ColumnDef::new(SubscriptionTokens::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::current_timestamp()),
+                    )
+                    .add_column(
+                        ColumnDef::new(SubscriptionTokens::ExpiresAt)
+                            .timestamp_with_time_zone()
+                            .not_null()
+                            .default(Expr::cust("now() + interval '1 day'")),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_subscription_tokens_expires_at")
+                    .table(SubscriptionTokens::Table)
+                    .col(SubscriptionTokens::ExpiresAt)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(SubscriptionTokens::Table)
+                    .drop_column(SubscriptionTokens::CreatedAt)
+                    .drop_column(SubscriptionTokens::ExpiresAt)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum SubscriptionTokens {
+    Table,
+    CreatedAt,
+    ExpiresAt,
+}
@@ -0,0 +1,80 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ConsentEvents::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ConsentEvents::Id)
+                            .uuid()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(ConsentEvents::SubscriberId)
+                            .uuid()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(ConsentEvents::Kind).text().not_null())
+                    .col(
+                        ColumnDef::new(ConsentEvents::OccurredAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(ConsentEvents::IpAddress).text())
+                    .col(ColumnDef::new(ConsentEvents::UserAgent).text())
+                    .col(ColumnDef::new(ConsentEvents::Source).text())
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .from_tbl(ConsentEvents::Table)
+                            .from_col(ConsentEvents::SubscriberId)
+                            .to_tbl(Subscriptions::Table)
+                            .to_col(Subscriptions::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_consent_events_subscriber_id")
+                    .table(ConsentEvents::Table)
+                    .col(ConsentEvents::SubscriberId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ConsentEvents::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum ConsentEvents {
+    Table,
+    Id,
+    SubscriberId,
+    Kind,
+    OccurredAt,
+    IpAddress,
+    UserAgent,
+    Source,
+}
+
+#[derive(DeriveIden)]
+enum Subscriptions {
+    Table,
+    Id,
+}
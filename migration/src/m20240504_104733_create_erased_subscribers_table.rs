@@ -0,0 +1,42 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(ErasedSubscribers::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(ErasedSubscribers::EmailHash)
+                            .text()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(ErasedSubscribers::ErasedAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(ErasedSubscribers::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum ErasedSubscribers {
+    Table,
+    EmailHash,
+    ErasedAt,
+}
@@ -0,0 +1,59 @@
+use sea_orm_migration::prelude::{extension::postgres::Type, *};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_type(
+                Type::create()
+                    .as_enum(SubscriptionStatus::Enum)
+                    .values([
+                        SubscriptionStatus::PendingConfirmation,
+                        SubscriptionStatus::Confirmed,
+                        SubscriptionStatus::Unsubscribed,
+                    ])
+                    .to_owned(),
+            )
+            .await?;
+
+        // Fails on any status outside of the enum rather than guessing what
+        // it was meant to be.
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "ALTER TABLE subscriptions \
+                ALTER COLUMN status TYPE subscription_status \
+                USING status::subscription_status",
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "ALTER TABLE subscriptions \
+                ALTER COLUMN status TYPE text \
+                USING status::text",
+            )
+            .await?;
+
+        manager
+            .drop_type(Type::drop().name(SubscriptionStatus::Enum).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum SubscriptionStatus {
+    #[sea_orm(iden = "subscription_status")]
+    Enum,
+    PendingConfirmation,
+    Confirmed,
+    Unsubscribed,
+}
@@ -0,0 +1,252 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_table(
+                Table::create()
+                    .table(Lists::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(Lists::Id).uuid().not_null().primary_key())
+                    .col(ColumnDef::new(Lists::Slug).text().not_null().unique_key())
+                    .col(ColumnDef::new(Lists::Name).text().not_null())
+                    .col(
+                        ColumnDef::new(Lists::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(ListMemberships::Table)
+                    .if_not_exists()
+                    .col(ColumnDef::new(ListMemberships::ListId).uuid().not_null())
+                    .col(
+                        ColumnDef::new(ListMemberships::SubscriberId)
+                            .uuid()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ListMemberships::Status)
+                            .custom(SubscriptionStatus::Enum)
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(ListMemberships::JoinedAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .primary_key(
+                        Index::create()
+                            .col(ListMemberships::ListId)
+                            .col(ListMemberships::SubscriberId),
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .from_tbl(ListMemberships::Table)
+                            .from_col(ListMemberships::ListId)
+                            .to_tbl(Lists::Table)
+                            .to_col(Lists::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .from_tbl(ListMemberships::Table)
+                            .from_col(ListMemberships::SubscriberId)
+                            .to_tbl(Subscriptions::Table)
+                            .to_col(Subscriptions::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_list_memberships_subscriber_id")
+                    .table(ListMemberships::Table)
+                    .col(ListMemberships::SubscriberId)
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(NewsletterIssueLists::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(NewsletterIssueLists::NewsletterIssueId)
+                            .uuid()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(NewsletterIssueLists::ListId)
+                            .uuid()
+                            .not_null(),
+                    )
+                    .primary_key(
+                        Index::create()
+                            .col(NewsletterIssueLists::NewsletterIssueId)
+                            .col(NewsletterIssueLists::ListId),
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .from_tbl(NewsletterIssueLists::Table)
+                            .from_col(NewsletterIssueLists::NewsletterIssueId)
+                            .to_tbl(NewsletterIssues::Table)
+                            .to_col(NewsletterIssues::NewsletterIssueId)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .from_tbl(NewsletterIssueLists::Table)
+                            .from_col(NewsletterIssueLists::ListId)
+                            .to_tbl(Lists::Table)
+                            .to_col(Lists::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(SubscriptionTokens::Table)
+                    .add_column(ColumnDef::new(SubscriptionTokens::ListId).uuid())
+                    .add_foreign_key(
+                        TableForeignKey::new()
+                            .name("fk_subscription_tokens_list_id")
+                            .from_tbl(SubscriptionTokens::Table)
+                            .from_col(SubscriptionTokens::ListId)
+                            .to_tbl(Lists::Table)
+                            .to_col(Lists::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        // Everything so far has been sent to a single list, it becomes the
+        // default one.
+        let connection = manager.get_connection();
+        connection
+            .execute_unprepared(
+                "INSERT INTO lists (id, slug, name, created_at) \
+                VALUES (gen_random_uuid(), 'newsletter', 'Newsletter', now())",
+            )
+            .await?;
+        connection
+            .execute_unprepared(
+                "INSERT INTO list_memberships (list_id, subscriber_id, status, joined_at) \
+                SELECT lists.id, subscriptions.id, subscriptions.status, subscriptions.subscribed_at \
+                FROM subscriptions, lists WHERE lists.slug = 'newsletter'",
+            )
+            .await?;
+        connection
+            .execute_unprepared(
+                "INSERT INTO newsletter_issue_lists (newsletter_issue_id, list_id) \
+                SELECT newsletter_issues.newsletter_issue_id, lists.id \
+                FROM newsletter_issues, lists WHERE lists.slug = 'newsletter'",
+            )
+            .await?;
+        connection
+            .execute_unprepared(
+                "UPDATE subscription_tokens SET list_id = lists.id \
+                FROM lists WHERE lists.slug = 'newsletter'",
+            )
+            .await?;
+
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(SubscriptionTokens::Table)
+                    .modify_column(ColumnDef::new(SubscriptionTokens::ListId).not_null())
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(SubscriptionTokens::Table)
+                    .drop_column(SubscriptionTokens::ListId)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_table(Table::drop().table(NewsletterIssueLists::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(ListMemberships::Table).to_owned())
+            .await?;
+        manager
+            .drop_table(Table::drop().table(Lists::Table).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Lists {
+    Table,
+    Id,
+    Slug,
+    Name,
+    CreatedAt,
+}
+
+#[derive(DeriveIden)]
+enum ListMemberships {
+    Table,
+    ListId,
+    SubscriberId,
+    Status,
+    JoinedAt,
+}
+
+#[derive(DeriveIden)]
+enum NewsletterIssueLists {
+    Table,
+    NewsletterIssueId,
+    ListId,
+}
+
+#[derive(DeriveIden)]
+enum Subscriptions {
+    Table,
+    Id,
+}
+
+#[derive(DeriveIden)]
+enum SubscriptionTokens {
+    Table,
+    ListId,
+}
+
+#[derive(DeriveIden)]
+enum NewsletterIssues {
+    Table,
+    NewsletterIssueId,
+}
+
+#[derive(DeriveIden)]
+enum SubscriptionStatus {
+    #[sea_orm(iden = "subscription_status")]
+    Enum,
+}
@@ -0,0 +1,133 @@
+use sea_orm_migration::prelude::{extension::postgres::Type, *};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_type(
+                Type::create()
+                    .as_enum(DigestFrequency::Enum)
+                    .values([
+                        DigestFrequency::Immediately,
+                        DigestFrequency::Daily,
+                        DigestFrequency::Weekly,
+                    ])
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Subscriptions::Table)
+                    .add_column(
+                        ColumnDef::new(Subscriptions::DigestFrequency)
+                            .custom(DigestFrequency::Enum)
+                            .not_null()
+                            .default(Expr::cust("'immediately'")),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+
+        manager
+            .create_table(
+                Table::create()
+                    .table(EmailChangeRequests::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(EmailChangeRequests::Token)
+                            .text()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(
+                        ColumnDef::new(EmailChangeRequests::SubscriberId)
+                            .uuid()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(EmailChangeRequests::NewEmail)
+                            .text()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(EmailChangeRequests::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(EmailChangeRequests::ExpiresAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(EmailChangeRequests::ConsumedAt).timestamp_with_time_zone())
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .from_tbl(EmailChangeRequests::Table)
+                            .from_col(EmailChangeRequests::SubscriberId)
+                            .to_tbl(Subscriptions::Table)
+                            .to_col(Subscriptions::Id)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_index(
+                Index::create()
+                    .name("idx_email_change_requests_subscriber_id")
+                    .table(EmailChangeRequests::Table)
+                    .col(EmailChangeRequests::SubscriberId)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(EmailChangeRequests::Table).to_owned())
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Subscriptions::Table)
+                    .drop_column(Subscriptions::DigestFrequency)
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .drop_type(Type::drop().name(DigestFrequency::Enum).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum Subscriptions {
+    Table,
+    Id,
+    DigestFrequency,
+}
+
+#[derive(DeriveIden)]
+enum EmailChangeRequests {
+    Table,
+    Token,
+    SubscriberId,
+    NewEmail,
+    CreatedAt,
+    ExpiresAt,
+    ConsumedAt,
+}
+
+#[derive(DeriveIden)]
+enum DigestFrequency {
+    #[sea_orm(iden = "digest_frequency")]
+    Enum,
+    Immediately,
+    Daily,
+    Weekly,
+}
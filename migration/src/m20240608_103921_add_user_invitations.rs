@@ -0,0 +1,100 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // Users who joined through an invitation keep the address it was
+        // sent to.
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Users::Table)
+                    .add_column(ColumnDef::new(Users::Email).text().unique_key())
+                    .to_owned(),
+            )
+            .await?;
+        manager
+            .create_table(
+                Table::create()
+                    .table(UserInvitations::Table)
+                    .if_not_exists()
+                    .col(
+                        ColumnDef::new(UserInvitations::Token)
+                            .text()
+                            .not_null()
+                            .primary_key(),
+                    )
+                    .col(ColumnDef::new(UserInvitations::Email).text().not_null())
+                    .col(
+                        ColumnDef::new(UserInvitations::Role)
+                            .custom(UserRole::Enum)
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(UserInvitations::InvitedBy).uuid().not_null())
+                    .col(
+                        ColumnDef::new(UserInvitations::CreatedAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .col(
+                        ColumnDef::new(UserInvitations::ExpiresAt)
+                            .timestamp_with_time_zone()
+                            .not_null(),
+                    )
+                    .col(ColumnDef::new(UserInvitations::AcceptedAt).timestamp_with_time_zone())
+                    .foreign_key(
+                        ForeignKeyCreateStatement::new()
+                            .from_tbl(UserInvitations::Table)
+                            .from_col(UserInvitations::InvitedBy)
+                            .to_tbl(Users::Table)
+                            .to_col(Users::UserId)
+                            .on_delete(ForeignKeyAction::Cascade)
+                            .on_update(ForeignKeyAction::Cascade),
+                    )
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .drop_table(Table::drop().table(UserInvitations::Table).to_owned())
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(Users::Table)
+                    .drop_column(Users::Email)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum UserInvitations {
+    Table,
+    Token,
+    Email,
+    Role,
+    InvitedBy,
+    CreatedAt,
+    ExpiresAt,
+    AcceptedAt,
+}
+
+#[derive(DeriveIden)]
+enum Users {
+    Table,
+    UserId,
+    Email,
+}
+
+#[derive(DeriveIden)]
+enum UserRole {
+    #[sea_orm(iden = "user_role")]
+    Enum,
+}
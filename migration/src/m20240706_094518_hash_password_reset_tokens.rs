@@ -0,0 +1,46 @@
+use sea_orm_migration::prelude::*;
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        // The tokens stored so far cannot be hashed into something the links
+        // in the sent emails still match, they are short-lived anyway.
+        manager
+            .get_connection()
+            .execute_unprepared("DELETE FROM password_reset_tokens")
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PasswordResetTokens::Table)
+                    .rename_column(PasswordResetTokens::Token, PasswordResetTokens::TokenHash)
+                    .to_owned(),
+            )
+            .await
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .get_connection()
+            .execute_unprepared("DELETE FROM password_reset_tokens")
+            .await?;
+        manager
+            .alter_table(
+                Table::alter()
+                    .table(PasswordResetTokens::Table)
+                    .rename_column(PasswordResetTokens::TokenHash, PasswordResetTokens::Token)
+                    .to_owned(),
+            )
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum PasswordResetTokens {
+    Table,
+    Token,
+    TokenHash,
+}
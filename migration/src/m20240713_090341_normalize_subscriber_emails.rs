@@ -0,0 +1,63 @@
+use sea_orm_migration::{prelude::*, sea_orm::Statement};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        // Subscribers whose addresses only differ in case have their own
+        // consent trail and lists each, which one to keep is for a human to
+        // decide.
+        let duplicate = db
+            .query_one(Statement::from_string(
+                manager.get_database_backend(),
+                "SELECT lower(trim(email)) AS email FROM subscriptions \
+                GROUP BY lower(trim(email)) HAVING count(*) > 1 LIMIT 1",
+            ))
+            .await?;
+        if let Some(duplicate) = duplicate {
+            let email: String = duplicate.try_get("", "email")?;
+            return Err(DbErr::Migration(format!(
+                "Several subscribers have the address {}, merge them before migrating.",
+                email
+            )));
+        }
+
+        db.execute_unprepared(
+            "UPDATE subscriptions SET email = lower(trim(email)) \
+            WHERE email <> lower(trim(email))",
+        )
+        .await?;
+        db.execute_unprepared(
+            "UPDATE issue_delivery_queue SET subscriber_email = lower(trim(subscriber_email)) \
+            WHERE subscriber_email <> lower(trim(subscriber_email))",
+        )
+        .await?;
+        db.execute_unprepared(
+            "UPDATE email_change_requests SET new_email = lower(trim(new_email)) \
+            WHERE new_email <> lower(trim(new_email))",
+        )
+        .await?;
+
+        db.execute_unprepared("ALTER TABLE subscriptions DROP CONSTRAINT subscriptions_email_key")
+            .await?;
+        db.execute_unprepared(
+            "CREATE UNIQUE INDEX subscriptions_email_key ON subscriptions (lower(email))",
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        let db = manager.get_connection();
+        db.execute_unprepared("DROP INDEX subscriptions_email_key")
+            .await?;
+        db.execute_unprepared(
+            "ALTER TABLE subscriptions ADD CONSTRAINT subscriptions_email_key UNIQUE (email)",
+        )
+        .await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,63 @@
+use sea_orm_migration::prelude::{extension::postgres::Type, *};
+
+#[derive(DeriveMigrationName)]
+pub struct Migration;
+
+#[async_trait::async_trait]
+impl MigrationTrait for Migration {
+    async fn up(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .create_type(
+                Type::create()
+                    .as_enum(IssueStatus::Enum)
+                    .values([
+                        IssueStatus::Scheduled,
+                        IssueStatus::Published,
+                        IssueStatus::Cancelled,
+                    ])
+                    .to_owned(),
+            )
+            .await?;
+
+        // Fails on any status outside of the enum rather than guessing what
+        // it was meant to be.
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "ALTER TABLE newsletter_issues \
+                ALTER COLUMN status DROP DEFAULT, \
+                ALTER COLUMN status TYPE issue_status \
+                USING status::issue_status, \
+                ALTER COLUMN status SET DEFAULT 'published'",
+            )
+            .await?;
+
+        Ok(())
+    }
+
+    async fn down(&self, manager: &SchemaManager) -> Result<(), DbErr> {
+        manager
+            .get_connection()
+            .execute_unprepared(
+                "ALTER TABLE newsletter_issues \
+                ALTER COLUMN status DROP DEFAULT, \
+                ALTER COLUMN status TYPE text \
+                USING status::text, \
+                ALTER COLUMN status SET DEFAULT 'published'",
+            )
+            .await?;
+
+        manager
+            .drop_type(Type::drop().name(IssueStatus::Enum).to_owned())
+            .await
+    }
+}
+
+#[derive(DeriveIden)]
+enum IssueStatus {
+    #[sea_orm(iden = "issue_status")]
+    Enum,
+    Scheduled,
+    Published,
+    Cancelled,
+}
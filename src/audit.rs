@@ -0,0 +1,44 @@
+use entity::audit_events;
+use sea_orm::{ActiveModelTrait, ActiveValue::Set, ConnectionTrait, DbErr};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::consent::RequestOrigin;
+
+/// Security relevant things that happened on the admin side.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AuditEvent {
+    /// Logins were blocked after too many failed attempts.
+    LoginLockout,
+}
+
+impl AuditEvent {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::LoginLockout => "login_lockout",
+        }
+    }
+}
+
+/// Appends an event to the audit log.
+#[tracing::instrument(skip(db, origin))]
+pub async fn record_audit_event(
+    db: &impl ConnectionTrait,
+    event: AuditEvent,
+    username: Option<&str>,
+    origin: &RequestOrigin,
+    details: Option<&str>,
+) -> Result<(), DbErr> {
+    audit_events::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        kind: Set(event.as_str().to_owned()),
+        occurred_at: Set(OffsetDateTime::now_utc()),
+        username: Set(username.map(ToOwned::to_owned)),
+        ip_address: Set(origin.ip_address.clone()),
+        user_agent: Set(origin.user_agent.clone()),
+        details: Set(details.map(ToOwned::to_owned)),
+    }
+    .insert(db)
+    .await?;
+    Ok(())
+}
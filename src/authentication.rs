@@ -1,5 +1,11 @@
 mod middleware;
 mod password;
+mod totp;
+mod two_factor;
 
-pub use middleware::{reject_anonymous_users, UserId};
-pub use password::{change_password, validate_credentials, AuthError, Credentials};
+pub use middleware::{is_allowed, reject_anonymous_users, require_editor, require_owner, UserId};
+pub use password::{
+    change_password, compute_password_hash, validate_credentials, AuthError, Credentials,
+};
+pub use totp::{generate_totp_secret, otpauth_uri, totp_code, totp_step};
+pub use two_factor::{disable_two_factor, enable_two_factor, use_totp_code, verify_second_factor};
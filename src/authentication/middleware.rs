@@ -1,13 +1,20 @@
 use std::ops::Deref;
 
 use axum::{
-    extract::Request,
+    extract::{Request, State},
+    http::StatusCode,
     middleware::Next,
     response::{IntoResponse, Redirect, Response},
+    Extension,
 };
+use entity::{
+    sea_orm_active_enums::UserRole,
+    users::{self, Entity as Users},
+};
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
 use uuid::Uuid;
 
-use crate::{session_state::TypedSession, utils::e500};
+use crate::{session_state::TypedSession, startup::AppState, utils::e500};
 
 #[derive(Copy, Clone, Debug)]
 pub struct UserId(Uuid);
@@ -24,23 +31,112 @@ impl Deref for UserId {
     }
 }
 
+/// Lets logged-in users through, together with their [`UserId`] and their
+/// [`UserRole`]. The user is looked up on every request, so that disabling
+/// them, changing their role or revoking their sessions takes effect right
+/// away.
 pub async fn reject_anonymous_users(
+    State(state): State<AppState>,
     session: TypedSession,
     mut request: Request,
     next: Next,
 ) -> Result<Response, Response> {
-    match session.get_user_id().await.map_err(e500)? {
-        Some(user_id) => {
-            request.extensions_mut().insert(UserId(user_id));
-            let response = next.run(request).await;
-            Ok(response)
+    let Some(user_id) = session.get_user_id().await.map_err(e500)? else {
+        let response = Redirect::to("/login");
+        // TODO how to handle internal errors in axum?
+        let e = anyhow::anyhow!("The user has not logged in");
+        tracing::error!(error = %e, "The user has not logged in");
+        return Err(response.into_response());
+    };
+    let user = Users::find_by_id(user_id)
+        .filter(users::Column::DisabledAt.is_null())
+        .one(&state.connection)
+        .await
+        .map_err(e500)?;
+    let Some(user) = user else {
+        tracing::warn!(%user_id, "The user has been disabled or removed");
+        session.log_out().await.map_err(e500)?;
+        return Err(Redirect::to("/login").into_response());
+    };
+    if let Some(revoked_at) = user.sessions_revoked_at {
+        let logged_in_at = session.get_logged_in_at().await.map_err(e500)?;
+        if logged_in_at.is_none_or(|logged_in_at| logged_in_at < revoked_at) {
+            tracing::warn!(%user_id, "The session has been revoked");
+            session.log_out().await.map_err(e500)?;
+            return Err(Redirect::to("/login").into_response());
         }
-        None => {
-            let response = Redirect::to("/login");
-            // TODO how to handle internal errors in axum?
-            let e = anyhow::anyhow!("The user has not logged in");
-            tracing::error!(error = %e, "The user has not logged in");
-            Err(response.into_response())
+    }
+    request.extensions_mut().insert(UserId(user_id));
+    request.extensions_mut().insert(user.role);
+    Ok(next.run(request).await)
+}
+
+/// Has to run after [`reject_anonymous_users`].
+pub async fn require_editor(
+    Extension(role): Extension<UserRole>,
+    request: Request,
+    next: Next,
+) -> Response {
+    require_role(UserRole::Editor, role, request, next).await
+}
+
+/// Has to run after [`reject_anonymous_users`].
+pub async fn require_owner(
+    Extension(role): Extension<UserRole>,
+    request: Request,
+    next: Next,
+) -> Response {
+    require_role(UserRole::Owner, role, request, next).await
+}
+
+async fn require_role(
+    required: UserRole,
+    role: UserRole,
+    request: Request,
+    next: Next,
+) -> Response {
+    if is_allowed(role, required) {
+        next.run(request).await
+    } else {
+        tracing::warn!(?role, ?required, "The user lacks the permission");
+        (StatusCode::FORBIDDEN, "You are not allowed to do this.").into_response()
+    }
+}
+
+/// Owners can do everything editors can, who can do everything viewers can.
+pub fn is_allowed(role: UserRole, required: UserRole) -> bool {
+    fn rank(role: UserRole) -> u8 {
+        match role {
+            UserRole::Viewer => 0,
+            UserRole::Editor => 1,
+            UserRole::Owner => 2,
         }
     }
+    rank(role) >= rank(required)
+}
+
+#[cfg(test)]
+mod tests {
+    use entity::sea_orm_active_enums::UserRole;
+
+    use super::is_allowed;
+
+    #[test]
+    fn owners_can_do_everything() {
+        assert!(is_allowed(UserRole::Owner, UserRole::Owner));
+        assert!(is_allowed(UserRole::Owner, UserRole::Editor));
+        assert!(is_allowed(UserRole::Owner, UserRole::Viewer));
+    }
+
+    #[test]
+    fn editors_cannot_manage_users() {
+        assert!(!is_allowed(UserRole::Editor, UserRole::Owner));
+        assert!(is_allowed(UserRole::Editor, UserRole::Editor));
+    }
+
+    #[test]
+    fn viewers_can_only_look() {
+        assert!(!is_allowed(UserRole::Viewer, UserRole::Editor));
+        assert!(is_allowed(UserRole::Viewer, UserRole::Viewer));
+    }
 }
@@ -6,7 +6,8 @@ use argon2::{
 use entity::users::{self, Entity as Users};
 use migration::Expr;
 use sea_orm::{
-    ColumnTrait, DatabaseConnection, DerivePartialModel, EntityTrait, FromQueryResult, QueryFilter,
+    ColumnTrait, ConnectionTrait, DatabaseConnection, DerivePartialModel, EntityTrait,
+    FromQueryResult, QueryFilter,
 };
 use secrecy::{ExposeSecret, Secret};
 use uuid::Uuid;
@@ -69,8 +70,11 @@ async fn get_stored_credentials(
         password_hash: String,
     }
 
+    // Disabled users cannot log in, they are told the same as everyone with
+    // a wrong password.
     let row = Users::find()
-        .filter(users::Column::Username.contains(username))
+        .filter(users::Column::Username.eq(username))
+        .filter(users::Column::DisabledAt.is_null())
         .into_partial_model::<Row>()
         .one(conn)
         .await
@@ -102,7 +106,7 @@ fn verify_password_hash(
 pub async fn change_password(
     user_id: uuid::Uuid,
     password: Secret<String>,
-    conn: &DatabaseConnection,
+    conn: &impl ConnectionTrait,
 ) -> Result<(), anyhow::Error> {
     let password_hash = spawn_blocking_with_tracing(move || compute_password_hash(password))
         .await?
@@ -120,7 +124,7 @@ pub async fn change_password(
     Ok(())
 }
 
-fn compute_password_hash(password: Secret<String>) -> Result<Secret<String>, anyhow::Error> {
+pub fn compute_password_hash(password: Secret<String>) -> Result<Secret<String>, anyhow::Error> {
     let salt = SaltString::generate(&mut rand::thread_rng());
     let password_hash = Argon2::new(
         Algorithm::Argon2id,
@@ -0,0 +1,196 @@
+//! Time-based one-time passwords as described in RFC 6238, the flavour every
+//! authenticator app understands: HMAC-SHA1, six digits, 30 second steps.
+
+use base32::Alphabet;
+use hmac::{Hmac, Mac};
+use rand::{distributions::Alphanumeric, thread_rng, Rng, RngCore};
+use secrecy::{ExposeSecret, Secret};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use time::OffsetDateTime;
+
+const ISSUER: &str = "zero2prod";
+const STEP_SECONDS: i64 = 30;
+const DIGITS: u32 = 6;
+/// Codes from the step before and after the current one are accepted too,
+/// to make up for clocks that are slightly off.
+const ALLOWED_DRIFT: i64 = 1;
+const SECRET_BYTES: usize = 20;
+const BASE32: Alphabet = Alphabet::Rfc4648 { padding: false };
+const RECOVERY_CODES: usize = 10;
+
+/// A random secret, base32 encoded as authenticator apps expect it.
+pub fn generate_totp_secret() -> Secret<String> {
+    let mut bytes = [0u8; SECRET_BYTES];
+    thread_rng().fill_bytes(&mut bytes);
+    Secret::new(base32::encode(BASE32, &bytes))
+}
+
+/// The URI authenticator apps enrol with, usually scanned as a QR code.
+pub fn otpauth_uri(secret: &Secret<String>, username: &str) -> String {
+    format!(
+        "otpauth://totp/{issuer}:{username}?secret={secret}&issuer={issuer}\
+        &algorithm=SHA1&digits={DIGITS}&period={STEP_SECONDS}",
+        issuer = urlencoding::encode(ISSUER),
+        username = urlencoding::encode(username),
+        secret = secret.expose_secret(),
+    )
+}
+
+/// The time step a point in time falls into.
+pub fn totp_step(now: OffsetDateTime) -> i64 {
+    now.unix_timestamp().div_euclid(STEP_SECONDS)
+}
+
+/// The code for a time step, `None` if the secret is not valid base32.
+pub fn totp_code(secret: &Secret<String>, step: i64) -> Option<String> {
+    let key = base32::decode(BASE32, secret.expose_secret())?;
+    let mut mac = Hmac::<Sha1>::new_from_slice(&key).ok()?;
+    mac.update(&step.to_be_bytes());
+    let hash = mac.finalize().into_bytes();
+    // Dynamic truncation, RFC 4226 section 5.3.
+    let offset = (hash[hash.len() - 1] & 0x0f) as usize;
+    let binary = u32::from_be_bytes([
+        hash[offset] & 0x7f,
+        hash[offset + 1],
+        hash[offset + 2],
+        hash[offset + 3],
+    ]);
+    Some(format!(
+        "{:0width$}",
+        binary % 10u32.pow(DIGITS),
+        width = DIGITS as usize
+    ))
+}
+
+/// Returns the time step the code belongs to if it is valid around `now`.
+/// Callers should reject steps that were already used, so that a code cannot
+/// be replayed.
+pub fn verify_totp(secret: &Secret<String>, code: &str, now: OffsetDateTime) -> Option<i64> {
+    let code = code.trim();
+    if code.len() != DIGITS as usize || !code.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let current = totp_step(now);
+    (current - ALLOWED_DRIFT..=current + ALLOWED_DRIFT)
+        .find(|step| totp_code(secret, *step).is_some_and(|expected| expected == code))
+}
+
+/// Single-use codes for when the authenticator is lost, e.g. `k3x9-p2mq`.
+pub fn generate_recovery_codes() -> Vec<Secret<String>> {
+    let mut rng = thread_rng();
+    (0..RECOVERY_CODES)
+        .map(|_| {
+            let chars: String = std::iter::repeat_with(|| rng.sample(Alphanumeric))
+                .map(|c| char::from(c).to_ascii_lowercase())
+                .take(8)
+                .collect();
+            Secret::new(format!("{}-{}", &chars[..4], &chars[4..]))
+        })
+        .collect()
+}
+
+/// Recovery codes are random enough that a plain hash is all they need.
+pub fn hash_recovery_code(code: &str) -> String {
+    let normalized = code.trim().to_ascii_lowercase();
+    hex::encode(Sha256::digest(normalized.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use secrecy::Secret;
+    use time::OffsetDateTime;
+
+    use super::{
+        generate_recovery_codes, generate_totp_secret, hash_recovery_code, otpauth_uri, totp_code,
+        totp_step, verify_totp, BASE32,
+    };
+
+    /// The SHA1 seed of the RFC 6238 test vectors.
+    fn rfc_secret() -> Secret<String> {
+        Secret::new(base32::encode(BASE32, b"12345678901234567890"))
+    }
+
+    fn at(timestamp: i64) -> OffsetDateTime {
+        OffsetDateTime::from_unix_timestamp(timestamp).unwrap()
+    }
+
+    #[test]
+    fn codes_match_the_rfc_test_vectors() {
+        // The last six digits of the eight digit codes in appendix B.
+        let vectors = [
+            (59, "287082"),
+            (1111111109, "081804"),
+            (1111111111, "050471"),
+            (1234567890, "005924"),
+            (2000000000, "279037"),
+        ];
+        for (timestamp, expected) in vectors {
+            assert_eq!(
+                totp_code(&rfc_secret(), totp_step(at(timestamp))).unwrap(),
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn codes_of_the_neighbouring_steps_are_accepted() {
+        let secret = rfc_secret();
+        let now = at(1111111111);
+        let step = totp_step(now);
+
+        assert_eq!(
+            verify_totp(&secret, &totp_code(&secret, step - 1).unwrap(), now),
+            Some(step - 1)
+        );
+        assert_eq!(
+            verify_totp(&secret, &totp_code(&secret, step + 1).unwrap(), now),
+            Some(step + 1)
+        );
+        assert_eq!(
+            verify_totp(&secret, &totp_code(&secret, step - 2).unwrap(), now),
+            None
+        );
+    }
+
+    #[test]
+    fn malformed_codes_are_rejected() {
+        let now = at(59);
+        assert_eq!(verify_totp(&rfc_secret(), "28708", now), None);
+        assert_eq!(verify_totp(&rfc_secret(), "2870822", now), None);
+        assert_eq!(verify_totp(&rfc_secret(), "28708a", now), None);
+    }
+
+    #[test]
+    fn a_generated_secret_can_be_used() {
+        let secret = generate_totp_secret();
+        let now = OffsetDateTime::now_utc();
+        let code = totp_code(&secret, totp_step(now)).unwrap();
+        assert_eq!(verify_totp(&secret, &code, now), Some(totp_step(now)));
+    }
+
+    #[test]
+    fn the_uri_names_the_user_and_carries_the_secret() {
+        let secret = Secret::new("JBSWY3DPEHPK3PXP".to_owned());
+        let uri = otpauth_uri(&secret, "ursula le guin");
+        assert_eq!(
+            uri,
+            "otpauth://totp/zero2prod:ursula%20le%20guin?secret=JBSWY3DPEHPK3PXP\
+            &issuer=zero2prod&algorithm=SHA1&digits=6&period=30"
+        );
+    }
+
+    #[test]
+    fn recovery_codes_are_distinct_and_hashed_case_insensitively() {
+        use secrecy::ExposeSecret;
+        let codes = generate_recovery_codes();
+        let mut exposed: Vec<_> = codes.iter().map(|c| c.expose_secret().clone()).collect();
+        exposed.sort();
+        exposed.dedup();
+        assert_eq!(exposed.len(), 10);
+        assert_eq!(
+            hash_recovery_code("K3X9-P2MQ "),
+            hash_recovery_code("k3x9-p2mq")
+        );
+    }
+}
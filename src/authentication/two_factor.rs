@@ -0,0 +1,150 @@
+use anyhow::Context;
+use entity::{
+    user_recovery_codes::{self, Entity as UserRecoveryCodes},
+    users::{self, Entity as Users},
+};
+use sea_orm::{
+    sea_query::Expr, ActiveValue::Set, ColumnTrait, Condition, DatabaseConnection, EntityTrait,
+    QueryFilter, TransactionTrait,
+};
+use secrecy::{ExposeSecret, Secret};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use super::totp::{generate_recovery_codes, hash_recovery_code, verify_totp};
+
+/// Accepts a code from the authenticator of the user once: the time step of
+/// the code is recorded, and codes of that step or an earlier one are
+/// rejected from then on.
+#[tracing::instrument(name = "Use a TOTP code", skip(user, code, conn), fields(user_id = %user.user_id))]
+pub async fn use_totp_code(
+    user: &users::Model,
+    code: &str,
+    conn: &DatabaseConnection,
+) -> Result<bool, anyhow::Error> {
+    let Some(secret) = user.totp_secret.clone().map(Secret::new) else {
+        return Ok(false);
+    };
+    let Some(step) = verify_totp(&secret, code, OffsetDateTime::now_utc()) else {
+        return Ok(false);
+    };
+    let result = Users::update_many()
+        .col_expr(users::Column::TotpLastStep, Expr::value(step))
+        .filter(users::Column::UserId.eq(user.user_id))
+        .filter(
+            Condition::any()
+                .add(users::Column::TotpLastStep.is_null())
+                .add(users::Column::TotpLastStep.lt(step)),
+        )
+        .exec(conn)
+        .await
+        .context("Failed to record the TOTP step.")?;
+    Ok(result.rows_affected == 1)
+}
+
+#[tracing::instrument(name = "Use a recovery code", skip(code, conn))]
+pub async fn use_recovery_code(
+    user_id: Uuid,
+    code: &str,
+    conn: &DatabaseConnection,
+) -> Result<bool, anyhow::Error> {
+    let result = UserRecoveryCodes::update_many()
+        .col_expr(
+            user_recovery_codes::Column::UsedAt,
+            Expr::value(OffsetDateTime::now_utc()),
+        )
+        .filter(user_recovery_codes::Column::CodeHash.eq(hash_recovery_code(code)))
+        .filter(user_recovery_codes::Column::UserId.eq(user_id))
+        .filter(user_recovery_codes::Column::UsedAt.is_null())
+        .exec(conn)
+        .await
+        .context("Failed to use the recovery code.")?;
+    Ok(result.rows_affected == 1)
+}
+
+/// Accepts either a code from the authenticator or one of the recovery codes.
+pub async fn verify_second_factor(
+    user: &users::Model,
+    code: &str,
+    conn: &DatabaseConnection,
+) -> Result<bool, anyhow::Error> {
+    Ok(use_totp_code(user, code, conn).await?
+        || use_recovery_code(user.user_id, code, conn).await?)
+}
+
+/// Turns two-factor authentication on for a user whose secret has been
+/// confirmed, and returns a fresh set of recovery codes.
+#[tracing::instrument(name = "Enable two-factor authentication", skip(conn))]
+pub async fn enable_two_factor(
+    user_id: Uuid,
+    conn: &DatabaseConnection,
+) -> Result<Vec<Secret<String>>, anyhow::Error> {
+    let now = OffsetDateTime::now_utc();
+    let codes = generate_recovery_codes();
+    let txn = conn
+        .begin()
+        .await
+        .context("Failed to begin a transaction.")?;
+    Users::update_many()
+        .col_expr(users::Column::TotpEnabledAt, Expr::value(now))
+        .filter(users::Column::UserId.eq(user_id))
+        .exec(&txn)
+        .await
+        .context("Failed to enable two-factor authentication.")?;
+    UserRecoveryCodes::delete_many()
+        .filter(user_recovery_codes::Column::UserId.eq(user_id))
+        .exec(&txn)
+        .await
+        .context("Failed to delete the old recovery codes.")?;
+    UserRecoveryCodes::insert_many(codes.iter().map(|code| user_recovery_codes::ActiveModel {
+        code_hash: Set(hash_recovery_code(code.expose_secret())),
+        user_id: Set(user_id),
+        created_at: Set(now),
+        used_at: Set(None),
+    }))
+    .exec_without_returning(&txn)
+    .await
+    .context("Failed to store the recovery codes.")?;
+    txn.commit()
+        .await
+        .context("Failed to commit two-factor authentication.")?;
+    Ok(codes)
+}
+
+/// Forgets the secret and the recovery codes of a user.
+#[tracing::instrument(name = "Disable two-factor authentication", skip(conn))]
+pub async fn disable_two_factor(
+    user_id: Uuid,
+    conn: &DatabaseConnection,
+) -> Result<(), anyhow::Error> {
+    let txn = conn
+        .begin()
+        .await
+        .context("Failed to begin a transaction.")?;
+    Users::update_many()
+        .col_expr(
+            users::Column::TotpSecret,
+            Expr::value(Option::<String>::None),
+        )
+        .col_expr(
+            users::Column::TotpEnabledAt,
+            Expr::value(Option::<OffsetDateTime>::None),
+        )
+        .col_expr(
+            users::Column::TotpLastStep,
+            Expr::value(Option::<i64>::None),
+        )
+        .filter(users::Column::UserId.eq(user_id))
+        .exec(&txn)
+        .await
+        .context("Failed to disable two-factor authentication.")?;
+    UserRecoveryCodes::delete_many()
+        .filter(user_recovery_codes::Column::UserId.eq(user_id))
+        .exec(&txn)
+        .await
+        .context("Failed to delete the recovery codes.")?;
+    txn.commit()
+        .await
+        .context("Failed to commit disabling two-factor authentication.")?;
+    Ok(())
+}
@@ -1,3 +1,5 @@
+use std::net::IpAddr;
+
 use migration::{Migrator, MigratorTrait};
 use sea_orm::{ConnectionTrait, DatabaseConnection, Statement};
 use secrecy::{ExposeSecret, Secret};
@@ -5,7 +7,12 @@ use serde::Deserialize;
 use serde_aux::field_attributes::deserialize_number_from_string;
 use strum::{Display, EnumString};
 
-use crate::domain::SubscriberEmail;
+use crate::{
+    domain::SubscriberEmail,
+    email_client::{
+        CircuitBreaker, EmailClient, FileDropTransport, HttpTransport, RetryPolicy, SmtpTransport,
+    },
+};
 
 #[derive(serde::Deserialize, Clone)]
 pub struct Settings {
@@ -13,6 +20,8 @@ pub struct Settings {
     pub application: ApplicationSettings,
     pub email_client: EmailClientSettings,
     pub redis: RedisSettings,
+    #[serde(default)]
+    pub login_throttle: LoginThrottleSettings,
 }
 
 #[derive(serde::Deserialize, Clone)]
@@ -22,6 +31,44 @@ pub struct RedisSettings {
     pub port: u16,
 }
 
+#[derive(serde::Deserialize, Clone)]
+#[serde(default)]
+pub struct LoginThrottleSettings {
+    /// Prefix of the attempt counters in Redis
+    pub key_prefix: String,
+    /// Failed attempts for one username after which it is locked out
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_failures_per_username: u64,
+    /// Failed attempts from one IP address after which it is locked out
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_failures_per_ip: u64,
+    /// How long a failed attempt is counted
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub window_seconds: i64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub lockout_seconds: i64,
+    /// Wait before checking the password after the first failed attempt,
+    /// doubled with every further one
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub base_delay_milliseconds: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_delay_milliseconds: u64,
+}
+
+impl Default for LoginThrottleSettings {
+    fn default() -> Self {
+        Self {
+            key_prefix: "login_throttle".to_owned(),
+            max_failures_per_username: 5,
+            max_failures_per_ip: 20,
+            window_seconds: 15 * 60,
+            lockout_seconds: 15 * 60,
+            base_delay_milliseconds: 250,
+            max_delay_milliseconds: 4000,
+        }
+    }
+}
+
 #[derive(serde::Deserialize, Clone)]
 pub struct DatabaseSettings {
     pub username: String,
@@ -40,17 +87,164 @@ pub struct ApplicationSettings {
     pub host: String,
     pub base_url: String,
     pub hmac_secret: Secret<String>,
+    /// Load balancers whose `X-Forwarded-For` header is believed
+    #[serde(default)]
+    pub trusted_proxies: Vec<IpAddr>,
 }
 
 #[derive(serde::Deserialize, Clone)]
 pub struct EmailClientSettings {
+    #[serde(default)]
+    pub transport: EmailTransportKind,
     pub base_url: String,
     pub sender_email: String,
     pub authorization_token: Secret<String>,
     pub timeout_milliseconds: u64,
+    /// How many emails the `http` transport sends per request
+    #[serde(default = "default_batch_size")]
+    pub batch_size: usize,
+    /// Required by the `smtp` transport
+    pub smtp: Option<SmtpSettings>,
+    /// Required by the `file_drop` transport
+    pub file_drop_directory: Option<String>,
+    #[serde(default)]
+    pub retry: RetrySettings,
+    #[serde(default)]
+    pub circuit_breaker: CircuitBreakerSettings,
+}
+
+#[derive(serde::Deserialize, Clone)]
+#[serde(default)]
+pub struct RetrySettings {
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_attempts: u32,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub base_backoff_milliseconds: u64,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub max_backoff_milliseconds: u64,
+}
+
+impl Default for RetrySettings {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_backoff_milliseconds: 500,
+            max_backoff_milliseconds: 5000,
+        }
+    }
+}
+
+impl RetrySettings {
+    pub fn policy(&self) -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: self.max_attempts,
+            base_backoff: std::time::Duration::from_millis(self.base_backoff_milliseconds),
+            max_backoff: std::time::Duration::from_millis(self.max_backoff_milliseconds),
+        }
+    }
+}
+
+#[derive(serde::Deserialize, Clone)]
+#[serde(default)]
+pub struct CircuitBreakerSettings {
+    /// Consecutive failed calls after which the provider is no longer called
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub failure_threshold: u32,
+    /// How long the provider is left alone before it is tried again
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub open_duration_milliseconds: u64,
+}
+
+impl Default for CircuitBreakerSettings {
+    fn default() -> Self {
+        Self {
+            failure_threshold: 5,
+            open_duration_milliseconds: 30_000,
+        }
+    }
+}
+
+impl CircuitBreakerSettings {
+    pub fn circuit_breaker(&self) -> CircuitBreaker {
+        CircuitBreaker::new(
+            self.failure_threshold,
+            std::time::Duration::from_millis(self.open_duration_milliseconds),
+        )
+    }
+}
+
+fn default_batch_size() -> usize {
+    50
+}
+
+#[derive(Deserialize, Default, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum EmailTransportKind {
+    #[default]
+    Http,
+    Smtp,
+    FileDrop,
+}
+
+#[derive(serde::Deserialize, Clone)]
+pub struct SmtpSettings {
+    pub host: String,
+    #[serde(deserialize_with = "deserialize_number_from_string")]
+    pub port: u16,
+    pub username: Option<String>,
+    pub password: Option<Secret<String>>,
+    #[serde(default)]
+    pub require_tls: bool,
 }
 
 impl EmailClientSettings {
+    pub fn client(self) -> EmailClient {
+        let retry_policy = self.retry.policy();
+        let circuit_breaker = self.circuit_breaker.circuit_breaker();
+        self.transport_client()
+            .with_retry_policy(retry_policy)
+            .with_circuit_breaker(circuit_breaker)
+    }
+
+    fn transport_client(self) -> EmailClient {
+        let sender_email = self.sender().expect("Invalid sender email address.");
+        let timeout = self.timeout();
+        match self.transport {
+            EmailTransportKind::Http => EmailClient::new(
+                sender_email,
+                HttpTransport::new(
+                    self.base_url,
+                    self.authorization_token,
+                    timeout,
+                    self.batch_size,
+                ),
+            ),
+            EmailTransportKind::Smtp => {
+                let smtp = self
+                    .smtp
+                    .expect("The smtp transport requires `email_client.smtp` settings.");
+                let credentials = smtp.username.zip(smtp.password);
+                let transport = SmtpTransport::new(
+                    &smtp.host,
+                    smtp.port,
+                    credentials,
+                    smtp.require_tls,
+                    timeout,
+                )
+                .expect("Invalid SMTP settings.");
+                EmailClient::new(sender_email, transport)
+            }
+            EmailTransportKind::FileDrop => {
+                let directory = self
+                    .file_drop_directory
+                    .expect("The file_drop transport requires `email_client.file_drop_directory`.");
+                std::fs::create_dir_all(&directory)
+                    .expect("Failed to create the file drop directory.");
+                EmailClient::new(sender_email, FileDropTransport::new(directory))
+            }
+        }
+    }
+
     pub fn sender(&self) -> Result<SubscriberEmail, String> {
         SubscriberEmail::parse(self.sender_email.clone())
     }
@@ -0,0 +1,233 @@
+use std::{
+    convert::Infallible,
+    net::{IpAddr, SocketAddr},
+};
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts},
+    http::{header, request::Parts},
+};
+use entity::{
+    consent_events::{self, Entity as ConsentEvents},
+    subscriptions::{self, Entity as Subscriptions},
+};
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::Set, ColumnTrait, ConnectionTrait, DbErr, EntityTrait,
+    QueryFilter, QueryOrder,
+};
+use serde::Serialize;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::utils::format_rfc3339;
+
+/// The steps of the double opt-in that we have to be able to prove.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ConsentEvent {
+    SignedUp,
+    ConfirmationSent,
+    Confirmed,
+    Unsubscribed,
+    /// A new address was confirmed in place of the old one.
+    EmailChanged,
+}
+
+impl ConsentEvent {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::SignedUp => "signed_up",
+            Self::ConfirmationSent => "confirmation_sent",
+            Self::Confirmed => "confirmed",
+            Self::Unsubscribed => "unsubscribed",
+            Self::EmailChanged => "email_changed",
+        }
+    }
+}
+
+/// A consent event as it is handed out in exports.
+#[derive(Serialize)]
+pub struct ConsentEventRecord {
+    pub event: String,
+    pub occurred_at: String,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub source: Option<String>,
+}
+
+impl From<consent_events::Model> for ConsentEventRecord {
+    fn from(event: consent_events::Model) -> Self {
+        Self {
+            event: event.kind,
+            occurred_at: format_rfc3339(event.occurred_at),
+            ip_address: event.ip_address,
+            user_agent: event.user_agent,
+            source: event.source,
+        }
+    }
+}
+
+/// Who sent a request, as far as we can tell.
+#[derive(Clone, Debug, Default)]
+pub struct RequestOrigin {
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+/// The load balancers in front of the application. Only they get to say who
+/// the client is, anyone else can put whatever they like in `X-Forwarded-For`.
+#[derive(Clone, Debug, Default)]
+pub struct TrustedProxies(pub Vec<IpAddr>);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for RequestOrigin
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(req: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let forwarded_for = req
+            .headers
+            .get_all("X-Forwarded-For")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .collect::<Vec<_>>()
+            .join(",");
+        let trusted_proxies = req
+            .extensions
+            .get::<TrustedProxies>()
+            .map(|proxies| proxies.0.as_slice())
+            .unwrap_or_default();
+        let ip_address =
+            req.extensions
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(address)| {
+                    client_address(address.ip(), &forwarded_for, trusted_proxies).to_string()
+                });
+        let user_agent = req
+            .headers
+            .get(header::USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(ToOwned::to_owned);
+        Ok(Self {
+            ip_address,
+            user_agent,
+        })
+    }
+}
+
+/// Every proxy appends the address it got the request from to
+/// `X-Forwarded-For`, so the chain is followed from the right for as long as
+/// the hops are our own proxies. The first address that is not is the client,
+/// everything to the left of it may have been made up.
+fn client_address(peer: IpAddr, forwarded_for: &str, trusted_proxies: &[IpAddr]) -> IpAddr {
+    let mut client = peer;
+    for hop in forwarded_for.rsplit(',').map(str::trim) {
+        if !trusted_proxies.contains(&client) {
+            break;
+        }
+        match hop.parse() {
+            Ok(address) => client = address,
+            Err(_) => break,
+        }
+    }
+    client
+}
+
+/// Appends an event to the consent trail of a subscriber.
+#[tracing::instrument(skip(db, origin))]
+pub async fn record_consent_event(
+    db: &impl ConnectionTrait,
+    subscriber_id: Uuid,
+    event: ConsentEvent,
+    origin: &RequestOrigin,
+    source: Option<&str>,
+) -> Result<(), DbErr> {
+    consent_events::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        subscriber_id: Set(subscriber_id),
+        kind: Set(event.as_str().to_owned()),
+        occurred_at: Set(OffsetDateTime::now_utc()),
+        ip_address: Set(origin.ip_address.clone()),
+        user_agent: Set(origin.user_agent.clone()),
+        source: Set(source.map(ToOwned::to_owned)),
+    }
+    .insert(db)
+    .await?;
+    Ok(())
+}
+
+/// The subscriber behind an email address and their consent trail, oldest
+/// event first.
+#[tracing::instrument(skip(db))]
+pub async fn get_consent_trail(
+    db: &impl ConnectionTrait,
+    email: &str,
+) -> Result<Option<(subscriptions::Model, Vec<consent_events::Model>)>, DbErr> {
+    let Some(subscriber) = Subscriptions::find()
+        .filter(subscriptions::Column::Email.eq(email))
+        .one(db)
+        .await?
+    else {
+        return Ok(None);
+    };
+    let events = ConsentEvents::find()
+        .filter(consent_events::Column::SubscriberId.eq(subscriber.id))
+        .order_by_asc(consent_events::Column::OccurredAt)
+        .all(db)
+        .await?;
+    Ok(Some((subscriber, events)))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+
+    use super::client_address;
+
+    fn ip(address: &str) -> IpAddr {
+        address.parse().unwrap()
+    }
+
+    #[test]
+    fn the_header_of_an_untrusted_peer_is_ignored() {
+        assert_eq!(
+            client_address(ip("203.0.113.7"), "198.51.100.1", &[]),
+            ip("203.0.113.7")
+        );
+    }
+
+    #[test]
+    fn a_trusted_proxy_reports_the_client() {
+        assert_eq!(
+            client_address(ip("10.0.0.1"), "203.0.113.7", &[ip("10.0.0.1")]),
+            ip("203.0.113.7")
+        );
+    }
+
+    #[test]
+    fn the_right_most_untrusted_hop_is_the_client() {
+        let proxies = [ip("10.0.0.1"), ip("10.0.0.2")];
+        assert_eq!(
+            client_address(
+                ip("10.0.0.2"),
+                "198.51.100.1, 203.0.113.7, 10.0.0.1",
+                &proxies
+            ),
+            ip("203.0.113.7")
+        );
+    }
+
+    #[test]
+    fn a_chain_of_trusted_proxies_without_a_client_ends_at_the_last_one() {
+        assert_eq!(
+            client_address(ip("10.0.0.1"), "", &[ip("10.0.0.1")]),
+            ip("10.0.0.1")
+        );
+        assert_eq!(
+            client_address(ip("10.0.0.1"), "not-an-address", &[ip("10.0.0.1")]),
+            ip("10.0.0.1")
+        );
+    }
+}
@@ -1,7 +1,11 @@
+mod list_slug;
 mod new_subscriber;
 mod subscriber_email;
 mod subscriber_name;
+mod username;
 
+pub use list_slug::ListSlug;
 pub use new_subscriber::NewSubscriber;
 pub use subscriber_email::SubscriberEmail;
 pub use subscriber_name::SubscriberName;
+pub use username::Username;
@@ -0,0 +1,57 @@
+/// The identifier a mailing list is referred to by in forms and links.
+#[derive(Debug, Clone)]
+pub struct ListSlug(String);
+
+impl ListSlug {
+    pub fn parse(s: String) -> Result<Self, String> {
+        let is_too_long = s.len() > 64;
+        let has_valid_characters = s
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-');
+        let has_dangling_dash = s.starts_with('-') || s.ends_with('-');
+
+        if s.is_empty() || is_too_long || !has_valid_characters || has_dangling_dash {
+            Err(format!("{} is not a valid list identifier.", s))
+        } else {
+            Ok(Self(s))
+        }
+    }
+}
+
+impl AsRef<str> for ListSlug {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ListSlug;
+    use claims::{assert_err, assert_ok};
+
+    #[test]
+    fn lowercase_words_joined_by_dashes_are_valid() {
+        assert_ok!(ListSlug::parse("release-notes-2024".to_string()));
+    }
+
+    #[test]
+    fn empty_string_is_rejected() {
+        assert_err!(ListSlug::parse("".to_string()));
+    }
+
+    #[test]
+    fn a_slug_longer_than_64_characters_is_rejected() {
+        assert_err!(ListSlug::parse("a".repeat(65)));
+    }
+
+    #[test]
+    fn uppercase_letters_and_spaces_are_rejected() {
+        assert_err!(ListSlug::parse("Release notes".to_string()));
+    }
+
+    #[test]
+    fn leading_or_trailing_dashes_are_rejected() {
+        assert_err!(ListSlug::parse("-news".to_string()));
+        assert_err!(ListSlug::parse("news-".to_string()));
+    }
+}
@@ -4,13 +4,21 @@ use validator::ValidateEmail;
 pub struct SubscriberEmail(String);
 
 impl SubscriberEmail {
+    /// The address is stored normalized, see [`SubscriberEmail::normalize`].
     pub fn parse(s: String) -> Result<SubscriberEmail, String> {
-        if s.validate_email() {
-            Ok(Self(s))
+        let normalized = Self::normalize(&s);
+        if normalized.validate_email() {
+            Ok(Self(normalized))
         } else {
             Err(format!("{} is not a valid subscriber email.", s))
         }
     }
+
+    /// The form addresses are compared in: addresses differing only in case
+    /// or surrounding whitespace belong to the same subscriber.
+    pub fn normalize(s: &str) -> String {
+        s.trim().to_lowercase()
+    }
 }
 
 impl AsRef<str> for SubscriberEmail {
@@ -80,6 +88,12 @@ mod tests {
       }
     }
 
+    #[test]
+    fn emails_are_normalized() {
+        let email = SubscriberEmail::parse(" Ursula@Domain.com ".to_string()).unwrap();
+        assert_eq!(email.as_ref(), "ursula@domain.com");
+    }
+
     #[test]
     fn empty_string_is_rejected() {
         let email = "".to_string();
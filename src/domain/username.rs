@@ -0,0 +1,49 @@
+/// The name an admin user logs in with.
+#[derive(Debug, Clone)]
+pub struct Username(String);
+
+impl Username {
+    pub fn parse(s: String) -> Result<Self, String> {
+        let is_too_long = s.chars().count() > 64;
+        let has_invalid_characters = s.chars().any(|c| c.is_whitespace() || c.is_control());
+
+        if s.is_empty() || is_too_long || has_invalid_characters {
+            Err(format!("{} is not a valid username.", s))
+        } else {
+            Ok(Self(s))
+        }
+    }
+}
+
+impl AsRef<str> for Username {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Username;
+    use claims::{assert_err, assert_ok};
+
+    #[test]
+    fn a_single_word_is_valid() {
+        assert_ok!(Username::parse("ursula.le-guin".to_string()));
+    }
+
+    #[test]
+    fn empty_string_is_rejected() {
+        assert_err!(Username::parse("".to_string()));
+    }
+
+    #[test]
+    fn a_username_longer_than_64_characters_is_rejected() {
+        assert_err!(Username::parse("a".repeat(65)));
+    }
+
+    #[test]
+    fn whitespace_is_rejected() {
+        assert_err!(Username::parse("ursula le guin".to_string()));
+        assert_err!(Username::parse(" ursula".to_string()));
+    }
+}
@@ -1,202 +1,326 @@
-use reqwest::Client;
-use secrecy::{ExposeSecret, Secret};
+use std::{sync::Arc, time::Duration};
+
+use axum::async_trait;
+use lettre::{
+    message::{
+        header::{HeaderName, HeaderValue},
+        Mailbox, MultiPart,
+    },
+    transport::smtp::response::Category,
+    Message,
+};
+use reqwest::StatusCode;
 
 use crate::domain::SubscriberEmail;
 
+mod circuit_breaker;
+mod file_drop;
+mod http;
+mod retry;
+mod smtp;
+
+pub use circuit_breaker::CircuitBreaker;
+pub use file_drop::FileDropTransport;
+pub use http::HttpTransport;
+pub use retry::RetryPolicy;
+pub use smtp::SmtpTransport;
+
+/// Sends the emails of the application, the actual delivery is left to the
+/// configured [`EmailTransport`].
+///
+/// Transient failures are retried according to the [`RetryPolicy`], an
+/// optional [`CircuitBreaker`] stops calling a transport that keeps failing.
 pub struct EmailClient {
-    http_client: Client,
-    base_url: reqwest::Url,
     sender: SubscriberEmail,
-    authorization_token: Secret<String>,
+    transport: Box<dyn EmailTransport>,
+    retry_policy: RetryPolicy,
+    circuit_breaker: Option<CircuitBreaker>,
 }
 
 impl EmailClient {
-    pub fn new(
-        base_url: String,
-        sender: SubscriberEmail,
-        authorization_token: Secret<String>,
-        timeout: std::time::Duration,
-    ) -> Self {
-        let http_client = Client::builder().timeout(timeout).build().unwrap();
+    pub fn new(sender: SubscriberEmail, transport: impl EmailTransport + 'static) -> Self {
         Self {
-            http_client,
-            base_url: reqwest::Url::parse(&base_url).expect("Invalid base URL"),
             sender,
-            authorization_token,
+            transport: Box::new(transport),
+            retry_policy: RetryPolicy::none(),
+            circuit_breaker: None,
         }
     }
 
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    pub fn with_circuit_breaker(mut self, circuit_breaker: CircuitBreaker) -> Self {
+        self.circuit_breaker = Some(circuit_breaker);
+        self
+    }
+
     pub async fn send_email(
         &self,
         recipient: &SubscriberEmail,
         subject: &str,
         html_content: &str,
         text_content: &str,
-    ) -> Result<(), reqwest::Error> {
-        let url = self.base_url.join("email").expect("valid URL");
-        let recipients = vec![SendEmailRequestRecipient {
-            email: recipient.as_ref(),
-            name: None,
-        }];
-        let request_body = SendEmailRequest {
-            from: SendEmailRequestRecipient {
-                email: self.sender.as_ref(),
-                name: None,
-            },
-            to: &recipients,
+    ) -> Result<(), EmailError> {
+        self.send_email_with_headers(recipient, subject, html_content, text_content, &[])
+            .await
+    }
+
+    /// Same as [`EmailClient::send_email`], the additional headers are added
+    /// to the outgoing email.
+    pub async fn send_email_with_headers(
+        &self,
+        recipient: &SubscriberEmail,
+        subject: &str,
+        html_content: &str,
+        text_content: &str,
+        headers: &[EmailHeader<'_>],
+    ) -> Result<(), EmailError> {
+        let email = Email {
+            from: &self.sender,
+            to: recipient,
             subject,
-            html: html_content,
-            text: text_content,
+            html_content,
+            text_content,
+            headers,
         };
-        let _builder = self
-            .http_client
-            .post(url)
-            .header(
-                "Authorization",
-                format!("Bearer {}", self.authorization_token.expose_secret()),
-            )
-            .json(&request_body)
-            .send()
-            .await?
-            .error_for_status()?;
-        Ok(())
+        self.deliver(&[email])
+            .await
+            .pop()
+            .expect("One outcome per email")
     }
-}
 
-#[derive(serde::Serialize)]
-struct SendEmailRequest<'a> {
-    from: SendEmailRequestRecipient<'a>,
-    to: &'a [SendEmailRequestRecipient<'a>],
-    subject: &'a str,
-    html: &'a str,
-    text: &'a str,
-}
+    /// How many emails the transport accepts in a single call.
+    pub fn max_batch_size(&self) -> usize {
+        self.transport.max_batch_size().max(1)
+    }
 
-#[derive(serde::Serialize)]
-struct SendEmailRequestRecipient<'a> {
-    email: &'a str,
-    name: Option<&'a str>,
-}
+    /// Sends every message, grouped into batches the transport can take at
+    /// once. The outcomes are returned per message and in the same order, a
+    /// rejected address does not fail the other messages of its batch.
+    pub async fn send_batch(&self, messages: &[EmailMessage<'_>]) -> Vec<Result<(), EmailError>> {
+        let emails: Vec<Email<'_>> = messages
+            .iter()
+            .map(|message| Email {
+                from: &self.sender,
+                to: message.recipient,
+                subject: message.subject,
+                html_content: message.html_content,
+                text_content: message.text_content,
+                headers: message.headers,
+            })
+            .collect();
+        let mut outcomes = Vec::with_capacity(emails.len());
+        for batch in emails.chunks(self.max_batch_size()) {
+            outcomes.extend(self.deliver(batch).await);
+        }
+        outcomes
+    }
 
-#[cfg(test)]
-mod tests {
-    use crate::domain::SubscriberEmail;
-    use crate::email_client::EmailClient;
-    use claims::assert_err;
-    use claims::assert_ok;
-    use fake::faker::internet::en::SafeEmail;
-    use fake::faker::lorem::en::{Paragraph, Sentence};
-    use fake::{Fake, Faker};
-    use secrecy::Secret;
-    use wiremock::matchers::{any, header, header_exists, method, path};
-    use wiremock::{Mock, MockServer, Request, ResponseTemplate};
-
-    struct SendEmailBodyMatcher;
-
-    impl wiremock::Match for SendEmailBodyMatcher {
-        fn matches(&self, request: &Request) -> bool {
-            let result: Result<serde_json::Value, _> = serde_json::from_slice(&request.body);
-            if let Ok(body) = result {
-                body.get("from").is_some()
-                    && body.get("to").is_some()
-                    && body.get("subject").is_some()
-                    && body.get("html").is_some()
-                    && body.get("text").is_some()
-            } else {
-                false
+    /// Sends a batch and retries the emails that failed transiently.
+    async fn deliver(&self, emails: &[Email<'_>]) -> Vec<Result<(), EmailError>> {
+        let mut outcomes = self.attempt(emails).await;
+        for n_retry in 1..self.retry_policy.max_attempts {
+            let pending: Vec<usize> = outcomes
+                .iter()
+                .enumerate()
+                .filter(|(_, outcome)| matches!(outcome, Err(e) if e.is_transient()))
+                .map(|(i, _)| i)
+                .collect();
+            if pending.is_empty() {
+                break;
+            }
+            let retry_after = pending
+                .iter()
+                .filter_map(|&i| outcomes[i].as_ref().err()?.retry_after())
+                .max();
+            let Some(delay) = self.retry_policy.delay(n_retry, retry_after) else {
+                break;
+            };
+            tracing::warn!(
+                n_retry,
+                n_emails = pending.len(),
+                "Sending emails failed transiently, retrying in {:?}.",
+                delay
+            );
+            tokio::time::sleep(delay).await;
+            let retried: Vec<Email<'_>> = pending.iter().map(|&i| emails[i]).collect();
+            for (i, outcome) in pending.into_iter().zip(self.attempt(&retried).await) {
+                outcomes[i] = outcome;
             }
         }
+        outcomes
     }
 
-    fn subject() -> String {
-        Sentence(1..2).fake()
+    /// A single call to the transport, guarded by the circuit breaker.
+    async fn attempt(&self, emails: &[Email<'_>]) -> Vec<Result<(), EmailError>> {
+        let Some(circuit_breaker) = &self.circuit_breaker else {
+            return self.transport.send_batch(emails).await;
+        };
+        if !circuit_breaker.allow() {
+            return emails
+                .iter()
+                .map(|_| Err(EmailError::CircuitOpen))
+                .collect();
+        }
+        let outcomes = self.transport.send_batch(emails).await;
+        // Rejected addresses say nothing about the health of the provider,
+        // everything else that went wrong does.
+        if outcomes
+            .iter()
+            .any(|outcome| matches!(outcome, Err(e) if e.is_provider_failure()))
+        {
+            circuit_breaker.record_failure();
+        } else {
+            circuit_breaker.record_success();
+        }
+        outcomes
     }
+}
+
+/// One message of a batch, see [`EmailClient::send_batch`].
+pub struct EmailMessage<'a> {
+    pub recipient: &'a SubscriberEmail,
+    pub subject: &'a str,
+    pub html_content: &'a str,
+    pub text_content: &'a str,
+    pub headers: &'a [EmailHeader<'a>],
+}
+
+/// An email that is ready to be handed over to a transport.
+#[derive(Clone, Copy)]
+pub struct Email<'a> {
+    pub from: &'a SubscriberEmail,
+    pub to: &'a SubscriberEmail,
+    pub subject: &'a str,
+    pub html_content: &'a str,
+    pub text_content: &'a str,
+    pub headers: &'a [EmailHeader<'a>],
+}
+
+#[derive(serde::Serialize, Debug)]
+pub struct EmailHeader<'a> {
+    pub name: &'a str,
+    pub value: &'a str,
+}
+
+#[async_trait]
+pub trait EmailTransport: Send + Sync {
+    async fn send(&self, email: &Email<'_>) -> Result<(), EmailError>;
 
-    fn content() -> String {
-        Paragraph(1..10).fake()
+    /// Returns one outcome per email, in the same order. Transports without
+    /// a batch API send the emails one after the other.
+    async fn send_batch(&self, emails: &[Email<'_>]) -> Vec<Result<(), EmailError>> {
+        let mut outcomes = Vec::with_capacity(emails.len());
+        for email in emails {
+            outcomes.push(self.send(email).await);
+        }
+        outcomes
     }
 
-    fn email() -> SubscriberEmail {
-        SubscriberEmail::parse(SafeEmail().fake()).unwrap()
+    fn max_batch_size(&self) -> usize {
+        1
     }
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum EmailError {
+    #[error("Failed to build the email.")]
+    InvalidEmail(#[source] anyhow::Error),
+    #[error("The email API rejected the request.")]
+    Http(#[from] reqwest::Error),
+    #[error("The email API is unavailable right now (status {status}).")]
+    Unavailable {
+        status: u16,
+        retry_after: Option<Duration>,
+    },
+    #[error("The email API rejected the email: {0}")]
+    Rejected(String),
+    #[error("The email API returned {received} results for a batch of {expected} emails.")]
+    UnexpectedBatchResponse { expected: usize, received: usize },
+    #[error("The batch this email belongs to could not be sent.")]
+    BatchFailed(#[source] Arc<EmailError>),
+    #[error("The SMTP server rejected the email.")]
+    Smtp(#[from] lettre::transport::smtp::Error),
+    #[error("Failed to write the email to disk.")]
+    FileDrop(#[from] lettre::transport::file::Error),
+    #[error("The email provider keeps failing, calls to it are suspended for now.")]
+    CircuitOpen,
+}
 
-    fn email_client(base_url: String) -> EmailClient {
-        EmailClient::new(
-            base_url,
-            email(),
-            Secret::new(Faker.fake()),
-            std::time::Duration::from_millis(200),
-        )
+impl EmailError {
+    /// Whether trying again later might succeed.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::Unavailable { .. } => true,
+            Self::Http(e) => e.is_timeout() || e.is_connect(),
+            Self::Smtp(e) => e.is_transient() || e.is_timeout(),
+            Self::BatchFailed(e) => e.is_transient(),
+            _ => false,
+        }
     }
 
-    #[tokio::test]
-    async fn send_email_sends_the_expected_request() {
-        // Arrange
-        let mock_server = MockServer::start().await;
-        let email_client = email_client(mock_server.uri());
-
-        Mock::given(header_exists("Authorization"))
-            .and(header("Content-Type", "application/json"))
-            .and(path("/email"))
-            .and(method("POST"))
-            .and(SendEmailBodyMatcher)
-            .respond_with(ResponseTemplate::new(200))
-            .expect(1)
-            .mount(&mock_server)
-            .await;
-
-        // Act
-        let outcome = email_client
-            .send_email(&email(), &subject(), &content(), &content())
-            .await;
-
-        // Assert
-        // Mock expectations are checked on drop
-        assert_ok!(outcome)
+    /// Whether the provider itself is failing, e.g. it is down or no longer
+    /// accepts our credentials, as opposed to refusing a single recipient.
+    pub fn is_provider_failure(&self) -> bool {
+        match self {
+            Self::InvalidEmail(_) | Self::Rejected(_) | Self::CircuitOpen => false,
+            Self::Http(e) => match e.status() {
+                Some(status) if status.is_client_error() => matches!(
+                    status,
+                    StatusCode::UNAUTHORIZED
+                        | StatusCode::FORBIDDEN
+                        | StatusCode::REQUEST_TIMEOUT
+                        | StatusCode::TOO_MANY_REQUESTS
+                ),
+                _ => true,
+            },
+            // 55x replies are about the mailbox of the recipient.
+            Self::Smtp(e) => {
+                !(e.is_permanent()
+                    && e.status()
+                        .is_some_and(|code| code.category == Category::MailSystem))
+            }
+            Self::BatchFailed(e) => e.is_provider_failure(),
+            Self::Unavailable { .. } | Self::UnexpectedBatchResponse { .. } | Self::FileDrop(_) => {
+                true
+            }
+        }
     }
 
-    #[tokio::test]
-    async fn send_email_fails_if_the_server_returns_500() {
-        // Arrange
-        let mock_server = MockServer::start().await;
-        let email_client = email_client(mock_server.uri());
-
-        Mock::given(any())
-            // Not a 200 anymore!
-            .respond_with(ResponseTemplate::new(500))
-            .expect(1)
-            .mount(&mock_server)
-            .await;
-
-        // Act
-        let outcome = email_client
-            .send_email(&email(), &subject(), &content(), &content())
-            .await;
-
-        // Assert
-        assert_err!(outcome);
+    fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::Unavailable { retry_after, .. } => *retry_after,
+            Self::BatchFailed(e) => e.retry_after(),
+            _ => None,
+        }
     }
+}
 
-    #[tokio::test]
-    async fn send_email_times_out_if_the_server_takes_too_long() {
-        // Arrange
-        let mock_server = MockServer::start().await;
-        let email_client = email_client(mock_server.uri());
-
-        let response = ResponseTemplate::new(200) // 3 minutes!
-            .set_delay(std::time::Duration::from_secs(180));
-        Mock::given(any())
-            .respond_with(response)
-            .expect(1)
-            .mount(&mock_server)
-            .await;
-
-        // Act
-        let outcome = email_client
-            .send_email(&email(), &subject(), &content(), &content())
-            .await;
-
-        // Assert
-        assert_err!(outcome);
+/// Builds the MIME message used by the transports that speak the email
+/// format themselves.
+fn build_message(email: &Email<'_>) -> Result<Message, EmailError> {
+    let mailbox = |address: &SubscriberEmail| {
+        address
+            .as_ref()
+            .parse::<Mailbox>()
+            .map_err(|e| EmailError::InvalidEmail(e.into()))
+    };
+    let mut builder = Message::builder()
+        .from(mailbox(email.from)?)
+        .to(mailbox(email.to)?)
+        .subject(email.subject);
+    for header in email.headers {
+        let name = HeaderName::new_from_ascii(header.name.to_owned())
+            .map_err(|e| EmailError::InvalidEmail(e.into()))?;
+        builder = builder.raw_header(HeaderValue::new(name, header.value.to_owned()));
     }
+    builder
+        .multipart(MultiPart::alternative_plain_html(
+            email.text_content.to_owned(),
+            email.html_content.to_owned(),
+        ))
+        .map_err(|e| EmailError::InvalidEmail(e.into()))
 }
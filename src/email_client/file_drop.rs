@@ -0,0 +1,78 @@
+use std::path::PathBuf;
+
+use axum::async_trait;
+use lettre::{AsyncFileTransport, AsyncTransport, Tokio1Executor};
+
+use super::{build_message, Email, EmailError, EmailTransport};
+
+/// Writes every email as an `.eml` file into a directory instead of sending
+/// it, for local development and for tests.
+pub struct FileDropTransport {
+    transport: AsyncFileTransport<Tokio1Executor>,
+}
+
+impl FileDropTransport {
+    pub fn new(directory: impl Into<PathBuf>) -> Self {
+        Self {
+            transport: AsyncFileTransport::new(directory.into()),
+        }
+    }
+}
+
+#[async_trait]
+impl EmailTransport for FileDropTransport {
+    async fn send(&self, email: &Email<'_>) -> Result<(), EmailError> {
+        let message = build_message(email)?;
+        self.transport.send(message).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::SubscriberEmail;
+    use crate::email_client::{EmailClient, EmailHeader, FileDropTransport};
+    use claims::assert_ok;
+    use uuid::Uuid;
+
+    #[tokio::test]
+    async fn emails_are_written_as_eml_files() {
+        // Arrange
+        let directory = std::env::temp_dir().join(Uuid::new_v4().to_string());
+        std::fs::create_dir_all(&directory).unwrap();
+        let email_client = EmailClient::new(
+            SubscriberEmail::parse("sender@example.com".into()).unwrap(),
+            FileDropTransport::new(&directory),
+        );
+        let recipient = SubscriberEmail::parse("recipient@example.com".into()).unwrap();
+
+        // Act
+        let outcome = email_client
+            .send_email_with_headers(
+                &recipient,
+                "Newsletter title",
+                "<p>Newsletter body as HTML</p>",
+                "Newsletter body as plain text",
+                &[EmailHeader {
+                    name: "List-Unsubscribe-Post",
+                    value: "List-Unsubscribe=One-Click",
+                }],
+            )
+            .await;
+
+        // Assert
+        assert_ok!(outcome);
+        let files: Vec<_> = std::fs::read_dir(&directory)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(files.len(), 1);
+        assert_eq!(files[0].extension().unwrap(), "eml");
+        let email = std::fs::read_to_string(&files[0]).unwrap();
+        assert!(email.contains("To: recipient@example.com"));
+        assert!(email.contains("Subject: Newsletter title"));
+        assert!(email.contains("List-Unsubscribe-Post: List-Unsubscribe=One-Click"));
+        assert!(email.contains("Newsletter body as plain text"));
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,727 @@
+use std::{sync::Arc, time::Duration};
+
+use axum::async_trait;
+use reqwest::{header::RETRY_AFTER, Client, Response, StatusCode};
+use secrecy::{ExposeSecret, Secret};
+use time::{format_description::well_known::Rfc2822, OffsetDateTime};
+
+use super::{Email, EmailError, EmailHeader, EmailTransport};
+
+/// Sends emails through the JSON API of an email provider, a `POST` to
+/// `{base_url}/email` authenticated with a bearer token.
+///
+/// Batches go to `{base_url}/email/batch` as an array of the same objects,
+/// the provider answers with one `{"error_code", "message"}` entry per email
+/// where an `error_code` of 0 means the email has been accepted.
+pub struct HttpTransport {
+    http_client: Client,
+    base_url: reqwest::Url,
+    authorization_token: Secret<String>,
+    batch_size: usize,
+}
+
+impl HttpTransport {
+    pub fn new(
+        base_url: String,
+        authorization_token: Secret<String>,
+        timeout: std::time::Duration,
+        batch_size: usize,
+    ) -> Self {
+        let http_client = Client::builder().timeout(timeout).build().unwrap();
+        Self {
+            http_client,
+            base_url: reqwest::Url::parse(&base_url).expect("Invalid base URL"),
+            authorization_token,
+            batch_size,
+        }
+    }
+
+    async fn post_batch(
+        &self,
+        emails: &[Email<'_>],
+    ) -> Result<Vec<Result<(), EmailError>>, EmailError> {
+        let url = self.base_url.join("email/batch").expect("valid URL");
+        let request_body: Vec<_> = emails.iter().map(SendEmailRequest::from).collect();
+        let results: Vec<BatchResult> = self
+            .http_client
+            .post(url)
+            .header(
+                "Authorization",
+                format!("Bearer {}", self.authorization_token.expose_secret()),
+            )
+            .json(&request_body)
+            .send()
+            .await
+            .map_err(EmailError::from)
+            .and_then(check_status)?
+            .json()
+            .await?;
+        if results.len() != emails.len() {
+            return Err(EmailError::UnexpectedBatchResponse {
+                expected: emails.len(),
+                received: results.len(),
+            });
+        }
+        Ok(results
+            .into_iter()
+            .map(|result| match result.error_code {
+                0 => Ok(()),
+                _ => Err(EmailError::Rejected(result.message)),
+            })
+            .collect())
+    }
+}
+
+#[async_trait]
+impl EmailTransport for HttpTransport {
+    async fn send(&self, email: &Email<'_>) -> Result<(), EmailError> {
+        let url = self.base_url.join("email").expect("valid URL");
+        let request_body = SendEmailRequest::from(email);
+        self.http_client
+            .post(url)
+            .header(
+                "Authorization",
+                format!("Bearer {}", self.authorization_token.expose_secret()),
+            )
+            .json(&request_body)
+            .send()
+            .await
+            .map_err(EmailError::from)
+            .and_then(check_status)?;
+        Ok(())
+    }
+
+    async fn send_batch(&self, emails: &[Email<'_>]) -> Vec<Result<(), EmailError>> {
+        if let [email] = emails {
+            return vec![self.send(email).await];
+        }
+        match self.post_batch(emails).await {
+            Ok(outcomes) => outcomes,
+            Err(e) => {
+                let e = Arc::new(e);
+                emails
+                    .iter()
+                    .map(|_| Err(EmailError::BatchFailed(e.clone())))
+                    .collect()
+            }
+        }
+    }
+
+    fn max_batch_size(&self) -> usize {
+        self.batch_size
+    }
+}
+
+/// Turns the answers that ask us to come back later into
+/// [`EmailError::Unavailable`], all other error statuses into
+/// [`EmailError::Http`].
+fn check_status(response: Response) -> Result<Response, EmailError> {
+    match response.status() {
+        StatusCode::TOO_MANY_REQUESTS
+        | StatusCode::BAD_GATEWAY
+        | StatusCode::SERVICE_UNAVAILABLE
+        | StatusCode::GATEWAY_TIMEOUT => Err(EmailError::Unavailable {
+            status: response.status().as_u16(),
+            retry_after: response
+                .headers()
+                .get(RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(parse_retry_after),
+        }),
+        _ => Ok(response.error_for_status()?),
+    }
+}
+
+/// `Retry-After` holds either a number of seconds or an HTTP date.
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    if let Ok(seconds) = value.trim().parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = OffsetDateTime::parse(value.trim(), &Rfc2822).ok()?;
+    Some(
+        (date - OffsetDateTime::now_utc())
+            .try_into()
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+#[derive(serde::Serialize)]
+struct SendEmailRequest<'a> {
+    from: SendEmailRequestRecipient<'a>,
+    to: [SendEmailRequestRecipient<'a>; 1],
+    subject: &'a str,
+    html: &'a str,
+    text: &'a str,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    headers: &'a [EmailHeader<'a>],
+}
+
+impl<'a> From<&'a Email<'a>> for SendEmailRequest<'a> {
+    fn from(email: &'a Email<'a>) -> Self {
+        Self {
+            from: SendEmailRequestRecipient {
+                email: email.from.as_ref(),
+                name: None,
+            },
+            to: [SendEmailRequestRecipient {
+                email: email.to.as_ref(),
+                name: None,
+            }],
+            subject: email.subject,
+            html: email.html_content,
+            text: email.text_content,
+            headers: email.headers,
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+struct SendEmailRequestRecipient<'a> {
+    email: &'a str,
+    name: Option<&'a str>,
+}
+
+#[derive(serde::Deserialize)]
+struct BatchResult {
+    error_code: i64,
+    #[serde(default)]
+    message: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_retry_after;
+    use crate::domain::SubscriberEmail;
+    use crate::email_client::{
+        CircuitBreaker, EmailClient, EmailError, EmailHeader, EmailMessage, HttpTransport,
+        RetryPolicy,
+    };
+    use claims::assert_err;
+    use claims::assert_ok;
+    use fake::faker::internet::en::SafeEmail;
+    use fake::faker::lorem::en::{Paragraph, Sentence};
+    use fake::{Fake, Faker};
+    use secrecy::Secret;
+    use std::time::{Duration, Instant};
+    use time::{format_description::well_known::Rfc2822, OffsetDateTime};
+    use wiremock::matchers::{any, body_partial_json, header, header_exists, method, path};
+    use wiremock::{Mock, MockServer, Request, ResponseTemplate};
+
+    struct SendEmailBodyMatcher;
+
+    impl wiremock::Match for SendEmailBodyMatcher {
+        fn matches(&self, request: &Request) -> bool {
+            let result: Result<serde_json::Value, _> = serde_json::from_slice(&request.body);
+            if let Ok(body) = result {
+                body.get("from").is_some()
+                    && body.get("to").is_some()
+                    && body.get("subject").is_some()
+                    && body.get("html").is_some()
+                    && body.get("text").is_some()
+            } else {
+                false
+            }
+        }
+    }
+
+    fn subject() -> String {
+        Sentence(1..2).fake()
+    }
+
+    fn content() -> String {
+        Paragraph(1..10).fake()
+    }
+
+    fn email() -> SubscriberEmail {
+        SubscriberEmail::parse(SafeEmail().fake()).unwrap()
+    }
+
+    fn email_client(base_url: String) -> EmailClient {
+        EmailClient::new(
+            email(),
+            HttpTransport::new(
+                base_url,
+                Secret::new(Faker.fake()),
+                std::time::Duration::from_millis(200),
+                10,
+            ),
+        )
+    }
+
+    fn retrying_email_client(base_url: String) -> EmailClient {
+        email_client(base_url).with_retry_policy(RetryPolicy {
+            max_attempts: 3,
+            base_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_secs(2),
+        })
+    }
+
+    #[tokio::test]
+    async fn send_email_sends_the_expected_request() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(header_exists("Authorization"))
+            .and(header("Content-Type", "application/json"))
+            .and(path("/email"))
+            .and(method("POST"))
+            .and(SendEmailBodyMatcher)
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        // Assert
+        // Mock expectations are checked on drop
+        assert_ok!(outcome)
+    }
+
+    #[tokio::test]
+    async fn send_email_with_headers_passes_the_headers_through() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(path("/email"))
+            .and(method("POST"))
+            .and(body_partial_json(serde_json::json!({
+                "headers": [{"name": "List-Unsubscribe-Post", "value": "List-Unsubscribe=One-Click"}]
+            })))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_email_with_headers(
+                &email(),
+                &subject(),
+                &content(),
+                &content(),
+                &[EmailHeader {
+                    name: "List-Unsubscribe-Post",
+                    value: "List-Unsubscribe=One-Click",
+                }],
+            )
+            .await;
+
+        // Assert
+        assert_ok!(outcome);
+    }
+
+    #[tokio::test]
+    async fn send_batch_sends_one_request_per_batch() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+        let recipients: Vec<_> = (0..15).map(|_| email()).collect();
+        let (subject, content) = (subject(), content());
+        let messages: Vec<_> = recipients
+            .iter()
+            .map(|recipient| EmailMessage {
+                recipient,
+                subject: &subject,
+                html_content: &content,
+                text_content: &content,
+                headers: &[],
+            })
+            .collect();
+
+        Mock::given(path("/email/batch"))
+            .and(method("POST"))
+            .respond_with(|request: &Request| {
+                let body: Vec<serde_json::Value> = serde_json::from_slice(&request.body).unwrap();
+                let results: Vec<_> = body
+                    .iter()
+                    .map(|_| serde_json::json!({"error_code": 0, "message": "OK"}))
+                    .collect();
+                ResponseTemplate::new(200).set_body_json(results)
+            })
+            // A batch of 10 and one of 5
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcomes = email_client.send_batch(&messages).await;
+
+        // Assert
+        assert_eq!(outcomes.len(), 15);
+        assert!(outcomes.iter().all(Result::is_ok));
+    }
+
+    #[tokio::test]
+    async fn send_batch_reports_rejected_recipients_individually() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+        let recipients = [email(), email(), email()];
+        let (subject, content) = (subject(), content());
+        let messages: Vec<_> = recipients
+            .iter()
+            .map(|recipient| EmailMessage {
+                recipient,
+                subject: &subject,
+                html_content: &content,
+                text_content: &content,
+                headers: &[],
+            })
+            .collect();
+
+        Mock::given(path("/email/batch"))
+            .and(method("POST"))
+            .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+                {"error_code": 0, "message": "OK"},
+                {"error_code": 406, "message": "Inactive recipient"},
+                {"error_code": 0, "message": "OK"},
+            ])))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcomes = email_client.send_batch(&messages).await;
+
+        // Assert
+        assert_ok!(&outcomes[0]);
+        assert_err!(&outcomes[1]);
+        assert_ok!(&outcomes[2]);
+    }
+
+    #[tokio::test]
+    async fn send_batch_fails_every_email_if_the_server_returns_500() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+        let recipients = [email(), email()];
+        let (subject, content) = (subject(), content());
+        let messages: Vec<_> = recipients
+            .iter()
+            .map(|recipient| EmailMessage {
+                recipient,
+                subject: &subject,
+                html_content: &content,
+                text_content: &content,
+                headers: &[],
+            })
+            .collect();
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(500))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcomes = email_client.send_batch(&messages).await;
+
+        // Assert
+        assert_eq!(outcomes.len(), 2);
+        assert!(outcomes.iter().all(Result::is_err));
+    }
+
+    #[tokio::test]
+    async fn send_email_fails_if_the_server_returns_500() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        Mock::given(any())
+            // Not a 200 anymore!
+            .respond_with(ResponseTemplate::new(500))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        // Assert
+        assert_err!(outcome);
+    }
+
+    #[tokio::test]
+    async fn send_email_times_out_if_the_server_takes_too_long() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri());
+
+        let response = ResponseTemplate::new(200) // 3 minutes!
+            .set_delay(std::time::Duration::from_secs(180));
+        Mock::given(any())
+            .respond_with(response)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        // Assert
+        assert_err!(outcome);
+    }
+
+    #[tokio::test]
+    async fn transient_failures_are_retried() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = retrying_email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(2)
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        // Assert
+        assert_ok!(outcome);
+    }
+
+    #[tokio::test]
+    async fn retries_stop_after_the_maximum_number_of_attempts() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = retrying_email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(503))
+            .expect(3)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        // Assert
+        assert_err!(outcome);
+    }
+
+    #[tokio::test]
+    async fn client_errors_are_not_retried() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = retrying_email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(400))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        // Assert
+        assert_err!(outcome);
+    }
+
+    #[tokio::test]
+    async fn retry_after_is_honored() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = retrying_email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "1"))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let start = Instant::now();
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        // Assert
+        assert_ok!(outcome);
+        assert!(start.elapsed() >= Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn a_retry_after_beyond_the_maximum_backoff_is_not_waited_for() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = retrying_email_client(mock_server.uri());
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(429).insert_header("Retry-After", "3600"))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        // Assert
+        assert_err!(outcome);
+    }
+
+    #[tokio::test]
+    async fn the_circuit_breaker_stops_calling_a_failing_provider() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri())
+            .with_circuit_breaker(CircuitBreaker::new(2, Duration::from_secs(60)));
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(503))
+            // The third call never reaches the provider
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let mut outcomes = Vec::new();
+        for _ in 0..3 {
+            outcomes.push(
+                email_client
+                    .send_email(&email(), &subject(), &content(), &content())
+                    .await,
+            );
+        }
+
+        // Assert
+        assert!(matches!(outcomes[1], Err(EmailError::Unavailable { .. })));
+        assert!(matches!(outcomes[2], Err(EmailError::CircuitOpen)));
+    }
+
+    #[tokio::test]
+    async fn the_circuit_breaker_counts_rejected_credentials() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri())
+            .with_circuit_breaker(CircuitBreaker::new(2, Duration::from_secs(60)));
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(401))
+            .expect(2)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let mut outcomes = Vec::new();
+        for _ in 0..3 {
+            outcomes.push(
+                email_client
+                    .send_email(&email(), &subject(), &content(), &content())
+                    .await,
+            );
+        }
+
+        // Assert
+        assert!(matches!(outcomes[1], Err(EmailError::Http(_))));
+        assert!(matches!(outcomes[2], Err(EmailError::CircuitOpen)));
+    }
+
+    #[tokio::test]
+    async fn the_circuit_breaker_ignores_rejected_recipients() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri())
+            .with_circuit_breaker(CircuitBreaker::new(1, Duration::from_secs(60)));
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(422))
+            .expect(3)
+            .mount(&mock_server)
+            .await;
+
+        // Act
+        let mut outcomes = Vec::new();
+        for _ in 0..3 {
+            outcomes.push(
+                email_client
+                    .send_email(&email(), &subject(), &content(), &content())
+                    .await,
+            );
+        }
+
+        // Assert
+        assert!(outcomes
+            .iter()
+            .all(|outcome| matches!(outcome, Err(EmailError::Http(_)))));
+    }
+
+    #[tokio::test]
+    async fn the_circuit_breaker_lets_a_trial_call_through_after_a_while() {
+        // Arrange
+        let mock_server = MockServer::start().await;
+        let email_client = email_client(mock_server.uri())
+            .with_circuit_breaker(CircuitBreaker::new(1, Duration::from_millis(50)));
+
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(503))
+            .up_to_n_times(1)
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        Mock::given(any())
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&mock_server)
+            .await;
+        let _ = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        // Act
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let outcome = email_client
+            .send_email(&email(), &subject(), &content(), &content())
+            .await;
+
+        // Assert
+        assert_ok!(outcome);
+    }
+
+    #[test]
+    fn retry_after_accepts_seconds_and_http_dates() {
+        assert_eq!(parse_retry_after("120"), Some(Duration::from_secs(120)));
+        let in_a_minute = (OffsetDateTime::now_utc() + time::Duration::minutes(1))
+            .format(&Rfc2822)
+            .unwrap();
+        let delay = parse_retry_after(&in_a_minute).unwrap();
+        assert!(delay > Duration::from_secs(50) && delay <= Duration::from_secs(60));
+        // Dates in the past mean "right away"
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_retry_after("soon"), None);
+    }
+}
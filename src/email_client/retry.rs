@@ -0,0 +1,77 @@
+use std::time::Duration;
+
+/// How often and how patiently [`super::EmailClient`] retries transient
+/// failures of the transport.
+#[derive(Clone, Debug)]
+pub struct RetryPolicy {
+    /// Total number of attempts, the first one included
+    pub max_attempts: u32,
+    /// Delay before the first retry, it doubles with every further one
+    pub base_backoff: Duration,
+    /// Upper bound for a single delay. A server asking us to wait longer
+    /// than this with `Retry-After` is not retried at all.
+    pub max_backoff: Duration,
+}
+
+impl RetryPolicy {
+    /// Gives up after the first attempt.
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            base_backoff: Duration::ZERO,
+            max_backoff: Duration::ZERO,
+        }
+    }
+
+    /// The delay before the `n_retry`-th retry (starting at 1), `None` when
+    /// the server asks for a longer break than we are willing to wait.
+    pub(super) fn delay(&self, n_retry: u32, retry_after: Option<Duration>) -> Option<Duration> {
+        match retry_after {
+            Some(retry_after) if retry_after > self.max_backoff => None,
+            Some(retry_after) => Some(retry_after),
+            None => Some(
+                self.base_backoff
+                    .saturating_mul(2u32.saturating_pow(n_retry.saturating_sub(1)))
+                    .min(self.max_backoff),
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RetryPolicy;
+    use std::time::Duration;
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 5,
+            base_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(1),
+        }
+    }
+
+    #[test]
+    fn the_delay_doubles_with_every_retry_up_to_the_maximum() {
+        let policy = policy();
+        assert_eq!(policy.delay(1, None), Some(Duration::from_millis(100)));
+        assert_eq!(policy.delay(2, None), Some(Duration::from_millis(200)));
+        assert_eq!(policy.delay(3, None), Some(Duration::from_millis(400)));
+        assert_eq!(policy.delay(10, None), Some(Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn retry_after_takes_precedence() {
+        let policy = policy();
+        assert_eq!(
+            policy.delay(1, Some(Duration::from_millis(700))),
+            Some(Duration::from_millis(700))
+        );
+    }
+
+    #[test]
+    fn a_retry_after_beyond_the_maximum_is_not_waited_for() {
+        let policy = policy();
+        assert_eq!(policy.delay(1, Some(Duration::from_secs(60))), None);
+    }
+}
@@ -0,0 +1,159 @@
+use axum::async_trait;
+use lettre::{
+    transport::smtp::authentication::Credentials, AsyncSmtpTransport, AsyncTransport,
+    Tokio1Executor,
+};
+use secrecy::{ExposeSecret, Secret};
+
+use super::{build_message, Email, EmailError, EmailTransport};
+
+/// Hands emails over to an SMTP server.
+pub struct SmtpTransport {
+    mailer: AsyncSmtpTransport<Tokio1Executor>,
+}
+
+impl SmtpTransport {
+    /// Connects with STARTTLS if `require_tls` is set, otherwise in plain
+    /// text, which is only meant for local SMTP sinks.
+    pub fn new(
+        host: &str,
+        port: u16,
+        credentials: Option<(String, Secret<String>)>,
+        require_tls: bool,
+        timeout: std::time::Duration,
+    ) -> Result<Self, EmailError> {
+        let mut builder = if require_tls {
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(host)?
+        } else {
+            AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(host)
+        };
+        builder = builder.port(port).timeout(Some(timeout));
+        if let Some((username, password)) = credentials {
+            builder = builder.credentials(Credentials::new(
+                username,
+                password.expose_secret().to_owned(),
+            ));
+        }
+        Ok(Self {
+            mailer: builder.build(),
+        })
+    }
+}
+
+#[async_trait]
+impl EmailTransport for SmtpTransport {
+    async fn send(&self, email: &Email<'_>) -> Result<(), EmailError> {
+        let message = build_message(email)?;
+        self.mailer.send(message).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::domain::SubscriberEmail;
+    use crate::email_client::{EmailClient, SmtpTransport};
+    use claims::{assert_err, assert_ok};
+    use tokio::{
+        io::{AsyncBufReadExt, AsyncWriteExt, BufReader},
+        net::TcpListener,
+        sync::oneshot,
+    };
+
+    /// A bare-bones SMTP server accepting a single email, it hands the
+    /// received message (or the rejection) back to the test.
+    async fn spawn_smtp_sink(reject_recipients: bool) -> (u16, oneshot::Receiver<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let (sender, receiver) = oneshot::channel();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let (reader, mut writer) = stream.into_split();
+            let mut lines = BufReader::new(reader).lines();
+            writer.write_all(b"220 localhost ESMTP\r\n").await.unwrap();
+            let mut message = String::new();
+            while let Ok(Some(line)) = lines.next_line().await {
+                let command = line.to_uppercase();
+                let reply: &[u8] = if command.starts_with("EHLO") {
+                    b"250 localhost\r\n"
+                } else if command.starts_with("RCPT") && reject_recipients {
+                    b"550 No such user\r\n"
+                } else if command.starts_with("DATA") {
+                    writer.write_all(b"354 Go ahead\r\n").await.unwrap();
+                    while let Ok(Some(line)) = lines.next_line().await {
+                        if line == "." {
+                            break;
+                        }
+                        message.push_str(&line);
+                        message.push('\n');
+                    }
+                    b"250 Queued\r\n"
+                } else if command.starts_with("QUIT") {
+                    writer.write_all(b"221 Bye\r\n").await.unwrap();
+                    break;
+                } else {
+                    b"250 OK\r\n"
+                };
+                writer.write_all(reply).await.unwrap();
+            }
+            let _ = sender.send(message);
+        });
+        (port, receiver)
+    }
+
+    fn email_client(port: u16) -> EmailClient {
+        EmailClient::new(
+            SubscriberEmail::parse("sender@example.com".into()).unwrap(),
+            SmtpTransport::new(
+                "127.0.0.1",
+                port,
+                None,
+                false,
+                std::time::Duration::from_secs(5),
+            )
+            .unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn send_email_delivers_the_message_to_the_smtp_server() {
+        // Arrange
+        let (port, received) = spawn_smtp_sink(false).await;
+        let email_client = email_client(port);
+        let recipient = SubscriberEmail::parse("recipient@example.com".into()).unwrap();
+
+        // Act
+        let outcome = email_client
+            .send_email(
+                &recipient,
+                "Newsletter title",
+                "<p>Newsletter body as HTML</p>",
+                "Newsletter body as plain text",
+            )
+            .await;
+        drop(email_client);
+
+        // Assert
+        assert_ok!(outcome);
+        let message = received.await.unwrap();
+        assert!(message.contains("To: recipient@example.com"));
+        assert!(message.contains("Subject: Newsletter title"));
+        assert!(message.contains("Newsletter body as plain text"));
+    }
+
+    #[tokio::test]
+    async fn send_email_fails_if_the_smtp_server_rejects_the_recipient() {
+        // Arrange
+        let (port, _received) = spawn_smtp_sink(true).await;
+        let email_client = email_client(port);
+        let recipient = SubscriberEmail::parse("recipient@example.com".into()).unwrap();
+
+        // Act
+        let outcome = email_client
+            .send_email(&recipient, "Newsletter title", "<p>HTML</p>", "Text")
+            .await;
+
+        // Assert
+        assert_err!(outcome);
+    }
+}
@@ -0,0 +1,455 @@
+mod html_to_text;
+
+use std::sync::Arc;
+
+use handlebars::{Handlebars, RenderError, Template, TemplateError};
+use serde::Serialize;
+use serde_json::json;
+
+pub use html_to_text::html_to_text;
+
+/// Renders the emails we send, every named template comes in an HTML and a
+/// plain-text flavour that share the same variables.
+///
+/// Templates are embedded in the binary. They can wrap themselves in the
+/// `layout` partial block and include the other partials registered here.
+/// A template without a text flavour gets its text part generated from the
+/// rendered HTML.
+#[derive(Clone)]
+pub struct EmailTemplates {
+    html: Arc<Handlebars<'static>>,
+    text: Arc<Handlebars<'static>>,
+}
+
+pub struct RenderedEmail {
+    pub html_content: String,
+    pub text_content: String,
+}
+
+/// The content of a newsletter issue as written by its author, both parts
+/// may refer to the [`SubscriberVariables`].
+pub struct NewsletterContent<'a> {
+    pub title: &'a str,
+    pub html_content: &'a str,
+    /// Generated from the HTML content when empty.
+    pub text_content: &'a str,
+}
+
+#[derive(Serialize)]
+pub struct SubscriberVariables<'a> {
+    pub name: &'a str,
+    pub unsubscribe_url: &'a str,
+    pub preferences_url: &'a str,
+}
+
+impl EmailTemplates {
+    pub const CONFIRMATION: &'static str = "confirmation";
+    pub const NEWSLETTER: &'static str = "newsletter";
+    pub const DIGEST: &'static str = "digest";
+    pub const DATA_REQUEST: &'static str = "data_request";
+    pub const EMAIL_CHANGE: &'static str = "email_change";
+    pub const EMAIL_CHANGED: &'static str = "email_changed";
+    pub const INVITATION: &'static str = "invitation";
+    pub const PASSWORD_RESET: &'static str = "password_reset";
+
+    pub fn new() -> Self {
+        let mut html = Handlebars::new();
+        let mut text = Handlebars::new();
+        // HTML escaping would mangle the plain-text part.
+        text.register_escape_fn(handlebars::no_escape);
+
+        let html_templates = [
+            (
+                Self::CONFIRMATION,
+                include_str!("email_templates/confirmation.html.hbs"),
+            ),
+            (
+                Self::NEWSLETTER,
+                include_str!("email_templates/newsletter.html.hbs"),
+            ),
+            (
+                Self::DIGEST,
+                include_str!("email_templates/digest.html.hbs"),
+            ),
+            (
+                Self::DATA_REQUEST,
+                include_str!("email_templates/data_request.html.hbs"),
+            ),
+            (
+                Self::EMAIL_CHANGE,
+                include_str!("email_templates/email_change.html.hbs"),
+            ),
+            (
+                Self::EMAIL_CHANGED,
+                include_str!("email_templates/email_changed.html.hbs"),
+            ),
+            (
+                Self::INVITATION,
+                include_str!("email_templates/invitation.html.hbs"),
+            ),
+            (
+                Self::PASSWORD_RESET,
+                include_str!("email_templates/password_reset.html.hbs"),
+            ),
+        ];
+        let html_partials = [
+            ("layout", include_str!("email_templates/layout.html.hbs")),
+            (
+                "unsubscribe_footer",
+                include_str!("email_templates/unsubscribe_footer.html.hbs"),
+            ),
+        ];
+        let text_templates = [
+            (
+                Self::CONFIRMATION,
+                include_str!("email_templates/confirmation.txt.hbs"),
+            ),
+            (
+                Self::NEWSLETTER,
+                include_str!("email_templates/newsletter.txt.hbs"),
+            ),
+            (Self::DIGEST, include_str!("email_templates/digest.txt.hbs")),
+            (
+                Self::DATA_REQUEST,
+                include_str!("email_templates/data_request.txt.hbs"),
+            ),
+            (
+                Self::EMAIL_CHANGE,
+                include_str!("email_templates/email_change.txt.hbs"),
+            ),
+            (
+                Self::EMAIL_CHANGED,
+                include_str!("email_templates/email_changed.txt.hbs"),
+            ),
+            (
+                Self::INVITATION,
+                include_str!("email_templates/invitation.txt.hbs"),
+            ),
+            (
+                Self::PASSWORD_RESET,
+                include_str!("email_templates/password_reset.txt.hbs"),
+            ),
+        ];
+        let text_partials = [
+            ("layout", include_str!("email_templates/layout.txt.hbs")),
+            (
+                "unsubscribe_footer",
+                include_str!("email_templates/unsubscribe_footer.txt.hbs"),
+            ),
+        ];
+        for (name, template) in html_templates {
+            html.register_template_string(name, template)
+                .expect("Failed to register an HTML email template.");
+        }
+        for (name, partial) in html_partials {
+            html.register_partial(name, partial)
+                .expect("Failed to register an HTML email partial.");
+        }
+        for (name, template) in text_templates {
+            text.register_template_string(name, template)
+                .expect("Failed to register a text email template.");
+        }
+        for (name, partial) in text_partials {
+            text.register_partial(name, partial)
+                .expect("Failed to register a text email partial.");
+        }
+
+        Self {
+            html: Arc::new(html),
+            text: Arc::new(text),
+        }
+    }
+
+    /// Renders both parts of the named template.
+    pub fn render<T: Serialize>(&self, name: &str, data: &T) -> Result<RenderedEmail, RenderError> {
+        let html_content = self.html.render(name, data)?;
+        let text_content = if self.text.has_template(name) {
+            self.text.render(name, data)?
+        } else {
+            html_to_text(&html_content)
+        };
+        Ok(RenderedEmail {
+            html_content,
+            text_content,
+        })
+    }
+
+    /// Personalises a newsletter issue for one subscriber and wraps it in
+    /// the newsletter template.
+    pub fn render_newsletter(
+        &self,
+        content: &NewsletterContent<'_>,
+        subscriber: &SubscriberVariables<'_>,
+    ) -> Result<RenderedEmail, RenderError> {
+        let (html_body, text_body) = self.render_content(content, subscriber)?;
+        let html_content = self.html.render(
+            Self::NEWSLETTER,
+            &json!({
+                "title": content.title,
+                "body": html_body,
+                "name": subscriber.name,
+                "unsubscribe_url": subscriber.unsubscribe_url,
+                "preferences_url": subscriber.preferences_url,
+            }),
+        )?;
+        let text_content = self.text.render(
+            Self::NEWSLETTER,
+            &json!({
+                "title": content.title,
+                "body": text_body,
+                "name": subscriber.name,
+                "unsubscribe_url": subscriber.unsubscribe_url,
+                "preferences_url": subscriber.preferences_url,
+            }),
+        )?;
+        Ok(RenderedEmail {
+            html_content,
+            text_content,
+        })
+    }
+
+    /// Personalises several newsletter issues for one subscriber and puts
+    /// them one after the other into a single digest email.
+    pub fn render_digest(
+        &self,
+        title: &str,
+        issues: &[NewsletterContent<'_>],
+        subscriber: &SubscriberVariables<'_>,
+    ) -> Result<RenderedEmail, RenderError> {
+        let mut html_issues = Vec::with_capacity(issues.len());
+        let mut text_issues = Vec::with_capacity(issues.len());
+        for issue in issues {
+            let (html_body, text_body) = self.render_content(issue, subscriber)?;
+            html_issues.push(json!({ "title": issue.title, "body": html_body }));
+            text_issues.push(json!({ "title": issue.title, "body": text_body }));
+        }
+        let variables = |issues: Vec<serde_json::Value>| {
+            json!({
+                "title": title,
+                "issues": issues,
+                "name": subscriber.name,
+                "unsubscribe_url": subscriber.unsubscribe_url,
+                "preferences_url": subscriber.preferences_url,
+            })
+        };
+        Ok(RenderedEmail {
+            html_content: self.html.render(Self::DIGEST, &variables(html_issues))?,
+            text_content: self.text.render(Self::DIGEST, &variables(text_issues))?,
+        })
+    }
+
+    /// Both parts of the content of an issue with the subscriber variables
+    /// substituted.
+    fn render_content(
+        &self,
+        content: &NewsletterContent<'_>,
+        subscriber: &SubscriberVariables<'_>,
+    ) -> Result<(String, String), RenderError> {
+        let html_body = self
+            .html
+            .render_template(content.html_content, subscriber)?;
+        let text_body = if content.text_content.trim().is_empty() {
+            html_to_text(&html_body)
+        } else {
+            self.text
+                .render_template(content.text_content, subscriber)?
+        };
+        Ok((html_body, text_body))
+    }
+
+    /// Renders issue content outside of an email, e.g. for the public
+    /// archive. Subscriber variables render as empty strings.
+    pub fn render_issue_content(&self, content: &str) -> Result<String, RenderError> {
+        self.html.render_template(content, &json!({}))
+    }
+
+    /// Checks that content written by an author is a valid template, so that
+    /// a typo is reported when the issue is published rather than when it is
+    /// delivered.
+    pub fn validate_content(content: &str) -> Result<(), TemplateError> {
+        Template::compile(content).map(|_| ())
+    }
+}
+
+impl Default for EmailTemplates {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use claims::{assert_err, assert_ok};
+    use serde_json::json;
+
+    use super::{EmailTemplates, NewsletterContent, SubscriberVariables};
+
+    const UNSUBSCRIBE_URL: &str = "https://example.com/unsubscribe?id=1&signature=abc";
+    const PREFERENCES_URL: &str = "https://example.com/preferences?id=1&signature=abc";
+
+    fn subscriber() -> SubscriberVariables<'static> {
+        SubscriberVariables {
+            name: "Ursula",
+            unsubscribe_url: UNSUBSCRIBE_URL,
+            preferences_url: PREFERENCES_URL,
+        }
+    }
+
+    #[test]
+    fn confirmation_email_contains_the_link_in_both_parts() {
+        let link = "https://example.com/subscriptions/confirm?subscription_token=abc";
+        let email = EmailTemplates::new()
+            .render(
+                EmailTemplates::CONFIRMATION,
+                &json!({
+                    "name": "Ursula",
+                    "list_name": "Release notes",
+                    "confirmation_link": link,
+                }),
+            )
+            .unwrap();
+
+        assert!(email.html_content.contains(&format!("href=\"{}\"", link)));
+        assert!(email
+            .html_content
+            .contains("Welcome to our newsletter, Ursula!"));
+        assert!(email.text_content.contains(link));
+        assert!(email
+            .text_content
+            .contains("confirm your subscription to Release notes."));
+        assert!(!email.text_content.contains('<'));
+    }
+
+    #[test]
+    fn subscriber_variables_are_substituted_in_newsletter_content() {
+        let content = NewsletterContent {
+            title: "Issue",
+            html_content: "<p>Hi {{name}}!</p>",
+            text_content: "Hi {{name}}!",
+        };
+
+        let email = EmailTemplates::new()
+            .render_newsletter(&content, &subscriber())
+            .unwrap();
+
+        assert!(email.html_content.contains("<p>Hi Ursula!</p>"));
+        assert!(email.text_content.starts_with("Hi Ursula!"));
+    }
+
+    #[test]
+    fn subscriber_variables_are_escaped_in_html_only() {
+        let content = NewsletterContent {
+            title: "Issue",
+            html_content: "<p>Hi {{name}}!</p>",
+            text_content: "Hi {{name}}!",
+        };
+        let subscriber = SubscriberVariables {
+            name: "<b>Ursula</b>",
+            unsubscribe_url: UNSUBSCRIBE_URL,
+            preferences_url: PREFERENCES_URL,
+        };
+
+        let email = EmailTemplates::new()
+            .render_newsletter(&content, &subscriber)
+            .unwrap();
+
+        assert!(email.html_content.contains("Hi &lt;b&gt;Ursula&lt;/b&gt;!"));
+        assert!(email.text_content.contains("Hi <b>Ursula</b>!"));
+    }
+
+    #[test]
+    fn newsletter_carries_the_unsubscribe_url_unescaped_in_its_footer() {
+        let content = NewsletterContent {
+            title: "Issue",
+            html_content: "<p>Body</p>",
+            text_content: "Body",
+        };
+
+        let email = EmailTemplates::new()
+            .render_newsletter(&content, &subscriber())
+            .unwrap();
+
+        assert!(email
+            .html_content
+            .contains(&format!("<a href=\"{}\">Unsubscribe</a>", UNSUBSCRIBE_URL)));
+        assert!(email
+            .text_content
+            .contains(&format!("Unsubscribe: {}", UNSUBSCRIBE_URL)));
+    }
+
+    #[test]
+    fn newsletter_links_to_the_preferences_in_its_footer() {
+        let content = NewsletterContent {
+            title: "Issue",
+            html_content: "<p>Body</p>",
+            text_content: "Body",
+        };
+
+        let email = EmailTemplates::new()
+            .render_newsletter(&content, &subscriber())
+            .unwrap();
+
+        assert!(email.html_content.contains(&format!(
+            "<a href=\"{}\">Manage your preferences</a>",
+            PREFERENCES_URL
+        )));
+        assert!(email
+            .text_content
+            .contains(&format!("Manage your preferences: {}", PREFERENCES_URL)));
+    }
+
+    #[test]
+    fn an_empty_text_part_is_generated_from_the_html() {
+        let content = NewsletterContent {
+            title: "Issue",
+            html_content:
+                "<h1>News</h1><p>Hi {{name}}, read <a href=\"https://example.com\">this</a>.</p>",
+            text_content: "  ",
+        };
+
+        let email = EmailTemplates::new()
+            .render_newsletter(&content, &subscriber())
+            .unwrap();
+
+        assert!(email
+            .text_content
+            .starts_with("News\n\nHi Ursula, read this (https://example.com)."));
+    }
+
+    #[test]
+    fn a_digest_contains_every_issue_and_the_footer_once() {
+        let issues = [
+            NewsletterContent {
+                title: "First issue",
+                html_content: "<p>Hi {{name}}, first</p>",
+                text_content: "Hi {{name}}, first",
+            },
+            NewsletterContent {
+                title: "Second issue",
+                html_content: "<p>Second</p>",
+                text_content: "",
+            },
+        ];
+
+        let email = EmailTemplates::new()
+            .render_digest("Your weekly digest", &issues, &subscriber())
+            .unwrap();
+
+        for part in [&email.html_content, &email.text_content] {
+            assert!(part.contains("Your weekly digest"));
+            assert!(part.find("First issue") < part.find("Second issue"));
+            assert_eq!(part.matches(UNSUBSCRIBE_URL).count(), 1);
+        }
+        assert!(email.html_content.contains("<p>Hi Ursula, first</p>"));
+        assert!(email.text_content.contains("Hi Ursula, first"));
+        assert!(email.text_content.contains("Second"));
+        assert!(!email.text_content.contains("<p>"));
+    }
+
+    #[test]
+    fn invalid_content_is_rejected() {
+        assert_ok!(EmailTemplates::validate_content("Hi {{name}}!"));
+        assert_err!(EmailTemplates::validate_content("Hi {{name}!"));
+        assert_err!(EmailTemplates::validate_content("{{#if name}}Hi"));
+    }
+}
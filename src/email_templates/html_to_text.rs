@@ -0,0 +1,267 @@
+/// Tags whose content is never shown to a reader.
+const HIDDEN: [&str; 4] = ["head", "script", "style", "title"];
+/// Tags that start a new paragraph in the text output.
+const BLOCKS: [&str; 14] = [
+    "p",
+    "div",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "ul",
+    "ol",
+    "table",
+    "tr",
+    "blockquote",
+    "hr",
+];
+
+/// Derives a readable plain-text version of an email from its HTML.
+///
+/// This is not a general purpose HTML renderer: paragraphs and headings
+/// become blank-line separated blocks, list items get a dash, links keep
+/// their target in parentheses and every other tag is dropped.
+pub fn html_to_text(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut hidden_depth = 0usize;
+    // Where the text of the link that is currently open starts, and its target
+    let mut open_link: Option<(usize, String)> = None;
+    let mut rest = html;
+
+    while !rest.is_empty() {
+        let Some(tag_start) = rest.find('<') else {
+            if hidden_depth == 0 {
+                push_text(&mut text, rest);
+            }
+            break;
+        };
+        if hidden_depth == 0 {
+            push_text(&mut text, &rest[..tag_start]);
+        }
+        let Some(tag_len) = rest[tag_start..].find('>') else {
+            // A stray `<`, keep it as text
+            if hidden_depth == 0 {
+                push_text(&mut text, &rest[tag_start..]);
+            }
+            break;
+        };
+        let tag = &rest[tag_start + 1..tag_start + tag_len];
+        rest = &rest[tag_start + tag_len + 1..];
+
+        if tag.starts_with('!') {
+            // Comments and doctype
+            continue;
+        }
+        let closing = tag.starts_with('/');
+        let name = tag_name(tag.trim_start_matches('/'));
+        if HIDDEN.contains(&name.as_str()) {
+            hidden_depth = if closing {
+                hidden_depth.saturating_sub(1)
+            } else {
+                hidden_depth + 1
+            };
+            continue;
+        }
+        if hidden_depth > 0 {
+            continue;
+        }
+        match (name.as_str(), closing) {
+            ("br", _) => push_line_break(&mut text, 1),
+            ("li", false) => {
+                push_line_break(&mut text, 1);
+                text.push_str("- ");
+            }
+            ("a", false) => {
+                open_link = attribute(tag, "href").map(|href| (text.len(), href));
+            }
+            ("a", true) => {
+                if let Some((start, href)) = open_link.take() {
+                    if text[start..].trim() != href && !href.starts_with('#') {
+                        text.push_str(&format!(" ({})", href));
+                    }
+                }
+            }
+            (name, _) if BLOCKS.contains(&name) => push_line_break(&mut text, 2),
+            _ => {}
+        }
+    }
+
+    tidy(&text)
+}
+
+fn tag_name(tag: &str) -> String {
+    tag.split(|c: char| c.is_whitespace() || c == '/')
+        .next()
+        .unwrap_or_default()
+        .to_ascii_lowercase()
+}
+
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let lowercase = tag.to_ascii_lowercase();
+    let mut search_from = 0;
+    while let Some(offset) = lowercase[search_from..].find(name) {
+        let start = search_from + offset;
+        search_from = start + name.len();
+        let preceded_by_space = lowercase[..start].ends_with(char::is_whitespace);
+        let value = tag[search_from..].trim_start();
+        let Some(value) = value.strip_prefix('=').map(str::trim_start) else {
+            continue;
+        };
+        if !preceded_by_space {
+            continue;
+        }
+        let value = match value.chars().next() {
+            Some(quote @ ('"' | '\'')) => value[1..].split(quote).next().unwrap_or_default(),
+            _ => value.split(char::is_whitespace).next().unwrap_or_default(),
+        };
+        return Some(decode_entities(value));
+    }
+    None
+}
+
+/// Appends a text node, collapsing whitespace the way a browser would.
+fn push_text(text: &mut String, node: &str) {
+    let node = decode_entities(node);
+    for c in node.chars() {
+        if c.is_whitespace() {
+            if !text.is_empty() && !text.ends_with(char::is_whitespace) {
+                text.push(' ');
+            }
+        } else {
+            text.push(c);
+        }
+    }
+}
+
+fn push_line_break(text: &mut String, n_lines: usize) {
+    while text.ends_with(' ') {
+        text.pop();
+    }
+    if text.is_empty() {
+        return;
+    }
+    let n_present = text.len() - text.trim_end_matches('\n').len();
+    for _ in n_present..n_lines {
+        text.push('\n');
+    }
+}
+
+fn decode_entities(s: &str) -> String {
+    let mut decoded = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let entity = rest[1..]
+            .find(';')
+            .filter(|&end| end <= 10)
+            .and_then(|end| decode_entity(&rest[1..end + 1]).map(|c| (c, end + 2)));
+        match entity {
+            Some((c, len)) => {
+                decoded.push(c);
+                rest = &rest[len..];
+            }
+            None => {
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+fn decode_entity(entity: &str) -> Option<char> {
+    let c = match entity {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        "nbsp" => ' ',
+        _ => {
+            let code = if let Some(hex) = entity
+                .strip_prefix("#x")
+                .or_else(|| entity.strip_prefix("#X"))
+            {
+                u32::from_str_radix(hex, 16).ok()?
+            } else {
+                entity.strip_prefix('#')?.parse().ok()?
+            };
+            char::from_u32(code)?
+        }
+    };
+    Some(c)
+}
+
+/// Trims every line and the text as a whole.
+fn tidy(text: &str) -> String {
+    text.lines()
+        .map(str::trim)
+        .collect::<Vec<_>>()
+        .join("\n")
+        .trim()
+        .to_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::html_to_text;
+
+    #[test]
+    fn tags_are_stripped_and_whitespace_is_collapsed() {
+        assert_eq!(
+            html_to_text("<span>Hello,\n   <b>world</b>!</span>"),
+            "Hello, world!"
+        );
+    }
+
+    #[test]
+    fn blocks_are_separated_by_a_blank_line() {
+        assert_eq!(
+            html_to_text("<h1>Title</h1><p>First</p><div>Second</div>"),
+            "Title\n\nFirst\n\nSecond"
+        );
+    }
+
+    #[test]
+    fn line_breaks_and_list_items_start_a_new_line() {
+        assert_eq!(
+            html_to_text("Hello<br/>there<ul><li>one</li><li>two</li></ul>"),
+            "Hello\nthere\n\n- one\n- two"
+        );
+    }
+
+    #[test]
+    fn links_keep_their_target() {
+        assert_eq!(
+            html_to_text(r#"Click <a class="x" href="https://example.com/?a=1&amp;b=2">here</a>."#),
+            "Click here (https://example.com/?a=1&b=2)."
+        );
+        assert_eq!(
+            html_to_text("<a href='https://example.com'>https://example.com</a>"),
+            "https://example.com"
+        );
+    }
+
+    #[test]
+    fn entities_are_decoded() {
+        assert_eq!(
+            html_to_text("Tom &amp; Jerry &lt;3 &#39;quoted&#x27; &#x3D; &bogus; & more"),
+            "Tom & Jerry <3 'quoted' = &bogus; & more"
+        );
+    }
+
+    #[test]
+    fn hidden_content_is_dropped() {
+        assert_eq!(
+            html_to_text(
+                "<!DOCTYPE html><html><head><title>Subject</title><style>p {}</style></head>\
+                <body><!-- note --><p>Body</p></body></html>"
+            ),
+            "Body"
+        );
+    }
+}
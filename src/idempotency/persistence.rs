@@ -0,0 +1,141 @@
+use anyhow::Context;
+use axum::{
+    body::{to_bytes, Body},
+    http::{HeaderName, HeaderValue, StatusCode},
+    response::Response,
+};
+use entity::idempotency::{self, Entity as Idempotency};
+use sea_orm::{
+    sea_query::OnConflict, ActiveModelTrait, ActiveValue::Set, ColumnTrait, DatabaseConnection,
+    DatabaseTransaction, EntityTrait, QueryFilter, TransactionTrait,
+};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use super::IdempotencyKey;
+
+#[derive(serde::Serialize, serde::Deserialize)]
+struct HeaderPair {
+    name: String,
+    value: Vec<u8>,
+}
+
+pub enum NextAction {
+    // Return transaction for later usage
+    StartProcessing(DatabaseTransaction),
+    ReturnSavedResponse(Response),
+}
+
+#[tracing::instrument(name = "Try processing an idempotent request", skip(conn))]
+pub async fn try_processing(
+    conn: &DatabaseConnection,
+    idempotency_key: &IdempotencyKey,
+    user_id: Uuid,
+) -> Result<NextAction, anyhow::Error> {
+    let txn = conn
+        .begin()
+        .await
+        .context("Failed to start a transaction.")?;
+    let placeholder = idempotency::ActiveModel {
+        user_id: Set(user_id),
+        idempotency_key: Set(idempotency_key.as_ref().to_owned()),
+        created_at: Set(OffsetDateTime::now_utc()),
+        ..Default::default()
+    };
+    // A concurrent request with the same key blocks on this insert until the
+    // first one commits, afterwards it finds the saved response.
+    let n_inserted_rows = Idempotency::insert(placeholder)
+        .on_conflict(
+            OnConflict::columns([
+                idempotency::Column::UserId,
+                idempotency::Column::IdempotencyKey,
+            ])
+            .do_nothing()
+            .to_owned(),
+        )
+        .exec_without_returning(&txn)
+        .await
+        .context("Failed to insert the idempotency placeholder.")?;
+    if n_inserted_rows > 0 {
+        Ok(NextAction::StartProcessing(txn))
+    } else {
+        let saved_response = get_saved_response(conn, idempotency_key, user_id)
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("We expected a saved response, we didn't find it"))?;
+        Ok(NextAction::ReturnSavedResponse(saved_response))
+    }
+}
+
+#[tracing::instrument(name = "Get saved response", skip(conn))]
+async fn get_saved_response(
+    conn: &DatabaseConnection,
+    idempotency_key: &IdempotencyKey,
+    user_id: Uuid,
+) -> Result<Option<Response>, anyhow::Error> {
+    let saved = Idempotency::find_by_id((user_id, idempotency_key.as_ref().to_owned()))
+        .filter(idempotency::Column::ResponseStatusCode.is_not_null())
+        .one(conn)
+        .await
+        .context("Failed to perform a query to retrieve a saved response.")?;
+
+    let Some(saved) = saved else {
+        return Ok(None);
+    };
+    let status_code = saved
+        .response_status_code
+        .context("The saved response has no status code.")?;
+    let status_code = StatusCode::from_u16(status_code.try_into()?)?;
+    let headers: Vec<HeaderPair> =
+        serde_json::from_value(saved.response_headers.unwrap_or_default())
+            .context("Failed to deserialize the saved response headers.")?;
+
+    let mut response = Response::builder().status(status_code);
+    for HeaderPair { name, value } in headers {
+        response = response.header(
+            HeaderName::try_from(name)?,
+            HeaderValue::from_bytes(&value)?,
+        );
+    }
+    let response = response.body(Body::from(saved.response_body.unwrap_or_default()))?;
+    Ok(Some(response))
+}
+
+#[tracing::instrument(name = "Save response", skip(txn, http_response))]
+pub async fn save_response(
+    txn: DatabaseTransaction,
+    idempotency_key: &IdempotencyKey,
+    user_id: Uuid,
+    http_response: Response,
+) -> Result<Response, anyhow::Error> {
+    let (response_head, body) = http_response.into_parts();
+    let body = to_bytes(body, usize::MAX)
+        .await
+        .context("Failed to buffer the response body.")?;
+    let status_code = response_head.status.as_u16() as i16;
+    let headers: Vec<HeaderPair> = response_head
+        .headers
+        .iter()
+        .map(|(name, value)| HeaderPair {
+            name: name.as_str().to_owned(),
+            value: value.as_bytes().to_owned(),
+        })
+        .collect();
+
+    idempotency::ActiveModel {
+        user_id: Set(user_id),
+        idempotency_key: Set(idempotency_key.as_ref().to_owned()),
+        response_status_code: Set(Some(status_code)),
+        response_headers: Set(Some(serde_json::to_value(headers)?)),
+        response_body: Set(Some(body.to_vec())),
+        ..Default::default()
+    }
+    .update(&txn)
+    .await
+    .context("Failed to save the response.")?;
+    txn.commit()
+        .await
+        .context("Failed to commit the idempotency transaction.")?;
+
+    let http_response = Response::from_parts(response_head, Body::from(body));
+    Ok(http_response)
+}
@@ -0,0 +1,676 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+
+use entity::{
+    issue_delivery_queue::{self, Entity as IssueDeliveryQueue},
+    list_memberships::{self, Entity as ListMemberships},
+    newsletter_issue_lists::{self, Entity as NewsletterIssueLists},
+    newsletter_issues::{self, Entity as NewsletterIssues},
+    sea_orm_active_enums::{DigestFrequency, SubscriptionStatus},
+    subscriptions::{self, Entity as Subscriptions},
+};
+use sea_orm::{
+    sea_query::{Expr, LockBehavior, LockType, Query},
+    ActiveModelTrait,
+    ActiveValue::Set,
+    ColumnTrait, ConnectionTrait, DatabaseConnection, DatabaseTransaction, DbErr,
+    DerivePartialModel, EntityTrait, FromQueryResult, IntoActiveModel, ModelTrait, QueryFilter,
+    QuerySelect, TransactionTrait,
+};
+use time::{macros::time, OffsetDateTime, Time};
+use tokio_util::sync::CancellationToken;
+use tracing::Span;
+use uuid::Uuid;
+
+use crate::{
+    domain::SubscriberEmail,
+    email_client::{EmailClient, EmailError, EmailHeader, EmailMessage},
+    email_templates::{EmailTemplates, NewsletterContent, SubscriberVariables},
+    link_signer::LinkSigner,
+};
+
+/// A delivery task is dropped after it failed this many times.
+const MAX_RETRIES: i16 = 5;
+/// Delay before the first retry, it doubles with every further attempt.
+const BASE_BACKOFF: Duration = Duration::from_secs(30);
+/// Upper bound for the delay between two attempts.
+const MAX_BACKOFF: Duration = Duration::from_secs(60 * 60);
+/// Time of day (UTC) at which daily and weekly digests go out.
+const DIGEST_TIME: Time = time!(08:00);
+
+pub enum ExecutionOutcome {
+    TaskCompleted,
+    EmptyQueue,
+}
+
+pub async fn run_worker_until_stopped(
+    connection: DatabaseConnection,
+    email_client: Arc<EmailClient>,
+    link_signer: LinkSigner,
+    email_templates: EmailTemplates,
+    shutdown: CancellationToken,
+) -> Result<(), anyhow::Error> {
+    // The shutdown signal is only checked between two tasks, a task that is
+    // in flight is always completed (or rolled back) before the worker stops.
+    while !shutdown.is_cancelled() {
+        match try_execute_task(&connection, &email_client, &link_signer, &email_templates).await {
+            Ok(ExecutionOutcome::EmptyQueue) => {
+                sleep_until_cancelled(&shutdown, Duration::from_secs(10)).await;
+            }
+            Err(_) => {
+                sleep_until_cancelled(&shutdown, Duration::from_secs(1)).await;
+            }
+            Ok(ExecutionOutcome::TaskCompleted) => {}
+        }
+    }
+    tracing::info!("Issue delivery worker stopped.");
+    Ok(())
+}
+
+pub(crate) async fn sleep_until_cancelled(shutdown: &CancellationToken, duration: Duration) {
+    tokio::select! {
+        _ = shutdown.cancelled() => {}
+        _ = tokio::time::sleep(duration) => {}
+    }
+}
+
+/// The personalised email for one or more dequeued tasks of a subscriber,
+/// several tasks make up a digest.
+struct Delivery {
+    tasks: Vec<issue_delivery_queue::Model>,
+    recipient: SubscriberEmail,
+    subject: String,
+    html_content: String,
+    text_content: String,
+    list_unsubscribe: String,
+}
+
+/// Executes up to one batch of delivery tasks, the batch size is given by
+/// the email transport. The due tasks of a subscriber who asked for a digest
+/// are sent together as a single email.
+#[tracing::instrument(skip_all, fields(n_tasks = tracing::field::Empty), err)]
+pub async fn try_execute_task(
+    connection: &DatabaseConnection,
+    email_client: &EmailClient,
+    link_signer: &LinkSigner,
+    email_templates: &EmailTemplates,
+) -> Result<ExecutionOutcome, anyhow::Error> {
+    let Some((txn, mut tasks)) = dequeue_tasks(connection, email_client.max_batch_size()).await?
+    else {
+        return Ok(ExecutionOutcome::EmptyQueue);
+    };
+    let subscribers = get_confirmed_subscribers(&txn, &tasks).await?;
+    dequeue_digest_tasks(&txn, &mut tasks, &subscribers).await?;
+    Span::current().record("n_tasks", tasks.len());
+    let issues = get_issues(&txn, &tasks).await?;
+    let issue_lists = get_issue_lists(&txn, &tasks).await?;
+
+    // One group of tasks per email to send.
+    let mut groups: Vec<(&Subscriber, Vec<issue_delivery_queue::Model>)> = Vec::new();
+    let mut digest_groups: HashMap<String, usize> = HashMap::new();
+    for task in tasks {
+        // The subscriber may have unsubscribed after the issue was queued.
+        let Some(subscriber) = subscribers
+            .get(&task.subscriber_email)
+            .filter(|subscriber| {
+                issue_lists
+                    .get(&task.newsletter_issue_id)
+                    .is_some_and(|list_ids| !list_ids.is_disjoint(&subscriber.list_ids))
+            })
+        else {
+            tracing::info!(
+                newsletter_issue_id = %task.newsletter_issue_id,
+                subscriber_email = %task.subscriber_email,
+                "Skipping a subscriber that is no longer confirmed."
+            );
+            delete_task(&txn, task).await?;
+            continue;
+        };
+        if subscriber.digest_frequency == DigestFrequency::Immediately {
+            groups.push((subscriber, vec![task]));
+            continue;
+        }
+        match digest_groups.get(&task.subscriber_email) {
+            Some(&i) => groups[i].1.push(task),
+            None => {
+                digest_groups.insert(task.subscriber_email.clone(), groups.len());
+                groups.push((subscriber, vec![task]));
+            }
+        }
+    }
+
+    let mut deliveries = Vec::with_capacity(groups.len());
+    for (subscriber, tasks) in groups {
+        let recipient = match SubscriberEmail::parse(tasks[0].subscriber_email.clone()) {
+            Ok(recipient) => recipient,
+            Err(e) => {
+                tracing::error!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    subscriber_email = %tasks[0].subscriber_email,
+                    "Skipping a confirmed subscriber. \
+                    Their stored contact details are invalid",
+                );
+                fail_tasks(&txn, tasks).await?;
+                continue;
+            }
+        };
+        let mut group_issues = Vec::with_capacity(tasks.len());
+        for task in &tasks {
+            let issue = issues.get(&task.newsletter_issue_id).ok_or_else(|| {
+                anyhow::anyhow!("Newsletter issue {} not found.", task.newsletter_issue_id)
+            })?;
+            group_issues.push(issue);
+        }
+        // Oldest issue first.
+        group_issues.sort_by_key(|issue| issue.published_at);
+        let contents: Vec<_> = group_issues
+            .iter()
+            .map(|issue| NewsletterContent {
+                title: &issue.title,
+                html_content: &issue.html_content,
+                text_content: &issue.text_content,
+            })
+            .collect();
+        let unsubscribe_url = link_signer.unsubscribe_url(subscriber.id);
+        let preferences_url = link_signer.preferences_url(subscriber.id);
+        let variables = SubscriberVariables {
+            name: &subscriber.name,
+            unsubscribe_url: &unsubscribe_url,
+            preferences_url: &preferences_url,
+        };
+        let (subject, email) = match contents.as_slice() {
+            [content] => (
+                content.title.to_owned(),
+                email_templates.render_newsletter(content, &variables),
+            ),
+            _ => {
+                let title = digest_title(subscriber.digest_frequency);
+                let email = email_templates.render_digest(&title, &contents, &variables);
+                (title, email)
+            }
+        };
+        let email = match email {
+            Ok(email) => email,
+            Err(e) => {
+                // Retrying would fail the same way.
+                tracing::error!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    subscriber_email = %tasks[0].subscriber_email,
+                    "Skipping a confirmed subscriber. \
+                    The newsletter issue could not be rendered for them",
+                );
+                fail_tasks(&txn, tasks).await?;
+                continue;
+            }
+        };
+        deliveries.push(Delivery {
+            tasks,
+            recipient,
+            subject,
+            html_content: email.html_content,
+            text_content: email.text_content,
+            list_unsubscribe: format!("<{}>", unsubscribe_url),
+        });
+    }
+    let headers: Vec<_> = deliveries
+        .iter()
+        .map(|delivery| {
+            [
+                EmailHeader {
+                    name: "List-Unsubscribe",
+                    value: &delivery.list_unsubscribe,
+                },
+                EmailHeader {
+                    name: "List-Unsubscribe-Post",
+                    value: "List-Unsubscribe=One-Click",
+                },
+            ]
+        })
+        .collect();
+    let messages: Vec<_> = deliveries
+        .iter()
+        .zip(&headers)
+        .map(|(delivery, headers)| EmailMessage {
+            recipient: &delivery.recipient,
+            subject: &delivery.subject,
+            html_content: &delivery.html_content,
+            text_content: &delivery.text_content,
+            headers,
+        })
+        .collect();
+    let outcomes = email_client.send_batch(&messages).await;
+
+    for (delivery, outcome) in deliveries.into_iter().zip(outcomes) {
+        let tasks = delivery.tasks;
+        // The tasks of a digest are always attempted together.
+        let n_retries = tasks.iter().map(|task| task.n_retries).max().unwrap_or(0);
+        match outcome {
+            Ok(()) => {
+                for task in tasks {
+                    count_delivery_outcome(
+                        &txn,
+                        task.newsletter_issue_id,
+                        newsletter_issues::Column::NDelivered,
+                    )
+                    .await?;
+                    delete_task(&txn, task).await?;
+                }
+            }
+            Err(EmailError::CircuitOpen) => {
+                // The provider has not been called, this is not an attempt.
+                for task in tasks {
+                    postpone_task(&txn, task).await?;
+                }
+            }
+            // A rejected address or request does not get any better by
+            // trying again, unlike a provider that is down or overloaded.
+            Err(e) if !e.is_transient() && !e.is_provider_failure() => {
+                tracing::error!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    n_issues = tasks.len(),
+                    subscriber_email = %delivery.recipient,
+                    "Failed to deliver issue to a confirmed subscriber. \
+                    The failure is permanent, it will not be retried.",
+                );
+                fail_tasks(&txn, tasks).await?;
+            }
+            Err(e) if n_retries + 1 < MAX_RETRIES => {
+                tracing::warn!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    n_issues = tasks.len(),
+                    subscriber_email = %delivery.recipient,
+                    n_retries,
+                    "Failed to deliver issue to a confirmed subscriber. \
+                    The delivery will be retried.",
+                );
+                for task in tasks {
+                    reschedule_task(&txn, task).await?;
+                }
+            }
+            Err(e) => {
+                tracing::error!(
+                    error.cause_chain = ?e,
+                    error.message = %e,
+                    n_issues = tasks.len(),
+                    subscriber_email = %delivery.recipient,
+                    "Failed to deliver issue to a confirmed subscriber. \
+                    Giving up after {} attempts.",
+                    MAX_RETRIES,
+                );
+                fail_tasks(&txn, tasks).await?;
+            }
+        }
+    }
+    txn.commit().await?;
+    Ok(ExecutionOutcome::TaskCompleted)
+}
+
+/// Queues one delivery task per subscriber who confirmed any of the lists
+/// the issue is sent to, and records how many recipients the issue has.
+/// Subscribers who asked for a digest get the issue at their next slot,
+/// together with the other issues due for them then.
+#[tracing::instrument(skip_all)]
+pub async fn enqueue_delivery_tasks(
+    txn: &DatabaseTransaction,
+    newsletter_issue_id: Uuid,
+) -> Result<(), DbErr> {
+    let now = OffsetDateTime::now_utc();
+    let execute_after = Expr::case(
+        subscriptions::Column::DigestFrequency.eq(DigestFrequency::Daily),
+        digest_slot(DigestFrequency::Daily, now),
+    )
+    .case(
+        subscriptions::Column::DigestFrequency.eq(DigestFrequency::Weekly),
+        digest_slot(DigestFrequency::Weekly, now),
+    )
+    .finally(now);
+    let confirmed_subscribers = Query::select()
+        .distinct()
+        .expr(Expr::val(newsletter_issue_id))
+        .column((Subscriptions, subscriptions::Column::Email))
+        .expr(execute_after)
+        .from(Subscriptions)
+        .inner_join(
+            ListMemberships,
+            Expr::col((ListMemberships, list_memberships::Column::SubscriberId))
+                .equals((Subscriptions, subscriptions::Column::Id)),
+        )
+        .inner_join(
+            NewsletterIssueLists,
+            Expr::col((NewsletterIssueLists, newsletter_issue_lists::Column::ListId))
+                .equals((ListMemberships, list_memberships::Column::ListId)),
+        )
+        .and_where(newsletter_issue_lists::Column::NewsletterIssueId.eq(newsletter_issue_id))
+        .and_where(list_memberships::Column::Status.eq(SubscriptionStatus::Confirmed))
+        .to_owned();
+    let insert = Query::insert()
+        .into_table(IssueDeliveryQueue)
+        .columns([
+            issue_delivery_queue::Column::NewsletterIssueId,
+            issue_delivery_queue::Column::SubscriberEmail,
+            issue_delivery_queue::Column::ExecuteAfter,
+        ])
+        .select_from(confirmed_subscribers)
+        .map_err(|e| DbErr::Custom(e.to_string()))?
+        .to_owned();
+    let n_recipients = txn
+        .execute(txn.get_database_backend().build(&insert))
+        .await?
+        .rows_affected();
+    NewsletterIssues::update_many()
+        .col_expr(
+            newsletter_issues::Column::NRecipients,
+            Expr::value(i32::try_from(n_recipients).unwrap_or(i32::MAX)),
+        )
+        .filter(newsletter_issues::Column::NewsletterIssueId.eq(newsletter_issue_id))
+        .exec(txn)
+        .await?;
+    Ok(())
+}
+
+#[tracing::instrument(skip(connection))]
+async fn dequeue_tasks(
+    connection: &DatabaseConnection,
+    batch_size: usize,
+) -> Result<Option<(DatabaseTransaction, Vec<issue_delivery_queue::Model>)>, anyhow::Error> {
+    let txn = connection.begin().await?;
+    let tasks = IssueDeliveryQueue::find()
+        .filter(issue_delivery_queue::Column::ExecuteAfter.lte(OffsetDateTime::now_utc()))
+        .limit(u64::try_from(batch_size).unwrap_or(u64::MAX))
+        .lock_with_behavior(LockType::Update, LockBehavior::SkipLocked)
+        .all(&txn)
+        .await?;
+    if tasks.is_empty() {
+        return Ok(None);
+    }
+    Ok(Some((txn, tasks)))
+}
+
+/// Adds the other due tasks of the subscribers in the batch who asked for a
+/// digest, so that all their issues go out in one email.
+#[tracing::instrument(skip_all)]
+async fn dequeue_digest_tasks(
+    txn: &DatabaseTransaction,
+    tasks: &mut Vec<issue_delivery_queue::Model>,
+    subscribers: &HashMap<String, Subscriber>,
+) -> Result<(), anyhow::Error> {
+    let digest_emails: HashSet<&String> = tasks
+        .iter()
+        .map(|task| &task.subscriber_email)
+        .filter(|email| {
+            subscribers
+                .get(*email)
+                .is_some_and(|s| s.digest_frequency != DigestFrequency::Immediately)
+        })
+        .collect();
+    if digest_emails.is_empty() {
+        return Ok(());
+    }
+    let dequeued: HashSet<(Uuid, String)> = tasks
+        .iter()
+        .map(|task| (task.newsletter_issue_id, task.subscriber_email.clone()))
+        .collect();
+    let more_tasks = IssueDeliveryQueue::find()
+        .filter(issue_delivery_queue::Column::SubscriberEmail.is_in(digest_emails))
+        .filter(issue_delivery_queue::Column::ExecuteAfter.lte(OffsetDateTime::now_utc()))
+        .lock_with_behavior(LockType::Update, LockBehavior::SkipLocked)
+        .all(txn)
+        .await?;
+    tasks.extend(more_tasks.into_iter().filter(|task| {
+        !dequeued.contains(&(task.newsletter_issue_id, task.subscriber_email.clone()))
+    }));
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+async fn delete_task(
+    txn: &DatabaseTransaction,
+    task: issue_delivery_queue::Model,
+) -> Result<(), anyhow::Error> {
+    task.delete(txn).await?;
+    Ok(())
+}
+
+/// Gives up on tasks, they count as failed deliveries of their issue.
+#[tracing::instrument(skip_all)]
+async fn fail_tasks(
+    txn: &DatabaseTransaction,
+    tasks: Vec<issue_delivery_queue::Model>,
+) -> Result<(), anyhow::Error> {
+    for task in tasks {
+        count_delivery_outcome(
+            txn,
+            task.newsletter_issue_id,
+            newsletter_issues::Column::NFailed,
+        )
+        .await?;
+        delete_task(txn, task).await?;
+    }
+    Ok(())
+}
+
+#[tracing::instrument(skip(txn))]
+async fn count_delivery_outcome(
+    txn: &DatabaseTransaction,
+    issue_id: Uuid,
+    outcome_column: newsletter_issues::Column,
+) -> Result<(), anyhow::Error> {
+    NewsletterIssues::update_many()
+        .col_expr(outcome_column, Expr::col(outcome_column).add(1))
+        .filter(newsletter_issues::Column::NewsletterIssueId.eq(issue_id))
+        .exec(txn)
+        .await?;
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+async fn reschedule_task(
+    txn: &DatabaseTransaction,
+    task: issue_delivery_queue::Model,
+) -> Result<(), anyhow::Error> {
+    let n_retries = task.n_retries + 1;
+    let execute_after = OffsetDateTime::now_utc() + backoff(n_retries);
+    let mut task = task.into_active_model();
+    task.n_retries = Set(n_retries);
+    task.execute_after = Set(execute_after);
+    task.update(txn).await?;
+    Ok(())
+}
+
+#[tracing::instrument(skip_all)]
+async fn postpone_task(
+    txn: &DatabaseTransaction,
+    task: issue_delivery_queue::Model,
+) -> Result<(), anyhow::Error> {
+    let mut task = task.into_active_model();
+    task.execute_after = Set(OffsetDateTime::now_utc() + BASE_BACKOFF);
+    task.update(txn).await?;
+    Ok(())
+}
+
+/// When an issue published at `now` is due for a subscriber with the given
+/// digest frequency: daily digests go out every day and weekly digests every
+/// Monday, both at [`DIGEST_TIME`].
+fn digest_slot(frequency: DigestFrequency, now: OffsetDateTime) -> OffsetDateTime {
+    let today = now.replace_time(DIGEST_TIME);
+    match frequency {
+        DigestFrequency::Immediately => now,
+        DigestFrequency::Daily if today > now => today,
+        DigestFrequency::Daily => today + time::Duration::DAY,
+        DigestFrequency::Weekly => {
+            let days_since_monday = now.weekday().number_days_from_monday();
+            let monday = today - time::Duration::days(days_since_monday.into());
+            if monday > now {
+                monday
+            } else {
+                monday + time::Duration::WEEK
+            }
+        }
+    }
+}
+
+/// The subject and heading of a digest email.
+fn digest_title(frequency: DigestFrequency) -> String {
+    match frequency {
+        DigestFrequency::Weekly => "Your weekly digest".to_owned(),
+        _ => "Your daily digest".to_owned(),
+    }
+}
+
+fn backoff(n_retries: i16) -> Duration {
+    let exponent = u32::try_from(n_retries.saturating_sub(1)).unwrap_or(0);
+    BASE_BACKOFF
+        .saturating_mul(2u32.saturating_pow(exponent))
+        .min(MAX_BACKOFF)
+}
+
+struct Subscriber {
+    id: Uuid,
+    name: String,
+    digest_frequency: DigestFrequency,
+    /// The lists whose membership the subscriber has confirmed.
+    list_ids: HashSet<Uuid>,
+}
+
+/// Maps the email address of every task to its subscriber, subscribers that
+/// are no longer confirmed on any list are left out.
+#[tracing::instrument(skip_all)]
+async fn get_confirmed_subscribers(
+    txn: &DatabaseTransaction,
+    tasks: &[issue_delivery_queue::Model],
+) -> Result<HashMap<String, Subscriber>, anyhow::Error> {
+    let subscribers = Subscriptions::find()
+        .find_with_related(ListMemberships)
+        .filter(
+            subscriptions::Column::Email.is_in(tasks.iter().map(|t| t.subscriber_email.clone())),
+        )
+        .filter(list_memberships::Column::Status.eq(SubscriptionStatus::Confirmed))
+        .all(txn)
+        .await?;
+    Ok(subscribers
+        .into_iter()
+        .map(|(subscriber, memberships)| {
+            let subscriber_record = Subscriber {
+                id: subscriber.id,
+                name: subscriber.name,
+                digest_frequency: subscriber.digest_frequency,
+                list_ids: memberships.into_iter().map(|m| m.list_id).collect(),
+            };
+            (subscriber.email, subscriber_record)
+        })
+        .collect())
+}
+
+/// Maps every issue of the tasks to the lists it is sent to.
+#[tracing::instrument(skip_all)]
+async fn get_issue_lists(
+    txn: &DatabaseTransaction,
+    tasks: &[issue_delivery_queue::Model],
+) -> Result<HashMap<Uuid, HashSet<Uuid>>, anyhow::Error> {
+    let issue_lists = NewsletterIssueLists::find()
+        .filter(
+            newsletter_issue_lists::Column::NewsletterIssueId
+                .is_in(tasks.iter().map(|t| t.newsletter_issue_id)),
+        )
+        .all(txn)
+        .await?;
+    let mut lists: HashMap<Uuid, HashSet<Uuid>> = HashMap::new();
+    for issue_list in issue_lists {
+        lists
+            .entry(issue_list.newsletter_issue_id)
+            .or_default()
+            .insert(issue_list.list_id);
+    }
+    Ok(lists)
+}
+
+#[derive(DerivePartialModel, FromQueryResult)]
+#[sea_orm(entity = "NewsletterIssues")]
+struct NewsletterIssue {
+    newsletter_issue_id: Uuid,
+    title: String,
+    text_content: String,
+    html_content: String,
+    published_at: OffsetDateTime,
+}
+
+#[tracing::instrument(skip_all)]
+async fn get_issues(
+    txn: &DatabaseTransaction,
+    tasks: &[issue_delivery_queue::Model],
+) -> Result<HashMap<Uuid, NewsletterIssue>, anyhow::Error> {
+    let issues = NewsletterIssues::find()
+        .filter(
+            newsletter_issues::Column::NewsletterIssueId
+                .is_in(tasks.iter().map(|t| t.newsletter_issue_id)),
+        )
+        .into_partial_model::<NewsletterIssue>()
+        .all(txn)
+        .await?;
+    Ok(issues
+        .into_iter()
+        .map(|issue| (issue.newsletter_issue_id, issue))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use entity::sea_orm_active_enums::DigestFrequency;
+    use time::macros::datetime;
+
+    use super::{backoff, digest_slot, BASE_BACKOFF, MAX_BACKOFF};
+
+    #[test]
+    fn backoff_doubles_with_every_retry() {
+        assert_eq!(backoff(1), BASE_BACKOFF);
+        assert_eq!(backoff(2), BASE_BACKOFF * 2);
+        assert_eq!(backoff(3), BASE_BACKOFF * 4);
+    }
+
+    #[test]
+    fn backoff_is_capped() {
+        assert_eq!(backoff(i16::MAX), MAX_BACKOFF);
+    }
+
+    #[test]
+    fn immediate_delivery_is_not_delayed() {
+        let now = datetime!(2024-05-22 15:30 UTC);
+        assert_eq!(digest_slot(DigestFrequency::Immediately, now), now);
+    }
+
+    #[test]
+    fn daily_digests_go_out_at_the_next_slot() {
+        assert_eq!(
+            digest_slot(DigestFrequency::Daily, datetime!(2024-05-22 07:59 UTC)),
+            datetime!(2024-05-22 08:00 UTC)
+        );
+        assert_eq!(
+            digest_slot(DigestFrequency::Daily, datetime!(2024-05-22 08:00 UTC)),
+            datetime!(2024-05-23 08:00 UTC)
+        );
+    }
+
+    #[test]
+    fn weekly_digests_go_out_on_the_next_monday() {
+        // 2024-05-20 is a Monday.
+        assert_eq!(
+            digest_slot(DigestFrequency::Weekly, datetime!(2024-05-20 07:00 UTC)),
+            datetime!(2024-05-20 08:00 UTC)
+        );
+        assert_eq!(
+            digest_slot(DigestFrequency::Weekly, datetime!(2024-05-20 09:00 UTC)),
+            datetime!(2024-05-27 08:00 UTC)
+        );
+        assert_eq!(
+            digest_slot(DigestFrequency::Weekly, datetime!(2024-05-26 23:00 UTC)),
+            datetime!(2024-05-27 08:00 UTC)
+        );
+    }
+}
@@ -0,0 +1,67 @@
+use std::time::Duration;
+
+use entity::{
+    newsletter_issues::{self, Entity as NewsletterIssues},
+    sea_orm_active_enums::IssueStatus,
+};
+use sea_orm::{
+    sea_query::{LockBehavior, LockType},
+    ActiveModelTrait,
+    ActiveValue::Set,
+    ColumnTrait, DatabaseConnection, EntityTrait, IntoActiveModel, QueryFilter, QueryOrder,
+    QuerySelect, TransactionTrait,
+};
+use time::OffsetDateTime;
+use tokio_util::sync::CancellationToken;
+
+use crate::issue_delivery_worker::{enqueue_delivery_tasks, sleep_until_cancelled};
+
+/// How often the scheduler looks for issues that are due.
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+pub async fn run_scheduler_until_stopped(
+    connection: DatabaseConnection,
+    shutdown: CancellationToken,
+) -> Result<(), anyhow::Error> {
+    while !shutdown.is_cancelled() {
+        // Errors are already logged by `release_due_issues`, the next poll
+        // tries again.
+        let _ = release_due_issues(&connection).await;
+        sleep_until_cancelled(&shutdown, POLL_INTERVAL).await;
+    }
+    tracing::info!("Newsletter scheduler stopped.");
+    Ok(())
+}
+
+/// Hands every scheduled issue whose `send_at` has passed over to the
+/// delivery queue, returns how many issues have been released.
+#[tracing::instrument(skip_all, err)]
+pub async fn release_due_issues(connection: &DatabaseConnection) -> Result<u64, anyhow::Error> {
+    let mut n_released = 0;
+    loop {
+        let txn = connection.begin().await?;
+        // Locking the row makes a concurrent cancellation wait for the
+        // release, it then finds the issue no longer scheduled.
+        let Some(issue) = NewsletterIssues::find()
+            .filter(newsletter_issues::Column::Status.eq(IssueStatus::Scheduled))
+            .filter(newsletter_issues::Column::SendAt.lte(OffsetDateTime::now_utc()))
+            .order_by_asc(newsletter_issues::Column::SendAt)
+            .lock_with_behavior(LockType::Update, LockBehavior::SkipLocked)
+            .one(&txn)
+            .await?
+        else {
+            return Ok(n_released);
+        };
+        tracing::info!(
+            newsletter_issue_id = %issue.newsletter_issue_id,
+            "Releasing a scheduled newsletter issue."
+        );
+        enqueue_delivery_tasks(&txn, issue.newsletter_issue_id).await?;
+        let mut issue = issue.into_active_model();
+        issue.status = Set(IssueStatus::Published);
+        issue.published_at = Set(OffsetDateTime::now_utc());
+        issue.update(&txn).await?;
+        txn.commit().await?;
+        n_released += 1;
+    }
+}
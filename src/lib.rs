@@ -1,9 +1,20 @@
+pub mod audit;
 pub mod authentication;
 pub mod configuration;
+pub mod consent;
 pub mod domain;
 pub mod email_client;
+pub mod email_templates;
+pub mod idempotency;
+pub mod issue_delivery_worker;
+pub mod issue_scheduler;
+pub mod link_signer;
+pub mod lists;
+pub mod login_throttle;
 pub mod routes;
 pub mod session_state;
 pub mod startup;
+pub mod subscriber_data;
+pub mod subscription_sweeper;
 pub mod telemetry;
 pub mod utils;
@@ -0,0 +1,247 @@
+use hmac::{Hmac, Mac};
+use secrecy::ExposeSecret;
+use sha2::Sha256;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::startup::HmacSecret;
+
+/// Builds links that are sent to subscribers and lets the app verify them
+/// when they come back, without storing a token per link.
+///
+/// Every signature is bound to a purpose, a signature issued for one kind of
+/// link is never accepted for another one.
+#[derive(Clone)]
+pub struct LinkSigner {
+    base_url: String,
+    hmac_secret: HmacSecret,
+}
+
+#[derive(thiserror::Error, Debug)]
+#[error("The link signature is invalid.")]
+pub struct InvalidSignature;
+
+impl LinkSigner {
+    const UNSUBSCRIBE: &'static str = "unsubscribe";
+    const DATA_REQUEST: &'static str = "data_request";
+    const PREFERENCES: &'static str = "preferences";
+
+    pub fn new(base_url: String, hmac_secret: HmacSecret) -> Self {
+        Self {
+            base_url,
+            hmac_secret,
+        }
+    }
+
+    pub fn unsubscribe_url(&self, subscriber_id: Uuid) -> String {
+        format!(
+            "{}/subscriptions/unsubscribe?subscriber_id={}&signature={}",
+            self.base_url,
+            subscriber_id,
+            self.sign(Self::UNSUBSCRIBE, &subscriber_id.to_string())
+        )
+    }
+
+    pub fn verify_unsubscribe(
+        &self,
+        subscriber_id: Uuid,
+        signature: &str,
+    ) -> Result<(), InvalidSignature> {
+        self.verify(Self::UNSUBSCRIBE, &subscriber_id.to_string(), signature)
+    }
+
+    /// Lets a subscriber manage what they receive. Like the unsubscribe link
+    /// it is part of every issue and does not expire.
+    pub fn preferences_url(&self, subscriber_id: Uuid) -> String {
+        format!(
+            "{}/preferences?subscriber_id={}&signature={}",
+            self.base_url,
+            subscriber_id,
+            self.sign(Self::PREFERENCES, &subscriber_id.to_string())
+        )
+    }
+
+    pub fn verify_preferences(
+        &self,
+        subscriber_id: Uuid,
+        signature: &str,
+    ) -> Result<(), InvalidSignature> {
+        self.verify(Self::PREFERENCES, &subscriber_id.to_string(), signature)
+    }
+
+    /// Lets a subscriber download or erase their data until `expires_at`.
+    pub fn data_request_url(&self, subscriber_id: Uuid, expires_at: OffsetDateTime) -> String {
+        let expires_at = expires_at.unix_timestamp();
+        format!(
+            "{}/subscriptions/data/manage?subscriber_id={}&expires_at={}&signature={}",
+            self.base_url,
+            subscriber_id,
+            expires_at,
+            self.sign(
+                Self::DATA_REQUEST,
+                &format!("{}:{}", subscriber_id, expires_at)
+            )
+        )
+    }
+
+    /// Expired links are rejected as well.
+    pub fn verify_data_request(
+        &self,
+        subscriber_id: Uuid,
+        expires_at: i64,
+        signature: &str,
+    ) -> Result<(), InvalidSignature> {
+        self.verify(
+            Self::DATA_REQUEST,
+            &format!("{}:{}", subscriber_id, expires_at),
+            signature,
+        )?;
+        if expires_at <= OffsetDateTime::now_utc().unix_timestamp() {
+            return Err(InvalidSignature);
+        }
+        Ok(())
+    }
+
+    fn mac(&self, purpose: &str, payload: &str) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.hmac_secret.0.expose_secret().as_bytes())
+            .expect("HMAC can take key of any size");
+        mac.update(purpose.as_bytes());
+        mac.update(b":");
+        mac.update(payload.as_bytes());
+        mac
+    }
+
+    fn sign(&self, purpose: &str, payload: &str) -> String {
+        hex::encode(self.mac(purpose, payload).finalize().into_bytes())
+    }
+
+    fn verify(
+        &self,
+        purpose: &str,
+        payload: &str,
+        signature: &str,
+    ) -> Result<(), InvalidSignature> {
+        let signature = hex::decode(signature).map_err(|_| InvalidSignature)?;
+        // `verify_slice` compares in constant time
+        self.mac(purpose, payload)
+            .verify_slice(&signature)
+            .map_err(|_| InvalidSignature)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::LinkSigner;
+    use crate::startup::HmacSecret;
+    use claims::{assert_err, assert_ok};
+    use secrecy::Secret;
+    use time::{Duration, OffsetDateTime};
+    use uuid::Uuid;
+
+    fn signer(secret: &str) -> LinkSigner {
+        LinkSigner::new(
+            "http://127.0.0.1".into(),
+            HmacSecret(Secret::new(secret.into())),
+        )
+    }
+
+    fn query_value(url: &str, key: &str) -> String {
+        let url = reqwest::Url::parse(url).unwrap();
+        url.query_pairs()
+            .find(|(k, _)| k == key)
+            .unwrap()
+            .1
+            .into_owned()
+    }
+
+    fn signature_of(url: &str) -> String {
+        query_value(url, "signature")
+    }
+
+    #[test]
+    fn a_signed_unsubscribe_link_is_accepted() {
+        let signer = signer("secret");
+        let subscriber_id = Uuid::new_v4();
+        let signature = signature_of(&signer.unsubscribe_url(subscriber_id));
+        assert_ok!(signer.verify_unsubscribe(subscriber_id, &signature));
+    }
+
+    #[test]
+    fn a_signature_for_another_subscriber_is_rejected() {
+        let signer = signer("secret");
+        let signature = signature_of(&signer.unsubscribe_url(Uuid::new_v4()));
+        assert_err!(signer.verify_unsubscribe(Uuid::new_v4(), &signature));
+    }
+
+    #[test]
+    fn a_signature_made_with_another_secret_is_rejected() {
+        let subscriber_id = Uuid::new_v4();
+        let signature = signature_of(&signer("other").unsubscribe_url(subscriber_id));
+        assert_err!(signer("secret").verify_unsubscribe(subscriber_id, &signature));
+    }
+
+    #[test]
+    fn a_malformed_signature_is_rejected() {
+        assert_err!(signer("secret").verify_unsubscribe(Uuid::new_v4(), "not-hex"));
+    }
+
+    #[test]
+    fn a_data_request_link_is_accepted_until_it_expires() {
+        let signer = signer("secret");
+        let subscriber_id = Uuid::new_v4();
+        let url = signer.data_request_url(
+            subscriber_id,
+            OffsetDateTime::now_utc() + Duration::hours(1),
+        );
+        let expires_at: i64 = query_value(&url, "expires_at").parse().unwrap();
+        assert_ok!(signer.verify_data_request(subscriber_id, expires_at, &signature_of(&url)));
+
+        let url = signer.data_request_url(
+            subscriber_id,
+            OffsetDateTime::now_utc() - Duration::seconds(1),
+        );
+        let expires_at: i64 = query_value(&url, "expires_at").parse().unwrap();
+        assert_err!(signer.verify_data_request(subscriber_id, expires_at, &signature_of(&url)));
+    }
+
+    #[test]
+    fn the_expiry_of_a_data_request_link_cannot_be_extended() {
+        let signer = signer("secret");
+        let subscriber_id = Uuid::new_v4();
+        let url = signer.data_request_url(
+            subscriber_id,
+            OffsetDateTime::now_utc() + Duration::hours(1),
+        );
+        let expires_at: i64 = query_value(&url, "expires_at").parse().unwrap();
+        assert_err!(signer.verify_data_request(
+            subscriber_id,
+            expires_at + 3600,
+            &signature_of(&url)
+        ));
+    }
+
+    #[test]
+    fn a_signed_preferences_link_is_accepted() {
+        let signer = signer("secret");
+        let subscriber_id = Uuid::new_v4();
+        let signature = signature_of(&signer.preferences_url(subscriber_id));
+        assert_ok!(signer.verify_preferences(subscriber_id, &signature));
+    }
+
+    #[test]
+    fn an_unsubscribe_signature_is_not_a_preferences_signature() {
+        let signer = signer("secret");
+        let subscriber_id = Uuid::new_v4();
+        let signature = signature_of(&signer.unsubscribe_url(subscriber_id));
+        assert_err!(signer.verify_preferences(subscriber_id, &signature));
+    }
+
+    #[test]
+    fn an_unsubscribe_signature_is_not_a_data_request_signature() {
+        let signer = signer("secret");
+        let subscriber_id = Uuid::new_v4();
+        let signature = signature_of(&signer.unsubscribe_url(subscriber_id));
+        let expires_at = (OffsetDateTime::now_utc() + Duration::hours(1)).unix_timestamp();
+        assert_err!(signer.verify_data_request(subscriber_id, expires_at, &signature));
+    }
+}
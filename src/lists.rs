@@ -0,0 +1,81 @@
+use entity::{
+    list_memberships::{self, Entity as ListMemberships},
+    lists::{self, Entity as Lists},
+    sea_orm_active_enums::SubscriptionStatus,
+};
+use sea_orm::{
+    sea_query::OnConflict, ActiveValue::Set, ColumnTrait, ConnectionTrait, DbErr, EntityTrait,
+    QueryFilter, QueryOrder,
+};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+/// The list subscribers join when they do not ask for a specific one. It is
+/// created by the migration that introduced lists.
+pub const DEFAULT_LIST: &str = "newsletter";
+
+#[tracing::instrument(skip(db))]
+pub async fn find_list(
+    db: &impl ConnectionTrait,
+    slug: &str,
+) -> Result<Option<lists::Model>, DbErr> {
+    Lists::find()
+        .filter(lists::Column::Slug.eq(slug))
+        .one(db)
+        .await
+}
+
+#[tracing::instrument(skip(db))]
+pub async fn default_list(db: &impl ConnectionTrait) -> Result<lists::Model, DbErr> {
+    find_list(db, DEFAULT_LIST)
+        .await?
+        .ok_or_else(|| DbErr::RecordNotFound("The default list is missing.".to_owned()))
+}
+
+#[tracing::instrument(skip(db))]
+pub async fn all_lists(db: &impl ConnectionTrait) -> Result<Vec<lists::Model>, DbErr> {
+    Lists::find()
+        .order_by_asc(lists::Column::Name)
+        .all(db)
+        .await
+}
+
+#[tracing::instrument(skip(db))]
+pub async fn find_membership(
+    db: &impl ConnectionTrait,
+    list_id: Uuid,
+    subscriber_id: Uuid,
+) -> Result<Option<list_memberships::Model>, DbErr> {
+    ListMemberships::find_by_id((list_id, subscriber_id))
+        .one(db)
+        .await
+}
+
+/// Adds a subscriber to a list, or moves their existing membership to the
+/// given status.
+#[tracing::instrument(skip(db))]
+pub async fn join_list(
+    db: &impl ConnectionTrait,
+    list_id: Uuid,
+    subscriber_id: Uuid,
+    status: SubscriptionStatus,
+) -> Result<(), DbErr> {
+    let membership = list_memberships::ActiveModel {
+        list_id: Set(list_id),
+        subscriber_id: Set(subscriber_id),
+        status: Set(status),
+        joined_at: Set(OffsetDateTime::now_utc()),
+    };
+    ListMemberships::insert(membership)
+        .on_conflict(
+            OnConflict::columns([
+                list_memberships::Column::ListId,
+                list_memberships::Column::SubscriberId,
+            ])
+            .update_column(list_memberships::Column::Status)
+            .to_owned(),
+        )
+        .exec_without_returning(db)
+        .await?;
+    Ok(())
+}
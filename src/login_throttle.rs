@@ -0,0 +1,192 @@
+use sha2::{Digest, Sha256};
+use tower_sessions_redis_store::fred::{
+    clients::RedisPool, error::RedisError, interfaces::KeysInterface, types::Expiration,
+};
+
+use crate::configuration::LoginThrottleSettings;
+
+/// Counts failed logins per username and per IP address in Redis, slows
+/// down further attempts and locks them out for a while once there are too
+/// many.
+#[derive(Clone)]
+pub struct LoginThrottle {
+    pool: RedisPool,
+    settings: LoginThrottleSettings,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LockoutScope {
+    Username,
+    IpAddress,
+}
+
+/// A lockout that has just been triggered.
+#[derive(Debug)]
+pub struct Lockout {
+    pub scope: LockoutScope,
+    pub failures: u64,
+    pub duration: time::Duration,
+}
+
+impl LoginThrottle {
+    pub fn new(pool: RedisPool, settings: LoginThrottleSettings) -> Self {
+        Self { pool, settings }
+    }
+
+    /// How much longer logins are blocked for the username or the address,
+    /// if they are.
+    #[tracing::instrument(skip(self, username))]
+    pub async fn locked_for(
+        &self,
+        username: &str,
+        ip_address: Option<&str>,
+    ) -> Result<Option<time::Duration>, RedisError> {
+        let mut remaining = None;
+        for key in self.keys("lockout", username, ip_address) {
+            let ttl: i64 = self.pool.ttl(key).await?;
+            if ttl > 0 {
+                remaining = remaining.max(Some(time::Duration::seconds(ttl)));
+            }
+        }
+        Ok(remaining)
+    }
+
+    /// How long to wait before checking the password, longer with every
+    /// failed attempt for the username or from the address.
+    #[tracing::instrument(skip(self, username))]
+    pub async fn delay(
+        &self,
+        username: &str,
+        ip_address: Option<&str>,
+    ) -> Result<std::time::Duration, RedisError> {
+        let mut failures = 0;
+        for key in self.keys("failures", username, ip_address) {
+            let count: Option<u64> = self.pool.get(key).await?;
+            failures = failures.max(count.unwrap_or(0));
+        }
+        Ok(progressive_delay(
+            failures,
+            std::time::Duration::from_millis(self.settings.base_delay_milliseconds),
+            std::time::Duration::from_millis(self.settings.max_delay_milliseconds),
+        ))
+    }
+
+    /// Counts a failed attempt, and locks the username and the address out
+    /// once they reach their limit. Both can be locked out by the same
+    /// attempt, every lockout it triggered is returned.
+    #[tracing::instrument(skip(self, username))]
+    pub async fn record_failure(
+        &self,
+        username: &str,
+        ip_address: Option<&str>,
+    ) -> Result<Vec<Lockout>, RedisError> {
+        let mut lockouts = Vec::new();
+        let limits = [
+            (
+                LockoutScope::Username,
+                Some(self.username_key("failures", username)),
+                self.settings.max_failures_per_username,
+            ),
+            (
+                LockoutScope::IpAddress,
+                ip_address.map(|ip| self.ip_key("failures", ip)),
+                self.settings.max_failures_per_ip,
+            ),
+        ];
+        for (scope, key, max_failures) in limits {
+            let Some(key) = key else { continue };
+            let failures: u64 = self.pool.incr(&key).await?;
+            if failures == 1 {
+                let _: () = self.pool.expire(&key, self.settings.window_seconds).await?;
+            }
+            if failures >= max_failures {
+                let lockout_key = key.replacen(":failures:", ":lockout:", 1);
+                let _: () = self
+                    .pool
+                    .set(
+                        lockout_key,
+                        failures,
+                        Some(Expiration::EX(self.settings.lockout_seconds)),
+                        None,
+                        false,
+                    )
+                    .await?;
+                // Once the lockout is over the count starts from scratch.
+                let _: () = self.pool.del(&key).await?;
+                lockouts.push(Lockout {
+                    scope,
+                    failures,
+                    duration: time::Duration::seconds(self.settings.lockout_seconds),
+                });
+            }
+        }
+        Ok(lockouts)
+    }
+
+    /// Forgets the failed attempts for a username after a successful login.
+    /// Those from the address are kept, one valid account must not reset the
+    /// count of an attacker trying many.
+    #[tracing::instrument(skip(self, username))]
+    pub async fn record_success(&self, username: &str) -> Result<(), RedisError> {
+        self.pool.del(self.username_key("failures", username)).await
+    }
+
+    fn keys(&self, kind: &str, username: &str, ip_address: Option<&str>) -> Vec<String> {
+        let mut keys = vec![self.username_key(kind, username)];
+        keys.extend(ip_address.map(|ip| self.ip_key(kind, ip)));
+        keys
+    }
+
+    /// Usernames are hashed, so that whatever is typed into the form makes a
+    /// short key and does not end up in Redis as is.
+    fn username_key(&self, kind: &str, username: &str) -> String {
+        let hash = hex::encode(Sha256::digest(username.as_bytes()));
+        format!("{}:{}:username:{}", self.settings.key_prefix, kind, hash)
+    }
+
+    fn ip_key(&self, kind: &str, ip_address: &str) -> String {
+        format!("{}:{}:ip:{}", self.settings.key_prefix, kind, ip_address)
+    }
+}
+
+/// No delay for the first attempt, then `base` doubled with every failure,
+/// at most `max`.
+pub fn progressive_delay(
+    failures: u64,
+    base: std::time::Duration,
+    max: std::time::Duration,
+) -> std::time::Duration {
+    if failures == 0 {
+        return std::time::Duration::ZERO;
+    }
+    let factor = 2u32.saturating_pow((failures - 1).min(31) as u32);
+    base.saturating_mul(factor).min(max)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::progressive_delay;
+
+    const BASE: Duration = Duration::from_millis(250);
+    const MAX: Duration = Duration::from_secs(4);
+
+    #[test]
+    fn the_first_attempt_is_not_delayed() {
+        assert_eq!(progressive_delay(0, BASE, MAX), Duration::ZERO);
+    }
+
+    #[test]
+    fn the_delay_doubles_with_every_failure() {
+        assert_eq!(progressive_delay(1, BASE, MAX), Duration::from_millis(250));
+        assert_eq!(progressive_delay(2, BASE, MAX), Duration::from_millis(500));
+        assert_eq!(progressive_delay(3, BASE, MAX), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn the_delay_is_capped() {
+        assert_eq!(progressive_delay(6, BASE, MAX), MAX);
+        assert_eq!(progressive_delay(u64::MAX, BASE, MAX), MAX);
+    }
+}
@@ -1,18 +1,29 @@
 mod admin;
+mod archive;
 mod health_check;
 mod home;
+mod invitations;
 mod login;
+mod preferences;
+mod single_use_token;
 mod subscriptions;
 mod subscriptions_confirm;
+mod subscriptions_data;
+mod subscriptions_unsubscribe;
 
 pub use admin::*;
+pub use archive::*;
 use axum::response::{IntoResponse, Response};
 use axum_macros::FromRequest;
 pub use health_check::*;
 pub use home::*;
+pub use invitations::*;
 pub use login::*;
+pub use preferences::*;
 pub use subscriptions::*;
 pub use subscriptions_confirm::*;
+pub use subscriptions_data::*;
+pub use subscriptions_unsubscribe::*;
 
 #[derive(FromRequest)]
 #[from_request(via(axum::Json), rejection(SubscribeError))]
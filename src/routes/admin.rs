@@ -1,9 +1,17 @@
 mod dashboard;
+mod lists;
 mod logout;
 mod newsletter;
 mod password;
+mod subscribers;
+mod two_factor;
+mod users;
 
 pub use dashboard::admin_dashboard;
+pub use lists::*;
 pub use logout::log_out;
 pub use newsletter::*;
 pub use password::*;
+pub use subscribers::*;
+pub use two_factor::*;
+pub use users::*;
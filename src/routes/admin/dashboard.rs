@@ -3,16 +3,20 @@ use axum::{
     extract::State,
     http::StatusCode,
     response::{Html, IntoResponse, Redirect, Response},
+    Extension,
 };
-use entity::prelude::Users;
+use entity::{prelude::Users, sea_orm_active_enums::UserRole};
 use handlebars::Handlebars;
 use sea_orm::{DatabaseConnection, DerivePartialModel, EntityTrait, FromQueryResult};
 use uuid::Uuid;
 
-use crate::{session_state::TypedSession, startup::AppState, utils::e500};
+use crate::{
+    authentication::is_allowed, session_state::TypedSession, startup::AppState, utils::e500,
+};
 
 pub async fn admin_dashboard(
     State(state): State<AppState>,
+    Extension(role): Extension<UserRole>,
     session: TypedSession,
 ) -> Result<Response, Response> {
     let username = if let Some(user_id) = session.get_user_id().await.map_err(e500)? {
@@ -27,7 +31,11 @@ pub async fn admin_dashboard(
     let html = reg
         .render_template(
             include_str!("./dashboard.html"),
-            &serde_json::json!({"username": username}),
+            &serde_json::json!({
+                "username": username,
+                "can_edit": is_allowed(role, UserRole::Editor),
+                "can_manage_users": is_allowed(role, UserRole::Owner),
+            }),
         )
         .map_err(e500)?;
 
@@ -0,0 +1,4 @@
+mod get;
+pub use get::mailing_lists;
+mod post;
+pub use post::create_mailing_list;
@@ -0,0 +1,52 @@
+use axum::{
+    extract::State,
+    response::{Html, IntoResponse, Response},
+};
+use axum_messages::Messages;
+use entity::{
+    list_memberships::{self, Entity as ListMemberships},
+    sea_orm_active_enums::SubscriptionStatus,
+};
+use handlebars::Handlebars;
+use sea_orm::{ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter};
+use serde_json::json;
+use std::fmt::Write;
+
+use crate::{
+    lists::{all_lists, DEFAULT_LIST},
+    startup::AppState,
+    utils::e500,
+};
+
+pub async fn mailing_lists(
+    State(state): State<AppState>,
+    messages: Messages,
+) -> Result<Response, Response> {
+    let mut msg_html = String::new();
+    for m in messages.into_iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.message).unwrap();
+    }
+    let mut lists = Vec::new();
+    for list in all_lists(&state.connection).await.map_err(e500)? {
+        let n_confirmed = ListMemberships::find()
+            .filter(list_memberships::Column::ListId.eq(list.id))
+            .filter(list_memberships::Column::Status.eq(SubscriptionStatus::Confirmed))
+            .count(&state.connection)
+            .await
+            .map_err(e500)?;
+        lists.push(json!({
+            "slug": list.slug,
+            "name": list.name,
+            "is_default": list.slug == DEFAULT_LIST,
+            "n_confirmed": n_confirmed,
+        }));
+    }
+
+    let html = Handlebars::new()
+        .render_template(
+            include_str!("./get.html"),
+            &json!({ "messages": msg_html, "lists": lists }),
+        )
+        .map_err(e500)?;
+    Ok(Html::from(html).into_response())
+}
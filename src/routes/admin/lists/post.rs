@@ -0,0 +1,61 @@
+use axum::{
+    extract::State,
+    response::{IntoResponse, Redirect, Response},
+    Form,
+};
+use axum_messages::Messages;
+use entity::lists::{self, Entity as Lists};
+use sea_orm::{sea_query::OnConflict, ActiveValue::Set, EntityTrait};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::{domain::ListSlug, startup::AppState, utils::e500};
+
+#[derive(serde::Deserialize, Debug)]
+pub struct FormData {
+    slug: String,
+    name: String,
+}
+
+#[tracing::instrument(name = "Create a mailing list", skip(state, messages))]
+pub async fn create_mailing_list(
+    State(state): State<AppState>,
+    messages: Messages,
+    Form(form): Form<FormData>,
+) -> Result<Response, Response> {
+    let to_lists = Redirect::to("/admin/lists").into_response();
+    let slug = match ListSlug::parse(form.slug.trim().to_owned()) {
+        Ok(slug) => slug,
+        Err(e) => {
+            messages.error(e);
+            return Ok(to_lists);
+        }
+    };
+    let name = form.name.trim();
+    if name.is_empty() {
+        messages.error("The list needs a name.");
+        return Ok(to_lists);
+    }
+
+    let list = lists::ActiveModel {
+        id: Set(Uuid::new_v4()),
+        slug: Set(slug.as_ref().to_owned()),
+        name: Set(name.to_owned()),
+        created_at: Set(OffsetDateTime::now_utc()),
+    };
+    let n_inserted_rows = Lists::insert(list)
+        .on_conflict(
+            OnConflict::column(lists::Column::Slug)
+                .do_nothing()
+                .to_owned(),
+        )
+        .exec_without_returning(&state.connection)
+        .await
+        .map_err(e500)?;
+    if n_inserted_rows == 0 {
+        messages.error(format!("There already is a list called {}.", slug.as_ref()));
+    } else {
+        messages.info(format!("The list {} has been created.", slug.as_ref()));
+    }
+    Ok(to_lists)
+}
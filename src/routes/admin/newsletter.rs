@@ -1,5 +1,9 @@
 mod get;
+mod history;
 mod post;
+mod scheduled;
 
 pub use get::publish_newsletter_form;
+pub use history::{newsletter_history, newsletter_issue_preview};
 pub use post::publish_newsletter;
+pub use scheduled::{cancel_scheduled_newsletter, reschedule_newsletter, scheduled_newsletters};
@@ -1,14 +1,39 @@
-use axum::response::{Html, IntoResponse, Response};
+use axum::{
+    extract::State,
+    response::{Html, IntoResponse, Response},
+};
 use axum_messages::Messages;
 use handlebars::Handlebars;
 use serde_json::json;
 use std::fmt::Write;
+use uuid::Uuid;
 
-pub async fn publish_newsletter_form(messages: Messages) -> Response {
+use crate::{
+    lists::{all_lists, DEFAULT_LIST},
+    startup::AppState,
+    utils::e500,
+};
+
+pub async fn publish_newsletter_form(
+    State(state): State<AppState>,
+    messages: Messages,
+) -> Result<Response, Response> {
     let mut msg_html = String::new();
     for m in messages.into_iter() {
         writeln!(msg_html, "<p><i>{}</i></p>", m.message).unwrap();
     }
+    let lists: Vec<_> = all_lists(&state.connection)
+        .await
+        .map_err(e500)?
+        .into_iter()
+        .map(|list| {
+            json!({
+                "slug": list.slug,
+                "name": list.name,
+                "checked": list.slug == DEFAULT_LIST,
+            })
+        })
+        .collect();
 
     let reg = Handlebars::new();
     let html = reg
@@ -16,9 +41,11 @@ pub async fn publish_newsletter_form(messages: Messages) -> Response {
             include_str!("./get.html"),
             &json!({
                 "messages": msg_html,
+                "idempotency_key": Uuid::new_v4().to_string(),
+                "lists": lists,
             }),
         )
         .expect("Failed to render password page.");
 
-    Html::from(html).into_response()
+    Ok(Html::from(html).into_response())
 }
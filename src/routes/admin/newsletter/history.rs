@@ -0,0 +1,97 @@
+use anyhow::Context;
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Response},
+};
+use entity::{
+    newsletter_issues::{self, Entity as NewsletterIssues},
+    sea_orm_active_enums::IssueStatus,
+    users::Entity as Users,
+};
+use handlebars::Handlebars;
+use sea_orm::{DatabaseConnection, EntityTrait, QueryOrder};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    startup::AppState,
+    utils::{e500, format_timestamp},
+};
+
+#[derive(serde::Serialize)]
+struct IssueSummary {
+    newsletter_issue_id: Uuid,
+    title: String,
+    published_at: String,
+    status: IssueStatus,
+    author: Option<String>,
+    n_recipients: i32,
+    n_delivered: i32,
+    n_failed: i32,
+}
+
+pub async fn newsletter_history(State(state): State<AppState>) -> Result<Response, Response> {
+    let issues = get_issue_summaries(&state.connection).await.map_err(e500)?;
+
+    let reg = Handlebars::new();
+    let html = reg
+        .render_template(include_str!("./history.html"), &json!({ "issues": issues }))
+        .map_err(e500)?;
+
+    Ok(Html::from(html).into_response())
+}
+
+pub async fn newsletter_issue_preview(
+    State(state): State<AppState>,
+    Path(issue_id): Path<Uuid>,
+) -> Result<Response, Response> {
+    let Some(issue) = NewsletterIssues::find_by_id(issue_id)
+        .one(&state.connection)
+        .await
+        .map_err(e500)?
+    else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+
+    let reg = Handlebars::new();
+    let html = reg
+        .render_template(
+            include_str!("./preview.html"),
+            &json!({
+                "newsletter_issue_id": issue.newsletter_issue_id,
+                "title": issue.title,
+                "published_at": format_timestamp(issue.published_at),
+                "text_content": issue.text_content,
+                "html_content": issue.html_content,
+            }),
+        )
+        .map_err(e500)?;
+
+    Ok(Html::from(html).into_response())
+}
+
+#[tracing::instrument(name = "Get newsletter issue summaries", skip(conn))]
+async fn get_issue_summaries(
+    conn: &DatabaseConnection,
+) -> Result<Vec<IssueSummary>, anyhow::Error> {
+    let issues = NewsletterIssues::find()
+        .find_also_related(Users)
+        .order_by_desc(newsletter_issues::Column::PublishedAt)
+        .all(conn)
+        .await
+        .context("Failed to perform a query to retrieve the newsletter issues.")?
+        .into_iter()
+        .map(|(issue, author)| IssueSummary {
+            newsletter_issue_id: issue.newsletter_issue_id,
+            title: issue.title,
+            published_at: format_timestamp(issue.published_at),
+            status: issue.status,
+            author: author.map(|a| a.username),
+            n_recipients: issue.n_recipients,
+            n_delivered: issue.n_delivered,
+            n_failed: issue.n_failed,
+        })
+        .collect();
+    Ok(issues)
+}
@@ -1,40 +1,58 @@
-use std::ops::Deref;
-
 use anyhow::Context;
 use axum::{
     extract::State,
     http::{header, HeaderMap, HeaderValue, StatusCode},
-    response::{IntoResponse, Response},
-    Extension, Form,
+    response::{IntoResponse, Redirect, Response},
+    Extension,
+};
+use axum_extra::extract::{Form, FormRejection};
+use axum_messages::Messages;
+use entity::{
+    newsletter_issue_lists::{self, Entity as NewsletterIssueLists},
+    newsletter_issues,
+    sea_orm_active_enums::IssueStatus,
 };
-use entity::subscriptions::{self, Entity as Subscriptions};
 use sea_orm::{
-    ColumnTrait, DatabaseConnection, DerivePartialModel, EntityTrait, FromQueryResult, QueryFilter,
+    ActiveModelTrait, ActiveValue::Set, DatabaseConnection, DatabaseTransaction, DbErr, EntityTrait,
 };
 use serde::Serialize;
+use time::OffsetDateTime;
+use uuid::Uuid;
 
 use crate::{
     authentication::UserId,
-    domain::SubscriberEmail,
+    email_templates::EmailTemplates,
+    idempotency::{save_response, try_processing, IdempotencyKey, NextAction},
+    issue_delivery_worker::enqueue_delivery_tasks,
+    lists::{default_list, find_list},
     routes::{error_chain_fmt, AppJson},
     startup::AppState,
+    utils::{format_timestamp, parse_timestamp},
 };
 
 #[derive(serde::Deserialize)]
 pub struct FormData {
     title: String,
     content_html: String,
+    /// Left empty to generate it from the HTML content.
+    #[serde(default)]
     content_txt: String,
-}
-
-struct ConfirmedSubscriber {
-    email: SubscriberEmail,
+    idempotency_key: String,
+    /// Left empty to publish the issue right away.
+    #[serde(default)]
+    send_at: String,
+    /// The lists to send the issue to, left out to send it to the default
+    /// list.
+    #[serde(default)]
+    lists: Vec<String>,
 }
 
 #[derive(thiserror::Error)]
 pub enum PublishError {
-    #[error("Authentication failed")]
-    AuthError(#[source] anyhow::Error),
+    #[error("{0}")]
+    ValidationError(String),
+    #[error("{0}")]
+    InvalidForm(String),
     #[error(transparent)]
     UnexpectedError(#[from] anyhow::Error),
 }
@@ -57,7 +75,8 @@ impl IntoResponse for PublishError {
 
         let status = match &self {
             Self::UnexpectedError(_) => StatusCode::INTERNAL_SERVER_ERROR,
-            Self::AuthError(_) => StatusCode::UNAUTHORIZED,
+            Self::ValidationError(_) => StatusCode::BAD_REQUEST,
+            Self::InvalidForm(_) => StatusCode::UNPROCESSABLE_ENTITY,
         };
 
         let message = self.to_string();
@@ -68,65 +87,176 @@ impl IntoResponse for PublishError {
     }
 }
 
+// `axum_extra`'s form answers 400 to a body it cannot deserialize, we keep
+// answering 422 like `axum::Form` did.
+impl From<FormRejection> for PublishError {
+    fn from(rejection: FormRejection) -> Self {
+        Self::InvalidForm(rejection.to_string())
+    }
+}
+
 #[tracing::instrument(
     name = "Publish a newsletter issue",
-    skip(state, body),
+    skip(state, messages, form),
     fields(username=tracing::field::Empty, user_id=tracing::field::Empty)
 )]
 pub async fn publish_newsletter(
     State(state): State<AppState>,
     user_id: Extension<UserId>,
-    Form(body): Form<FormData>,
-) -> Result<StatusCode, PublishError> {
-    tracing::info!("Publishing a newsletter issue: {}", user_id.deref());
-    let subscribers = get_confirmed_subscribers(&state.connection).await?;
-    for subscriber in subscribers {
-        match subscriber {
-            Ok(subscriber) => {
-                state
-                    .email_client
-                    .send_email(
-                        &subscriber.email,
-                        &body.title,
-                        &body.content_html,
-                        &body.content_txt,
-                    )
-                    .await
-                    .with_context(|| {
-                        format!("Failed to send newsletter issue to {}", subscriber.email)
-                    })?;
-            }
-            Err(error) => {
-                tracing::warn!(
-                    error.cause_chain = ?error,
-                    "Skipping a confirmed subscriber. \
-                    Their stored contact details are invalid",
-                );
-            }
+    messages: Messages,
+    form: Result<Form<FormData>, FormRejection>,
+) -> Result<Response, PublishError> {
+    let Form(form) = form?;
+    tracing::info!("Publishing a newsletter issue: {}", *user_id);
+    let user_id = **user_id;
+    let FormData {
+        title,
+        content_html,
+        content_txt,
+        idempotency_key,
+        send_at,
+        lists,
+    } = form;
+    let idempotency_key: IdempotencyKey = idempotency_key
+        .try_into()
+        .map_err(|e: anyhow::Error| PublishError::ValidationError(e.to_string()))?;
+    let send_at = parse_send_at(&send_at)?;
+    validate_content(&content_html, &content_txt)?;
+    let list_ids = resolve_lists(&state.connection, &lists).await?;
+    let txn = match try_processing(&state.connection, &idempotency_key, user_id).await? {
+        NextAction::StartProcessing(txn) => txn,
+        NextAction::ReturnSavedResponse(saved_response) => {
+            success_message(messages, send_at);
+            return Ok(saved_response);
         }
+    };
+
+    let issue_id =
+        insert_newsletter_issue(&txn, user_id, &title, &content_txt, &content_html, send_at)
+            .await
+            .context("Failed to store newsletter issue details")?;
+    insert_issue_lists(&txn, issue_id, &list_ids)
+        .await
+        .context("Failed to store the lists of the newsletter issue")?;
+    // Scheduled issues are queued by the scheduler once they are due.
+    if send_at.is_none() {
+        enqueue_delivery_tasks(&txn, issue_id)
+            .await
+            .context("Failed to enqueue delivery tasks")?;
+    }
+    success_message(messages, send_at);
+    let response = Redirect::to("/admin/newsletters").into_response();
+    let response = save_response(txn, &idempotency_key, user_id, response).await?;
+    Ok(response)
+}
+
+fn success_message(messages: Messages, send_at: Option<OffsetDateTime>) {
+    match send_at {
+        Some(send_at) => messages.info(format!(
+            "The newsletter issue has been scheduled for {}!",
+            format_timestamp(send_at)
+        )),
+        None => messages.info("The newsletter issue has been published!"),
+    };
+}
+
+/// Issue content may refer to subscriber variables, it is rendered for every
+/// recipient when the issue is delivered.
+fn validate_content(content_html: &str, content_txt: &str) -> Result<(), PublishError> {
+    for (part, content) in [("HTML", content_html), ("text", content_txt)] {
+        EmailTemplates::validate_content(content).map_err(|e| {
+            PublishError::ValidationError(format!("Invalid {} content: {}", part, e))
+        })?;
+    }
+    Ok(())
+}
+
+/// An empty value means "now", anything else has to lie in the future.
+fn parse_send_at(send_at: &str) -> Result<Option<OffsetDateTime>, PublishError> {
+    if send_at.trim().is_empty() {
+        return Ok(None);
+    }
+    let send_at = parse_timestamp(send_at)
+        .map_err(|e| PublishError::ValidationError(format!("Invalid send_at: {}", e)))?;
+    if send_at <= OffsetDateTime::now_utc() {
+        return Err(PublishError::ValidationError(
+            "send_at must be in the future.".to_owned(),
+        ));
     }
-    Ok(StatusCode::OK)
+    Ok(Some(send_at))
 }
 
-#[tracing::instrument(name = "Get confirmed subscribers", skip(conn))]
-async fn get_confirmed_subscribers(
-    conn: &DatabaseConnection,
-) -> Result<Vec<Result<ConfirmedSubscriber, anyhow::Error>>, anyhow::Error> {
-    #[derive(DerivePartialModel, FromQueryResult, Debug)]
-    #[sea_orm(entity = "Subscriptions")]
-    struct Row {
-        email: String,
+/// Looks up the lists an issue is sent to by their slug.
+async fn resolve_lists(
+    connection: &DatabaseConnection,
+    slugs: &[String],
+) -> Result<Vec<Uuid>, PublishError> {
+    if slugs.is_empty() {
+        let list = default_list(connection)
+            .await
+            .context("Failed to look up the default list")?;
+        return Ok(vec![list.id]);
+    }
+    let mut list_ids = Vec::with_capacity(slugs.len());
+    for slug in slugs {
+        let list = find_list(connection, slug)
+            .await
+            .context("Failed to look up a list")?
+            .ok_or_else(|| {
+                PublishError::ValidationError(format!("There is no list called {}.", slug))
+            })?;
+        if !list_ids.contains(&list.id) {
+            list_ids.push(list.id);
+        }
     }
+    Ok(list_ids)
+}
+
+#[tracing::instrument(skip_all)]
+async fn insert_issue_lists(
+    txn: &DatabaseTransaction,
+    newsletter_issue_id: Uuid,
+    list_ids: &[Uuid],
+) -> Result<(), DbErr> {
+    NewsletterIssueLists::insert_many(list_ids.iter().map(|list_id| {
+        newsletter_issue_lists::ActiveModel {
+            newsletter_issue_id: Set(newsletter_issue_id),
+            list_id: Set(*list_id),
+        }
+    }))
+    .exec_without_returning(txn)
+    .await?;
+    Ok(())
+}
 
-    Ok(Subscriptions::find()
-        .filter(subscriptions::Column::Status.contains("confirmed"))
-        .into_partial_model::<Row>()
-        .all(conn)
-        .await?
-        .into_iter()
-        .map(|r| match SubscriberEmail::parse(r.email) {
-            Ok(email) => Ok(ConfirmedSubscriber { email }),
-            Err(error) => Err(anyhow::anyhow!(error)),
-        })
-        .collect())
+#[tracing::instrument(skip_all)]
+async fn insert_newsletter_issue(
+    txn: &DatabaseTransaction,
+    author_id: Uuid,
+    title: &str,
+    text_content: &str,
+    html_content: &str,
+    send_at: Option<OffsetDateTime>,
+) -> Result<Uuid, DbErr> {
+    let newsletter_issue_id = Uuid::new_v4();
+    let status = if send_at.is_some() {
+        IssueStatus::Scheduled
+    } else {
+        IssueStatus::Published
+    };
+    newsletter_issues::ActiveModel {
+        newsletter_issue_id: Set(newsletter_issue_id),
+        title: Set(title.to_owned()),
+        text_content: Set(text_content.to_owned()),
+        html_content: Set(html_content.to_owned()),
+        // Overwritten with the actual time once a scheduled issue is released
+        published_at: Set(send_at.unwrap_or_else(OffsetDateTime::now_utc)),
+        author_id: Set(Some(author_id)),
+        status: Set(status),
+        send_at: Set(send_at),
+        ..Default::default()
+    }
+    .insert(txn)
+    .await?;
+    Ok(newsletter_issue_id)
 }
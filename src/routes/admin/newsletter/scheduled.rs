@@ -0,0 +1,139 @@
+use anyhow::Context;
+use axum::{
+    extract::{Path, State},
+    response::{Html, IntoResponse, Redirect, Response},
+    Form,
+};
+use axum_messages::Messages;
+use entity::{
+    newsletter_issues::{self, Entity as NewsletterIssues},
+    sea_orm_active_enums::IssueStatus,
+};
+use handlebars::Handlebars;
+use sea_orm::{
+    sea_query::Expr, ActiveEnum, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter,
+    QueryOrder,
+};
+use serde_json::json;
+use std::fmt::Write;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::{
+    startup::AppState,
+    utils::{e500, format_timestamp, parse_timestamp},
+};
+
+#[derive(serde::Serialize)]
+struct ScheduledIssue {
+    newsletter_issue_id: Uuid,
+    title: String,
+    send_at: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct RescheduleFormData {
+    send_at: String,
+}
+
+pub async fn scheduled_newsletters(
+    State(state): State<AppState>,
+    messages: Messages,
+) -> Result<Response, Response> {
+    let mut msg_html = String::new();
+    for m in messages.into_iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.message).unwrap();
+    }
+    let issues = get_scheduled_issues(&state.connection)
+        .await
+        .map_err(e500)?;
+
+    let reg = Handlebars::new();
+    let html = reg
+        .render_template(
+            include_str!("./scheduled.html"),
+            &json!({ "messages": msg_html, "issues": issues }),
+        )
+        .map_err(e500)?;
+
+    Ok(Html::from(html).into_response())
+}
+
+#[tracing::instrument(name = "Cancel a scheduled newsletter issue", skip(state, messages))]
+pub async fn cancel_scheduled_newsletter(
+    State(state): State<AppState>,
+    Path(issue_id): Path<Uuid>,
+    messages: Messages,
+) -> Result<Response, Response> {
+    let n_updated = NewsletterIssues::update_many()
+        .col_expr(
+            newsletter_issues::Column::Status,
+            IssueStatus::Cancelled.as_enum(),
+        )
+        .filter(newsletter_issues::Column::NewsletterIssueId.eq(issue_id))
+        .filter(newsletter_issues::Column::Status.eq(IssueStatus::Scheduled))
+        .exec(&state.connection)
+        .await
+        .map_err(e500)?
+        .rows_affected;
+    if n_updated == 0 {
+        messages.error("The newsletter issue is no longer scheduled.");
+    } else {
+        messages.info("The scheduled newsletter issue has been cancelled.");
+    }
+    Ok(Redirect::to("/admin/newsletters/scheduled").into_response())
+}
+
+#[tracing::instrument(name = "Reschedule a newsletter issue", skip(state, messages, form))]
+pub async fn reschedule_newsletter(
+    State(state): State<AppState>,
+    Path(issue_id): Path<Uuid>,
+    messages: Messages,
+    Form(form): Form<RescheduleFormData>,
+) -> Result<Response, Response> {
+    let send_at = match parse_timestamp(&form.send_at) {
+        Ok(send_at) if send_at > OffsetDateTime::now_utc() => send_at,
+        _ => {
+            messages.error("The new sending time must be a valid time in the future.");
+            return Ok(Redirect::to("/admin/newsletters/scheduled").into_response());
+        }
+    };
+    let n_updated = NewsletterIssues::update_many()
+        .col_expr(newsletter_issues::Column::SendAt, Expr::value(send_at))
+        .col_expr(newsletter_issues::Column::PublishedAt, Expr::value(send_at))
+        .filter(newsletter_issues::Column::NewsletterIssueId.eq(issue_id))
+        .filter(newsletter_issues::Column::Status.eq(IssueStatus::Scheduled))
+        .exec(&state.connection)
+        .await
+        .map_err(e500)?
+        .rows_affected;
+    if n_updated == 0 {
+        messages.error("The newsletter issue is no longer scheduled.");
+    } else {
+        messages.info(format!(
+            "The newsletter issue has been rescheduled for {}.",
+            format_timestamp(send_at)
+        ));
+    }
+    Ok(Redirect::to("/admin/newsletters/scheduled").into_response())
+}
+
+#[tracing::instrument(name = "Get scheduled newsletter issues", skip(conn))]
+async fn get_scheduled_issues(
+    conn: &DatabaseConnection,
+) -> Result<Vec<ScheduledIssue>, anyhow::Error> {
+    let issues = NewsletterIssues::find()
+        .filter(newsletter_issues::Column::Status.eq(IssueStatus::Scheduled))
+        .order_by_asc(newsletter_issues::Column::SendAt)
+        .all(conn)
+        .await
+        .context("Failed to perform a query to retrieve the scheduled issues.")?
+        .into_iter()
+        .map(|issue| ScheduledIssue {
+            newsletter_issue_id: issue.newsletter_issue_id,
+            title: issue.title,
+            send_at: format_timestamp(issue.send_at.unwrap_or(issue.published_at)),
+        })
+        .collect();
+    Ok(issues)
+}
@@ -0,0 +1,16 @@
+mod actions;
+mod consent;
+mod detail;
+mod export;
+mod import;
+mod list;
+
+pub use actions::{
+    admin_confirm_subscriber, admin_delete_subscriber, admin_resend_confirmation,
+    admin_unsubscribe_subscriber,
+};
+pub use consent::export_consent_trail;
+pub use detail::subscriber_detail;
+pub use export::export_subscribers;
+pub use import::{import_subscribers, import_subscribers_form};
+pub use list::list_subscribers;
@@ -0,0 +1,157 @@
+use axum::{
+    extract::{Path, State},
+    response::{IntoResponse, Redirect, Response},
+};
+use axum_messages::Messages;
+use entity::{
+    list_memberships::{self, Entity as ListMemberships},
+    lists::Entity as Lists,
+    sea_orm_active_enums::SubscriptionStatus,
+    subscriptions::Entity as Subscriptions,
+};
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter, TransactionTrait};
+use uuid::Uuid;
+
+use crate::{
+    consent::{record_consent_event, ConsentEvent, RequestOrigin},
+    domain::{NewSubscriber, SubscriberEmail, SubscriberName},
+    routes::{
+        generate_subscription_token, mark_subscriber_as_confirmed, mark_subscriber_as_unsubscribed,
+        send_confirmation_email, store_token,
+    },
+    startup::AppState,
+    subscriber_data::delete_subscriber,
+    utils::e500,
+};
+
+/// What admin actions are recorded as in the consent trail.
+const ADMIN_SOURCE: &str = "admin";
+
+fn to_detail_page(subscriber_id: Uuid) -> Response {
+    Redirect::to(&format!("/admin/subscribers/{}", subscriber_id)).into_response()
+}
+
+#[tracing::instrument(name = "Confirm a subscriber manually", skip(state, origin, messages))]
+pub async fn admin_confirm_subscriber(
+    State(state): State<AppState>,
+    Path(subscriber_id): Path<Uuid>,
+    origin: RequestOrigin,
+    messages: Messages,
+) -> Result<Response, Response> {
+    let txn = state.connection.begin().await.map_err(e500)?;
+    let confirmed = mark_subscriber_as_confirmed(&txn, subscriber_id, None, &origin, ADMIN_SOURCE)
+        .await
+        .map_err(e500)?;
+    txn.commit().await.map_err(e500)?;
+    if confirmed {
+        messages.info("The subscriber has been confirmed.");
+    } else {
+        messages.error("The subscriber is already confirmed.");
+    }
+    Ok(to_detail_page(subscriber_id))
+}
+
+#[tracing::instrument(
+    name = "Unsubscribe a subscriber manually",
+    skip(state, origin, messages)
+)]
+pub async fn admin_unsubscribe_subscriber(
+    State(state): State<AppState>,
+    Path(subscriber_id): Path<Uuid>,
+    origin: RequestOrigin,
+    messages: Messages,
+) -> Result<Response, Response> {
+    let unsubscribed =
+        mark_subscriber_as_unsubscribed(&state.connection, subscriber_id, &origin, ADMIN_SOURCE)
+            .await
+            .map_err(e500)?;
+    if unsubscribed {
+        messages.info("The subscriber has been unsubscribed.");
+    } else {
+        messages.error("The subscriber has already unsubscribed.");
+    }
+    Ok(to_detail_page(subscriber_id))
+}
+
+#[tracing::instrument(name = "Delete a subscriber", skip(state, messages))]
+pub async fn admin_delete_subscriber(
+    State(state): State<AppState>,
+    Path(subscriber_id): Path<Uuid>,
+    messages: Messages,
+) -> Result<Response, Response> {
+    if delete_subscriber(&state.connection, subscriber_id)
+        .await
+        .map_err(e500)?
+    {
+        messages.info("The subscriber has been deleted.");
+    } else {
+        messages.error("The subscriber no longer exists.");
+    }
+    Ok(Redirect::to("/admin/subscribers").into_response())
+}
+
+#[tracing::instrument(name = "Resend a confirmation email", skip(state, messages))]
+pub async fn admin_resend_confirmation(
+    State(state): State<AppState>,
+    Path(subscriber_id): Path<Uuid>,
+    messages: Messages,
+) -> Result<Response, Response> {
+    let Some(subscriber) = Subscriptions::find_by_id(subscriber_id)
+        .one(&state.connection)
+        .await
+        .map_err(e500)?
+    else {
+        messages.error("The subscriber no longer exists.");
+        return Ok(Redirect::to("/admin/subscribers").into_response());
+    };
+    if subscriber.status != SubscriptionStatus::PendingConfirmation {
+        messages.error("Only pending subscribers can be sent a confirmation email.");
+        return Ok(to_detail_page(subscriber_id));
+    }
+    let pending_lists = Lists::find()
+        .inner_join(ListMemberships)
+        .filter(list_memberships::Column::SubscriberId.eq(subscriber_id))
+        .filter(list_memberships::Column::Status.eq(SubscriptionStatus::PendingConfirmation))
+        .all(&state.connection)
+        .await
+        .map_err(e500)?;
+    if pending_lists.is_empty() {
+        messages.error("The subscriber has no list membership to confirm.");
+        return Ok(to_detail_page(subscriber_id));
+    }
+
+    // One email per list, every confirmation is scoped to a list.
+    for list in pending_lists {
+        let new_subscriber = NewSubscriber {
+            email: SubscriberEmail::parse(subscriber.email.clone()).map_err(e500)?,
+            name: SubscriberName::parse(subscriber.name.clone()).map_err(e500)?,
+        };
+        let subscription_token = generate_subscription_token();
+        let txn = state.connection.begin().await.map_err(e500)?;
+        store_token(&txn, subscriber_id, list.id, &subscription_token)
+            .await
+            .map_err(e500)?;
+        txn.commit().await.map_err(e500)?;
+        send_confirmation_email(
+            &state.email_client,
+            &state.email_templates,
+            new_subscriber,
+            &list.name,
+            &state.base_url,
+            &subscription_token,
+        )
+        .await
+        .map_err(e500)?;
+        record_consent_event(
+            &state.connection,
+            subscriber_id,
+            ConsentEvent::ConfirmationSent,
+            &RequestOrigin::default(),
+            Some(ADMIN_SOURCE),
+        )
+        .await
+        .map_err(e500)?;
+    }
+    messages.info("The confirmation email has been sent again.");
+    Ok(to_detail_page(subscriber_id))
+}
@@ -0,0 +1,51 @@
+use axum::{
+    extract::{Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use serde::Serialize;
+
+use crate::{
+    consent::{get_consent_trail, ConsentEventRecord},
+    domain::SubscriberEmail,
+    routes::AppJson,
+    startup::AppState,
+    subscriber_data::SubscriberRecord,
+    utils::e500,
+};
+
+#[derive(serde::Deserialize)]
+pub struct ExportParameters {
+    email: String,
+}
+
+#[derive(Serialize)]
+struct ConsentTrail {
+    subscriber: SubscriberRecord,
+    events: Vec<ConsentEventRecord>,
+}
+
+/// Serves the consent trail of a subscriber as a JSON download, to answer
+/// requests for proof of consent.
+#[tracing::instrument(name = "Export a consent trail", skip(state, params))]
+pub async fn export_consent_trail(
+    State(state): State<AppState>,
+    Query(params): Query<ExportParameters>,
+) -> Result<Response, Response> {
+    let email = SubscriberEmail::normalize(&params.email);
+    let Some((subscriber, events)) = get_consent_trail(&state.connection, &email)
+        .await
+        .map_err(e500)?
+    else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+    let trail = ConsentTrail {
+        subscriber: subscriber.into(),
+        events: events.into_iter().map(Into::into).collect(),
+    };
+    let disposition = format!(
+        "attachment; filename=\"consent-{}.json\"",
+        trail.subscriber.id
+    );
+    Ok(([(header::CONTENT_DISPOSITION, disposition)], AppJson(trail)).into_response())
+}
@@ -0,0 +1,114 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Response},
+};
+use axum_messages::Messages;
+use entity::{
+    consent_events::{self, Entity as ConsentEvents},
+    list_memberships::{self, Entity as ListMemberships},
+    lists::Entity as Lists,
+    sea_orm_active_enums::SubscriptionStatus,
+    subscription_tokens::{self, Entity as SubscriptionTokens},
+    subscriptions::Entity as Subscriptions,
+};
+use handlebars::Handlebars;
+use sea_orm::{EntityTrait, ModelTrait, QueryOrder};
+use serde_json::json;
+use std::fmt::Write;
+use uuid::Uuid;
+
+use crate::{
+    startup::AppState,
+    utils::{e500, format_timestamp},
+};
+
+pub async fn subscriber_detail(
+    State(state): State<AppState>,
+    Path(subscriber_id): Path<Uuid>,
+    messages: Messages,
+) -> Result<Response, Response> {
+    let mut msg_html = String::new();
+    for m in messages.into_iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.message).unwrap();
+    }
+    let Some(subscriber) = Subscriptions::find_by_id(subscriber_id)
+        .one(&state.connection)
+        .await
+        .map_err(e500)?
+    else {
+        return Ok(StatusCode::NOT_FOUND.into_response());
+    };
+    let memberships: Vec<_> = subscriber
+        .find_related(ListMemberships)
+        .find_also_related(Lists)
+        .order_by_asc(list_memberships::Column::JoinedAt)
+        .all(&state.connection)
+        .await
+        .map_err(e500)?
+        .into_iter()
+        .map(|(membership, list)| {
+            json!({
+                "list": list.map(|list| list.name),
+                "status": membership.status,
+                "joined_at": format_timestamp(membership.joined_at),
+            })
+        })
+        .collect();
+    let events: Vec<_> = subscriber
+        .find_related(ConsentEvents)
+        .order_by_asc(consent_events::Column::OccurredAt)
+        .all(&state.connection)
+        .await
+        .map_err(e500)?
+        .into_iter()
+        .map(|event| {
+            json!({
+                "kind": event.kind,
+                "occurred_at": format_timestamp(event.occurred_at),
+                "ip_address": event.ip_address,
+                "source": event.source,
+            })
+        })
+        .collect();
+    let tokens: Vec<_> = subscriber
+        .find_related(SubscriptionTokens)
+        .order_by_asc(subscription_tokens::Column::CreatedAt)
+        .all(&state.connection)
+        .await
+        .map_err(e500)?
+        .into_iter()
+        .map(|token| {
+            json!({
+                "created_at": format_timestamp(token.created_at),
+                "expires_at": format_timestamp(token.expires_at),
+                "consumed_at": token.consumed_at.map(format_timestamp),
+            })
+        })
+        .collect();
+
+    let reg = Handlebars::new();
+    let html = reg
+        .render_template(
+            include_str!("./detail.html"),
+            &json!({
+                "messages": msg_html,
+                "id": subscriber.id,
+                "email": subscriber.email,
+                "email_query": urlencoding::encode(&subscriber.email),
+                "name": subscriber.name,
+                "status": subscriber.status,
+                "digest_frequency": subscriber.digest_frequency,
+                "subscribed_at": format_timestamp(subscriber.subscribed_at),
+                "can_confirm": subscriber.status != SubscriptionStatus::Confirmed,
+                "can_unsubscribe": subscriber.status != SubscriptionStatus::Unsubscribed,
+                "can_resend_confirmation": subscriber.status == SubscriptionStatus::PendingConfirmation,
+                "memberships": memberships,
+                "events": events,
+                "tokens": tokens,
+            }),
+        )
+        .map_err(e500)?;
+
+    Ok(Html::from(html).into_response())
+}
@@ -0,0 +1,63 @@
+use axum::{
+    extract::{Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use entity::subscriptions::{self, Entity as Subscriptions};
+use sea_orm::{ActiveEnum, ColumnTrait, EntityTrait, QueryFilter, QueryOrder};
+
+use crate::{
+    startup::AppState,
+    utils::{e500, format_rfc3339},
+};
+
+use super::list::StatusFilter;
+
+#[derive(serde::Deserialize)]
+pub struct ExportParameters {
+    #[serde(default)]
+    status: StatusFilter,
+}
+
+/// Serves the subscribers as a CSV download, in the format the import
+/// expects plus their status and subscription date.
+#[tracing::instrument(name = "Export subscribers", skip(state, params))]
+pub async fn export_subscribers(
+    State(state): State<AppState>,
+    Query(params): Query<ExportParameters>,
+) -> Result<Response, Response> {
+    let mut query = Subscriptions::find().order_by_asc(subscriptions::Column::SubscribedAt);
+    if let Some(status) = params.status.subscription_status() {
+        query = query.filter(subscriptions::Column::Status.eq(status));
+    }
+    let subscribers = query.all(&state.connection).await.map_err(e500)?;
+
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer
+        .write_record(["email", "name", "status", "subscribed_at"])
+        .map_err(e500)?;
+    for subscriber in subscribers {
+        writer
+            .write_record([
+                subscriber.email,
+                subscriber.name,
+                subscriber.status.to_value(),
+                format_rfc3339(subscriber.subscribed_at),
+            ])
+            .map_err(e500)?;
+    }
+    let body = writer.into_inner().map_err(e500)?;
+
+    let disposition = format!(
+        "attachment; filename=\"subscribers-{}.csv\"",
+        params.status.as_str()
+    );
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_owned()),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        body,
+    )
+        .into_response())
+}
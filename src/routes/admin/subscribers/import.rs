@@ -0,0 +1,369 @@
+use std::collections::HashSet;
+
+use anyhow::Context;
+use axum::{
+    extract::{Multipart, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Response},
+};
+use entity::{
+    sea_orm_active_enums::{DigestFrequency, SubscriptionStatus},
+    subscriptions::{self, Entity as Subscriptions},
+};
+use handlebars::Handlebars;
+use sea_orm::{
+    ActiveModelTrait, ActiveValue::Set, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter,
+    TransactionTrait,
+};
+use serde_json::json;
+use time::OffsetDateTime;
+use tracing::Instrument;
+use uuid::Uuid;
+
+use crate::{
+    consent::{record_consent_event, ConsentEvent, RequestOrigin},
+    domain::{NewSubscriber, SubscriberEmail, SubscriberName},
+    lists::{default_list, join_list},
+    routes::{generate_subscription_token, send_confirmation_email, store_token},
+    startup::AppState,
+    subscriber_data::is_erased,
+    utils::e500,
+};
+
+/// What imported subscribers are recorded as in the consent trail.
+const IMPORT_SOURCE: &str = "import";
+
+/// Whether imported subscribers still have to opt in.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ImportMode {
+    /// Their consent was collected by the list we migrate from.
+    MarkConfirmed,
+    SendConfirmation,
+}
+
+struct ImportRequest {
+    csv: Vec<u8>,
+    mode: ImportMode,
+    dry_run: bool,
+}
+
+#[derive(serde::Serialize)]
+struct RejectedRow {
+    line: u64,
+    email: String,
+    reason: String,
+}
+
+#[derive(Default, serde::Serialize)]
+struct ImportReport {
+    n_rows: usize,
+    n_imported: usize,
+    rejected: Vec<RejectedRow>,
+    /// How many imported subscribers are being sent a confirmation email.
+    n_confirmations: usize,
+}
+
+pub async fn import_subscribers_form() -> Html<&'static str> {
+    Html(include_str!("./import.html"))
+}
+
+/// Imports subscribers from a CSV file with an `email` and a `name` column,
+/// other columns are ignored. Rows that cannot be imported are reported
+/// rather than failing the whole import.
+#[tracing::instrument(name = "Import subscribers", skip_all)]
+pub async fn import_subscribers(
+    State(state): State<AppState>,
+    origin: RequestOrigin,
+    multipart: Multipart,
+) -> Result<Response, Response> {
+    let request = match read_import_request(multipart).await {
+        Ok(request) => request,
+        Err(e) => return Ok(render_error(&e.to_string())),
+    };
+    let rows = match parse_csv(&request.csv) {
+        Ok(rows) => rows,
+        Err(e) => return Ok(render_error(&e)),
+    };
+    let mut report = ImportReport {
+        n_rows: rows.len(),
+        ..Default::default()
+    };
+    let mut accepted = Vec::new();
+    for row in rows {
+        match row {
+            CsvRow::Valid { line, subscriber } => accepted.push((line, subscriber)),
+            CsvRow::Invalid {
+                line,
+                email,
+                reason,
+            } => report.rejected.push(RejectedRow {
+                line,
+                email,
+                reason,
+            }),
+        }
+    }
+
+    // Imported subscribers join the default list.
+    let list = default_list(&state.connection).await.map_err(e500)?;
+    let to_confirm = store_subscribers(
+        &state.connection,
+        &request,
+        &origin,
+        list.id,
+        accepted,
+        &mut report,
+    )
+    .await
+    .map_err(e500)?;
+    // Sending takes a while for a large file, the report does not wait for it.
+    report.n_confirmations = to_confirm.len();
+    if !to_confirm.is_empty() {
+        tokio::spawn(send_confirmation_emails(state, list.name, to_confirm).in_current_span());
+    }
+
+    let html = Handlebars::new()
+        .render_template(
+            include_str!("./import_report.html"),
+            &json!({ "report": report, "dry_run": request.dry_run }),
+        )
+        .map_err(e500)?;
+    Ok(Html::from(html).into_response())
+}
+
+/// Failures are only logged, the confirmation email can be resent from the
+/// page of the subscriber.
+#[tracing::instrument(skip_all, fields(n_subscribers = to_confirm.len()))]
+async fn send_confirmation_emails(
+    state: AppState,
+    list_name: String,
+    to_confirm: Vec<(Uuid, NewSubscriber, String)>,
+) {
+    for (subscriber_id, new_subscriber, subscription_token) in to_confirm {
+        if let Err(e) = send_confirmation_email(
+            &state.email_client,
+            &state.email_templates,
+            new_subscriber,
+            &list_name,
+            &state.base_url,
+            &subscription_token,
+        )
+        .await
+        {
+            tracing::error!(
+                error.cause_chain = ?e,
+                %subscriber_id,
+                "Failed to send a confirmation email."
+            );
+            continue;
+        }
+        if let Err(e) = record_consent_event(
+            &state.connection,
+            subscriber_id,
+            ConsentEvent::ConfirmationSent,
+            &RequestOrigin::default(),
+            Some(IMPORT_SOURCE),
+        )
+        .await
+        {
+            tracing::error!(
+                error.cause_chain = ?e,
+                %subscriber_id,
+                "Failed to record the confirmation email in the consent trail."
+            );
+        }
+    }
+}
+
+async fn read_import_request(mut multipart: Multipart) -> Result<ImportRequest, anyhow::Error> {
+    let mut csv = None;
+    let mut mode = None;
+    let mut dry_run = false;
+    while let Some(field) = multipart
+        .next_field()
+        .await
+        .context("The upload could not be read.")?
+    {
+        match field.name() {
+            Some("file") => {
+                csv = Some(
+                    field
+                        .bytes()
+                        .await
+                        .context("The file could not be read.")?
+                        .to_vec(),
+                )
+            }
+            Some("mode") => {
+                mode = match field.text().await.unwrap_or_default().as_str() {
+                    "confirmed" => Some(ImportMode::MarkConfirmed),
+                    "send_confirmation" => Some(ImportMode::SendConfirmation),
+                    _ => anyhow::bail!("Choose what to do with the imported subscribers."),
+                }
+            }
+            Some("dry_run") => dry_run = true,
+            _ => {}
+        }
+    }
+    Ok(ImportRequest {
+        csv: csv.context("Choose a CSV file to import.")?,
+        mode: mode.context("Choose what to do with the imported subscribers.")?,
+        dry_run,
+    })
+}
+
+enum CsvRow {
+    Valid {
+        line: u64,
+        subscriber: NewSubscriber,
+    },
+    Invalid {
+        line: u64,
+        email: String,
+        reason: String,
+    },
+}
+
+fn parse_csv(csv: &[u8]) -> Result<Vec<CsvRow>, String> {
+    let mut reader = csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .flexible(true)
+        .from_reader(csv);
+    let headers = reader
+        .headers()
+        .map_err(|e| format!("The CSV file could not be read: {}", e))?;
+    let column = |name: &str| headers.iter().position(|h| h.eq_ignore_ascii_case(name));
+    let (Some(email_column), Some(name_column)) = (column("email"), column("name")) else {
+        return Err("The CSV file needs an email and a name column.".into());
+    };
+
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| format!("The CSV file could not be read: {}", e))?;
+        let line = record.position().map_or(0, |p| p.line());
+        let email = record.get(email_column).unwrap_or_default().to_owned();
+        let name = record.get(name_column).unwrap_or_default().to_owned();
+        let subscriber = SubscriberEmail::parse(email.clone()).and_then(|email| {
+            let name = SubscriberName::parse(name)?;
+            Ok(NewSubscriber { email, name })
+        });
+        rows.push(match subscriber {
+            Ok(subscriber) => CsvRow::Valid { line, subscriber },
+            Err(reason) => CsvRow::Invalid {
+                line,
+                email,
+                reason,
+            },
+        });
+    }
+    Ok(rows)
+}
+
+/// Stores the valid rows in a single transaction, which is rolled back on a
+/// dry run. Returns the subscribers that have to be sent a confirmation
+/// email along with their token.
+#[tracing::instrument(skip_all, fields(dry_run = request.dry_run, mode = ?request.mode))]
+async fn store_subscribers(
+    connection: &DatabaseConnection,
+    request: &ImportRequest,
+    origin: &RequestOrigin,
+    list_id: Uuid,
+    rows: Vec<(u64, NewSubscriber)>,
+    report: &mut ImportReport,
+) -> Result<Vec<(Uuid, NewSubscriber, String)>, anyhow::Error> {
+    let txn = connection.begin().await?;
+    let mut seen = HashSet::new();
+    let mut to_confirm = Vec::new();
+    for (line, new_subscriber) in rows {
+        let email = new_subscriber.email.as_ref().to_owned();
+        let reject = |reason: &str| RejectedRow {
+            line,
+            email: email.clone(),
+            reason: reason.to_owned(),
+        };
+        if !seen.insert(email.clone()) {
+            report
+                .rejected
+                .push(reject("The address appears earlier in the file."));
+            continue;
+        }
+        let existing = Subscriptions::find()
+            .filter(subscriptions::Column::Email.eq(&email))
+            .one(&txn)
+            .await?;
+        if existing.is_some() {
+            report
+                .rejected
+                .push(reject("The address is already subscribed."));
+            continue;
+        }
+        if is_erased(&txn, &email).await? {
+            report
+                .rejected
+                .push(reject("The subscriber asked for their data to be erased."));
+            continue;
+        }
+
+        let subscriber_id = Uuid::new_v4();
+        let status = match request.mode {
+            ImportMode::MarkConfirmed => SubscriptionStatus::Confirmed,
+            ImportMode::SendConfirmation => SubscriptionStatus::PendingConfirmation,
+        };
+        subscriptions::ActiveModel {
+            id: Set(subscriber_id),
+            email: Set(email.clone()),
+            name: Set(new_subscriber.name.as_ref().to_owned()),
+            subscribed_at: Set(OffsetDateTime::now_utc()),
+            status: Set(status),
+            digest_frequency: Set(DigestFrequency::Immediately),
+        }
+        .insert(&txn)
+        .await?;
+        join_list(&txn, list_id, subscriber_id, status).await?;
+        record_consent_event(
+            &txn,
+            subscriber_id,
+            ConsentEvent::SignedUp,
+            origin,
+            Some(IMPORT_SOURCE),
+        )
+        .await?;
+        match request.mode {
+            ImportMode::MarkConfirmed => {
+                record_consent_event(
+                    &txn,
+                    subscriber_id,
+                    ConsentEvent::Confirmed,
+                    origin,
+                    Some(IMPORT_SOURCE),
+                )
+                .await?;
+            }
+            ImportMode::SendConfirmation => {
+                let subscription_token = generate_subscription_token();
+                store_token(&txn, subscriber_id, list_id, &subscription_token).await?;
+                to_confirm.push((subscriber_id, new_subscriber, subscription_token));
+            }
+        }
+        report.n_imported += 1;
+    }
+    report.rejected.sort_by_key(|row| row.line);
+
+    if request.dry_run {
+        txn.rollback().await?;
+        return Ok(Vec::new());
+    }
+    txn.commit().await?;
+    Ok(to_confirm)
+}
+
+/// The upload as a whole cannot be imported.
+fn render_error(error: &str) -> Response {
+    match Handlebars::new().render_template(
+        include_str!("./import_report.html"),
+        &json!({ "error": error }),
+    ) {
+        Ok(html) => (StatusCode::BAD_REQUEST, Html::from(html)).into_response(),
+        Err(e) => e500(e),
+    }
+}
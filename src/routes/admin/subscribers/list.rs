@@ -0,0 +1,262 @@
+use anyhow::Context;
+use axum::{
+    extract::{Query, State},
+    response::{Html, IntoResponse, Response},
+};
+use axum_messages::Messages;
+use entity::{
+    sea_orm_active_enums::SubscriptionStatus,
+    subscriptions::{self, Entity as Subscriptions},
+};
+use handlebars::Handlebars;
+use sea_orm::{
+    sea_query::{Expr, Func, LikeExpr},
+    ColumnTrait, Condition, DatabaseConnection, EntityTrait, ItemsAndPagesNumber, PaginatorTrait,
+    QueryFilter, QueryOrder,
+};
+use serde_json::json;
+use std::fmt::Write;
+use uuid::Uuid;
+
+use crate::{
+    startup::AppState,
+    utils::{e500, format_timestamp},
+};
+
+const PAGE_SIZE: u64 = 50;
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StatusFilter {
+    #[default]
+    All,
+    PendingConfirmation,
+    Confirmed,
+    Unsubscribed,
+}
+
+impl StatusFilter {
+    const ALL: [(Self, &'static str); 4] = [
+        (Self::All, "All"),
+        (Self::PendingConfirmation, "Pending confirmation"),
+        (Self::Confirmed, "Confirmed"),
+        (Self::Unsubscribed, "Unsubscribed"),
+    ];
+
+    pub(super) fn as_str(self) -> &'static str {
+        match self {
+            Self::All => "all",
+            Self::PendingConfirmation => "pending_confirmation",
+            Self::Confirmed => "confirmed",
+            Self::Unsubscribed => "unsubscribed",
+        }
+    }
+
+    pub(super) fn subscription_status(self) -> Option<SubscriptionStatus> {
+        match self {
+            Self::All => None,
+            Self::PendingConfirmation => Some(SubscriptionStatus::PendingConfirmation),
+            Self::Confirmed => Some(SubscriptionStatus::Confirmed),
+            Self::Unsubscribed => Some(SubscriptionStatus::Unsubscribed),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SortOrder {
+    #[default]
+    Newest,
+    Oldest,
+}
+
+impl SortOrder {
+    const ALL: [(Self, &'static str); 2] = [
+        (Self::Newest, "Newest first"),
+        (Self::Oldest, "Oldest first"),
+    ];
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Newest => "newest",
+            Self::Oldest => "oldest",
+        }
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct ListParameters {
+    /// Matched against emails and names, case-insensitively.
+    #[serde(default)]
+    q: String,
+    #[serde(default)]
+    status: StatusFilter,
+    #[serde(default)]
+    order: SortOrder,
+    #[serde(default = "first_page")]
+    page: u64,
+}
+
+fn first_page() -> u64 {
+    1
+}
+
+impl ListParameters {
+    /// The query string of another page of the same list.
+    fn page_query(&self, page: u64) -> String {
+        format!(
+            "q={}&status={}&order={}&page={}",
+            urlencoding::encode(self.q.trim()),
+            self.status.as_str(),
+            self.order.as_str(),
+            page
+        )
+    }
+}
+
+#[derive(serde::Serialize)]
+struct SubscriberRow {
+    id: Uuid,
+    email: String,
+    name: String,
+    status: SubscriptionStatus,
+    subscribed_at: String,
+}
+
+struct SubscriberPage {
+    subscribers: Vec<SubscriberRow>,
+    n_subscribers: u64,
+    n_pages: u64,
+}
+
+pub async fn list_subscribers(
+    State(state): State<AppState>,
+    Query(params): Query<ListParameters>,
+    messages: Messages,
+) -> Result<Response, Response> {
+    let mut msg_html = String::new();
+    for m in messages.into_iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.message).unwrap();
+    }
+    let page = params.page.max(1);
+    let SubscriberPage {
+        subscribers,
+        n_subscribers,
+        n_pages,
+    } = get_subscriber_page(&state.connection, &params, page)
+        .await
+        .map_err(e500)?;
+
+    let statuses: Vec<_> = StatusFilter::ALL
+        .iter()
+        .map(|(filter, label)| {
+            json!({
+                "value": filter.as_str(),
+                "label": label,
+                "selected": *filter == params.status,
+            })
+        })
+        .collect();
+    let orders: Vec<_> = SortOrder::ALL
+        .iter()
+        .map(|(order, label)| {
+            json!({
+                "value": order.as_str(),
+                "label": label,
+                "selected": *order == params.order,
+            })
+        })
+        .collect();
+    let previous_page = (page > 1).then(|| params.page_query(page - 1));
+    let next_page = (page < n_pages).then(|| params.page_query(page + 1));
+
+    let reg = Handlebars::new();
+    let html = reg
+        .render_template(
+            include_str!("./list.html"),
+            &json!({
+                "messages": msg_html,
+                "q": params.q,
+                "status": params.status.as_str(),
+                "statuses": statuses,
+                "orders": orders,
+                "subscribers": subscribers,
+                "n_subscribers": n_subscribers,
+                "page": page,
+                "n_pages": n_pages.max(1),
+                "previous_page": previous_page,
+                "next_page": next_page,
+            }),
+        )
+        .map_err(e500)?;
+
+    Ok(Html::from(html).into_response())
+}
+
+#[tracing::instrument(name = "Get a page of subscribers", skip(conn, params))]
+async fn get_subscriber_page(
+    conn: &DatabaseConnection,
+    params: &ListParameters,
+    page: u64,
+) -> Result<SubscriberPage, anyhow::Error> {
+    let mut query = Subscriptions::find();
+    let search = params.q.trim();
+    if !search.is_empty() {
+        let pattern = format!(
+            "%{}%",
+            search
+                .to_lowercase()
+                .replace('\\', "\\\\")
+                .replace('%', "\\%")
+                .replace('_', "\\_")
+        );
+        let matches = |column: subscriptions::Column| {
+            Expr::expr(Func::lower(Expr::col(column)))
+                .like(LikeExpr::new(pattern.clone()).escape('\\'))
+        };
+        query = query.filter(
+            Condition::any()
+                .add(matches(subscriptions::Column::Email))
+                .add(matches(subscriptions::Column::Name)),
+        );
+    }
+    if let Some(status) = params.status.subscription_status() {
+        query = query.filter(subscriptions::Column::Status.eq(status));
+    }
+    // The id breaks ties, so that pages do not overlap.
+    query = match params.order {
+        SortOrder::Newest => query
+            .order_by_desc(subscriptions::Column::SubscribedAt)
+            .order_by_desc(subscriptions::Column::Id),
+        SortOrder::Oldest => query
+            .order_by_asc(subscriptions::Column::SubscribedAt)
+            .order_by_asc(subscriptions::Column::Id),
+    };
+
+    let paginator = query.paginate(conn, PAGE_SIZE);
+    let ItemsAndPagesNumber {
+        number_of_items,
+        number_of_pages,
+    } = paginator
+        .num_items_and_pages()
+        .await
+        .context("Failed to count the subscribers.")?;
+    let subscribers = paginator
+        .fetch_page(page - 1)
+        .await
+        .context("Failed to perform a query to retrieve the subscribers.")?
+        .into_iter()
+        .map(|subscriber| SubscriberRow {
+            id: subscriber.id,
+            email: subscriber.email,
+            name: subscriber.name,
+            status: subscriber.status,
+            subscribed_at: format_timestamp(subscriber.subscribed_at),
+        })
+        .collect();
+    Ok(SubscriberPage {
+        subscribers,
+        n_subscribers: number_of_items,
+        n_pages: number_of_pages,
+    })
+}
@@ -0,0 +1,5 @@
+mod get;
+mod post;
+
+pub use get::two_factor_settings;
+pub use post::{confirm_two_factor, disable_two_factor, start_two_factor_setup};
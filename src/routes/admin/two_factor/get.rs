@@ -0,0 +1,76 @@
+use std::fmt::Write;
+
+use anyhow::Context;
+use axum::{
+    extract::State,
+    response::{Html, IntoResponse, Response},
+    Extension,
+};
+use axum_messages::Messages;
+use entity::{
+    user_recovery_codes::{self, Entity as UserRecoveryCodes},
+    users::{self, Entity as Users},
+};
+use handlebars::Handlebars;
+use qrcode::{render::svg, QrCode};
+use sea_orm::{ColumnTrait, DatabaseConnection, EntityTrait, PaginatorTrait, QueryFilter};
+use secrecy::{ExposeSecret, Secret};
+use serde_json::json;
+
+use crate::{authentication::otpauth_uri, authentication::UserId, startup::AppState, utils::e500};
+
+pub async fn find_current_user(
+    user_id: UserId,
+    conn: &DatabaseConnection,
+) -> Result<users::Model, anyhow::Error> {
+    Users::find_by_id(*user_id)
+        .one(conn)
+        .await
+        .context("Failed to look up the current user.")?
+        .context("The current user is missing.")
+}
+
+pub async fn two_factor_settings(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<UserId>,
+    messages: Messages,
+) -> Result<Response, Response> {
+    let mut msg_html = String::new();
+    for m in messages.into_iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.message).unwrap();
+    }
+    let user = find_current_user(user_id, &state.connection)
+        .await
+        .map_err(e500)?;
+
+    let mut data = json!({
+        "messages": msg_html,
+        "is_enabled": user.totp_enabled_at.is_some(),
+    });
+    if user.totp_enabled_at.is_some() {
+        let unused_recovery_codes = UserRecoveryCodes::find()
+            .filter(user_recovery_codes::Column::UserId.eq(user.user_id))
+            .filter(user_recovery_codes::Column::UsedAt.is_null())
+            .count(&state.connection)
+            .await
+            .map_err(e500)?;
+        data["unused_recovery_codes"] = json!(unused_recovery_codes);
+    } else if let Some(secret) = user.totp_secret {
+        // Enrolment has started but no code confirmed it yet.
+        let secret = Secret::new(secret);
+        let uri = otpauth_uri(&secret, &user.username);
+        let qr_code = QrCode::new(&uri)
+            .map_err(e500)?
+            .render::<svg::Color>()
+            .min_dimensions(200, 200)
+            .build();
+        data["otpauth_uri"] = json!(uri);
+        data["secret"] = json!(secret.expose_secret());
+        data["qr_code"] = json!(qr_code);
+    }
+
+    let html = Handlebars::new()
+        .render_template(include_str!("./get.html"), &data)
+        .map_err(e500)?;
+    Ok(Html::from(html).into_response())
+}
@@ -0,0 +1,120 @@
+use axum::{
+    extract::State,
+    response::{Html, IntoResponse, Redirect, Response},
+    Extension, Form,
+};
+use axum_messages::Messages;
+use entity::users::{self, Entity as Users};
+use handlebars::Handlebars;
+use sea_orm::{sea_query::Expr, ColumnTrait, EntityTrait, QueryFilter};
+use secrecy::ExposeSecret;
+use serde_json::json;
+
+use crate::{
+    authentication::{self, generate_totp_secret, use_totp_code, verify_second_factor, UserId},
+    startup::AppState,
+    utils::e500,
+};
+
+use super::get::find_current_user;
+
+#[derive(serde::Deserialize)]
+pub struct CodeFormData {
+    code: String,
+}
+
+fn to_settings() -> Response {
+    Redirect::to("/admin/two-factor").into_response()
+}
+
+/// Stores a new secret, two-factor authentication is only turned on once the
+/// user has entered a code it generated.
+#[tracing::instrument(name = "Start two-factor setup", skip(state, messages))]
+pub async fn start_two_factor_setup(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<UserId>,
+    messages: Messages,
+) -> Result<Response, Response> {
+    let updated = Users::update_many()
+        .col_expr(
+            users::Column::TotpSecret,
+            Expr::value(generate_totp_secret().expose_secret()),
+        )
+        .filter(users::Column::UserId.eq(*user_id))
+        .filter(users::Column::TotpEnabledAt.is_null())
+        .exec(&state.connection)
+        .await
+        .map_err(e500)?;
+    if updated.rows_affected == 0 {
+        messages.error("Two-factor authentication is already on.");
+    }
+    Ok(to_settings())
+}
+
+#[tracing::instrument(name = "Confirm two-factor setup", skip(state, messages, form))]
+pub async fn confirm_two_factor(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<UserId>,
+    messages: Messages,
+    Form(form): Form<CodeFormData>,
+) -> Result<Response, Response> {
+    let user = find_current_user(user_id, &state.connection)
+        .await
+        .map_err(e500)?;
+    if user.totp_enabled_at.is_some() {
+        messages.error("Two-factor authentication is already on.");
+        return Ok(to_settings());
+    }
+    if !use_totp_code(&user, &form.code, &state.connection)
+        .await
+        .map_err(e500)?
+    {
+        messages.error("The code is not valid.");
+        return Ok(to_settings());
+    }
+    let codes = authentication::enable_two_factor(user.user_id, &state.connection)
+        .await
+        .map_err(e500)?;
+    let codes: Vec<_> = codes.iter().map(|code| code.expose_secret()).collect();
+
+    // The codes are shown once, they are not put into a flash message that
+    // would end up in the session store.
+    let html = Handlebars::new()
+        .render_template(
+            include_str!("./recovery_codes.html"),
+            &json!({ "codes": codes }),
+        )
+        .map_err(e500)?;
+    Ok(Html::from(html).into_response())
+}
+
+#[tracing::instrument(
+    name = "Turn off two-factor authentication",
+    skip(state, messages, form)
+)]
+pub async fn disable_two_factor(
+    State(state): State<AppState>,
+    Extension(user_id): Extension<UserId>,
+    messages: Messages,
+    Form(form): Form<CodeFormData>,
+) -> Result<Response, Response> {
+    let user = find_current_user(user_id, &state.connection)
+        .await
+        .map_err(e500)?;
+    if user.totp_enabled_at.is_none() {
+        messages.error("Two-factor authentication is already off.");
+        return Ok(to_settings());
+    }
+    if !verify_second_factor(&user, &form.code, &state.connection)
+        .await
+        .map_err(e500)?
+    {
+        messages.error("The code is not valid.");
+        return Ok(to_settings());
+    }
+    authentication::disable_two_factor(user.user_id, &state.connection)
+        .await
+        .map_err(e500)?;
+    messages.info("Two-factor authentication is now off.");
+    Ok(to_settings())
+}
@@ -0,0 +1,7 @@
+mod actions;
+mod get;
+mod post;
+
+pub use actions::{change_user_role, disable_user, enable_user};
+pub use get::list_users;
+pub use post::invite_user;
@@ -0,0 +1,121 @@
+use axum::{
+    extract::{Path, State},
+    response::{IntoResponse, Redirect, Response},
+    Extension, Form,
+};
+use axum_messages::Messages;
+use entity::{
+    sea_orm_active_enums::UserRole,
+    users::{self, Entity as Users},
+};
+use sea_orm::{
+    sea_query::Expr, ActiveEnum, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter,
+};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::{authentication::UserId, startup::AppState, utils::e500};
+
+#[derive(serde::Deserialize, Debug)]
+pub struct RoleFormData {
+    role: UserRole,
+}
+
+fn to_users() -> Response {
+    Redirect::to("/admin/users").into_response()
+}
+
+async fn find_user(
+    connection: &DatabaseConnection,
+    user_id: Uuid,
+) -> Result<Option<users::Model>, Response> {
+    Users::find_by_id(user_id)
+        .one(connection)
+        .await
+        .map_err(e500)
+}
+
+#[tracing::instrument(name = "Change the role of a user", skip(state, messages))]
+pub async fn change_user_role(
+    State(state): State<AppState>,
+    Extension(current_user_id): Extension<UserId>,
+    Path(user_id): Path<Uuid>,
+    messages: Messages,
+    Form(form): Form<RoleFormData>,
+) -> Result<Response, Response> {
+    // An owner demoting themselves could leave nobody to manage users.
+    if user_id == *current_user_id {
+        messages.error("You cannot change your own role.");
+        return Ok(to_users());
+    }
+    let Some(user) = find_user(&state.connection, user_id).await? else {
+        messages.error("There is no such user.");
+        return Ok(to_users());
+    };
+    Users::update_many()
+        .col_expr(users::Column::Role, form.role.as_enum())
+        .filter(users::Column::UserId.eq(user_id))
+        .exec(&state.connection)
+        .await
+        .map_err(e500)?;
+    messages.info(format!(
+        "{} is now {}.",
+        user.username,
+        form.role.to_value()
+    ));
+    Ok(to_users())
+}
+
+/// A disabled user can no longer log in, and their sessions stop working
+/// with their next request.
+#[tracing::instrument(name = "Disable a user", skip(state, messages))]
+pub async fn disable_user(
+    State(state): State<AppState>,
+    Extension(current_user_id): Extension<UserId>,
+    Path(user_id): Path<Uuid>,
+    messages: Messages,
+) -> Result<Response, Response> {
+    if user_id == *current_user_id {
+        messages.error("You cannot disable your own account.");
+        return Ok(to_users());
+    }
+    let Some(user) = find_user(&state.connection, user_id).await? else {
+        messages.error("There is no such user.");
+        return Ok(to_users());
+    };
+    Users::update_many()
+        .col_expr(
+            users::Column::DisabledAt,
+            Expr::value(OffsetDateTime::now_utc()),
+        )
+        .filter(users::Column::UserId.eq(user_id))
+        .filter(users::Column::DisabledAt.is_null())
+        .exec(&state.connection)
+        .await
+        .map_err(e500)?;
+    messages.info(format!("{} has been disabled.", user.username));
+    Ok(to_users())
+}
+
+#[tracing::instrument(name = "Enable a user", skip(state, messages))]
+pub async fn enable_user(
+    State(state): State<AppState>,
+    Path(user_id): Path<Uuid>,
+    messages: Messages,
+) -> Result<Response, Response> {
+    let Some(user) = find_user(&state.connection, user_id).await? else {
+        messages.error("There is no such user.");
+        return Ok(to_users());
+    };
+    Users::update_many()
+        .col_expr(
+            users::Column::DisabledAt,
+            Expr::value(Option::<OffsetDateTime>::None),
+        )
+        .filter(users::Column::UserId.eq(user_id))
+        .exec(&state.connection)
+        .await
+        .map_err(e500)?;
+    messages.info(format!("{} has been enabled.", user.username));
+    Ok(to_users())
+}
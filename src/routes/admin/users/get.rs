@@ -0,0 +1,86 @@
+use axum::{
+    extract::State,
+    response::{Html, IntoResponse, Response},
+    Extension,
+};
+use axum_messages::Messages;
+use entity::{
+    sea_orm_active_enums::UserRole,
+    user_invitations::{self, Entity as UserInvitations},
+    users::{self, Entity as Users},
+};
+use handlebars::Handlebars;
+use sea_orm::{ActiveEnum, ColumnTrait, EntityTrait, Iterable, QueryFilter, QueryOrder};
+use serde_json::json;
+use std::fmt::Write;
+use time::OffsetDateTime;
+
+use crate::{authentication::UserId, startup::AppState, utils::e500};
+
+pub async fn list_users(
+    State(state): State<AppState>,
+    Extension(current_user_id): Extension<UserId>,
+    messages: Messages,
+) -> Result<Response, Response> {
+    let mut msg_html = String::new();
+    for m in messages.into_iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.message).unwrap();
+    }
+    let users: Vec<_> = Users::find()
+        .order_by_asc(users::Column::Username)
+        .all(&state.connection)
+        .await
+        .map_err(e500)?
+        .into_iter()
+        .map(|user| {
+            let roles: Vec<_> = UserRole::iter()
+                .map(|role| {
+                    json!({
+                        "value": role.to_value(),
+                        "selected": role == user.role,
+                    })
+                })
+                .collect();
+            json!({
+                "id": user.user_id,
+                "username": user.username,
+                "email": user.email,
+                "role": user.role.to_value(),
+                "roles": roles,
+                "is_disabled": user.disabled_at.is_some(),
+                // Owners cannot lock themselves out.
+                "is_current": user.user_id == *current_user_id,
+            })
+        })
+        .collect();
+    let invitations: Vec<_> = UserInvitations::find()
+        .filter(user_invitations::Column::AcceptedAt.is_null())
+        .filter(user_invitations::Column::ExpiresAt.gt(OffsetDateTime::now_utc()))
+        .order_by_asc(user_invitations::Column::CreatedAt)
+        .all(&state.connection)
+        .await
+        .map_err(e500)?
+        .into_iter()
+        .map(|invitation| {
+            json!({
+                "email": invitation.email,
+                "role": invitation.role.to_value(),
+                "expires_at": invitation.expires_at.to_string(),
+            })
+        })
+        .collect();
+    let roles: Vec<_> = UserRole::iter().map(|role| role.to_value()).collect();
+
+    let html = Handlebars::new()
+        .render_template(
+            include_str!("./get.html"),
+            &json!({
+                "messages": msg_html,
+                "users": users,
+                "invitations": invitations,
+                "roles": roles,
+            }),
+        )
+        .map_err(e500)?;
+    Ok(Html::from(html).into_response())
+}
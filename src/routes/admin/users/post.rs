@@ -0,0 +1,122 @@
+use anyhow::Context;
+use axum::{
+    extract::State,
+    response::{IntoResponse, Redirect, Response},
+    Extension, Form,
+};
+use axum_messages::Messages;
+use entity::{
+    sea_orm_active_enums::UserRole,
+    user_invitations::{self, Entity as UserInvitations},
+    users::{self, Entity as Users},
+};
+use sea_orm::{
+    ActiveEnum, ActiveValue::Set, ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter,
+};
+use serde_json::json;
+use time::{Duration, OffsetDateTime};
+
+use crate::{
+    authentication::UserId, domain::SubscriberEmail, email_templates::EmailTemplates,
+    routes::generate_subscription_token, startup::AppState, utils::e500,
+};
+
+const INVITATION_TTL: Duration = Duration::hours(72);
+
+#[derive(serde::Deserialize, Debug)]
+pub struct FormData {
+    email: String,
+    role: UserRole,
+}
+
+/// Emails a single-use link to the collaborator, who picks their own
+/// username and password when they follow it.
+#[tracing::instrument(name = "Invite a user", skip(state, messages))]
+pub async fn invite_user(
+    State(state): State<AppState>,
+    Extension(current_user_id): Extension<UserId>,
+    messages: Messages,
+    Form(form): Form<FormData>,
+) -> Result<Response, Response> {
+    let to_users = Redirect::to("/admin/users").into_response();
+    let Ok(email) = SubscriberEmail::parse(form.email.trim().to_owned()) else {
+        messages.error(format!(
+            "{} is not a valid email address.",
+            form.email.trim()
+        ));
+        return Ok(to_users);
+    };
+    let is_taken = Users::find()
+        .filter(users::Column::Email.eq(email.as_ref()))
+        .count(&state.connection)
+        .await
+        .map_err(e500)?
+        > 0;
+    if is_taken {
+        messages.error(format!("There already is a user with {}.", email.as_ref()));
+        return Ok(to_users);
+    }
+    let inviter = Users::find_by_id(*current_user_id)
+        .one(&state.connection)
+        .await
+        .map_err(e500)?
+        .context("The current user is missing.")
+        .map_err(e500)?;
+
+    let token = generate_subscription_token();
+    let now = OffsetDateTime::now_utc();
+    let invitation = user_invitations::ActiveModel {
+        token: Set(token.clone()),
+        email: Set(email.as_ref().to_owned()),
+        role: Set(form.role),
+        invited_by: Set(inviter.user_id),
+        created_at: Set(now),
+        expires_at: Set(now + INVITATION_TTL),
+        accepted_at: Set(None),
+    };
+    UserInvitations::insert(invitation)
+        .exec_without_returning(&state.connection)
+        .await
+        .map_err(e500)?;
+    send_invitation_email(&state, &email, &inviter.username, form.role, &token)
+        .await
+        .map_err(e500)?;
+
+    messages.info(format!(
+        "An invitation has been sent to {}.",
+        email.as_ref()
+    ));
+    Ok(to_users)
+}
+
+#[tracing::instrument(skip_all)]
+async fn send_invitation_email(
+    state: &AppState,
+    email: &SubscriberEmail,
+    inviter: &str,
+    role: UserRole,
+    token: &str,
+) -> Result<(), anyhow::Error> {
+    let invitation_link = format!("{}/invitations/accept?token={}", state.base_url, token);
+    let content = state
+        .email_templates
+        .render(
+            EmailTemplates::INVITATION,
+            &json!({
+                "inviter": inviter,
+                "role": role.to_value(),
+                "invitation_link": invitation_link,
+            }),
+        )
+        .context("Failed to render the invitation email.")?;
+    state
+        .email_client
+        .send_email(
+            email,
+            "You have been invited",
+            &content.html_content,
+            &content.text_content,
+        )
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,36 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Response},
+};
+use entity::{
+    newsletter_issues::{self, Entity as NewsletterIssues},
+    sea_orm_active_enums::IssueStatus,
+};
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use uuid::Uuid;
+
+use crate::{startup::AppState, utils::e500};
+
+#[tracing::instrument(name = "Serve an archived newsletter issue", skip(state))]
+pub async fn newsletter_archive(
+    State(state): State<AppState>,
+    Path(issue_id): Path<Uuid>,
+) -> Result<Response, Response> {
+    // Scheduled and cancelled issues are not public (yet)
+    match NewsletterIssues::find_by_id(issue_id)
+        .filter(newsletter_issues::Column::Status.eq(IssueStatus::Published))
+        .one(&state.connection)
+        .await
+        .map_err(e500)?
+    {
+        Some(issue) => {
+            let html_content = state
+                .email_templates
+                .render_issue_content(&issue.html_content)
+                .map_err(e500)?;
+            Ok(Html::from(html_content).into_response())
+        }
+        None => Ok(StatusCode::NOT_FOUND.into_response()),
+    }
+}
@@ -0,0 +1,209 @@
+use anyhow::Context;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+    Form,
+};
+use axum_messages::Messages;
+use entity::{
+    user_invitations::{self, Entity as UserInvitations},
+    users::{self, Entity as Users},
+};
+use handlebars::Handlebars;
+use sea_orm::{
+    sea_query::{Expr, OnConflict},
+    ActiveEnum,
+    ActiveValue::Set,
+    ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter, TransactionTrait,
+};
+use secrecy::{ExposeSecret, Secret};
+use serde_json::json;
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::{
+    authentication::compute_password_hash, domain::Username, startup::AppState,
+    telemetry::spawn_blocking_with_tracing,
+};
+
+use super::{
+    error_chain_fmt,
+    single_use_token::{find_open_token, TokenError},
+};
+
+#[derive(serde::Deserialize)]
+pub struct InvitationParameters {
+    token: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct AcceptInvitationFormData {
+    token: String,
+    username: String,
+    password: Secret<String>,
+    password_check: Secret<String>,
+}
+
+#[derive(thiserror::Error)]
+pub enum InvitationError {
+    #[error("The invitation link is not valid.")]
+    InvalidLink,
+    #[error("The invitation link has expired.")]
+    ExpiredLink,
+    #[error("There already is a user with the invited address.")]
+    AddressTaken,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for InvitationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl From<TokenError> for InvitationError {
+    fn from(e: TokenError) -> Self {
+        match e {
+            TokenError::Invalid => InvitationError::InvalidLink,
+            TokenError::Expired => InvitationError::ExpiredLink,
+            TokenError::Unexpected(e) => InvitationError::UnexpectedError(e),
+        }
+    }
+}
+
+impl IntoResponse for InvitationError {
+    fn into_response(self) -> Response {
+        tracing::error!(exception.details = ?self, exception.message = %self);
+        match self {
+            InvitationError::InvalidLink => StatusCode::UNAUTHORIZED.into_response(),
+            InvitationError::ExpiredLink => StatusCode::GONE.into_response(),
+            InvitationError::AddressTaken => StatusCode::CONFLICT.into_response(),
+            InvitationError::UnexpectedError(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            }
+        }
+    }
+}
+
+fn render_form(
+    invitation: &user_invitations::Model,
+    username: &str,
+    messages: &[String],
+) -> Result<String, InvitationError> {
+    Handlebars::new()
+        .render_template(
+            include_str!("./invitations/accept.html"),
+            &json!({
+                "token": invitation.token,
+                "email": invitation.email,
+                "role": invitation.role.to_value(),
+                "username": username,
+                "messages": messages,
+            }),
+        )
+        .context("Failed to render the invitation form.")
+        .map_err(Into::into)
+}
+
+#[tracing::instrument(name = "Show an invitation", skip(state, params))]
+pub async fn invitation_form(
+    State(state): State<AppState>,
+    Query(params): Query<InvitationParameters>,
+) -> Result<Html<String>, InvitationError> {
+    let invitation =
+        find_open_token::<UserInvitations>(&state.connection, params.token.clone(), false).await?;
+    Ok(Html::from(render_form(&invitation, "", &[])?))
+}
+
+/// Creates the account of the invitee with the role they were invited as.
+#[tracing::instrument(name = "Accept an invitation", skip(state, messages, form))]
+pub async fn accept_invitation(
+    State(state): State<AppState>,
+    messages: Messages,
+    Form(form): Form<AcceptInvitationFormData>,
+) -> Result<Response, InvitationError> {
+    let txn = state
+        .connection
+        .begin()
+        .await
+        .context("Failed to begin a transaction.")?;
+    let invitation = find_open_token::<UserInvitations>(&txn, form.token.clone(), true).await?;
+    let username = form.username.trim();
+    let mut errors = Vec::new();
+    let parsed_username = Username::parse(username.to_owned())
+        .map_err(|e| errors.push(e))
+        .ok();
+    if form.password.expose_secret().is_empty() {
+        errors.push("The password cannot be empty.".to_owned());
+    } else if form.password.expose_secret() != form.password_check.expose_secret() {
+        errors
+            .push("You entered two different passwords - the field values must match.".to_owned());
+    }
+    let Some(username) = parsed_username.filter(|_| errors.is_empty()) else {
+        let html = render_form(&invitation, username, &errors)?;
+        return Ok((StatusCode::BAD_REQUEST, Html::from(html)).into_response());
+    };
+    // The address may have joined through another invitation in the meantime.
+    let is_taken = Users::find()
+        .filter(users::Column::Email.eq(&invitation.email))
+        .count(&txn)
+        .await
+        .context("Failed to look up the invited address.")?
+        > 0;
+    if is_taken {
+        return Err(InvitationError::AddressTaken);
+    }
+
+    let password_hash = spawn_blocking_with_tracing(move || compute_password_hash(form.password))
+        .await
+        .context("Failed to spawn blocking task.")??;
+    let user = users::ActiveModel {
+        user_id: Set(Uuid::new_v4()),
+        username: Set(username.as_ref().to_owned()),
+        password_hash: Set(password_hash.expose_secret().to_owned()),
+        role: Set(invitation.role),
+        disabled_at: Set(None),
+        email: Set(Some(invitation.email.clone())),
+        sessions_revoked_at: Set(None),
+        totp_secret: Set(None),
+        totp_enabled_at: Set(None),
+        totp_last_step: Set(None),
+    };
+    let n_inserted_rows = Users::insert(user)
+        .on_conflict(
+            OnConflict::column(users::Column::Username)
+                .do_nothing()
+                .to_owned(),
+        )
+        .exec_without_returning(&txn)
+        .await
+        .context("Failed to create the user.")?;
+    if n_inserted_rows == 0 {
+        let errors = [format!(
+            "There already is a user called {}.",
+            username.as_ref()
+        )];
+        let html = render_form(&invitation, username.as_ref(), &errors)?;
+        return Ok((StatusCode::BAD_REQUEST, Html::from(html)).into_response());
+    }
+    UserInvitations::update_many()
+        .col_expr(
+            user_invitations::Column::AcceptedAt,
+            Expr::value(OffsetDateTime::now_utc()),
+        )
+        .filter(user_invitations::Column::Token.eq(&invitation.token))
+        .exec(&txn)
+        .await
+        .context("Failed to mark the invitation as accepted.")?;
+    txn.commit()
+        .await
+        .context("Failed to commit the new user.")?;
+
+    messages.success(format!(
+        "Welcome, {}! You can log in now.",
+        username.as_ref()
+    ));
+    Ok(Redirect::to("/login").into_response())
+}
@@ -1,4 +1,10 @@
+mod forgot;
 mod get;
 mod post;
+mod reset;
+mod two_factor;
+pub use forgot::{forgot_password_form, request_password_reset};
 pub use get::login_form;
 pub use post::login;
+pub use reset::{password_reset_form, reset_password};
+pub use two_factor::{two_factor_form, verify_two_factor};
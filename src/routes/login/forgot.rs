@@ -0,0 +1,137 @@
+use std::fmt::Write;
+
+use anyhow::Context;
+use axum::{
+    extract::State,
+    response::{Html, IntoResponse, Redirect, Response},
+    Form,
+};
+use axum_messages::Messages;
+use entity::{
+    password_reset_tokens::{self, Entity as PasswordResetTokens},
+    users::{self, Entity as Users},
+};
+use handlebars::Handlebars;
+use sea_orm::{ActiveValue::Set, ColumnTrait, EntityTrait, QueryFilter};
+use serde_json::json;
+use time::{Duration, OffsetDateTime};
+
+use tracing::Instrument;
+
+use super::reset::hash_reset_token;
+use crate::{
+    domain::SubscriberEmail, email_templates::EmailTemplates, routes::generate_subscription_token,
+    startup::AppState, utils::e500,
+};
+
+/// How long the link resetting a password stays valid.
+const PASSWORD_RESET_TTL: Duration = Duration::hours(1);
+
+#[derive(serde::Deserialize)]
+pub struct ForgotPasswordFormData {
+    username: String,
+}
+
+pub async fn forgot_password_form(messages: Messages) -> Result<Html<String>, Response> {
+    let mut msg_html = String::new();
+    for m in messages.into_iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.message).unwrap();
+    }
+    let html = Handlebars::new()
+        .render_template(
+            include_str!("./forgot.html"),
+            &json!({ "messages": msg_html }),
+        )
+        .map_err(e500)?;
+    Ok(Html::from(html))
+}
+
+/// Emails a reset link to the address of the account. The answer is the same
+/// whether the account exists or not, so that it cannot be used to find out
+/// which usernames are taken. The lookup and the email happen in the
+/// background for the same reason: waiting on them would tell the two cases
+/// apart by how long the answer takes.
+#[tracing::instrument(name = "Request a password reset", skip(state, messages, form))]
+pub async fn request_password_reset(
+    State(state): State<AppState>,
+    messages: Messages,
+    Form(form): Form<ForgotPasswordFormData>,
+) -> Response {
+    tokio::spawn(send_password_reset_email(state, form.username).in_current_span());
+    messages.info(
+        "If there is an account with that username, a link to reset its password \
+        has been sent to its email address.",
+    );
+    Redirect::to("/login/forgot").into_response()
+}
+
+#[tracing::instrument(skip_all)]
+async fn send_password_reset_email(state: AppState, username: String) {
+    let user = match Users::find()
+        .filter(users::Column::Username.eq(username.trim()))
+        .filter(users::Column::DisabledAt.is_null())
+        .one(&state.connection)
+        .await
+    {
+        Ok(Some(user)) => user,
+        Ok(None) => {
+            tracing::info!("There is no active user with that username.");
+            return;
+        }
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to look up the user.");
+            return;
+        }
+    };
+    if let Err(e) = email_reset_link(&state, &user).await {
+        tracing::error!(
+            error.cause_chain = ?e,
+            error.message = %e,
+            "Failed to send a password reset email."
+        );
+    }
+}
+
+#[tracing::instrument(skip_all, fields(user_id = %user.user_id))]
+async fn email_reset_link(state: &AppState, user: &users::Model) -> Result<(), anyhow::Error> {
+    let email = user
+        .email
+        .clone()
+        .context("The user has no email address.")?;
+    let email = SubscriberEmail::parse(email).map_err(anyhow::Error::msg)?;
+    let token = generate_subscription_token();
+    let now = OffsetDateTime::now_utc();
+    let reset_token = password_reset_tokens::ActiveModel {
+        token_hash: Set(hash_reset_token(&token)),
+        user_id: Set(user.user_id),
+        created_at: Set(now),
+        expires_at: Set(now + PASSWORD_RESET_TTL),
+        consumed_at: Set(None),
+    };
+    PasswordResetTokens::insert(reset_token)
+        .exec_without_returning(&state.connection)
+        .await
+        .context("Failed to store the password reset token.")?;
+
+    let reset_link = format!("{}/login/reset?token={}", state.base_url, token);
+    let content = state
+        .email_templates
+        .render(
+            EmailTemplates::PASSWORD_RESET,
+            &json!({
+                "username": user.username,
+                "reset_link": reset_link,
+            }),
+        )
+        .context("Failed to render the password reset email.")?;
+    state
+        .email_client
+        .send_email(
+            &email,
+            "Reset your password",
+            &content.html_content,
+            &content.text_content,
+        )
+        .await?;
+    Ok(())
+}
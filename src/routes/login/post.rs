@@ -1,3 +1,4 @@
+use anyhow::Context;
 use axum::{
     extract::State,
     http::StatusCode,
@@ -5,12 +6,18 @@ use axum::{
     Form,
 };
 use axum_messages::Messages;
+use entity::users::Entity as Users;
+use sea_orm::EntityTrait;
 use secrecy::Secret;
+use time::OffsetDateTime;
 
 use crate::{
+    audit::{record_audit_event, AuditEvent},
     authentication::{validate_credentials, AuthError, Credentials},
+    consent::RequestOrigin,
+    login_throttle::LockoutScope,
     routes::error_chain_fmt,
-    session_state::TypedSession,
+    session_state::{PendingTwoFactor, TypedSession},
     startup::AppState,
 };
 
@@ -21,28 +28,83 @@ pub struct FormData {
 }
 
 #[tracing::instrument(
-    skip(state, flash, session, form),
+    skip(state, flash, session, origin, form),
     fields(username=tracing::field::Empty, user_id=tracing::field::Empty)
 )]
 pub async fn login(
     State(state): State<AppState>,
     flash: Messages,
     session: TypedSession,
+    origin: RequestOrigin,
     Form(form): Form<FormData>,
 ) -> Result<Response, Response> {
     let credentials = Credentials {
         username: form.username,
         password: form.password,
     };
-    tracing::Span::current().record("username", &tracing::field::display(&credentials.username));
+    tracing::Span::current().record("username", tracing::field::display(&credentials.username));
+    let username = credentials.username.clone();
+    let ip_address = origin.ip_address.as_deref();
+    let throttle = &state.login_throttle;
+    let unexpected =
+        |e: anyhow::Error| login_redirect(flash.clone(), LoginError::UnexpectedError(e));
+
+    // A locked out username or address does not even get its password checked.
+    if let Some(remaining) = throttle
+        .locked_for(&username, ip_address)
+        .await
+        .context("Failed to check the login lockout.")
+        .map_err(unexpected)?
+    {
+        tracing::warn!(%remaining, "The login is locked out.");
+        return Err(login_redirect(
+            flash,
+            LoginError::LockedOut(minutes(remaining)),
+        ));
+    }
+    let delay = throttle
+        .delay(&username, ip_address)
+        .await
+        .context("Failed to compute the login delay.")
+        .map_err(unexpected)?;
+    tokio::time::sleep(delay).await;
+
     match validate_credentials(credentials, &state.connection).await {
         Ok(user_id) => {
-            tracing::Span::current().record("user_id", &tracing::field::debug(&user_id));
+            tracing::Span::current().record("user_id", tracing::field::debug(&user_id));
 
+            let has_two_factor = Users::find_by_id(user_id)
+                .one(&state.connection)
+                .await
+                .context("Failed to look up the user.")
+                .map_err(unexpected)?
+                .is_some_and(|user| user.totp_enabled_at.is_some());
+            // With two-factor authentication the failures are only forgotten
+            // once the code is right as well.
+            if !has_two_factor {
+                throttle
+                    .record_success(&username)
+                    .await
+                    .context("Failed to reset the failed login attempts.")
+                    .map_err(unexpected)?;
+            }
             let redirect = move |e: tower_sessions::session::Error| {
                 login_redirect(flash.clone(), LoginError::UnexpectedError(e.into()))
             };
             session.cycle_id().await.map_err(&redirect)?;
+            if has_two_factor {
+                let pending = PendingTwoFactor {
+                    user_id,
+                    started_at: OffsetDateTime::now_utc(),
+                };
+                session
+                    .insert_pending_two_factor(&pending)
+                    .await
+                    .map_err(&redirect)?;
+                return Ok(
+                    (StatusCode::SEE_OTHER, Redirect::to("/login/two-factor")).into_response()
+                );
+            }
             session.insert_user_id(user_id).await.map_err(&redirect)?;
 
             Ok((StatusCode::SEE_OTHER, Redirect::to("/admin/dashboard")).into_response())
@@ -53,6 +115,13 @@ pub async fn login(
                 AuthError::InvalidCredentials(_) => LoginError::AuthError(e.into()),
                 AuthError::UnexpectedError(_) => LoginError::UnexpectedError(e.into()),
             };
+            let e = match e {
+                LoginError::AuthError(_) => record_failure(&state, &username, &origin)
+                    .await
+                    .map_err(unexpected)?
+                    .unwrap_or(e),
+                e => e,
+            };
 
             let _flash = flash.error(e.to_string());
 
@@ -61,6 +130,50 @@ pub async fn login(
     }
 }
 
+/// Counts a failed login, returns the error to show if it triggered a
+/// lockout.
+pub(super) async fn record_failure(
+    state: &AppState,
+    username: &str,
+    origin: &RequestOrigin,
+) -> Result<Option<LoginError>, anyhow::Error> {
+    let lockouts = state
+        .login_throttle
+        .record_failure(username, origin.ip_address.as_deref())
+        .await
+        .context("Failed to count the failed login.")?;
+    for lockout in &lockouts {
+        tracing::warn!(?lockout, "Too many failed logins, locking out.");
+        let details = match lockout.scope {
+            LockoutScope::Username => {
+                format!("{} failed attempts for the username", lockout.failures)
+            }
+            LockoutScope::IpAddress => {
+                format!("{} failed attempts from the IP address", lockout.failures)
+            }
+        };
+        record_audit_event(
+            &state.connection,
+            AuditEvent::LoginLockout,
+            Some(username),
+            origin,
+            Some(&details),
+        )
+        .await
+        .context("Failed to record the lockout.")?;
+    }
+    Ok(lockouts
+        .iter()
+        .map(|lockout| lockout.duration)
+        .max()
+        .map(|duration| LoginError::LockedOut(minutes(duration))))
+}
+
+/// Whole minutes, rounded up.
+pub(super) fn minutes(duration: time::Duration) -> i64 {
+    (duration.whole_seconds() + 59) / 60
+}
+
 // Redirect to the login page with an error message.
 fn login_redirect(flash: Messages, e: LoginError) -> Response {
     flash.error(e.to_string());
@@ -71,6 +184,8 @@ fn login_redirect(flash: Messages, e: LoginError) -> Response {
 pub enum LoginError {
     #[error("Authentication failed")]
     AuthError(#[source] anyhow::Error),
+    #[error("Too many failed login attempts, please try again in {0} minutes.")]
+    LockedOut(i64),
     #[error("Something went wrong")]
     UnexpectedError(#[from] anyhow::Error),
 }
@@ -0,0 +1,160 @@
+use anyhow::Context;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+    Form,
+};
+use axum_messages::Messages;
+use entity::{
+    password_reset_tokens::{self, Entity as PasswordResetTokens},
+    users::{self, Entity as Users},
+};
+use handlebars::Handlebars;
+use sea_orm::{sea_query::Expr, ColumnTrait, EntityTrait, QueryFilter, TransactionTrait};
+use secrecy::{ExposeSecret, Secret};
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use time::OffsetDateTime;
+
+use crate::{
+    authentication,
+    routes::{
+        error_chain_fmt,
+        single_use_token::{find_open_token, TokenError},
+    },
+    startup::AppState,
+};
+
+#[derive(serde::Deserialize)]
+pub struct PasswordResetParameters {
+    token: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct PasswordResetFormData {
+    token: String,
+    new_password: Secret<String>,
+    new_password_check: Secret<String>,
+}
+
+#[derive(thiserror::Error)]
+pub enum PasswordResetError {
+    #[error("The password reset link is not valid.")]
+    InvalidLink,
+    #[error("The password reset link has expired.")]
+    ExpiredLink,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for PasswordResetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl From<TokenError> for PasswordResetError {
+    fn from(e: TokenError) -> Self {
+        match e {
+            TokenError::Invalid => PasswordResetError::InvalidLink,
+            TokenError::Expired => PasswordResetError::ExpiredLink,
+            TokenError::Unexpected(e) => PasswordResetError::UnexpectedError(e),
+        }
+    }
+}
+
+impl IntoResponse for PasswordResetError {
+    fn into_response(self) -> Response {
+        tracing::error!(exception.details = ?self, exception.message = %self);
+        match self {
+            PasswordResetError::InvalidLink => StatusCode::UNAUTHORIZED.into_response(),
+            PasswordResetError::ExpiredLink => StatusCode::GONE.into_response(),
+            PasswordResetError::UnexpectedError(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            }
+        }
+    }
+}
+
+/// Only the hash of a token is stored, so that a leaked table cannot be used
+/// to take over accounts. The token is random enough that a plain hash will do.
+pub(super) fn hash_reset_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+fn render_form(token: &str, messages: &[&str]) -> Result<String, PasswordResetError> {
+    Handlebars::new()
+        .render_template(
+            include_str!("./reset.html"),
+            &json!({ "token": token, "messages": messages }),
+        )
+        .context("Failed to render the password reset form.")
+        .map_err(Into::into)
+}
+
+#[tracing::instrument(name = "Show the password reset form", skip(state, params))]
+pub async fn password_reset_form(
+    State(state): State<AppState>,
+    Query(params): Query<PasswordResetParameters>,
+) -> Result<Html<String>, PasswordResetError> {
+    find_open_token::<PasswordResetTokens>(
+        &state.connection,
+        hash_reset_token(&params.token),
+        false,
+    )
+    .await?;
+    Ok(Html::from(render_form(&params.token, &[])?))
+}
+
+/// Sets the new password and logs the user out everywhere, in case someone
+/// else got hold of the old one.
+#[tracing::instrument(name = "Reset a password", skip(state, messages, form))]
+pub async fn reset_password(
+    State(state): State<AppState>,
+    messages: Messages,
+    Form(form): Form<PasswordResetFormData>,
+) -> Result<Response, PasswordResetError> {
+    let txn = state
+        .connection
+        .begin()
+        .await
+        .context("Failed to begin a transaction.")?;
+    let reset_token =
+        find_open_token::<PasswordResetTokens>(&txn, hash_reset_token(&form.token), true).await?;
+    if form.new_password.expose_secret().is_empty() {
+        let html = render_form(&form.token, &["The new password cannot be empty."])?;
+        return Ok((StatusCode::BAD_REQUEST, Html::from(html)).into_response());
+    }
+    if form.new_password.expose_secret() != form.new_password_check.expose_secret() {
+        let html = render_form(
+            &form.token,
+            &["You entered two different new passwords - the field values must match."],
+        )?;
+        return Ok((StatusCode::BAD_REQUEST, Html::from(html)).into_response());
+    }
+
+    let user_id = reset_token.user_id;
+    authentication::change_password(user_id, form.new_password, &txn).await?;
+    let now = OffsetDateTime::now_utc();
+    Users::update_many()
+        .col_expr(users::Column::SessionsRevokedAt, Expr::value(now))
+        .filter(users::Column::UserId.eq(user_id))
+        .exec(&txn)
+        .await
+        .context("Failed to revoke the sessions of the user.")?;
+    // Other links that were sent to the user stop working as well.
+    PasswordResetTokens::update_many()
+        .col_expr(password_reset_tokens::Column::ConsumedAt, Expr::value(now))
+        .filter(password_reset_tokens::Column::UserId.eq(user_id))
+        .filter(password_reset_tokens::Column::ConsumedAt.is_null())
+        .exec(&txn)
+        .await
+        .context("Failed to consume the password reset tokens.")?;
+    txn.commit()
+        .await
+        .context("Failed to commit the new password.")?;
+
+    messages.success("Your password has been reset. You can log in with the new one now.");
+    Ok(Redirect::to("/login").into_response())
+}
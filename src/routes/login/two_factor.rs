@@ -0,0 +1,134 @@
+use std::fmt::Write;
+
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+    Form,
+};
+use axum_messages::Messages;
+use entity::users::{self, Entity as Users};
+use handlebars::Handlebars;
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use serde_json::json;
+use time::{Duration, OffsetDateTime};
+
+use super::post::{minutes, record_failure, LoginError};
+use crate::{
+    authentication::verify_second_factor,
+    consent::RequestOrigin,
+    session_state::{PendingTwoFactor, TypedSession},
+    startup::AppState,
+    utils::e500,
+};
+
+/// How long a user has to enter their code after their password.
+const PENDING_TTL: Duration = Duration::minutes(5);
+
+#[derive(serde::Deserialize)]
+pub struct TwoFactorFormData {
+    code: String,
+}
+
+/// The pending login of the session, unless there is none or it is too old.
+async fn get_pending(session: &TypedSession) -> Result<Option<PendingTwoFactor>, Response> {
+    let pending = session.get_pending_two_factor().await.map_err(e500)?;
+    match pending {
+        Some(pending) if pending.started_at + PENDING_TTL >= OffsetDateTime::now_utc() => {
+            Ok(Some(pending))
+        }
+        Some(_) => {
+            session.remove_pending_two_factor().await.map_err(e500)?;
+            Ok(None)
+        }
+        None => Ok(None),
+    }
+}
+
+pub async fn two_factor_form(
+    session: TypedSession,
+    messages: Messages,
+) -> Result<Response, Response> {
+    if get_pending(&session).await?.is_none() {
+        return Ok(Redirect::to("/login").into_response());
+    }
+    let mut msg_html = String::new();
+    for m in messages.into_iter() {
+        writeln!(msg_html, "<p><i>{}</i></p>", m.message).unwrap();
+    }
+    let html = Handlebars::new()
+        .render_template(
+            include_str!("./two_factor.html"),
+            &json!({ "messages": msg_html }),
+        )
+        .map_err(e500)?;
+    Ok((StatusCode::OK, Html::from(html)).into_response())
+}
+
+/// The second step of the login, for users with two-factor authentication.
+/// Wrong codes count as failed logins of the user, so they are locked out
+/// just the same however many times they enter their password in between.
+#[tracing::instrument(
+    skip(state, session, messages, origin, form),
+    fields(user_id = tracing::field::Empty)
+)]
+pub async fn verify_two_factor(
+    State(state): State<AppState>,
+    session: TypedSession,
+    messages: Messages,
+    origin: RequestOrigin,
+    Form(form): Form<TwoFactorFormData>,
+) -> Result<Response, Response> {
+    let Some(pending) = get_pending(&session).await? else {
+        messages.error("Please log in again.");
+        return Ok(Redirect::to("/login").into_response());
+    };
+    tracing::Span::current().record("user_id", tracing::field::display(&pending.user_id));
+    let user = Users::find_by_id(pending.user_id)
+        .filter(users::Column::DisabledAt.is_null())
+        .one(&state.connection)
+        .await
+        .map_err(e500)?;
+    let Some(user) = user else {
+        session.remove_pending_two_factor().await.map_err(e500)?;
+        return Ok(Redirect::to("/login").into_response());
+    };
+
+    let throttle = &state.login_throttle;
+    if let Some(remaining) = throttle
+        .locked_for(&user.username, origin.ip_address.as_deref())
+        .await
+        .map_err(e500)?
+    {
+        tracing::warn!(%remaining, "The login is locked out.");
+        session.remove_pending_two_factor().await.map_err(e500)?;
+        messages.error(LoginError::LockedOut(minutes(remaining)).to_string());
+        return Ok(Redirect::to("/login").into_response());
+    }
+
+    if verify_second_factor(&user, &form.code, &state.connection)
+        .await
+        .map_err(e500)?
+    {
+        throttle
+            .record_success(&user.username)
+            .await
+            .map_err(e500)?;
+        session.remove_pending_two_factor().await.map_err(e500)?;
+        session.cycle_id().await.map_err(e500)?;
+        session.insert_user_id(user.user_id).await.map_err(e500)?;
+        return Ok((StatusCode::SEE_OTHER, Redirect::to("/admin/dashboard")).into_response());
+    }
+
+    tracing::warn!("Wrong second factor.");
+    if let Some(e) = record_failure(&state, &user.username, &origin)
+        .await
+        .map_err(e500)?
+    {
+        session.remove_pending_two_factor().await.map_err(e500)?;
+        messages.error(e.to_string());
+        return Ok(Redirect::to("/login").into_response());
+    }
+    messages.error("The code is not valid.");
+    Ok(Redirect::to("/login/two-factor").into_response())
+}
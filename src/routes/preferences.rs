@@ -0,0 +1,610 @@
+use std::collections::HashSet;
+
+use anyhow::Context;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Response},
+};
+use axum_extra::extract::Form;
+use entity::{
+    email_change_requests::{self, Entity as EmailChangeRequests},
+    issue_delivery_queue::{self, Entity as IssueDeliveryQueue},
+    list_memberships::{self, Entity as ListMemberships},
+    lists,
+    sea_orm_active_enums::{DigestFrequency, SubscriptionStatus},
+    subscriptions::{self, Entity as Subscriptions},
+};
+use handlebars::Handlebars;
+use sea_orm::{
+    sea_query::Expr, ActiveEnum, ActiveValue::Set, ColumnTrait, DatabaseConnection, DbErr,
+    EntityTrait, PaginatorTrait, QueryFilter, QuerySelect, TransactionTrait,
+};
+use serde_json::json;
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+
+use crate::{
+    consent::{record_consent_event, ConsentEvent, RequestOrigin},
+    domain::{SubscriberEmail, SubscriberName},
+    email_templates::EmailTemplates,
+    lists::{all_lists, join_list},
+    routes::generate_subscription_token,
+    startup::AppState,
+};
+
+use super::error_chain_fmt;
+
+/// How long the link confirming a new address stays valid.
+const EMAIL_CHANGE_TTL: Duration = Duration::hours(24);
+
+/// The digest frequencies in the order they are offered.
+const FREQUENCIES: [(DigestFrequency, &str); 3] = [
+    (
+        DigestFrequency::Immediately,
+        "Every issue as soon as it is published",
+    ),
+    (
+        DigestFrequency::Daily,
+        "A daily digest of the new issues, at 08:00 UTC",
+    ),
+    (
+        DigestFrequency::Weekly,
+        "A weekly digest of the new issues, on Mondays at 08:00 UTC",
+    ),
+];
+
+#[derive(serde::Deserialize)]
+pub struct PreferencesParameters {
+    subscriber_id: Uuid,
+    signature: String,
+}
+
+impl PreferencesParameters {
+    fn verify(&self, state: &AppState) -> Result<(), PreferencesError> {
+        state
+            .link_signer
+            .verify_preferences(self.subscriber_id, &self.signature)
+            .map_err(|_| PreferencesError::InvalidLink)
+    }
+
+    fn query(&self) -> String {
+        format!(
+            "subscriber_id={}&signature={}",
+            self.subscriber_id, self.signature
+        )
+    }
+}
+
+#[derive(serde::Deserialize)]
+pub struct PreferencesFormData {
+    name: String,
+    email: String,
+    #[serde(default)]
+    lists: Vec<String>,
+    frequency: DigestFrequency,
+}
+
+#[derive(serde::Deserialize)]
+pub struct EmailChangeParameters {
+    token: String,
+}
+
+/// Preferences as submitted, once validated.
+struct Preferences {
+    name: SubscriberName,
+    email: SubscriberEmail,
+    list_ids: HashSet<Uuid>,
+    frequency: DigestFrequency,
+}
+
+impl Preferences {
+    fn parse(form: &PreferencesFormData, lists: &[lists::Model]) -> Result<Self, String> {
+        let name = SubscriberName::parse(form.name.clone())?;
+        let email = SubscriberEmail::parse(form.email.trim().to_owned())?;
+        let list_ids = form
+            .lists
+            .iter()
+            .map(|slug| {
+                lists
+                    .iter()
+                    .find(|list| &list.slug == slug)
+                    .map(|list| list.id)
+                    .ok_or_else(|| format!("There is no list called {}.", slug))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self {
+            name,
+            email,
+            list_ids,
+            frequency: form.frequency,
+        })
+    }
+}
+
+#[derive(thiserror::Error)]
+pub enum PreferencesError {
+    #[error("The preferences link is invalid.")]
+    InvalidLink,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for PreferencesError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl IntoResponse for PreferencesError {
+    fn into_response(self) -> Response {
+        tracing::error!(exception.details = ?self, exception.message = %self);
+        match self {
+            PreferencesError::InvalidLink => StatusCode::UNAUTHORIZED.into_response(),
+            PreferencesError::UnexpectedError(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            }
+        }
+    }
+}
+
+#[derive(thiserror::Error)]
+pub enum EmailChangeError {
+    #[error("The email change link is not valid.")]
+    InvalidLink,
+    #[error("The email change link has expired.")]
+    ExpiredLink,
+    #[error("The new address is already subscribed.")]
+    AddressTaken,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for EmailChangeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl IntoResponse for EmailChangeError {
+    fn into_response(self) -> Response {
+        tracing::error!(exception.details = ?self, exception.message = %self);
+        match self {
+            EmailChangeError::InvalidLink => StatusCode::UNAUTHORIZED.into_response(),
+            EmailChangeError::ExpiredLink => StatusCode::GONE.into_response(),
+            EmailChangeError::AddressTaken => StatusCode::CONFLICT.into_response(),
+            EmailChangeError::UnexpectedError(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            }
+        }
+    }
+}
+
+/// What the preferences form shows, either as stored or as submitted.
+struct PreferencesPage<'a> {
+    name: &'a str,
+    email: &'a str,
+    /// Slugs of the checked lists.
+    checked_lists: HashSet<&'a str>,
+    frequency: DigestFrequency,
+    messages: Vec<String>,
+}
+
+impl PreferencesPage<'_> {
+    fn render(
+        &self,
+        params: &PreferencesParameters,
+        lists: &[lists::Model],
+    ) -> Result<String, handlebars::RenderError> {
+        let lists: Vec<_> = lists
+            .iter()
+            .map(|list| {
+                json!({
+                    "slug": list.slug,
+                    "name": list.name,
+                    "checked": self.checked_lists.contains(list.slug.as_str()),
+                })
+            })
+            .collect();
+        let frequencies: Vec<_> = FREQUENCIES
+            .iter()
+            .map(|(frequency, label)| {
+                json!({
+                    "value": frequency.to_value(),
+                    "label": label,
+                    "checked": *frequency == self.frequency,
+                })
+            })
+            .collect();
+        Handlebars::new().render_template(
+            include_str!("./preferences/page.html"),
+            &json!({
+                "query": params.query(),
+                "name": self.name,
+                "email": self.email,
+                "lists": lists,
+                "frequencies": frequencies,
+                "messages": self.messages,
+            }),
+        )
+    }
+}
+
+/// Renders the preferences of a subscriber as they are stored.
+async fn render_stored_preferences(
+    connection: &DatabaseConnection,
+    params: &PreferencesParameters,
+    lists: &[lists::Model],
+    messages: Vec<String>,
+) -> Result<Response, PreferencesError> {
+    let Some((subscriber, memberships)) = Subscriptions::find_by_id(params.subscriber_id)
+        .find_with_related(ListMemberships)
+        .all(connection)
+        .await
+        .context("Failed to look up the subscriber.")?
+        .pop()
+    else {
+        // Erased in the meantime
+        return Err(PreferencesError::InvalidLink);
+    };
+    let confirmed_list_ids: HashSet<_> = memberships
+        .into_iter()
+        .filter(|membership| membership.status == SubscriptionStatus::Confirmed)
+        .map(|membership| membership.list_id)
+        .collect();
+    let page = PreferencesPage {
+        name: &subscriber.name,
+        email: &subscriber.email,
+        checked_lists: lists
+            .iter()
+            .filter(|list| confirmed_list_ids.contains(&list.id))
+            .map(|list| list.slug.as_str())
+            .collect(),
+        frequency: subscriber.digest_frequency,
+        messages,
+    };
+    let html = page
+        .render(params, lists)
+        .context("Failed to render the preferences page.")?;
+    Ok(Html::from(html).into_response())
+}
+
+#[tracing::instrument(name = "Show the preferences page", skip(state, params))]
+pub async fn preferences_form(
+    State(state): State<AppState>,
+    Query(params): Query<PreferencesParameters>,
+) -> Result<Response, PreferencesError> {
+    params.verify(&state)?;
+    let lists = all_lists(&state.connection)
+        .await
+        .context("Failed to load the lists.")?;
+    render_stored_preferences(&state.connection, &params, &lists, Vec::new()).await
+}
+
+/// Saves everything but the email address right away. A new address only
+/// replaces the current one once it has been confirmed.
+#[tracing::instrument(
+    name = "Update subscriber preferences",
+    skip(state, origin, params, form),
+    fields(subscriber_id = %params.subscriber_id)
+)]
+pub async fn update_preferences(
+    State(state): State<AppState>,
+    origin: RequestOrigin,
+    Query(params): Query<PreferencesParameters>,
+    Form(form): Form<PreferencesFormData>,
+) -> Result<Response, PreferencesError> {
+    params.verify(&state)?;
+    let lists = all_lists(&state.connection)
+        .await
+        .context("Failed to load the lists.")?;
+    let preferences = match Preferences::parse(&form, &lists) {
+        Ok(preferences) => preferences,
+        Err(e) => {
+            let page = PreferencesPage {
+                name: &form.name,
+                email: &form.email,
+                checked_lists: form.lists.iter().map(String::as_str).collect(),
+                frequency: form.frequency,
+                messages: vec![e],
+            };
+            let html = page
+                .render(&params, &lists)
+                .context("Failed to render the preferences page.")?;
+            return Ok((StatusCode::BAD_REQUEST, Html::from(html)).into_response());
+        }
+    };
+
+    let Some(email_change) = save_preferences(
+        &state.connection,
+        params.subscriber_id,
+        &preferences,
+        &origin,
+    )
+    .await
+    .context("Failed to save the preferences.")?
+    else {
+        return Err(PreferencesError::InvalidLink);
+    };
+    let mut messages = vec!["Your preferences have been saved.".to_owned()];
+    if let EmailChange::Requested { token } = &email_change {
+        send_email_change_email(&state, &preferences, token)
+            .await
+            .context("Failed to send the email change confirmation.")?;
+    }
+    if email_change != EmailChange::Unchanged {
+        // The message is the same when the address is taken, telling them
+        // apart would disclose who else is subscribed.
+        messages.push(format!(
+            "We sent a confirmation link to {}. Your address changes once you follow it.",
+            preferences.email.as_ref()
+        ));
+    }
+    render_stored_preferences(&state.connection, &params, &lists, messages).await
+}
+
+/// What became of the address submitted with the other preferences.
+#[derive(PartialEq, Eq)]
+enum EmailChange {
+    Unchanged,
+    Requested {
+        token: String,
+    },
+    /// The new address belongs to another subscriber.
+    AddressTaken,
+}
+
+/// Stores the new preferences of a subscriber, and a pending change request
+/// if they asked for another address.
+///
+/// Returns `None` if there is no such subscriber (any more).
+#[tracing::instrument(skip(connection, preferences, origin))]
+async fn save_preferences(
+    connection: &DatabaseConnection,
+    subscriber_id: Uuid,
+    preferences: &Preferences,
+    origin: &RequestOrigin,
+) -> Result<Option<EmailChange>, DbErr> {
+    let txn = connection.begin().await?;
+    let Some(subscriber) = Subscriptions::find_by_id(subscriber_id)
+        .lock_exclusive()
+        .one(&txn)
+        .await?
+    else {
+        return Ok(None);
+    };
+
+    for list_id in &preferences.list_ids {
+        join_list(&txn, *list_id, subscriber_id, SubscriptionStatus::Confirmed).await?;
+    }
+    ListMemberships::update_many()
+        .col_expr(
+            list_memberships::Column::Status,
+            SubscriptionStatus::Unsubscribed.as_enum(),
+        )
+        .filter(list_memberships::Column::SubscriberId.eq(subscriber_id))
+        .filter(list_memberships::Column::ListId.is_not_in(preferences.list_ids.iter().copied()))
+        .filter(list_memberships::Column::Status.eq(SubscriptionStatus::Confirmed))
+        .exec(&txn)
+        .await?;
+    // The subscriber status follows the lists: confirmed as long as they
+    // receive any of them.
+    let status = if preferences.list_ids.is_empty() {
+        if subscriber.status == SubscriptionStatus::Confirmed {
+            SubscriptionStatus::Unsubscribed
+        } else {
+            subscriber.status
+        }
+    } else {
+        SubscriptionStatus::Confirmed
+    };
+    if status != subscriber.status {
+        let event = match status {
+            SubscriptionStatus::Confirmed => ConsentEvent::Confirmed,
+            _ => ConsentEvent::Unsubscribed,
+        };
+        record_consent_event(&txn, subscriber_id, event, origin, Some("preferences")).await?;
+    }
+
+    Subscriptions::update_many()
+        .col_expr(
+            subscriptions::Column::Name,
+            Expr::value(preferences.name.as_ref()),
+        )
+        .col_expr(subscriptions::Column::Status, status.as_enum())
+        .col_expr(
+            subscriptions::Column::DigestFrequency,
+            preferences.frequency.as_enum(),
+        )
+        .filter(subscriptions::Column::Id.eq(subscriber_id))
+        .exec(&txn)
+        .await?;
+
+    let new_email = preferences.email.as_ref();
+    let email_change = if new_email == subscriber.email {
+        EmailChange::Unchanged
+    } else {
+        let is_taken = Subscriptions::find()
+            .filter(subscriptions::Column::Email.eq(new_email))
+            .count(&txn)
+            .await?
+            > 0;
+        if is_taken {
+            EmailChange::AddressTaken
+        } else {
+            let token = generate_subscription_token();
+            let now = OffsetDateTime::now_utc();
+            EmailChangeRequests::insert(email_change_requests::ActiveModel {
+                token: Set(token.clone()),
+                subscriber_id: Set(subscriber_id),
+                new_email: Set(new_email.to_owned()),
+                created_at: Set(now),
+                expires_at: Set(now + EMAIL_CHANGE_TTL),
+                consumed_at: Set(None),
+            })
+            .exec_without_returning(&txn)
+            .await?;
+            EmailChange::Requested { token }
+        }
+    };
+    txn.commit().await?;
+    Ok(Some(email_change))
+}
+
+#[tracing::instrument(skip_all)]
+async fn send_email_change_email(
+    state: &AppState,
+    preferences: &Preferences,
+    token: &str,
+) -> Result<(), anyhow::Error> {
+    let confirmation_link = format!("{}/preferences/email?token={}", state.base_url, token);
+    let content = state.email_templates.render(
+        EmailTemplates::EMAIL_CHANGE,
+        &json!({
+            "name": preferences.name.as_ref(),
+            "confirmation_link": confirmation_link,
+        }),
+    )?;
+    state
+        .email_client
+        .send_email(
+            &preferences.email,
+            "Confirm your new address",
+            &content.html_content,
+            &content.text_content,
+        )
+        .await?;
+    Ok(())
+}
+
+/// Tells the old address where the newsletter goes from now on, so that a
+/// change the subscriber did not ask for does not go unnoticed.
+#[tracing::instrument(skip_all)]
+async fn send_email_changed_notification(
+    state: &AppState,
+    subscriber: &subscriptions::Model,
+    new_email: &str,
+) -> Result<(), anyhow::Error> {
+    let old_email = SubscriberEmail::parse(subscriber.email.clone()).map_err(anyhow::Error::msg)?;
+    let content = state.email_templates.render(
+        EmailTemplates::EMAIL_CHANGED,
+        &json!({
+            "name": subscriber.name,
+            "new_email": new_email,
+        }),
+    )?;
+    state
+        .email_client
+        .send_email(
+            &old_email,
+            "Your address has been changed",
+            &content.html_content,
+            &content.text_content,
+        )
+        .await?;
+    Ok(())
+}
+
+/// Replaces the address of a subscriber with the one the link was sent to,
+/// and lets the old address know about it.
+#[tracing::instrument(name = "Confirm an email change", skip(state, origin, params))]
+pub async fn confirm_email_change(
+    State(state): State<AppState>,
+    origin: RequestOrigin,
+    Query(params): Query<EmailChangeParameters>,
+) -> Result<Response, EmailChangeError> {
+    let txn = state
+        .connection
+        .begin()
+        .await
+        .context("Failed to begin a transaction.")?;
+    let Some(request) = EmailChangeRequests::find_by_id(&params.token)
+        .lock_exclusive()
+        .one(&txn)
+        .await
+        .context("Failed to look up the email change request.")?
+    else {
+        return Err(EmailChangeError::InvalidLink);
+    };
+    if request.consumed_at.is_some() {
+        return Err(EmailChangeError::InvalidLink);
+    }
+    let now = OffsetDateTime::now_utc();
+    if request.expires_at < now {
+        return Err(EmailChangeError::ExpiredLink);
+    }
+    let subscriber = Subscriptions::find_by_id(request.subscriber_id)
+        .lock_exclusive()
+        .one(&txn)
+        .await
+        .context("Failed to look up the subscriber.")?
+        .ok_or(EmailChangeError::InvalidLink)?;
+    // Someone may have subscribed with the address since it was requested.
+    let is_taken = Subscriptions::find()
+        .filter(subscriptions::Column::Email.eq(&request.new_email))
+        .count(&txn)
+        .await
+        .context("Failed to look up the new address.")?
+        > 0;
+    if is_taken {
+        return Err(EmailChangeError::AddressTaken);
+    }
+
+    // Issues on their way follow the subscriber to the new address.
+    IssueDeliveryQueue::update_many()
+        .col_expr(
+            issue_delivery_queue::Column::SubscriberEmail,
+            Expr::value(request.new_email.clone()),
+        )
+        .filter(issue_delivery_queue::Column::SubscriberEmail.eq(&subscriber.email))
+        .exec(&txn)
+        .await
+        .context("Failed to move the pending deliveries.")?;
+    Subscriptions::update_many()
+        .col_expr(
+            subscriptions::Column::Email,
+            Expr::value(request.new_email.clone()),
+        )
+        .filter(subscriptions::Column::Id.eq(subscriber.id))
+        .exec(&txn)
+        .await
+        .context("Failed to change the address.")?;
+    // Links to any other address the subscriber asked for stop working.
+    EmailChangeRequests::update_many()
+        .col_expr(email_change_requests::Column::ConsumedAt, Expr::value(now))
+        .filter(email_change_requests::Column::SubscriberId.eq(subscriber.id))
+        .filter(email_change_requests::Column::ConsumedAt.is_null())
+        .exec(&txn)
+        .await
+        .context("Failed to consume the email change requests.")?;
+    record_consent_event(
+        &txn,
+        subscriber.id,
+        ConsentEvent::EmailChanged,
+        &origin,
+        Some("preferences"),
+    )
+    .await
+    .context("Failed to record the email change.")?;
+    txn.commit()
+        .await
+        .context("Failed to commit the email change.")?;
+
+    // The change is done, the subscriber should not be told otherwise
+    // because the notification could not be sent.
+    if let Err(e) = send_email_changed_notification(&state, &subscriber, &request.new_email).await {
+        tracing::error!(
+            error.cause_chain = ?e,
+            error.message = %e,
+            "Failed to notify the old address of an email change."
+        );
+    }
+
+    let html = Handlebars::new()
+        .render_template(
+            include_str!("./preferences/email_changed.html"),
+            &json!({ "email": request.new_email }),
+        )
+        .context("Failed to render the email change page.")?;
+    Ok(Html::from(html).into_response())
+}
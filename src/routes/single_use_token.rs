@@ -0,0 +1,69 @@
+//! Tokens sent in an email link that can be used once, until they expire:
+//! invitations and password resets.
+
+use anyhow::Context;
+use sea_orm::{ConnectionTrait, EntityTrait, PrimaryKeyTrait, QuerySelect};
+use time::OffsetDateTime;
+
+pub trait SingleUseToken {
+    fn is_used(&self) -> bool;
+    fn expires_at(&self) -> OffsetDateTime;
+}
+
+impl SingleUseToken for entity::user_invitations::Model {
+    fn is_used(&self) -> bool {
+        self.accepted_at.is_some()
+    }
+
+    fn expires_at(&self) -> OffsetDateTime {
+        self.expires_at
+    }
+}
+
+impl SingleUseToken for entity::password_reset_tokens::Model {
+    fn is_used(&self) -> bool {
+        self.consumed_at.is_some()
+    }
+
+    fn expires_at(&self) -> OffsetDateTime {
+        self.expires_at
+    }
+}
+
+#[derive(Debug)]
+pub enum TokenError {
+    Invalid,
+    Expired,
+    Unexpected(anyhow::Error),
+}
+
+/// Looks up a token that can still be used. With `lock` the row stays locked
+/// until the end of the transaction, so that the link cannot be used twice.
+pub async fn find_open_token<E>(
+    connection: &impl ConnectionTrait,
+    id: String,
+    lock: bool,
+) -> Result<E::Model, TokenError>
+where
+    E: EntityTrait,
+    E::Model: SingleUseToken,
+    String: Into<<E::PrimaryKey as PrimaryKeyTrait>::ValueType>,
+{
+    let mut query = E::find_by_id(id);
+    if lock {
+        query = query.lock_exclusive();
+    }
+    let token = query
+        .one(connection)
+        .await
+        .context("Failed to look up the token.")
+        .map_err(TokenError::Unexpected)?
+        .ok_or(TokenError::Invalid)?;
+    if token.is_used() {
+        return Err(TokenError::Invalid);
+    }
+    if token.expires_at() < OffsetDateTime::now_utc() {
+        return Err(TokenError::Expired);
+    }
+    Ok(token)
+}
@@ -1,5 +1,3 @@
-use std::mem;
-
 use anyhow::Context;
 use axum::{
     extract::State,
@@ -8,27 +6,47 @@ use axum::{
     Form,
 };
 use rand::{distributions::Alphanumeric, thread_rng, Rng};
-use sea_orm::{ActiveModelTrait, ActiveValue::Set, DatabaseTransaction, DbErr, TransactionTrait};
+use sea_orm::{
+    sea_query::{Expr, Func, OnConflict},
+    ActiveModelTrait,
+    ActiveValue::Set,
+    ColumnTrait, DatabaseTransaction, DbErr, EntityTrait, IntoActiveModel, QueryFilter,
+    QuerySelect, TransactionTrait,
+};
 use serde::Serialize;
-use time::OffsetDateTime;
+use serde_json::json;
+use time::{Duration, OffsetDateTime};
 use uuid::Uuid;
 
 use crate::{
+    consent::{record_consent_event, ConsentEvent, RequestOrigin},
     domain::{NewSubscriber, SubscriberEmail, SubscriberName},
     email_client::EmailClient,
+    email_templates::EmailTemplates,
+    lists::{default_list, find_list, find_membership, join_list},
     routes::AppJson,
     startup::AppState,
 };
 
+use entity::sea_orm_active_enums::{DigestFrequency, SubscriptionStatus};
 use entity::subscription_tokens::{self};
-use entity::subscriptions::{self};
+use entity::subscriptions::{self, Entity as Subscriptions};
 
 use super::error_chain_fmt;
 
+/// How long a confirmation link stays valid.
+pub const SUBSCRIPTION_TOKEN_TTL: Duration = Duration::hours(24);
+
 #[derive(serde::Deserialize, Debug)]
 pub struct FormData {
     email: String,
     name: String,
+    /// Where the signup form is embedded, kept as proof of consent.
+    #[serde(default)]
+    source: Option<String>,
+    /// The list to join, the default list if left out.
+    #[serde(default)]
+    list: Option<String>,
 }
 
 impl TryFrom<FormData> for NewSubscriber {
@@ -42,7 +60,7 @@ impl TryFrom<FormData> for NewSubscriber {
 
 #[tracing::instrument(
     name = "Adding a new subscriber",
-    skip(state, form),
+    skip(state, origin, form),
     fields(
         subscriber_email = %form.email,
         subscriber_name = %form.name
@@ -50,19 +68,54 @@ impl TryFrom<FormData> for NewSubscriber {
 )]
 pub async fn subscribe(
     State(state): State<AppState>,
-    form: Form<FormData>,
+    origin: RequestOrigin,
+    Form(form): Form<FormData>,
 ) -> Result<StatusCode, SubscribeError> {
     let txn = state
         .connection
         .begin()
         .await
         .context("Failed to acquire a Postgres connection from the pool")?;
-    let new_subscriber: NewSubscriber = form.0.try_into()?;
-    let subscriber_id = insert_subscriber(&txn, new_subscriber.clone())
+    let source = form
+        .source
+        .clone()
+        .filter(|source| !source.trim().is_empty());
+    let list_slug = form.list.clone().filter(|list| !list.trim().is_empty());
+    let new_subscriber: NewSubscriber = form.try_into()?;
+    let list = match list_slug {
+        Some(slug) => find_list(&txn, &slug)
+            .await
+            .context("Failed to look up the list to subscribe to.")?
+            .ok_or_else(|| {
+                SubscribeError::ValidationError(format!("There is no list called {}.", slug))
+            })?,
+        None => default_list(&txn)
+            .await
+            .context("Failed to look up the default list.")?,
+    };
+    let subscriber_id = match insert_subscriber(&txn, &new_subscriber, list.id)
         .await
-        .context("Failed to insert new subscriber in the database.")?;
+        .context("Failed to insert new subscriber in the database.")?
+    {
+        SubscriptionState::AwaitingConfirmation(subscriber_id) => subscriber_id,
+        SubscriptionState::AlreadyConfirmed => {
+            // Same response as for a new subscriber, the caller must not be
+            // able to tell which addresses are on the list.
+            tracing::info!("The subscriber has already confirmed their subscription.");
+            return Ok(StatusCode::OK);
+        }
+    };
+    record_consent_event(
+        &txn,
+        subscriber_id,
+        ConsentEvent::SignedUp,
+        &origin,
+        source.as_deref(),
+    )
+    .await
+    .context("Failed to record the signup in the consent trail.")?;
     let subscription_token = generate_subscription_token();
-    store_token(&txn, subscriber_id, &subscription_token)
+    store_token(&txn, subscriber_id, list.id, &subscription_token)
         .await
         .context("Failed to store the confirmation token for a new subscriber.")?;
     txn.commit()
@@ -71,34 +124,109 @@ pub async fn subscribe(
     let email_client = state.email_client;
     send_confirmation_email(
         &email_client,
+        &state.email_templates,
         new_subscriber,
+        &list.name,
         &state.base_url,
         &subscription_token,
     )
     .await
     .context("Failed to send a confirmation email.")?;
+    record_consent_event(
+        &state.connection,
+        subscriber_id,
+        ConsentEvent::ConfirmationSent,
+        &RequestOrigin::default(),
+        None,
+    )
+    .await
+    .context("Failed to record the confirmation email in the consent trail.")?;
 
     Ok(StatusCode::OK)
 }
 
+/// Where a subscription request leaves the subscriber.
+pub enum SubscriptionState {
+    /// A confirmation email has to be sent to the subscriber.
+    AwaitingConfirmation(Uuid),
+    AlreadyConfirmed,
+}
+
+/// Stores a new subscriber and their pending membership of a list.
+/// Subscribing again with the address of an existing subscriber reuses their
+/// row, unless they have already confirmed their membership of the list.
+/// Their name is left as it is: the form needs no login, anyone could type in
+/// the address of someone else.
+///
+/// Erased addresses are not turned away, unlike in imports: only the owner
+/// of the address can confirm, which is consent given anew. The tombstone is
+/// dropped once they do.
 #[tracing::instrument(
     name = "Saving new subscriber details in the database",
     skip(txn, new_subscriber)
 )]
 pub async fn insert_subscriber(
     txn: &DatabaseTransaction,
-    new_subscriber: NewSubscriber,
-) -> Result<Uuid, DbErr> {
+    new_subscriber: &NewSubscriber,
+    list_id: Uuid,
+) -> Result<SubscriptionState, DbErr> {
     let subscriber_id = Uuid::new_v4();
     let subscription = subscriptions::ActiveModel {
         id: Set(subscriber_id),
-        email: Set(mem::take(&mut new_subscriber.email.into())),
-        name: Set(mem::take(&mut new_subscriber.name.into())),
+        email: Set(new_subscriber.email.as_ref().to_owned()),
+        name: Set(new_subscriber.name.as_ref().to_owned()),
         subscribed_at: Set(OffsetDateTime::now_utc()),
-        status: Set("pending_confirmation".to_string()),
+        status: Set(SubscriptionStatus::PendingConfirmation),
+        digest_frequency: Set(DigestFrequency::Immediately),
     };
-    subscription.insert(txn).await?;
-    Ok(subscriber_id)
+    // A concurrent request for the same address waits here until the first
+    // one commits, then it takes the existing subscriber path.
+    let n_inserted_rows = Subscriptions::insert(subscription)
+        .on_conflict(
+            OnConflict::new()
+                .expr(Func::lower(Expr::col(subscriptions::Column::Email)))
+                .do_nothing()
+                .to_owned(),
+        )
+        .exec_without_returning(txn)
+        .await?;
+    if n_inserted_rows > 0 {
+        join_list(
+            txn,
+            list_id,
+            subscriber_id,
+            SubscriptionStatus::PendingConfirmation,
+        )
+        .await?;
+        return Ok(SubscriptionState::AwaitingConfirmation(subscriber_id));
+    }
+
+    let existing = Subscriptions::find()
+        .filter(subscriptions::Column::Email.eq(new_subscriber.email.as_ref()))
+        .lock_exclusive()
+        .one(txn)
+        .await?
+        .ok_or_else(|| DbErr::RecordNotFound("The existing subscriber vanished.".to_owned()))?;
+    let membership = find_membership(txn, list_id, existing.id).await?;
+    if membership.is_some_and(|m| m.status == SubscriptionStatus::Confirmed) {
+        return Ok(SubscriptionState::AlreadyConfirmed);
+    }
+    let subscriber_id = existing.id;
+    // Someone who confirmed another list stays confirmed while they are
+    // asked to confirm this one.
+    if existing.status != SubscriptionStatus::Confirmed {
+        let mut existing = existing.into_active_model();
+        existing.status = Set(SubscriptionStatus::PendingConfirmation);
+        existing.update(txn).await?;
+    }
+    join_list(
+        txn,
+        list_id,
+        subscriber_id,
+        SubscriptionStatus::PendingConfirmation,
+    )
+    .await?;
+    Ok(SubscriptionState::AwaitingConfirmation(subscriber_id))
 }
 
 #[tracing::instrument(
@@ -108,11 +236,17 @@ pub async fn insert_subscriber(
 pub async fn store_token(
     txn: &DatabaseTransaction,
     subscriber_id: Uuid,
+    list_id: Uuid,
     subscription_token: &str,
 ) -> Result<(), StoreTokenError> {
+    let now = OffsetDateTime::now_utc();
     let subscriptions_token = subscription_tokens::ActiveModel {
         subscriber_id: Set(subscriber_id),
         subscription_token: Set(subscription_token.to_string()),
+        created_at: Set(now),
+        expires_at: Set(now + SUBSCRIPTION_TOKEN_TTL),
+        consumed_at: Set(None),
+        list_id: Set(list_id),
     };
     subscriptions_token
         .insert(txn)
@@ -124,33 +258,48 @@ pub async fn store_token(
 
 #[tracing::instrument(
     name = "Send a confirmation email to a new subscriber",
-    skip(email_client, new_subscriber, base_url, subscription_token)
+    skip(
+        email_client,
+        email_templates,
+        new_subscriber,
+        base_url,
+        subscription_token
+    )
 )]
 pub async fn send_confirmation_email(
     email_client: &EmailClient,
+    email_templates: &EmailTemplates,
     new_subscriber: NewSubscriber,
+    list_name: &str,
     base_url: &str,
     subscription_token: &str,
-) -> Result<(), reqwest::Error> {
+) -> Result<(), anyhow::Error> {
     let confirmation_link = format!(
         "{}/subscriptions/confirm?subscription_token={}",
         base_url, subscription_token
     );
-    let plain_body = format!(
-        "Welcome to our newsletter!\nVisit {} to confirm your subscription.",
-        confirmation_link
-    );
-    let html_body = format!(
-        "Welcome to our newsletter!<br />\
-    Click <a href=\"{}\">here</a> to confirm your subscription.",
-        confirmation_link
-    );
+    let email = email_templates
+        .render(
+            EmailTemplates::CONFIRMATION,
+            &json!({
+                "name": new_subscriber.name.as_ref(),
+                "list_name": list_name,
+                "confirmation_link": confirmation_link,
+            }),
+        )
+        .context("Failed to render the confirmation email.")?;
     email_client
-        .send_email(&new_subscriber.email, "Welcome!", &html_body, &plain_body)
-        .await
+        .send_email(
+            &new_subscriber.email,
+            "Welcome!",
+            &email.html_content,
+            &email.text_content,
+        )
+        .await?;
+    Ok(())
 }
 
-fn generate_subscription_token() -> String {
+pub fn generate_subscription_token() -> String {
     let mut rng = thread_rng();
     std::iter::repeat_with(|| rng.sample(Alphanumeric))
         .map(char::from)
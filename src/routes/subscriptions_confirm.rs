@@ -1,84 +1,235 @@
 use axum::{
     extract::{Query, State},
-    http::StatusCode,
+    http::{HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Response},
 };
+use handlebars::Handlebars;
 use sea_orm::{
-    ActiveModelTrait, ColumnTrait, DatabaseConnection, DbErr, EntityTrait, IntoActiveModel,
-    QueryFilter, Set,
+    sea_query::Expr, ActiveEnum, ColumnTrait, DatabaseConnection, DatabaseTransaction, DbErr,
+    EntityTrait, QueryFilter, QuerySelect, TransactionTrait,
 };
+use serde::Serialize;
+use serde_json::json;
+use time::OffsetDateTime;
 use uuid::Uuid;
 
+use entity::list_memberships::{self, Entity as ListMemberships};
+use entity::sea_orm_active_enums::SubscriptionStatus;
 use entity::subscription_tokens::{self, Entity as SubscriptionToken};
-use entity::subscriptions::Entity as Subscription;
+use entity::subscriptions::{self, Entity as Subscription};
 
-use crate::startup::AppState;
+use crate::{
+    consent::{record_consent_event, ConsentEvent, RequestOrigin},
+    lists::find_membership,
+    routes::AppJson,
+    startup::AppState,
+    subscriber_data::lift_erasure,
+    utils::prefers_json,
+};
 
 #[derive(serde::Deserialize)]
 pub struct Parameters {
     subscription_token: String,
 }
 
-#[tracing::instrument(name = "Confirm a pending subscriber", skip(state, params))]
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Confirmation {
+    Confirmed,
+    AlreadyConfirmed,
+    InvalidLink,
+    ExpiredLink,
+    Failed,
+}
+
+impl Confirmation {
+    fn status_code(self) -> StatusCode {
+        match self {
+            Self::Confirmed | Self::AlreadyConfirmed => StatusCode::OK,
+            Self::InvalidLink => StatusCode::UNAUTHORIZED,
+            Self::ExpiredLink => StatusCode::GONE,
+            Self::Failed => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn message(self) -> &'static str {
+        match self {
+            Self::Confirmed => "The subscription has been confirmed.",
+            Self::AlreadyConfirmed => "The subscription has already been confirmed.",
+            Self::InvalidLink => "The confirmation link is not valid.",
+            Self::ExpiredLink => "The confirmation link has expired.",
+            Self::Failed => "The subscription could not be confirmed.",
+        }
+    }
+
+    fn page(self) -> Result<String, handlebars::RenderError> {
+        let template = match self {
+            Self::Confirmed => include_str!("./subscriptions_confirm/confirmed.html"),
+            Self::AlreadyConfirmed => {
+                include_str!("./subscriptions_confirm/already_confirmed.html")
+            }
+            Self::InvalidLink | Self::ExpiredLink => {
+                include_str!("./subscriptions_confirm/invalid_link.html")
+            }
+            Self::Failed => include_str!("./subscriptions_confirm/error.html"),
+        };
+        Handlebars::new().render_template(
+            template,
+            &json!({ "expired": matches!(self, Self::ExpiredLink) }),
+        )
+    }
+}
+
+/// Answers with a page for people following the link from their inbox, or
+/// with JSON for clients that ask for it.
+#[tracing::instrument(
+    name = "Confirm a pending subscriber",
+    skip(state, origin, headers, params)
+)]
 pub async fn confirm(
     State(state): State<AppState>,
+    origin: RequestOrigin,
+    headers: HeaderMap,
     Query(params): Query<Parameters>,
-) -> StatusCode {
-    let id = match get_subscriber_id_from_token(&state.connection, &params.subscription_token).await
-    {
-        Ok(id) => id,
-        Err(_) => return StatusCode::INTERNAL_SERVER_ERROR,
-    };
-    match id {
-        None => StatusCode::UNAUTHORIZED,
-        Some(subscriber_id) => {
-            if confirm_subscriber(&state.connection, subscriber_id)
-                .await
-                .is_err()
-            {
-                return StatusCode::INTERNAL_SERVER_ERROR;
-            }
-            StatusCode::OK
+) -> Response {
+    let confirmation = consume_token(&state.connection, &params.subscription_token, &origin)
+        .await
+        .unwrap_or_else(|e| {
+            tracing::error!("Failed to confirm a subscriber: {:?}", e);
+            Confirmation::Failed
+        });
+
+    if prefers_json(&headers) {
+        #[derive(Serialize)]
+        struct ConfirmationResponse {
+            status: Confirmation,
+            message: &'static str,
+        }
+
+        let body = ConfirmationResponse {
+            status: confirmation,
+            message: confirmation.message(),
+        };
+        return (confirmation.status_code(), AppJson(body)).into_response();
+    }
+    match confirmation.page() {
+        Ok(page) => (confirmation.status_code(), Html::from(page)).into_response(),
+        Err(e) => {
+            tracing::error!("Failed to render the confirmation page: {:?}", e);
+            confirmation.status_code().into_response()
         }
     }
 }
 
-#[tracing::instrument(name = "Mark subscriber as confirmed", skip(connection, subscriber_id))]
-async fn confirm_subscriber(
+/// Confirms the subscriber's membership of the list a token was issued for
+/// and spends the token, together with any other token they have been sent
+/// for that list.
+///
+/// Spent tokens are kept until they expire, so that following a link twice
+/// tells the subscriber they are already confirmed.
+#[tracing::instrument(name = "Consume a subscription token", skip_all)]
+async fn consume_token(
     connection: &DatabaseConnection,
-    subscriber_id: Uuid,
-) -> Result<(), DbErr> {
-    let subscriber = Subscription::find_by_id(subscriber_id)
-        .one(connection)
-        .await
-        .map_err(|e| {
-            tracing::error!("Failed to execute query: {:?}", e);
-            e
-        })?
-        .ok_or_else(|| {
-            tracing::error!("Subscriber with id {} not found", subscriber_id);
-            DbErr::RecordNotFound(format!("Subscriber with id {} not found", subscriber_id))
-        })?;
-    let mut subscriber = subscriber.into_active_model();
-    subscriber.status = Set("confirmed".to_string());
-    subscriber.update(connection).await?;
-    Ok(())
+    subscription_token: &str,
+    origin: &RequestOrigin,
+) -> Result<Confirmation, DbErr> {
+    let txn = connection.begin().await?;
+    // A concurrent confirmation with the same token waits on the lock and
+    // then finds the token spent.
+    let Some(token) = SubscriptionToken::find()
+        .filter(subscription_tokens::Column::SubscriptionToken.eq(subscription_token))
+        .lock_exclusive()
+        .one(&txn)
+        .await?
+    else {
+        return Ok(Confirmation::InvalidLink);
+    };
+    if token.consumed_at.is_some() {
+        let membership = find_membership(&txn, token.list_id, token.subscriber_id).await?;
+        // They may have unsubscribed since
+        return match membership {
+            Some(membership) if membership.status == SubscriptionStatus::Confirmed => {
+                Ok(Confirmation::AlreadyConfirmed)
+            }
+            _ => Ok(Confirmation::InvalidLink),
+        };
+    }
+    if token.expires_at <= OffsetDateTime::now_utc() {
+        return Ok(Confirmation::ExpiredLink);
+    }
+
+    mark_subscriber_as_confirmed(
+        &txn,
+        token.subscriber_id,
+        Some(token.list_id),
+        origin,
+        "confirmation_link",
+    )
+    .await?;
+    if let Some(subscriber) = Subscription::find_by_id(token.subscriber_id)
+        .one(&txn)
+        .await?
+    {
+        lift_erasure(&txn, &subscriber.email).await?;
+    }
+    txn.commit().await?;
+    Ok(Confirmation::Confirmed)
 }
 
-#[tracing::instrument(
-    name = "Get subscriber_id from token",
-    skip(connection, subscription_token)
-)]
-async fn get_subscriber_id_from_token(
-    connection: &DatabaseConnection,
-    subscription_token: &str,
-) -> Result<Option<Uuid>, DbErr> {
-    let result = SubscriptionToken::find()
-        .filter(subscription_tokens::Column::SubscriptionToken.contains(subscription_token))
-        .one(connection)
-        .await
-        .map_err(|e| {
-            tracing::error!("Failed to execute query: {:?}", e);
-            e
-        })?;
-    Ok(result.map(|r| r.subscriber_id))
+/// Confirms a subscriber's pending membership of a list, or of every list
+/// they are pending on if none is given, and spends the tokens they have
+/// been sent for it. Lists they unsubscribed from are left alone.
+///
+/// Returns `false` if they were confirmed already, nothing is recorded in
+/// the consent trail then.
+#[tracing::instrument(name = "Mark subscriber as confirmed", skip(txn, origin))]
+pub async fn mark_subscriber_as_confirmed(
+    txn: &DatabaseTransaction,
+    subscriber_id: Uuid,
+    list_id: Option<Uuid>,
+    origin: &RequestOrigin,
+    source: &str,
+) -> Result<bool, DbErr> {
+    let mut tokens = SubscriptionToken::update_many()
+        .col_expr(
+            subscription_tokens::Column::ConsumedAt,
+            Expr::value(OffsetDateTime::now_utc()),
+        )
+        .filter(subscription_tokens::Column::SubscriberId.eq(subscriber_id))
+        .filter(subscription_tokens::Column::ConsumedAt.is_null());
+    let mut memberships = ListMemberships::update_many()
+        .col_expr(
+            list_memberships::Column::Status,
+            SubscriptionStatus::Confirmed.as_enum(),
+        )
+        .filter(list_memberships::Column::SubscriberId.eq(subscriber_id))
+        .filter(list_memberships::Column::Status.eq(SubscriptionStatus::PendingConfirmation));
+    if let Some(list_id) = list_id {
+        tokens = tokens.filter(subscription_tokens::Column::ListId.eq(list_id));
+        memberships = memberships.filter(list_memberships::Column::ListId.eq(list_id));
+    }
+    tokens.exec(txn).await?;
+    let n_confirmed_memberships = memberships.exec(txn).await?.rows_affected;
+    let n_confirmed = Subscription::update_many()
+        .col_expr(
+            subscriptions::Column::Status,
+            SubscriptionStatus::Confirmed.as_enum(),
+        )
+        .filter(subscriptions::Column::Id.eq(subscriber_id))
+        .filter(subscriptions::Column::Status.eq(SubscriptionStatus::PendingConfirmation))
+        .exec(txn)
+        .await?
+        .rows_affected;
+    if n_confirmed == 0 && n_confirmed_memberships == 0 {
+        return Ok(false);
+    }
+    record_consent_event(
+        txn,
+        subscriber_id,
+        ConsentEvent::Confirmed,
+        origin,
+        Some(source),
+    )
+    .await?;
+    Ok(true)
 }
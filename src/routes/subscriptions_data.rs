@@ -0,0 +1,223 @@
+use anyhow::Context;
+use axum::{
+    extract::{Query, State},
+    http::{header, StatusCode},
+    response::{Html, IntoResponse, Response},
+    Form,
+};
+use entity::subscriptions::{self, Entity as Subscriptions};
+use handlebars::Handlebars;
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use serde_json::json;
+use time::{Duration, OffsetDateTime};
+use tracing::Instrument;
+use uuid::Uuid;
+
+use crate::{
+    domain::SubscriberEmail,
+    email_templates::EmailTemplates,
+    routes::AppJson,
+    startup::AppState,
+    subscriber_data::{erase_subscriber, export_subscriber_data},
+};
+
+use super::error_chain_fmt;
+
+/// How long a data request link stays valid.
+const DATA_REQUEST_TTL: Duration = Duration::hours(24);
+
+#[derive(serde::Deserialize)]
+pub struct DataRequestFormData {
+    email: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct DataRequestParameters {
+    subscriber_id: Uuid,
+    expires_at: i64,
+    signature: String,
+}
+
+impl DataRequestParameters {
+    fn verify(&self, state: &AppState) -> Result<(), DataRequestError> {
+        state
+            .link_signer
+            .verify_data_request(self.subscriber_id, self.expires_at, &self.signature)
+            .map_err(|_| DataRequestError::InvalidLink)
+    }
+
+    fn query(&self) -> String {
+        format!(
+            "subscriber_id={}&expires_at={}&signature={}",
+            self.subscriber_id, self.expires_at, self.signature
+        )
+    }
+}
+
+#[derive(thiserror::Error)]
+pub enum DataRequestError {
+    #[error("{0}")]
+    ValidationError(String),
+    #[error("The data request link is invalid or has expired.")]
+    InvalidLink,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for DataRequestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl IntoResponse for DataRequestError {
+    fn into_response(self) -> Response {
+        tracing::error!(exception.details = ?self, exception.message = %self);
+        match self {
+            DataRequestError::ValidationError(_) => StatusCode::BAD_REQUEST.into_response(),
+            DataRequestError::InvalidLink => StatusCode::UNAUTHORIZED.into_response(),
+            DataRequestError::UnexpectedError(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            }
+        }
+    }
+}
+
+pub async fn data_request_form() -> Html<&'static str> {
+    Html(include_str!("./subscriptions_data/request.html"))
+}
+
+/// Emails a link to the data of a subscriber. Only the owner of the address
+/// can follow it, so whoever fills in the form learns nothing. The lookup and
+/// the email happen in the background, so that how long the answer takes
+/// does not give away whether the address is subscribed either.
+#[tracing::instrument(name = "Request subscriber data", skip(state, form))]
+pub async fn request_data(
+    State(state): State<AppState>,
+    Form(form): Form<DataRequestFormData>,
+) -> Result<Response, DataRequestError> {
+    let email = SubscriberEmail::parse(form.email.trim().to_owned())
+        .map_err(DataRequestError::ValidationError)?;
+    tokio::spawn(send_data_request_email(state, email).in_current_span());
+    Ok(Html(include_str!("./subscriptions_data/requested.html")).into_response())
+}
+
+#[tracing::instrument(skip_all)]
+async fn send_data_request_email(state: AppState, email: SubscriberEmail) {
+    let subscriber = match Subscriptions::find()
+        .filter(subscriptions::Column::Email.eq(email.as_ref()))
+        .one(&state.connection)
+        .await
+    {
+        Ok(Some(subscriber)) => subscriber,
+        Ok(None) => {
+            tracing::info!("Nobody is subscribed with this address.");
+            return;
+        }
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to look up the subscriber.");
+            return;
+        }
+    };
+    if let Err(e) = email_data_request_link(&state, &email, &subscriber).await {
+        tracing::error!(
+            error.cause_chain = ?e,
+            error.message = %e,
+            "Failed to send a data request email."
+        );
+    }
+}
+
+#[tracing::instrument(skip_all, fields(subscriber_id = %subscriber.id))]
+async fn email_data_request_link(
+    state: &AppState,
+    email: &SubscriberEmail,
+    subscriber: &subscriptions::Model,
+) -> Result<(), anyhow::Error> {
+    let data_request_link = state
+        .link_signer
+        .data_request_url(subscriber.id, OffsetDateTime::now_utc() + DATA_REQUEST_TTL);
+    let content = state
+        .email_templates
+        .render(
+            EmailTemplates::DATA_REQUEST,
+            &json!({
+                "name": subscriber.name,
+                "data_request_link": data_request_link,
+            }),
+        )
+        .context("Failed to render the data request email.")?;
+    state
+        .email_client
+        .send_email(
+            email,
+            "Your data",
+            &content.html_content,
+            &content.text_content,
+        )
+        .await
+        .context("Failed to send the data request email.")?;
+    Ok(())
+}
+
+#[tracing::instrument(name = "Show the data management page", skip(state, params))]
+pub async fn manage_data(
+    State(state): State<AppState>,
+    Query(params): Query<DataRequestParameters>,
+) -> Result<Response, DataRequestError> {
+    params.verify(&state)?;
+    let Some(subscriber) = Subscriptions::find_by_id(params.subscriber_id)
+        .one(&state.connection)
+        .await
+        .context("Failed to look up the subscriber.")?
+    else {
+        // Already erased
+        return Err(DataRequestError::InvalidLink);
+    };
+
+    let html = Handlebars::new()
+        .render_template(
+            include_str!("./subscriptions_data/manage.html"),
+            &json!({
+                "email": subscriber.email,
+                "query": params.query(),
+            }),
+        )
+        .context("Failed to render the data management page.")?;
+    Ok(Html::from(html).into_response())
+}
+
+/// Serves everything we store about the subscriber as a JSON download.
+#[tracing::instrument(name = "Export subscriber data", skip(state, params))]
+pub async fn export_data(
+    State(state): State<AppState>,
+    Query(params): Query<DataRequestParameters>,
+) -> Result<Response, DataRequestError> {
+    params.verify(&state)?;
+    let data = export_subscriber_data(&state.connection, params.subscriber_id)
+        .await
+        .context("Failed to export the subscriber data.")?
+        .ok_or(DataRequestError::InvalidLink)?;
+    let disposition = format!(
+        "attachment; filename=\"subscriber-{}.json\"",
+        params.subscriber_id
+    );
+    Ok(([(header::CONTENT_DISPOSITION, disposition)], AppJson(data)).into_response())
+}
+
+#[tracing::instrument(
+    name = "Erase subscriber data",
+    skip(state, params),
+    fields(subscriber_id = %params.subscriber_id)
+)]
+pub async fn erase_data(
+    State(state): State<AppState>,
+    Query(params): Query<DataRequestParameters>,
+) -> Result<Response, DataRequestError> {
+    params.verify(&state)?;
+    // Erasing twice is not an error, the link may be followed again.
+    erase_subscriber(&state.connection, params.subscriber_id)
+        .await
+        .context("Failed to erase the subscriber.")?;
+    Ok(Html(include_str!("./subscriptions_data/erased.html")).into_response())
+}
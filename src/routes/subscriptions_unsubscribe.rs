@@ -0,0 +1,162 @@
+use anyhow::Context;
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Response},
+    Form,
+};
+use entity::{
+    list_memberships::{self, Entity as ListMemberships},
+    sea_orm_active_enums::SubscriptionStatus,
+    subscriptions::{self, Entity as Subscriptions},
+};
+use handlebars::Handlebars;
+use sea_orm::{
+    ActiveEnum, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, TransactionTrait,
+};
+use serde_json::json;
+use uuid::Uuid;
+
+use crate::{
+    consent::{record_consent_event, ConsentEvent, RequestOrigin},
+    startup::AppState,
+};
+
+use super::error_chain_fmt;
+
+#[derive(serde::Deserialize)]
+pub struct UnsubscribeParameters {
+    subscriber_id: Uuid,
+    signature: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct UnsubscribeFormData {
+    #[serde(default)]
+    source: Option<String>,
+}
+
+#[derive(thiserror::Error)]
+pub enum UnsubscribeError {
+    #[error("The unsubscribe link is invalid.")]
+    InvalidLink,
+    #[error(transparent)]
+    UnexpectedError(#[from] anyhow::Error),
+}
+
+impl std::fmt::Debug for UnsubscribeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        error_chain_fmt(self, f)
+    }
+}
+
+impl IntoResponse for UnsubscribeError {
+    fn into_response(self) -> Response {
+        tracing::error!(exception.details = ?self, exception.message = %self);
+        match self {
+            UnsubscribeError::InvalidLink => StatusCode::UNAUTHORIZED.into_response(),
+            UnsubscribeError::UnexpectedError(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            }
+        }
+    }
+}
+
+/// Asks for a confirmation, so that link scanners following every link in an
+/// email do not unsubscribe people by accident.
+#[tracing::instrument(name = "Show the unsubscribe form", skip(state, params))]
+pub async fn unsubscribe_form(
+    State(state): State<AppState>,
+    Query(params): Query<UnsubscribeParameters>,
+) -> Result<Response, UnsubscribeError> {
+    state
+        .link_signer
+        .verify_unsubscribe(params.subscriber_id, &params.signature)
+        .map_err(|_| UnsubscribeError::InvalidLink)?;
+
+    let reg = Handlebars::new();
+    let html = reg
+        .render_template(
+            include_str!("./subscriptions_unsubscribe/form.html"),
+            &json!({
+                "subscriber_id": params.subscriber_id.to_string(),
+                "signature": params.signature,
+            }),
+        )
+        .context("Failed to render the unsubscribe page.")?;
+    Ok(Html::from(html).into_response())
+}
+
+/// Also serves the one-click unsubscribe requests of mail clients (RFC 8058),
+/// which POST `List-Unsubscribe=One-Click` to the link of the
+/// `List-Unsubscribe` header.
+#[tracing::instrument(
+    name = "Unsubscribe a subscriber",
+    skip(state, origin, params, form),
+    fields(subscriber_id = %params.subscriber_id)
+)]
+pub async fn unsubscribe(
+    State(state): State<AppState>,
+    origin: RequestOrigin,
+    Query(params): Query<UnsubscribeParameters>,
+    form: Option<Form<UnsubscribeFormData>>,
+) -> Result<Response, UnsubscribeError> {
+    state
+        .link_signer
+        .verify_unsubscribe(params.subscriber_id, &params.signature)
+        .map_err(|_| UnsubscribeError::InvalidLink)?;
+    // Mail clients only send `List-Unsubscribe=One-Click`, our own page
+    // tells us it was used.
+    let source = form
+        .and_then(|Form(form)| form.source)
+        .unwrap_or_else(|| "one_click".to_owned());
+    mark_subscriber_as_unsubscribed(&state.connection, params.subscriber_id, &origin, &source)
+        .await
+        .context("Failed to unsubscribe the subscriber.")?;
+    Ok(Html::from(include_str!("./subscriptions_unsubscribe/done.html")).into_response())
+}
+
+/// Unsubscribes a subscriber from every list they belong to.
+///
+/// Returns `false` if the subscriber had unsubscribed already, nothing is
+/// recorded in the consent trail then.
+#[tracing::instrument(name = "Mark subscriber as unsubscribed", skip(connection, origin))]
+pub async fn mark_subscriber_as_unsubscribed(
+    connection: &DatabaseConnection,
+    subscriber_id: Uuid,
+    origin: &RequestOrigin,
+    source: &str,
+) -> Result<bool, sea_orm::DbErr> {
+    let txn = connection.begin().await?;
+    // Unsubscribing twice is not an error, the second request is a no-op.
+    let n_unsubscribed = Subscriptions::update_many()
+        .col_expr(
+            subscriptions::Column::Status,
+            SubscriptionStatus::Unsubscribed.as_enum(),
+        )
+        .filter(subscriptions::Column::Id.eq(subscriber_id))
+        .filter(subscriptions::Column::Status.ne(SubscriptionStatus::Unsubscribed))
+        .exec(&txn)
+        .await?
+        .rows_affected;
+    ListMemberships::update_many()
+        .col_expr(
+            list_memberships::Column::Status,
+            SubscriptionStatus::Unsubscribed.as_enum(),
+        )
+        .filter(list_memberships::Column::SubscriberId.eq(subscriber_id))
+        .exec(&txn)
+        .await?;
+    if n_unsubscribed > 0 {
+        record_consent_event(
+            &txn,
+            subscriber_id,
+            ConsentEvent::Unsubscribed,
+            origin,
+            Some(source),
+        )
+        .await?;
+    }
+    txn.commit().await?;
+    Ok(n_unsubscribed > 0)
+}
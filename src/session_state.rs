@@ -3,22 +3,37 @@ use axum::{
     extract::FromRequestParts,
     http::{request::Parts, StatusCode},
 };
+use time::OffsetDateTime;
 use tower_sessions::Session;
 use uuid::Uuid;
 
 pub struct TypedSession(Session);
 
+/// The first step of a login with two-factor authentication.
+#[derive(serde::Serialize, serde::Deserialize, Debug)]
+pub struct PendingTwoFactor {
+    pub user_id: Uuid,
+    pub started_at: OffsetDateTime,
+}
+
 impl TypedSession {
     const USER_ID_KEY: &'static str = "user_id";
+    const LOGGED_IN_AT_KEY: &'static str = "logged_in_at";
+    const PENDING_TWO_FACTOR_KEY: &'static str = "pending_two_factor";
 
     pub async fn cycle_id(&self) -> Result<(), tower_sessions::session::Error> {
         self.0.cycle_id().await
     }
 
+    /// Also records when the user logged in, so that their sessions can be
+    /// revoked.
     pub async fn insert_user_id(
         &self,
         user_id: Uuid,
     ) -> Result<(), tower_sessions::session::Error> {
+        self.0
+            .insert(Self::LOGGED_IN_AT_KEY, OffsetDateTime::now_utc())
+            .await?;
         self.0.insert(Self::USER_ID_KEY, user_id).await
     }
 
@@ -26,6 +41,36 @@ impl TypedSession {
         self.0.get(Self::USER_ID_KEY).await
     }
 
+    pub async fn get_logged_in_at(
+        &self,
+    ) -> Result<Option<OffsetDateTime>, tower_sessions::session::Error> {
+        self.0.get(Self::LOGGED_IN_AT_KEY).await
+    }
+
+    /// Remembers a user whose password was right but who still has to enter
+    /// a code from their authenticator. Whoever was logged in on this session
+    /// is logged out.
+    pub async fn insert_pending_two_factor(
+        &self,
+        pending: &PendingTwoFactor,
+    ) -> Result<(), tower_sessions::session::Error> {
+        self.0.remove::<Uuid>(Self::USER_ID_KEY).await?;
+        self.0.insert(Self::PENDING_TWO_FACTOR_KEY, pending).await
+    }
+
+    pub async fn get_pending_two_factor(
+        &self,
+    ) -> Result<Option<PendingTwoFactor>, tower_sessions::session::Error> {
+        self.0.get(Self::PENDING_TWO_FACTOR_KEY).await
+    }
+
+    pub async fn remove_pending_two_factor(&self) -> Result<(), tower_sessions::session::Error> {
+        self.0
+            .remove::<PendingTwoFactor>(Self::PENDING_TWO_FACTOR_KEY)
+            .await
+            .map(|_| ())
+    }
+
     pub async fn log_out(self) -> Result<(), tower_sessions::session::Error> {
         self.0.flush().await
     }
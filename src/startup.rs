@@ -1,18 +1,20 @@
 use axum::{
     body::Body,
+    extract::connect_info::{ConnectInfo, IntoMakeServiceWithConnectInfo},
     http::Request,
-    middleware,
+    middleware::{self, AddExtension},
     routing::{get, post},
     serve::Serve,
-    Router,
+    Extension, Router,
 };
 use axum_messages::MessagesManagerLayer;
 use migration::{Migrator, MigratorTrait};
 use sea_orm::DatabaseConnection;
 use secrecy::Secret;
-use std::net::TcpListener;
+use std::net::{IpAddr, SocketAddr, TcpListener};
 use std::sync::Arc;
 use time::Duration;
+use tokio_util::sync::CancellationToken;
 use tower::ServiceBuilder;
 use tower_http::trace::TraceLayer;
 use tower_sessions::{Expiry, SessionManagerLayer};
@@ -27,25 +29,55 @@ use tower_sessions_redis_store::{
 };
 
 use crate::{
-    authentication::reject_anonymous_users,
-    configuration::{RedisSettings, Settings},
+    authentication::{reject_anonymous_users, require_editor, require_owner},
+    configuration::{LoginThrottleSettings, RedisSettings, Settings},
+    consent::TrustedProxies,
     email_client::EmailClient,
+    email_templates::EmailTemplates,
+    issue_delivery_worker::run_worker_until_stopped,
+    issue_scheduler::run_scheduler_until_stopped,
+    link_signer::LinkSigner,
+    login_throttle::LoginThrottle,
     routes::{
-        admin_dashboard, change_password, change_password_form, confirm, health_check, home,
-        log_out, login, login_form, publish_newsletter, publish_newsletter_form, subscribe,
+        accept_invitation, admin_confirm_subscriber, admin_dashboard, admin_delete_subscriber,
+        admin_resend_confirmation, admin_unsubscribe_subscriber, cancel_scheduled_newsletter,
+        change_password, change_password_form, change_user_role, confirm, confirm_email_change,
+        confirm_two_factor, create_mailing_list, data_request_form, disable_two_factor,
+        disable_user, enable_user, erase_data, export_consent_trail, export_data,
+        export_subscribers, forgot_password_form, health_check, home, import_subscribers,
+        import_subscribers_form, invitation_form, invite_user, list_subscribers, list_users,
+        log_out, login, login_form, mailing_lists, manage_data, newsletter_archive,
+        newsletter_history, newsletter_issue_preview, password_reset_form, preferences_form,
+        publish_newsletter, publish_newsletter_form, request_data, request_password_reset,
+        reschedule_newsletter, reset_password, scheduled_newsletters, start_two_factor_setup,
+        subscribe, subscriber_detail, two_factor_form, two_factor_settings, unsubscribe,
+        unsubscribe_form, update_preferences, verify_two_factor,
     },
+    subscription_sweeper::run_sweeper_until_stopped,
 };
 
+type Server = Serve<
+    IntoMakeServiceWithConnectInfo<Router, SocketAddr>,
+    AddExtension<Router, ConnectInfo<SocketAddr>>,
+>;
+
 #[derive(Clone)]
 pub struct AppState {
     pub connection: DatabaseConnection,
     pub email_client: Arc<EmailClient>,
     pub base_url: String,
+    pub link_signer: LinkSigner,
+    pub email_templates: EmailTemplates,
+    pub login_throttle: LoginThrottle,
 }
 
 pub struct Application {
     port: u16,
-    server: Serve<Router, Router>,
+    server: Server,
+    connection: DatabaseConnection,
+    email_client: Arc<EmailClient>,
+    link_signer: LinkSigner,
+    email_templates: EmailTemplates,
 }
 
 impl Application {
@@ -55,17 +87,12 @@ impl Application {
             .expect("Failed to connect to the database.");
         Migrator::up(&connection, None).await?;
 
-        let sender_email = configuration
-            .email_client
-            .sender()
-            .expect("Invalid sender email address.");
-        let timeout = configuration.email_client.timeout();
-        let email_client = EmailClient::new(
-            configuration.email_client.base_url,
-            sender_email,
-            configuration.email_client.authorization_token,
-            timeout,
+        let email_client = Arc::new(configuration.email_client.client());
+        let link_signer = LinkSigner::new(
+            configuration.application.base_url.clone(),
+            HmacSecret(configuration.application.hmac_secret),
         );
+        let email_templates = EmailTemplates::new();
 
         let address = format!(
             "{}:{}",
@@ -75,43 +102,132 @@ impl Application {
         let port = listener.local_addr().unwrap().port();
         let server = run(
             listener,
-            connection,
-            email_client,
+            connection.clone(),
+            email_client.clone(),
             configuration.application.base_url,
-            configuration.application.hmac_secret,
+            link_signer.clone(),
+            email_templates.clone(),
             configuration.redis,
+            configuration.login_throttle,
+            configuration.application.trusted_proxies,
         )
         .await?;
 
-        Ok(Self { port, server })
+        Ok(Self {
+            port,
+            server,
+            connection,
+            email_client,
+            link_signer,
+            email_templates,
+        })
     }
 
     pub fn port(&self) -> u16 {
         self.port
     }
 
-    pub async fn run_until_stopped(self) -> Result<(), std::io::Error> {
-        self.server.await
+    /// Serves requests and runs the background workers until a shutdown
+    /// signal is received, then waits for all of them to finish.
+    pub async fn run_until_stopped(self) -> Result<(), anyhow::Error> {
+        let shutdown = CancellationToken::new();
+        let scheduler = tokio::spawn(run_scheduler_until_stopped(
+            self.connection.clone(),
+            shutdown.clone(),
+        ));
+        let sweeper = tokio::spawn(run_sweeper_until_stopped(
+            self.connection.clone(),
+            shutdown.clone(),
+        ));
+        let worker = tokio::spawn(run_worker_until_stopped(
+            self.connection,
+            self.email_client,
+            self.link_signer,
+            self.email_templates,
+            shutdown.clone(),
+        ));
+
+        let server_outcome = self
+            .server
+            .with_graceful_shutdown(shutdown_signal(shutdown.clone()))
+            .await;
+        shutdown.cancel();
+
+        report_exit("Background worker", worker.await);
+        report_exit("Newsletter scheduler", scheduler.await);
+        report_exit("Subscription sweeper", sweeper.await);
+        server_outcome?;
+        Ok(())
+    }
+}
+
+async fn shutdown_signal(shutdown: CancellationToken) {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to install Ctrl+C handler");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to install signal handler")
+            .recv()
+            .await;
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+        _ = shutdown.cancelled() => {},
+    }
+    tracing::info!("Shutdown signal received, stopping the application.");
+}
+
+fn report_exit(
+    task_name: &str,
+    outcome: Result<Result<(), anyhow::Error>, tokio::task::JoinError>,
+) {
+    match outcome {
+        Ok(Ok(())) => {
+            tracing::info!("{} has exited", task_name)
+        }
+        Ok(Err(e)) => {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "{} failed",
+                task_name
+            )
+        }
+        Err(e) => {
+            tracing::error!(
+                error.cause_chain = ?e,
+                error.message = %e,
+                "{} task failed to complete",
+                task_name
+            )
+        }
     }
 }
 
 pub struct ApplicationBaseUrl(pub String);
 
+#[allow(clippy::too_many_arguments)]
 async fn run(
     listener: TcpListener,
     connection: DatabaseConnection,
-    email_client: EmailClient,
+    email_client: Arc<EmailClient>,
     base_url: String,
-    _hmac_secret: Secret<String>,
+    link_signer: LinkSigner,
+    email_templates: EmailTemplates,
     redis: RedisSettings,
-) -> Result<Serve<Router, Router>, anyhow::Error> {
-    let email_client = Arc::new(email_client);
-    let state = AppState {
-        connection,
-        email_client,
-        base_url,
-    };
-
+    login_throttle: LoginThrottleSettings,
+    trusted_proxies: Vec<IpAddr>,
+) -> Result<Server, anyhow::Error> {
     let redis_config = RedisConfig {
         server: ServerConfig::Centralized {
             server: fred::types::Server::new(redis.host, redis.port),
@@ -122,27 +238,142 @@ async fn run(
     let _redis_conn = redis_pool.connect();
     redis_pool.wait_for_connect().await?;
 
+    let state = AppState {
+        connection,
+        email_client,
+        base_url,
+        link_signer,
+        email_templates,
+        login_throttle: LoginThrottle::new(redis_pool.clone(), login_throttle),
+    };
+
     let session_store = RedisStore::new(redis_pool);
     let session_layer = SessionManagerLayer::new(session_store)
         .with_secure(false)
         .with_expiry(Expiry::OnInactivity(Duration::seconds(10)));
 
+    // Viewers can look at everything, changes need an editor and managing
+    // users needs an owner.
+    let editor_only = || middleware::from_fn(require_editor);
+    let owner_only = || middleware::from_fn(require_owner);
     let admin_routes = Router::new()
         .route("/dashboard", get(admin_dashboard))
         .route("/password", get(change_password_form).post(change_password))
+        .route("/two-factor", get(two_factor_settings))
+        .route("/two-factor/setup", post(start_two_factor_setup))
+        .route("/two-factor/confirm", post(confirm_two_factor))
+        .route("/two-factor/disable", post(disable_two_factor))
         .route("/logout", post(log_out))
         .route(
             "/newsletters",
-            get(publish_newsletter_form).post(publish_newsletter),
+            get(publish_newsletter_form)
+                .post(publish_newsletter)
+                .route_layer(editor_only()),
+        )
+        .route("/newsletters/history", get(newsletter_history))
+        .route(
+            "/newsletters/history/:issue_id",
+            get(newsletter_issue_preview),
+        )
+        .route("/newsletters/scheduled", get(scheduled_newsletters))
+        .route(
+            "/newsletters/scheduled/:issue_id/cancel",
+            post(cancel_scheduled_newsletter).route_layer(editor_only()),
         )
-        .layer(middleware::from_fn(reject_anonymous_users));
+        .route(
+            "/newsletters/scheduled/:issue_id/reschedule",
+            post(reschedule_newsletter).route_layer(editor_only()),
+        )
+        .route(
+            "/lists",
+            get(mailing_lists).merge(post(create_mailing_list).route_layer(editor_only())),
+        )
+        .route("/subscribers", get(list_subscribers))
+        .route("/subscribers/consent", get(export_consent_trail))
+        .route("/subscribers/export", get(export_subscribers))
+        .route(
+            "/subscribers/import",
+            get(import_subscribers_form)
+                .post(import_subscribers)
+                .route_layer(editor_only()),
+        )
+        .route("/subscribers/:subscriber_id", get(subscriber_detail))
+        .route(
+            "/subscribers/:subscriber_id/confirm",
+            post(admin_confirm_subscriber).route_layer(editor_only()),
+        )
+        .route(
+            "/subscribers/:subscriber_id/unsubscribe",
+            post(admin_unsubscribe_subscriber).route_layer(editor_only()),
+        )
+        .route(
+            "/subscribers/:subscriber_id/resend_confirmation",
+            post(admin_resend_confirmation).route_layer(editor_only()),
+        )
+        .route(
+            "/subscribers/:subscriber_id/delete",
+            post(admin_delete_subscriber).route_layer(editor_only()),
+        )
+        .route(
+            "/users",
+            get(list_users).post(invite_user).route_layer(owner_only()),
+        )
+        .route(
+            "/users/:user_id/role",
+            post(change_user_role).route_layer(owner_only()),
+        )
+        .route(
+            "/users/:user_id/disable",
+            post(disable_user).route_layer(owner_only()),
+        )
+        .route(
+            "/users/:user_id/enable",
+            post(enable_user).route_layer(owner_only()),
+        )
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            reject_anonymous_users,
+        ));
 
     let app = Router::new()
         .route("/", get(home))
         .route("/health_check", get(health_check))
         .route("/subscriptions", post(subscribe))
         .route("/subscriptions/confirm", get(confirm))
+        .route(
+            "/subscriptions/unsubscribe",
+            get(unsubscribe_form).post(unsubscribe),
+        )
+        .route(
+            "/subscriptions/data",
+            get(data_request_form).post(request_data),
+        )
+        .route("/subscriptions/data/manage", get(manage_data))
+        .route("/subscriptions/data/export", get(export_data))
+        .route("/subscriptions/data/erase", post(erase_data))
+        .route(
+            "/preferences",
+            get(preferences_form).post(update_preferences),
+        )
+        .route("/preferences/email", get(confirm_email_change))
+        .route(
+            "/invitations/accept",
+            get(invitation_form).post(accept_invitation),
+        )
         .route("/login", get(login_form).post(login))
+        .route(
+            "/login/two-factor",
+            get(two_factor_form).post(verify_two_factor),
+        )
+        .route(
+            "/login/forgot",
+            get(forgot_password_form).post(request_password_reset),
+        )
+        .route(
+            "/login/reset",
+            get(password_reset_form).post(reset_password),
+        )
+        .route("/archive/:issue_id", get(newsletter_archive))
         .nest("/admin", admin_routes)
         .layer(
             ServiceBuilder::new()
@@ -166,14 +397,19 @@ async fn run(
                 // > tower-sessions doesn't provide signing because no data is stored in the cookie.
                 // > In other words, the cookie value is a pointer to the data stored server side.
                 .layer(session_layer)
-                .layer(MessagesManagerLayer),
+                .layer(MessagesManagerLayer)
+                .layer(Extension(TrustedProxies(trusted_proxies))),
         )
         .with_state(state);
 
     listener.set_nonblocking(true)?;
     let listener = tokio::net::TcpListener::from_std(listener)?;
 
-    let server = axum::serve(listener, app);
+    // The peer address ends up in the consent trail of subscribers.
+    let server = axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    );
     Ok(server)
 }
 
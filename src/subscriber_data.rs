@@ -0,0 +1,274 @@
+use entity::{
+    consent_events::{self, Entity as ConsentEvents},
+    email_change_requests::{self, Entity as EmailChangeRequests},
+    erased_subscribers::{self, Entity as ErasedSubscribers},
+    issue_delivery_queue::{self, Entity as IssueDeliveryQueue},
+    list_memberships::{self, Entity as ListMemberships},
+    lists::Entity as Lists,
+    sea_orm_active_enums::{DigestFrequency, SubscriptionStatus},
+    subscription_tokens::{self, Entity as SubscriptionTokens},
+    subscriptions::{self, Entity as Subscriptions},
+};
+use sea_orm::{
+    sea_query::OnConflict, ActiveValue::Set, ColumnTrait, ConnectionTrait, DatabaseConnection,
+    DatabaseTransaction, DbErr, EntityTrait, ModelTrait, QueryFilter, QueryOrder, QuerySelect,
+    TransactionTrait,
+};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use time::OffsetDateTime;
+use uuid::Uuid;
+
+use crate::{consent::ConsentEventRecord, domain::SubscriberEmail, utils::format_rfc3339};
+
+/// A subscriber as it is handed out in exports.
+#[derive(Serialize)]
+pub struct SubscriberRecord {
+    pub id: Uuid,
+    pub email: String,
+    pub name: String,
+    pub status: SubscriptionStatus,
+    pub digest_frequency: DigestFrequency,
+    pub subscribed_at: String,
+}
+
+impl From<subscriptions::Model> for SubscriberRecord {
+    fn from(subscriber: subscriptions::Model) -> Self {
+        Self {
+            id: subscriber.id,
+            email: subscriber.email,
+            name: subscriber.name,
+            status: subscriber.status,
+            digest_frequency: subscriber.digest_frequency,
+            subscribed_at: format_rfc3339(subscriber.subscribed_at),
+        }
+    }
+}
+
+/// Everything we store about a subscriber.
+#[derive(Serialize)]
+pub struct SubscriberData {
+    pub subscriber: SubscriberRecord,
+    pub lists: Vec<ListMembershipRecord>,
+    pub consent_events: Vec<ConsentEventRecord>,
+    pub confirmation_links: Vec<ConfirmationLinkRecord>,
+    pub email_changes: Vec<EmailChangeRecord>,
+    pub pending_deliveries: Vec<PendingDeliveryRecord>,
+}
+
+#[derive(Serialize)]
+pub struct ListMembershipRecord {
+    pub list: String,
+    pub status: SubscriptionStatus,
+    pub joined_at: String,
+}
+
+/// The token itself is left out, it is a credential.
+#[derive(Serialize)]
+pub struct ConfirmationLinkRecord {
+    pub created_at: String,
+    pub expires_at: String,
+    pub consumed_at: Option<String>,
+}
+
+/// The token is left out here as well.
+#[derive(Serialize)]
+pub struct EmailChangeRecord {
+    pub new_email: String,
+    pub created_at: String,
+    pub expires_at: String,
+    pub consumed_at: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct PendingDeliveryRecord {
+    pub newsletter_issue_id: Uuid,
+    pub n_retries: i16,
+    pub execute_after: String,
+}
+
+#[tracing::instrument(skip(db))]
+pub async fn export_subscriber_data(
+    db: &impl ConnectionTrait,
+    subscriber_id: Uuid,
+) -> Result<Option<SubscriberData>, DbErr> {
+    let Some(subscriber) = Subscriptions::find_by_id(subscriber_id).one(db).await? else {
+        return Ok(None);
+    };
+    let memberships = subscriber
+        .find_related(ListMemberships)
+        .find_also_related(Lists)
+        .order_by_asc(list_memberships::Column::JoinedAt)
+        .all(db)
+        .await?;
+    let consent_events = subscriber
+        .find_related(ConsentEvents)
+        .order_by_asc(consent_events::Column::OccurredAt)
+        .all(db)
+        .await?;
+    let tokens = subscriber
+        .find_related(SubscriptionTokens)
+        .order_by_asc(subscription_tokens::Column::CreatedAt)
+        .all(db)
+        .await?;
+    let email_changes = subscriber
+        .find_related(EmailChangeRequests)
+        .order_by_asc(email_change_requests::Column::CreatedAt)
+        .all(db)
+        .await?;
+    let deliveries = IssueDeliveryQueue::find()
+        .filter(issue_delivery_queue::Column::SubscriberEmail.eq(&subscriber.email))
+        .all(db)
+        .await?;
+
+    Ok(Some(SubscriberData {
+        subscriber: subscriber.into(),
+        lists: memberships
+            .into_iter()
+            .map(|(membership, list)| ListMembershipRecord {
+                list: list.map(|list| list.slug).unwrap_or_default(),
+                status: membership.status,
+                joined_at: format_rfc3339(membership.joined_at),
+            })
+            .collect(),
+        consent_events: consent_events.into_iter().map(Into::into).collect(),
+        confirmation_links: tokens
+            .into_iter()
+            .map(|token| ConfirmationLinkRecord {
+                created_at: format_rfc3339(token.created_at),
+                expires_at: format_rfc3339(token.expires_at),
+                consumed_at: token.consumed_at.map(format_rfc3339),
+            })
+            .collect(),
+        email_changes: email_changes
+            .into_iter()
+            .map(|request| EmailChangeRecord {
+                new_email: request.new_email,
+                created_at: format_rfc3339(request.created_at),
+                expires_at: format_rfc3339(request.expires_at),
+                consumed_at: request.consumed_at.map(format_rfc3339),
+            })
+            .collect(),
+        pending_deliveries: deliveries
+            .into_iter()
+            .map(|delivery| PendingDeliveryRecord {
+                newsletter_issue_id: delivery.newsletter_issue_id,
+                n_retries: delivery.n_retries,
+                execute_after: format_rfc3339(delivery.execute_after),
+            })
+            .collect(),
+    }))
+}
+
+/// Deletes a subscriber together with their tokens, consent trail, email
+/// changes and pending deliveries. Only a hash of the address is kept, so that the
+/// subscriber is not brought back by an import of an old list.
+///
+/// Returns `false` if there was no such subscriber (any more).
+#[tracing::instrument(skip(connection))]
+pub async fn erase_subscriber(
+    connection: &DatabaseConnection,
+    subscriber_id: Uuid,
+) -> Result<bool, DbErr> {
+    let txn = connection.begin().await?;
+    let Some(subscriber) = Subscriptions::find_by_id(subscriber_id)
+        .lock_exclusive()
+        .one(&txn)
+        .await?
+    else {
+        return Ok(false);
+    };
+    ErasedSubscribers::insert(erased_subscribers::ActiveModel {
+        email_hash: Set(email_hash(&subscriber.email)),
+        erased_at: Set(OffsetDateTime::now_utc()),
+    })
+    .on_conflict(
+        OnConflict::column(erased_subscribers::Column::EmailHash)
+            .update_column(erased_subscribers::Column::ErasedAt)
+            .to_owned(),
+    )
+    .exec_without_returning(&txn)
+    .await?;
+    remove_subscriber(&txn, subscriber).await?;
+    txn.commit().await?;
+    Ok(true)
+}
+
+/// Deletes a subscriber like [`erase_subscriber`] does, but without leaving
+/// a tombstone: they can subscribe or be imported again.
+#[tracing::instrument(skip(connection))]
+pub async fn delete_subscriber(
+    connection: &DatabaseConnection,
+    subscriber_id: Uuid,
+) -> Result<bool, DbErr> {
+    let txn = connection.begin().await?;
+    let Some(subscriber) = Subscriptions::find_by_id(subscriber_id)
+        .lock_exclusive()
+        .one(&txn)
+        .await?
+    else {
+        return Ok(false);
+    };
+    remove_subscriber(&txn, subscriber).await?;
+    txn.commit().await?;
+    Ok(true)
+}
+
+async fn remove_subscriber(
+    txn: &DatabaseTransaction,
+    subscriber: subscriptions::Model,
+) -> Result<(), DbErr> {
+    IssueDeliveryQueue::delete_many()
+        .filter(issue_delivery_queue::Column::SubscriberEmail.eq(&subscriber.email))
+        .exec(txn)
+        .await?;
+    // Tokens, consent events and email changes go with it
+    subscriber.delete(txn).await?;
+    Ok(())
+}
+
+/// Whether the subscriber behind an address asked to be forgotten.
+pub async fn is_erased(db: &impl ConnectionTrait, email: &str) -> Result<bool, DbErr> {
+    let tombstone = ErasedSubscribers::find_by_id(email_hash(email))
+        .one(db)
+        .await?;
+    Ok(tombstone.is_some())
+}
+
+/// Drops the tombstone of an address once its owner has confirmed a new
+/// subscription themselves, their fresh consent outweighs the old request.
+pub async fn lift_erasure(db: &impl ConnectionTrait, email: &str) -> Result<(), DbErr> {
+    ErasedSubscribers::delete_by_id(email_hash(email))
+        .exec(db)
+        .await?;
+    Ok(())
+}
+
+/// Addresses differing only in case or surrounding whitespace share a hash.
+fn email_hash(email: &str) -> String {
+    hex::encode(Sha256::digest(SubscriberEmail::normalize(email).as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::email_hash;
+
+    #[test]
+    fn the_hash_does_not_reveal_the_address() {
+        let hash = email_hash("ursula@example.com");
+        assert_eq!(hash.len(), 64);
+        assert!(!hash.contains("ursula"));
+    }
+
+    #[test]
+    fn the_hash_ignores_case_and_surrounding_whitespace() {
+        assert_eq!(
+            email_hash(" Ursula@Example.com "),
+            email_hash("ursula@example.com")
+        );
+        assert_ne!(
+            email_hash("ursula@example.com"),
+            email_hash("le.guin@example.com")
+        );
+    }
+}
@@ -0,0 +1,78 @@
+use std::time::Duration;
+
+use entity::{
+    sea_orm_active_enums::SubscriptionStatus,
+    subscription_tokens::{self, Entity as SubscriptionTokens},
+    subscriptions::{self, Entity as Subscriptions},
+};
+use sea_orm::{
+    sea_query::Query, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, TransactionTrait,
+};
+use time::OffsetDateTime;
+use tokio_util::sync::CancellationToken;
+
+use crate::issue_delivery_worker::sleep_until_cancelled;
+
+/// How often the sweeper purges stale subscription data.
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+pub async fn run_sweeper_until_stopped(
+    connection: DatabaseConnection,
+    shutdown: CancellationToken,
+) -> Result<(), anyhow::Error> {
+    while !shutdown.is_cancelled() {
+        // Errors are already logged by `sweep_stale_subscriptions`, the next
+        // sweep tries again.
+        let _ = sweep_stale_subscriptions(&connection).await;
+        sleep_until_cancelled(&shutdown, SWEEP_INTERVAL).await;
+    }
+    tracing::info!("Subscription sweeper stopped.");
+    Ok(())
+}
+
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct SweepOutcome {
+    pub n_expired_tokens: u64,
+    pub n_abandoned_subscriptions: u64,
+}
+
+/// Deletes expired subscription tokens and the pending subscriptions that
+/// are left without a valid token, i.e. whose owner never confirmed.
+#[tracing::instrument(skip_all, err)]
+pub async fn sweep_stale_subscriptions(
+    connection: &DatabaseConnection,
+) -> Result<SweepOutcome, anyhow::Error> {
+    let txn = connection.begin().await?;
+    let n_expired_tokens = SubscriptionTokens::delete_many()
+        .filter(subscription_tokens::Column::ExpiresAt.lte(OffsetDateTime::now_utc()))
+        .exec(&txn)
+        .await?
+        .rows_affected;
+    let n_abandoned_subscriptions = Subscriptions::delete_many()
+        .filter(subscriptions::Column::Status.eq(SubscriptionStatus::PendingConfirmation))
+        .filter(
+            subscriptions::Column::Id.not_in_subquery(
+                Query::select()
+                    .column(subscription_tokens::Column::SubscriberId)
+                    .from(SubscriptionTokens)
+                    .to_owned(),
+            ),
+        )
+        .exec(&txn)
+        .await?
+        .rows_affected;
+    txn.commit().await?;
+
+    let outcome = SweepOutcome {
+        n_expired_tokens,
+        n_abandoned_subscriptions,
+    };
+    if outcome != SweepOutcome::default() {
+        tracing::info!(
+            n_expired_tokens,
+            n_abandoned_subscriptions,
+            "Purged stale subscription data."
+        );
+    }
+    Ok(outcome)
+}
@@ -1,7 +1,11 @@
 use axum::{
-    http::StatusCode,
+    http::{header::ACCEPT, HeaderMap, StatusCode},
     response::{IntoResponse, Response},
 };
+use time::{
+    format_description::well_known::Rfc3339, macros::format_description, OffsetDateTime,
+    PrimitiveDateTime, UtcOffset,
+};
 
 pub fn e500<T>(e: T) -> Response
 where
@@ -10,3 +14,119 @@ where
     tracing::error!("{:?}", e);
     StatusCode::INTERNAL_SERVER_ERROR.into_response()
 }
+
+pub fn format_timestamp(timestamp: OffsetDateTime) -> String {
+    timestamp
+        .to_offset(UtcOffset::UTC)
+        .format(format_description!(
+            "[year]-[month]-[day] [hour]:[minute] UTC"
+        ))
+        .unwrap_or_else(|_| timestamp.to_string())
+}
+
+/// For machine-readable exports.
+pub fn format_rfc3339(timestamp: OffsetDateTime) -> String {
+    timestamp
+        .format(&Rfc3339)
+        .unwrap_or_else(|_| timestamp.to_string())
+}
+
+/// Accepts RFC 3339 timestamps as well as the value of an HTML
+/// `datetime-local` input, which carries no offset and is read as UTC.
+pub fn parse_timestamp(input: &str) -> Result<OffsetDateTime, time::error::Parse> {
+    let input = input.trim();
+    OffsetDateTime::parse(input, &Rfc3339).or_else(|_| {
+        PrimitiveDateTime::parse(
+            input,
+            format_description!("[year]-[month]-[day]T[hour]:[minute]"),
+        )
+        .map(PrimitiveDateTime::assume_utc)
+    })
+}
+
+/// Whether the `Accept` header ranks JSON above HTML. Browsers ask for HTML
+/// first, a client without an `Accept` header gets HTML as well.
+pub fn prefers_json(headers: &HeaderMap) -> bool {
+    let mut json_quality: Option<f32> = None;
+    let mut html_quality: Option<f32> = None;
+    let media_ranges = headers
+        .get_all(ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','));
+    for media_range in media_ranges {
+        let mut parameters = media_range.split(';');
+        let media_type = parameters.next().unwrap_or_default().trim();
+        let quality = parameters
+            .filter_map(|p| p.trim().strip_prefix("q="))
+            .find_map(|q| q.parse().ok())
+            .unwrap_or(1.0);
+        let best = if media_type.eq_ignore_ascii_case("application/json") {
+            &mut json_quality
+        } else if media_type.eq_ignore_ascii_case("text/html") {
+            &mut html_quality
+        } else {
+            continue;
+        };
+        *best = Some(best.map_or(quality, |b| b.max(quality)));
+    }
+    match (json_quality, html_quality) {
+        (Some(json), Some(html)) => json > html,
+        (Some(json), None) => json > 0.0,
+        (None, _) => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_timestamp, prefers_json};
+    use axum::http::{header::ACCEPT, HeaderMap, HeaderValue};
+    use time::macros::datetime;
+
+    #[test]
+    fn rfc3339_timestamps_are_accepted() {
+        assert_eq!(
+            parse_timestamp("2024-04-06T10:30:00+02:00").unwrap(),
+            datetime!(2024-04-06 08:30 UTC)
+        );
+    }
+
+    #[test]
+    fn datetime_local_values_are_read_as_utc() {
+        assert_eq!(
+            parse_timestamp("2024-04-06T10:30").unwrap(),
+            datetime!(2024-04-06 10:30 UTC)
+        );
+    }
+
+    #[test]
+    fn garbage_is_rejected() {
+        assert!(parse_timestamp("tomorrow").is_err());
+        assert!(parse_timestamp("").is_err());
+    }
+
+    fn accepting(value: &'static str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT, HeaderValue::from_static(value));
+        headers
+    }
+
+    #[test]
+    fn json_is_preferred_when_asked_for() {
+        assert!(prefers_json(&accepting("application/json")));
+        assert!(prefers_json(&accepting(
+            "application/json, text/html;q=0.5"
+        )));
+    }
+
+    #[test]
+    fn html_is_preferred_by_default() {
+        assert!(!prefers_json(&HeaderMap::new()));
+        assert!(!prefers_json(&accepting("*/*")));
+        assert!(!prefers_json(&accepting(
+            "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8"
+        )));
+        assert!(!prefers_json(&accepting("text/html, application/json")));
+        assert!(!prefers_json(&accepting("application/json;q=0")));
+    }
+}
@@ -0,0 +1,531 @@
+use entity::{
+    consent_events::{self, Entity as ConsentEvents},
+    sea_orm_active_enums::SubscriptionStatus,
+    subscription_tokens::{self, Entity as SubscriptionTokens},
+    subscriptions::Entity as Subscriptions,
+};
+use sea_orm::{ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter};
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use zero2prod::lists::{default_list, find_list, find_membership, join_list};
+
+use crate::helpers::{assert_is_redirect_to, insert_subscriber, spawn_app, TestApp};
+
+async fn get_status(app: &TestApp, subscriber_id: Uuid) -> SubscriptionStatus {
+    Subscriptions::find_by_id(subscriber_id)
+        .one(&app.dp_pool)
+        .await
+        .unwrap()
+        .unwrap()
+        .status
+}
+
+async fn membership_status(
+    app: &TestApp,
+    list_id: Uuid,
+    subscriber_id: Uuid,
+) -> SubscriptionStatus {
+    find_membership(&app.dp_pool, list_id, subscriber_id)
+        .await
+        .unwrap()
+        .unwrap()
+        .status
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_manage_subscribers() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = insert_subscriber(
+        &app,
+        "ursula@example.com",
+        "Ursula",
+        SubscriptionStatus::PendingConfirmation,
+        OffsetDateTime::now_utc(),
+    )
+    .await;
+
+    // Act
+    let list = app.get_subscribers(&[("q", "")]).await;
+    let detail = app.get_subscriber_detail(subscriber_id).await;
+    let delete = app.post_subscriber_action(subscriber_id, "delete").await;
+
+    // Assert
+    assert_is_redirect_to(&list, "/login");
+    assert_is_redirect_to(&detail, "/login");
+    assert_is_redirect_to(&delete, "/login");
+    assert_eq!(Subscriptions::find().count(&app.dp_pool).await.unwrap(), 1);
+}
+
+#[tokio::test]
+async fn subscribers_can_be_searched_and_filtered_by_status() {
+    // Arrange
+    let app = spawn_app().await;
+    let now = OffsetDateTime::now_utc();
+    insert_subscriber(
+        &app,
+        "ursula@example.com",
+        "Ursula",
+        SubscriptionStatus::Confirmed,
+        now,
+    )
+    .await;
+    insert_subscriber(
+        &app,
+        "octavia@example.com",
+        "Octavia",
+        SubscriptionStatus::PendingConfirmation,
+        now,
+    )
+    .await;
+    insert_subscriber(
+        &app,
+        "n.k@example.com",
+        "Jemisin URS",
+        SubscriptionStatus::Unsubscribed,
+        now,
+    )
+    .await;
+    app.login().await;
+
+    // Act - Part 1 - Search emails and names, ignoring case
+    let html_page = app
+        .get_subscribers(&[("q", "Urs")])
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("ursula@example.com"));
+    assert!(html_page.contains("n.k@example.com"));
+    assert!(!html_page.contains("octavia@example.com"));
+
+    // Act - Part 2 - Filter by status
+    let html_page = app
+        .get_subscribers(&[("q", "urs"), ("status", "confirmed")])
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("ursula@example.com"));
+    assert!(!html_page.contains("n.k@example.com"));
+    assert!(!html_page.contains("octavia@example.com"));
+
+    // Act - Part 3 - Wildcards are matched literally
+    let html_page = app
+        .get_subscribers(&[("q", "%")])
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("No subscriber matches."));
+}
+
+#[tokio::test]
+async fn an_unknown_status_filter_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+
+    // Act
+    let response = app.get_subscribers(&[("status", "banned")]).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn subscribers_are_sorted_by_subscription_date() {
+    // Arrange
+    let app = spawn_app().await;
+    let now = OffsetDateTime::now_utc();
+    insert_subscriber(
+        &app,
+        "older@example.com",
+        "Older",
+        SubscriptionStatus::Confirmed,
+        now - Duration::days(2),
+    )
+    .await;
+    insert_subscriber(
+        &app,
+        "newer@example.com",
+        "Newer",
+        SubscriptionStatus::Confirmed,
+        now,
+    )
+    .await;
+    app.login().await;
+
+    // Act
+    let newest_first = app
+        .get_subscribers(&[("q", "")])
+        .await
+        .text()
+        .await
+        .unwrap();
+    let oldest_first = app
+        .get_subscribers(&[("order", "oldest")])
+        .await
+        .text()
+        .await
+        .unwrap();
+
+    // Assert
+    assert!(newest_first.find("newer@example.com") < newest_first.find("older@example.com"));
+    assert!(oldest_first.find("older@example.com") < oldest_first.find("newer@example.com"));
+}
+
+#[tokio::test]
+async fn subscribers_are_listed_fifty_per_page() {
+    // Arrange
+    let app = spawn_app().await;
+    let now = OffsetDateTime::now_utc();
+    for i in 0..51 {
+        insert_subscriber(
+            &app,
+            &format!("subscriber{:02}@example.com", i),
+            "Subscriber",
+            SubscriptionStatus::Confirmed,
+            now - Duration::minutes(i),
+        )
+        .await;
+    }
+    app.login().await;
+
+    // Act - Part 1 - First page
+    let html_page = app
+        .get_subscribers(&[("q", "")])
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("51 subscriber(s), page 1 of 2."));
+    assert!(html_page.contains("subscriber49@example.com"));
+    assert!(!html_page.contains("subscriber50@example.com"));
+    assert!(html_page.contains("href=\"/admin/subscribers?q=&status=all&order=newest&page=2\""));
+
+    // Act - Part 2 - Second page
+    let html_page = app
+        .get_subscribers(&[("page", "2")])
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("page 2 of 2."));
+    assert!(html_page.contains("subscriber50@example.com"));
+    assert!(!html_page.contains("subscriber49@example.com"));
+}
+
+#[tokio::test]
+async fn the_detail_page_shows_the_subscriber_and_their_consent_trail() {
+    // Arrange
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com".into())
+        .await
+        .error_for_status()
+        .unwrap();
+    let subscriber = Subscriptions::find()
+        .one(&app.dp_pool)
+        .await
+        .unwrap()
+        .unwrap();
+    app.login().await;
+
+    // Act
+    let html_page = app.get_subscriber_detail_html(subscriber.id).await;
+
+    // Assert
+    assert!(html_page.contains("ursula_le_guin@gmail.com"));
+    assert!(html_page.contains("pending_confirmation"));
+    assert!(html_page.contains("signed_up"));
+    assert!(html_page.contains("confirmation_sent"));
+    assert!(html_page.contains(&format!("/admin/subscribers/{}/confirm", subscriber.id)));
+    assert!(html_page.contains(&format!(
+        "/admin/subscribers/{}/resend_confirmation",
+        subscriber.id
+    )));
+}
+
+#[tokio::test]
+async fn the_detail_page_of_an_unknown_subscriber_is_not_found() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+
+    // Act
+    let response = app.get_subscriber_detail(Uuid::new_v4()).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn admins_can_confirm_a_pending_subscriber() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = insert_subscriber(
+        &app,
+        "ursula@example.com",
+        "Ursula",
+        SubscriptionStatus::PendingConfirmation,
+        OffsetDateTime::now_utc(),
+    )
+    .await;
+    app.login().await;
+
+    // Act - Part 1 - Confirm
+    let response = app.post_subscriber_action(subscriber_id, "confirm").await;
+    assert_is_redirect_to(&response, &format!("/admin/subscribers/{}", subscriber_id));
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_subscriber_detail_html(subscriber_id).await;
+    assert!(html_page.contains("<p><i>The subscriber has been confirmed.</i></p>"));
+
+    // Assert
+    assert_eq!(
+        get_status(&app, subscriber_id).await,
+        SubscriptionStatus::Confirmed
+    );
+    let event = ConsentEvents::find()
+        .filter(consent_events::Column::SubscriberId.eq(subscriber_id))
+        .one(&app.dp_pool)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(event.kind, "confirmed");
+    assert_eq!(event.source.as_deref(), Some("admin"));
+
+    // Act - Part 3 - Confirm again
+    app.post_subscriber_action(subscriber_id, "confirm").await;
+    let html_page = app.get_subscriber_detail_html(subscriber_id).await;
+    assert!(html_page.contains("<p><i>The subscriber is already confirmed.</i></p>"));
+    assert_eq!(ConsentEvents::find().count(&app.dp_pool).await.unwrap(), 1);
+}
+
+#[tokio::test]
+async fn confirming_a_subscriber_leaves_the_lists_they_left_alone() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = insert_subscriber(
+        &app,
+        "ursula@example.com",
+        "Ursula",
+        SubscriptionStatus::PendingConfirmation,
+        OffsetDateTime::now_utc(),
+    )
+    .await;
+    app.login().await;
+    app.post_list("release-notes", "Release notes").await;
+    let release_notes = find_list(&app.dp_pool, "release-notes")
+        .await
+        .unwrap()
+        .unwrap();
+    join_list(
+        &app.dp_pool,
+        release_notes.id,
+        subscriber_id,
+        SubscriptionStatus::Unsubscribed,
+    )
+    .await
+    .unwrap();
+
+    // Act
+    app.post_subscriber_action(subscriber_id, "confirm").await;
+
+    // Assert
+    let default_list = default_list(&app.dp_pool).await.unwrap();
+    assert_eq!(
+        membership_status(&app, default_list.id, subscriber_id).await,
+        SubscriptionStatus::Confirmed
+    );
+    assert_eq!(
+        membership_status(&app, release_notes.id, subscriber_id).await,
+        SubscriptionStatus::Unsubscribed
+    );
+}
+
+#[tokio::test]
+async fn confirming_does_not_resubscribe_an_unsubscribed_subscriber() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = insert_subscriber(
+        &app,
+        "ursula@example.com",
+        "Ursula",
+        SubscriptionStatus::Unsubscribed,
+        OffsetDateTime::now_utc(),
+    )
+    .await;
+    app.login().await;
+
+    // Act
+    app.post_subscriber_action(subscriber_id, "confirm").await;
+
+    // Assert
+    assert_eq!(
+        get_status(&app, subscriber_id).await,
+        SubscriptionStatus::Unsubscribed
+    );
+    assert_eq!(ConsentEvents::find().count(&app.dp_pool).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn admins_can_unsubscribe_a_subscriber() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = insert_subscriber(
+        &app,
+        "ursula@example.com",
+        "Ursula",
+        SubscriptionStatus::Confirmed,
+        OffsetDateTime::now_utc(),
+    )
+    .await;
+    app.login().await;
+
+    // Act
+    let response = app
+        .post_subscriber_action(subscriber_id, "unsubscribe")
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, &format!("/admin/subscribers/{}", subscriber_id));
+    assert_eq!(
+        get_status(&app, subscriber_id).await,
+        SubscriptionStatus::Unsubscribed
+    );
+    let event = ConsentEvents::find()
+        .filter(consent_events::Column::SubscriberId.eq(subscriber_id))
+        .one(&app.dp_pool)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(event.kind, "unsubscribed");
+    assert_eq!(event.source.as_deref(), Some("admin"));
+}
+
+#[tokio::test]
+async fn admins_can_delete_a_subscriber() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = insert_subscriber(
+        &app,
+        "ursula@example.com",
+        "Ursula",
+        SubscriptionStatus::Confirmed,
+        OffsetDateTime::now_utc(),
+    )
+    .await;
+    app.login().await;
+
+    // Act - Part 1 - Delete
+    let response = app.post_subscriber_action(subscriber_id, "delete").await;
+    assert_is_redirect_to(&response, "/admin/subscribers");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app
+        .get_subscribers(&[("q", "")])
+        .await
+        .text()
+        .await
+        .unwrap();
+    assert!(html_page.contains("<p><i>The subscriber has been deleted.</i></p>"));
+    assert!(!html_page.contains("ursula@example.com"));
+
+    // Assert
+    assert!(Subscriptions::find_by_id(subscriber_id)
+        .one(&app.dp_pool)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn admins_can_resend_the_confirmation_email() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = insert_subscriber(
+        &app,
+        "ursula@example.com",
+        "Ursula",
+        SubscriptionStatus::PendingConfirmation,
+        OffsetDateTime::now_utc(),
+    )
+    .await;
+    app.login().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Resend
+    let response = app
+        .post_subscriber_action(subscriber_id, "resend_confirmation")
+        .await;
+    assert_is_redirect_to(&response, &format!("/admin/subscribers/{}", subscriber_id));
+
+    // Act - Part 2 - Follow the link
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // Assert
+    assert_eq!(
+        get_status(&app, subscriber_id).await,
+        SubscriptionStatus::Confirmed
+    );
+    let n_tokens = SubscriptionTokens::find()
+        .filter(subscription_tokens::Column::SubscriberId.eq(subscriber_id))
+        .count(&app.dp_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_tokens, 1);
+}
+
+#[tokio::test]
+async fn confirmed_subscribers_are_not_sent_a_confirmation_email_again() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = insert_subscriber(
+        &app,
+        "ursula@example.com",
+        "Ursula",
+        SubscriptionStatus::Confirmed,
+        OffsetDateTime::now_utc(),
+    )
+    .await;
+    app.login().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_subscriber_action(subscriber_id, "resend_confirmation")
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, &format!("/admin/subscribers/{}", subscriber_id));
+    let html_page = app.get_subscriber_detail_html(subscriber_id).await;
+    assert!(html_page
+        .contains("<p><i>Only pending subscribers can be sent a confirmation email.</i></p>"));
+}
@@ -0,0 +1,372 @@
+use entity::{
+    consent_events::{self, Entity as ConsentEvents},
+    sea_orm_active_enums::SubscriptionStatus,
+    subscriptions::{self, Entity as Subscriptions},
+};
+use sea_orm::{ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter, QueryOrder};
+use time::{Duration, OffsetDateTime};
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+use zero2prod::subscriber_data::erase_subscriber;
+
+use crate::helpers::{assert_is_redirect_to, insert_subscriber, spawn_app, TestApp};
+
+async fn find_subscriber(app: &TestApp, email: &str) -> Option<subscriptions::Model> {
+    Subscriptions::find()
+        .filter(subscriptions::Column::Email.eq(email))
+        .one(&app.dp_pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_import_or_export_subscribers() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let import = app
+        .post_subscriber_import(
+            "email,name\nursula@example.com,Ursula\n",
+            "confirmed",
+            false,
+        )
+        .await;
+    let export = app.get_subscriber_export("all").await;
+
+    // Assert
+    assert_is_redirect_to(&import, "/login");
+    assert_is_redirect_to(&export, "/login");
+    assert!(find_subscriber(&app, "ursula@example.com").await.is_none());
+}
+
+#[tokio::test]
+async fn imported_rows_can_be_marked_as_confirmed() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+    let csv = "Name,Email,Source\n\
+               Ursula,ursula@example.com,old list\n \
+               Octavia , octavia@example.com ,old list\n";
+
+    // Act
+    let response = app.post_subscriber_import(csv, "confirmed", false).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("2 of 2 row(s) have been imported."));
+    let octavia = find_subscriber(&app, "octavia@example.com").await.unwrap();
+    assert_eq!(octavia.name, "Octavia");
+    assert_eq!(octavia.status, SubscriptionStatus::Confirmed);
+    let events = ConsentEvents::find()
+        .filter(consent_events::Column::SubscriberId.eq(octavia.id))
+        .order_by_asc(consent_events::Column::OccurredAt)
+        .all(&app.dp_pool)
+        .await
+        .unwrap();
+    let events: Vec<_> = events
+        .iter()
+        .map(|event| (event.kind.as_str(), event.source.as_deref()))
+        .collect();
+    assert_eq!(
+        events,
+        [("signed_up", Some("import")), ("confirmed", Some("import"))]
+    );
+}
+
+#[tokio::test]
+async fn imported_rows_can_be_sent_a_confirmation_email() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Import
+    let response = app
+        .post_subscriber_import(
+            "email,name\nursula@example.com,Ursula\n",
+            "send_confirmation",
+            false,
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        find_subscriber(&app, "ursula@example.com")
+            .await
+            .unwrap()
+            .status,
+        SubscriptionStatus::PendingConfirmation
+    );
+
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("Confirmation emails are being sent to 1 subscriber(s)."));
+
+    // Act - Part 2 - Confirm
+    let email_request = &app.wait_for_emails(1).await[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // Assert
+    assert_eq!(
+        find_subscriber(&app, "ursula@example.com")
+            .await
+            .unwrap()
+            .status,
+        SubscriptionStatus::Confirmed
+    );
+}
+
+#[tokio::test]
+async fn a_dry_run_reports_without_importing() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_subscriber_import(
+            "email,name\nursula@example.com,Ursula\nnot-an-email,Nobody\n",
+            "send_confirmation",
+            true,
+        )
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("Dry run, nothing has been imported."));
+    assert!(html_page.contains("1 of 2 row(s) would be imported."));
+    assert!(html_page.contains("not-an-email is not a valid subscriber email."));
+    assert_eq!(Subscriptions::find().count(&app.dp_pool).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn rows_that_cannot_be_imported_are_reported_with_a_reason() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    insert_subscriber(
+        &app,
+        "existing@example.com",
+        "Existing",
+        SubscriptionStatus::Unsubscribed,
+        OffsetDateTime::now_utc(),
+    )
+    .await;
+    let erased_id = insert_subscriber(
+        &app,
+        "erased@example.com",
+        "Erased",
+        SubscriptionStatus::Confirmed,
+        OffsetDateTime::now_utc(),
+    )
+    .await;
+    erase_subscriber(&app.dp_pool, erased_id).await.unwrap();
+    let csv = "email,name\n\
+               ursula@example.com,Ursula\n\
+               not-an-email,Nobody\n\
+               blank@example.com,\n\
+               ursula@example.com,Ursula again\n\
+               existing@example.com,Existing\n\
+               ERASED@example.com,Erased\n";
+
+    // Act
+    let response = app.post_subscriber_import(csv, "confirmed", false).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("1 of 6 row(s) have been imported."));
+    for (line, reason) in [
+        (3, "not-an-email is not a valid subscriber email."),
+        (4, " is not a valid subscriber name."),
+        (5, "The address appears earlier in the file."),
+        (6, "The address is already subscribed."),
+        (7, "The subscriber asked for their data to be erased."),
+    ] {
+        assert!(
+            html_page.contains(&format!("<td>{}</td>", line)),
+            "line {} is not reported",
+            line
+        );
+        assert!(html_page.contains(reason), "{:?} is not reported", reason);
+    }
+    // An unsubscribed subscriber is not brought back
+    assert_eq!(
+        find_subscriber(&app, "existing@example.com")
+            .await
+            .unwrap()
+            .status,
+        SubscriptionStatus::Unsubscribed
+    );
+    assert!(find_subscriber(&app, "ERASED@example.com").await.is_none());
+    assert_eq!(Subscriptions::find().count(&app.dp_pool).await.unwrap(), 2);
+}
+
+#[tokio::test]
+async fn addresses_are_matched_regardless_of_case() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    insert_subscriber(
+        &app,
+        "existing@example.com",
+        "Existing",
+        SubscriptionStatus::Confirmed,
+        OffsetDateTime::now_utc(),
+    )
+    .await;
+    let csv = "email,name\n\
+               Ursula@Example.com,Ursula\n\
+               ursula@example.com,Ursula again\n\
+               Existing@Example.com,Existing\n";
+
+    // Act
+    let response = app.post_subscriber_import(csv, "confirmed", false).await;
+
+    // Assert
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("1 of 3 row(s) have been imported."));
+    assert!(html_page.contains("The address appears earlier in the file."));
+    assert!(html_page.contains("The address is already subscribed."));
+    assert!(find_subscriber(&app, "ursula@example.com").await.is_some());
+    assert_eq!(Subscriptions::find().count(&app.dp_pool).await.unwrap(), 2);
+}
+
+#[tokio::test]
+async fn a_file_without_the_expected_columns_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+
+    // Act
+    let response = app
+        .post_subscriber_import(
+            "address,full_name\nursula@example.com,Ursula\n",
+            "confirmed",
+            false,
+        )
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("The CSV file needs an email and a name column."));
+}
+
+#[tokio::test]
+async fn subscribers_can_be_exported_by_status() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let now = OffsetDateTime::now_utc();
+    insert_subscriber(
+        &app,
+        "ursula@example.com",
+        "Le Guin, Ursula",
+        SubscriptionStatus::Confirmed,
+        now - Duration::days(1),
+    )
+    .await;
+    insert_subscriber(
+        &app,
+        "octavia@example.com",
+        "Octavia",
+        SubscriptionStatus::Confirmed,
+        now,
+    )
+    .await;
+    insert_subscriber(
+        &app,
+        "n.k@example.com",
+        "Jemisin",
+        SubscriptionStatus::Unsubscribed,
+        now,
+    )
+    .await;
+
+    // Act
+    let response = app.get_subscriber_export("confirmed").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.headers()["Content-Type"]
+        .to_str()
+        .unwrap()
+        .starts_with("text/csv"));
+    assert!(response.headers()["Content-Disposition"]
+        .to_str()
+        .unwrap()
+        .starts_with("attachment"));
+    let csv = response.text().await.unwrap();
+    let lines: Vec<_> = csv.lines().collect();
+    assert_eq!(lines.len(), 3);
+    assert_eq!(lines[0], "email,name,status,subscribed_at");
+    assert!(lines[1].starts_with("ursula@example.com,\"Le Guin, Ursula\",confirmed,"));
+    assert!(lines[2].starts_with("octavia@example.com,Octavia,confirmed,"));
+}
+
+#[tokio::test]
+async fn an_export_can_be_imported_again() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    insert_subscriber(
+        &app,
+        "ursula@example.com",
+        "Le Guin, Ursula",
+        SubscriptionStatus::Confirmed,
+        OffsetDateTime::now_utc(),
+    )
+    .await;
+    let csv = app.get_subscriber_export("all").await.text().await.unwrap();
+    Subscriptions::delete_many()
+        .exec(&app.dp_pool)
+        .await
+        .unwrap();
+
+    // Act
+    let response = app.post_subscriber_import(&csv, "confirmed", false).await;
+
+    // Assert
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("1 of 1 row(s) have been imported."));
+    let subscriber = find_subscriber(&app, "ursula@example.com").await.unwrap();
+    assert_eq!(subscriber.name, "Le Guin, Ursula");
+}
@@ -0,0 +1,158 @@
+use entity::subscriptions::Entity as Subscriptions;
+use sea_orm::EntityTrait;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+
+#[tokio::test]
+async fn the_consent_trail_records_every_step_of_the_double_opt_in() {
+    // Arrange
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let client = reqwest::Client::new();
+
+    // Act - Part 1 - Sign up
+    client
+        .post(format!("{}/subscriptions", app.address))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .header("User-Agent", "signup-browser")
+        .header("X-Forwarded-For", "203.0.113.7, 10.0.0.1")
+        .body("name=le%20guin&email=ursula_le_guin%40gmail.com&source=blog_footer")
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // Act - Part 2 - Confirm
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+    client
+        .get(confirmation_links.html)
+        .header("User-Agent", "mail-client")
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // Act - Part 3 - Unsubscribe from the page, twice
+    let subscriber = Subscriptions::find()
+        .one(&app.dp_pool)
+        .await
+        .unwrap()
+        .unwrap();
+    for _ in 0..2 {
+        client
+            .post(app.get_unsubscribe_link(subscriber.id))
+            .form(&[
+                ("List-Unsubscribe", "One-Click"),
+                ("source", "unsubscribe_page"),
+            ])
+            .send()
+            .await
+            .unwrap()
+            .error_for_status()
+            .unwrap();
+    }
+
+    // Act - Part 4 - Export
+    app.login().await;
+    let response = app.get_consent_trail("ursula_le_guin@gmail.com").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.headers()["Content-Disposition"]
+        .to_str()
+        .unwrap()
+        .starts_with("attachment"));
+    let trail: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(trail["subscriber"]["id"], subscriber.id.to_string());
+    assert_eq!(trail["subscriber"]["status"], "unsubscribed");
+    let events = trail["events"].as_array().unwrap();
+    let kinds: Vec<_> = events
+        .iter()
+        .map(|e| e["event"].as_str().unwrap())
+        .collect();
+    assert_eq!(
+        kinds,
+        [
+            "signed_up",
+            "confirmation_sent",
+            "confirmed",
+            "unsubscribed"
+        ]
+    );
+    assert_eq!(events[0]["ip_address"], "203.0.113.7");
+    assert_eq!(events[0]["user_agent"], "signup-browser");
+    assert_eq!(events[0]["source"], "blog_footer");
+    assert_eq!(events[2]["ip_address"], "127.0.0.1");
+    assert_eq!(events[2]["user_agent"], "mail-client");
+    assert_eq!(events[3]["source"], "unsubscribe_page");
+}
+
+#[tokio::test]
+async fn one_click_unsubscribes_are_told_apart_from_the_unsubscribe_page() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = crate::helpers::create_confirmed_subscriber(&app).await;
+    let subscriber = Subscriptions::find_by_id(subscriber_id)
+        .one(&app.dp_pool)
+        .await
+        .unwrap()
+        .unwrap();
+
+    // Act
+    reqwest::Client::new()
+        .post(app.get_unsubscribe_link(subscriber_id))
+        .form(&[("List-Unsubscribe", "One-Click")])
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // Assert
+    app.login().await;
+    let trail: serde_json::Value = app
+        .get_consent_trail(&subscriber.email)
+        .await
+        .json()
+        .await
+        .unwrap();
+    let last_event = trail["events"].as_array().unwrap().last().unwrap().clone();
+    assert_eq!(last_event["event"], "unsubscribed");
+    assert_eq!(last_event["source"], "one_click");
+}
+
+#[tokio::test]
+async fn exporting_the_trail_of_an_unknown_address_returns_404() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+
+    // Act
+    let response = app.get_consent_trail("nobody@example.com").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_export_a_consent_trail() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.get_consent_trail("ursula_le_guin@gmail.com").await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
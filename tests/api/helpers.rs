@@ -1,13 +1,26 @@
 use argon2::{password_hash::SaltString, Algorithm, Argon2, Params, PasswordHasher, Version};
-use entity::users::{self, Entity as Users};
+use entity::{
+    sea_orm_active_enums::{DigestFrequency, SubscriptionStatus, UserRole},
+    subscriptions::{self, Entity as Subscriptions},
+    users::{self, Entity as Users},
+};
 use once_cell::sync::Lazy;
-use sea_orm::{DatabaseConnection, EntityTrait, Set};
+use sea_orm::{ActiveModelTrait, ColumnTrait, DatabaseConnection, EntityTrait, QueryFilter, Set};
 use std::future::IntoFuture;
+use time::OffsetDateTime;
 use uuid::Uuid;
-use wiremock::MockServer;
+use wiremock::matchers::{method, path};
+use wiremock::{Mock, MockServer, ResponseTemplate};
 use zero2prod::{
     configuration::{configure_database, get_configuration},
-    startup::Application,
+    email_client::EmailClient,
+    email_templates::EmailTemplates,
+    issue_delivery_worker::{try_execute_task, ExecutionOutcome},
+    issue_scheduler::release_due_issues,
+    link_signer::LinkSigner,
+    lists::{default_list, join_list},
+    startup::{Application, HmacSecret},
+    subscription_sweeper::{sweep_stale_subscriptions, SweepOutcome},
     telemetry::{get_subscriber, init_subscriber},
 };
 
@@ -30,24 +43,36 @@ pub struct TestApp {
     pub email_server: MockServer,
     pub test_user: TestUser,
     pub api_client: reqwest::Client,
+    pub email_client: EmailClient,
+    pub link_signer: LinkSigner,
+    pub email_templates: EmailTemplates,
 }
 
 pub struct TestUser {
     pub user_id: Uuid,
     pub username: String,
+    pub email: String,
     pub password: String,
+    pub role: UserRole,
 }
 
 impl TestUser {
     pub fn generate() -> Self {
+        Self::with_role(UserRole::Owner)
+    }
+
+    pub fn with_role(role: UserRole) -> Self {
+        let username = Uuid::new_v4().to_string();
         Self {
             user_id: Uuid::new_v4(),
-            username: Uuid::new_v4().to_string(),
+            email: format!("{}@example.com", username),
+            username,
             password: Uuid::new_v4().to_string(),
+            role,
         }
     }
 
-    async fn store(&self, conn: &DatabaseConnection) {
+    pub async fn store(&self, conn: &DatabaseConnection) {
         let salt = SaltString::generate(&mut rand::thread_rng());
         let password_hash = Argon2::new(
             Algorithm::Argon2id,
@@ -62,6 +87,13 @@ impl TestUser {
             user_id: Set(self.user_id),
             username: Set(self.username.clone()),
             password_hash: Set(password_hash),
+            role: Set(self.role),
+            disabled_at: Set(None),
+            email: Set(Some(self.email.clone())),
+            sessions_revoked_at: Set(None),
+            totp_secret: Set(None),
+            totp_enabled_at: Set(None),
+            totp_last_step: Set(None),
         };
 
         Users::insert(user)
@@ -77,8 +109,58 @@ pub struct ConfirmationLinks {
 }
 
 impl TestApp {
+    /// A client with its own cookies, as a second browser would have.
+    pub fn client() -> reqwest::Client {
+        reqwest::Client::builder()
+            .redirect(reqwest::redirect::Policy::none())
+            .cookie_store(true)
+            .build()
+            .unwrap()
+    }
+
+    pub async fn dispatch_all_pending_emails(&self) {
+        loop {
+            if let ExecutionOutcome::EmptyQueue = try_execute_task(
+                &self.dp_pool,
+                &self.email_client,
+                &self.link_signer,
+                &self.email_templates,
+            )
+            .await
+            .unwrap()
+            {
+                break;
+            }
+        }
+    }
+
+    /// Waits for emails that are sent in the background, after the response.
+    pub async fn wait_for_emails(&self, n: usize) -> Vec<wiremock::Request> {
+        for _ in 0..200 {
+            let requests = self.email_server.received_requests().await.unwrap();
+            if requests.len() >= n {
+                return requests;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(25)).await;
+        }
+        panic!("Expected {} email(s) to be sent.", n);
+    }
+
+    pub async fn release_due_issues(&self) {
+        release_due_issues(&self.dp_pool).await.unwrap();
+    }
+
+    pub async fn sweep_stale_subscriptions(&self) -> SweepOutcome {
+        sweep_stale_subscriptions(&self.dp_pool).await.unwrap()
+    }
+
     pub async fn login(&self) {
-        let login_body = serde_json::json!({ "username": &self.test_user.username, "password": &self.test_user.password});
+        self.login_as(&self.test_user).await;
+    }
+
+    pub async fn login_as(&self, user: &TestUser) {
+        let login_body =
+            serde_json::json!({ "username": &user.username, "password": &user.password});
         let _ = self
             .post_login(&login_body)
             .await
@@ -89,7 +171,7 @@ impl TestApp {
     pub async fn logout(&self) {
         let _ = self
             .api_client
-            .get(&format!("{}/admin/logout", &self.address))
+            .get(format!("{}/admin/logout", &self.address))
             .send()
             .await
             .unwrap();
@@ -97,7 +179,7 @@ impl TestApp {
 
     pub async fn post_subscriptions(&self, body: String) -> reqwest::Response {
         self.api_client
-            .post(&format!("{}/subscriptions", &self.address))
+            .post(format!("{}/subscriptions", &self.address))
             .header("Content-Type", "application/x-www-form-urlencoded")
             .body(body)
             .send()
@@ -126,9 +208,63 @@ impl TestApp {
         ConfirmationLinks { html, plain_text }
     }
 
-    pub async fn post_newsletters(&self, body: serde_json::Value) -> reqwest::Response {
+    pub fn get_unsubscribe_link(&self, subscriber_id: Uuid) -> reqwest::Url {
+        let mut unsubscribe_link =
+            reqwest::Url::parse(&self.link_signer.unsubscribe_url(subscriber_id)).unwrap();
+        unsubscribe_link.set_port(Some(self.port)).unwrap();
+        unsubscribe_link
+    }
+
+    pub fn get_preferences_link(&self, subscriber_id: Uuid) -> reqwest::Url {
+        let mut preferences_link =
+            reqwest::Url::parse(&self.link_signer.preferences_url(subscriber_id)).unwrap();
+        preferences_link.set_port(Some(self.port)).unwrap();
+        preferences_link
+    }
+
+    pub async fn post_preferences<Body>(
+        &self,
+        subscriber_id: Uuid,
+        body: &Body,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(self.get_preferences_link(subscriber_id))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub fn get_data_request_link(
+        &self,
+        subscriber_id: Uuid,
+        expires_at: OffsetDateTime,
+    ) -> reqwest::Url {
+        let mut data_request_link =
+            reqwest::Url::parse(&self.link_signer.data_request_url(subscriber_id, expires_at))
+                .unwrap();
+        data_request_link.set_port(Some(self.port)).unwrap();
+        data_request_link
+    }
+
+    pub async fn post_data_request(&self, email: &str) -> reqwest::Response {
         self.api_client
-            .post(&format!("{}/admin/newsletters", &self.address))
+            .post(format!("{}/subscriptions/data", &self.address))
+            .form(&[("email", email)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_newsletters<Body>(&self, body: Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/admin/newsletters", &self.address))
             .basic_auth(&self.test_user.username, Some(&self.test_user.password))
             .form(&body)
             .send()
@@ -141,7 +277,41 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/login", &self.address))
+            .post(format!("{}/login", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_login_from<Body>(&self, body: &Body, ip_address: &str) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/login", &self.address))
+            .header("X-Forwarded-For", ip_address)
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_forgot_password(&self, username: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/login/forgot", &self.address))
+            .form(&[("username", username)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_reset_password<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/login/reset", &self.address))
             .form(body)
             .send()
             .await
@@ -150,7 +320,7 @@ impl TestApp {
 
     pub async fn get_login_html(&self) -> String {
         self.api_client
-            .get(&format!("{}/login", &self.address))
+            .get(format!("{}/login", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -161,7 +331,227 @@ impl TestApp {
 
     pub async fn get_newsletter(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/admin/newsletters", &self.address))
+            .get(format!("{}/admin/newsletters", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_newsletter_html(&self) -> String {
+        self.get_newsletter().await.text().await.unwrap()
+    }
+
+    pub async fn get_newsletter_history(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/newsletters/history", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_scheduled_newsletters(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/newsletters/scheduled", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_consent_trail(&self, email: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/subscribers/consent", &self.address))
+            .query(&[("email", email)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_subscribers<Query>(&self, query: &Query) -> reqwest::Response
+    where
+        Query: serde::Serialize + ?Sized,
+    {
+        self.api_client
+            .get(format!("{}/admin/subscribers", &self.address))
+            .query(query)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_lists_html(&self) -> String {
+        self.api_client
+            .get(format!("{}/admin/lists", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+            .text()
+            .await
+            .unwrap()
+    }
+
+    pub async fn post_list(&self, slug: &str, name: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/lists", &self.address))
+            .form(&[("slug", slug), ("name", name)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_subscriber_detail(&self, subscriber_id: Uuid) -> reqwest::Response {
+        self.api_client
+            .get(format!(
+                "{}/admin/subscribers/{}",
+                &self.address, subscriber_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_subscriber_detail_html(&self, subscriber_id: Uuid) -> String {
+        self.get_subscriber_detail(subscriber_id)
+            .await
+            .text()
+            .await
+            .unwrap()
+    }
+
+    /// Uploads a CSV file, `mode` is `confirmed` or `send_confirmation`.
+    pub async fn post_subscriber_import(
+        &self,
+        csv: &str,
+        mode: &str,
+        dry_run: bool,
+    ) -> reqwest::Response {
+        let mut form = reqwest::multipart::Form::new()
+            .part(
+                "file",
+                reqwest::multipart::Part::text(csv.to_owned())
+                    .file_name("subscribers.csv")
+                    .mime_str("text/csv")
+                    .unwrap(),
+            )
+            .text("mode", mode.to_owned());
+        if dry_run {
+            form = form.text("dry_run", "true");
+        }
+        self.api_client
+            .post(format!("{}/admin/subscribers/import", &self.address))
+            .multipart(form)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_subscriber_export(&self, status: &str) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/subscribers/export", &self.address))
+            .query(&[("status", status)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// Performs one of the admin actions on a subscriber, e.g. `confirm`.
+    pub async fn post_subscriber_action(
+        &self,
+        subscriber_id: Uuid,
+        action: &str,
+    ) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/subscribers/{}/{}",
+                &self.address, subscriber_id, action
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_users(&self) -> reqwest::Response {
+        self.api_client
+            .get(format!("{}/admin/users", &self.address))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_users_html(&self) -> String {
+        self.get_users().await.text().await.unwrap()
+    }
+
+    pub async fn post_invite_user(&self, email: &str, role: &str) -> reqwest::Response {
+        self.api_client
+            .post(format!("{}/admin/users", &self.address))
+            .form(&[("email", email), ("role", role)])
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_accept_invitation<Body>(&self, body: &Body) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!("{}/invitations/accept", &self.address))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    /// Performs one of the owner actions on a user, e.g. `disable`.
+    pub async fn post_user_action<Body>(
+        &self,
+        user_id: Uuid,
+        action: &str,
+        body: &Body,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!(
+                "{}/admin/users/{}/{}",
+                &self.address, user_id, action
+            ))
+            .form(body)
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn get_scheduled_newsletters_html(&self) -> String {
+        self.get_scheduled_newsletters().await.text().await.unwrap()
+    }
+
+    pub async fn post_cancel_scheduled_newsletter(&self, issue_id: Uuid) -> reqwest::Response {
+        self.api_client
+            .post(format!(
+                "{}/admin/newsletters/scheduled/{}/cancel",
+                &self.address, issue_id
+            ))
+            .send()
+            .await
+            .expect("Failed to execute request.")
+    }
+
+    pub async fn post_reschedule_newsletter<Body>(
+        &self,
+        issue_id: Uuid,
+        body: &Body,
+    ) -> reqwest::Response
+    where
+        Body: serde::Serialize,
+    {
+        self.api_client
+            .post(format!(
+                "{}/admin/newsletters/scheduled/{}/reschedule",
+                &self.address, issue_id
+            ))
+            .form(body)
             .send()
             .await
             .expect("Failed to execute request.")
@@ -169,7 +559,7 @@ impl TestApp {
 
     pub async fn get_admin_dashboard(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/admin/dashboard", &self.address))
+            .get(format!("{}/admin/dashboard", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -181,7 +571,7 @@ impl TestApp {
 
     pub async fn get_change_password(&self) -> reqwest::Response {
         self.api_client
-            .get(&format!("{}/admin/password", &self.address))
+            .get(format!("{}/admin/password", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -192,7 +582,7 @@ impl TestApp {
         Body: serde::Serialize,
     {
         self.api_client
-            .post(&format!("{}/admin/password", &self.address))
+            .post(format!("{}/admin/password", &self.address))
             .form(body)
             .send()
             .await
@@ -205,7 +595,7 @@ impl TestApp {
 
     pub async fn post_logout(&self) -> reqwest::Response {
         self.api_client
-            .post(&format!("{}/admin/logout", &self.address))
+            .post(format!("{}/admin/logout", &self.address))
             .send()
             .await
             .expect("Failed to execute request.")
@@ -215,11 +605,7 @@ impl TestApp {
 pub async fn spawn_app() -> TestApp {
     Lazy::force(&TRACING);
 
-    let client = reqwest::Client::builder()
-        .redirect(reqwest::redirect::Policy::none())
-        .cookie_store(true)
-        .build()
-        .unwrap();
+    let client = TestApp::client();
 
     let email_server = MockServer::start().await;
 
@@ -228,6 +614,14 @@ pub async fn spawn_app() -> TestApp {
         c.database.database_name = Uuid::new_v4().to_string();
         c.application.port = 0;
         c.email_client.base_url = email_server.uri();
+        // Every test counts its own failed logins, and does not wait long.
+        c.login_throttle.key_prefix = Uuid::new_v4().to_string();
+        c.login_throttle.base_delay_milliseconds = 1;
+        c.login_throttle.max_delay_milliseconds = 10;
+        // The tests stand in for a load balancer, the way it forwards the
+        // address of the client.
+        c.application.trusted_proxies =
+            vec!["127.0.0.1".parse().unwrap(), "10.0.0.1".parse().unwrap()];
         c
     };
 
@@ -238,7 +632,7 @@ pub async fn spawn_app() -> TestApp {
         .expect("Failed to build application.");
     let application_port = application.port();
     let address = format!("http://127.0.0.1:{}", application_port);
-    let _ = tokio::spawn(application.run_until_stopped().into_future());
+    tokio::spawn(application.run_until_stopped().into_future());
 
     let test_app = TestApp {
         address,
@@ -249,11 +643,83 @@ pub async fn spawn_app() -> TestApp {
         email_server,
         test_user: TestUser::generate(),
         api_client: client,
+        email_client: configuration.email_client.client(),
+        link_signer: LinkSigner::new(
+            configuration.application.base_url.clone(),
+            HmacSecret(configuration.application.hmac_secret.clone()),
+        ),
+        email_templates: EmailTemplates::new(),
     };
     test_app.test_user.store(&test_app.dp_pool).await;
     test_app
 }
 
+/// Goes through the whole subscription flow, returns the subscriber id.
+pub async fn create_confirmed_subscriber(app: &TestApp) -> Uuid {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    let email = format!("{}@example.com", Uuid::new_v4());
+    app.post_subscriptions(format!(
+        "name=le%20guin&email={}",
+        urlencoding::encode(&email)
+    ))
+    .await
+    .error_for_status()
+    .unwrap();
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let confirmation_links = app.get_confirmation_links(&email_request);
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    Subscriptions::find()
+        .filter(subscriptions::Column::Email.eq(email))
+        .one(&app.dp_pool)
+        .await
+        .unwrap()
+        .unwrap()
+        .id
+}
+
+/// Stores a subscriber directly, without going through the subscription flow.
+pub async fn insert_subscriber(
+    app: &TestApp,
+    email: &str,
+    name: &str,
+    status: SubscriptionStatus,
+    subscribed_at: OffsetDateTime,
+) -> Uuid {
+    let subscriber_id = Uuid::new_v4();
+    subscriptions::ActiveModel {
+        id: Set(subscriber_id),
+        email: Set(email.to_owned()),
+        name: Set(name.to_owned()),
+        subscribed_at: Set(subscribed_at),
+        status: Set(status),
+        digest_frequency: Set(DigestFrequency::Immediately),
+    }
+    .insert(&app.dp_pool)
+    .await
+    .unwrap();
+    let list = default_list(&app.dp_pool).await.unwrap();
+    join_list(&app.dp_pool, list.id, subscriber_id, status)
+        .await
+        .unwrap();
+    subscriber_id
+}
+
 pub fn assert_is_redirect_to(response: &reqwest::Response, location: &str) {
     assert_eq!(response.status().as_u16(), 303);
     assert_eq!(response.headers().get("Location").unwrap(), location);
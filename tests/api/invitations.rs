@@ -0,0 +1,285 @@
+use entity::{
+    sea_orm_active_enums::UserRole,
+    user_invitations::{self, Entity as UserInvitations},
+    users::{self, Entity as Users},
+};
+use sea_orm::{sea_query::Expr, ColumnTrait, EntityTrait, QueryFilter};
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+const INVITEE: &str = "octavia@example.com";
+
+/// Invites an address as an owner and returns the token of the link in the
+/// invitation email.
+async fn invite(app: &TestApp, email: &str, role: &str) -> String {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    let response = app.post_invite_user(email, role).await;
+    assert_is_redirect_to(&response, "/admin/users");
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let links = app.get_confirmation_links(&email_request);
+    assert_eq!(links.html.path(), "/invitations/accept");
+    links
+        .html
+        .query_pairs()
+        .find(|(key, _)| key == "token")
+        .unwrap()
+        .1
+        .into_owned()
+}
+
+fn accept_form<'a>(token: &'a str, username: &'a str) -> Vec<(&'a str, &'a str)> {
+    vec![
+        ("token", token),
+        ("username", username),
+        ("password", "a-long-enough-password"),
+        ("password_check", "a-long-enough-password"),
+    ]
+}
+
+async fn get_user(app: &TestApp, username: &str) -> Option<users::Model> {
+    Users::find()
+        .filter(users::Column::Username.eq(username))
+        .one(&app.dp_pool)
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn owners_can_invite_collaborators_by_email() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+
+    // Act
+    invite(&app, INVITEE, "editor").await;
+
+    // Assert
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["to"][0]["email"], INVITEE);
+    assert!(body["text"]
+        .as_str()
+        .unwrap()
+        .contains(&format!("{} invited you", app.test_user.username)));
+    let html_page = app.get_users_html().await;
+    assert!(html_page.contains(&format!("An invitation has been sent to {}.", INVITEE)));
+    assert!(html_page.contains("Pending invitations"));
+}
+
+#[tokio::test]
+async fn an_invitee_picks_a_username_and_a_password() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let token = invite(&app, INVITEE, "editor").await;
+    app.logout().await;
+
+    // Act - Part 1 - Open the link
+    let response = app
+        .api_client
+        .get(format!(
+            "{}/invitations/accept?token={}",
+            &app.address, token
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.text().await.unwrap().contains(INVITEE));
+
+    // Act - Part 2 - Accept
+    let response = app
+        .post_accept_invitation(&accept_form(&token, "octavia"))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+
+    // Act - Part 3 - Log in
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": "octavia",
+            "password": "a-long-enough-password"
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    let user = get_user(&app, "octavia").await.unwrap();
+    assert_eq!(user.role, UserRole::Editor);
+    assert_eq!(user.email.as_deref(), Some(INVITEE));
+    assert!(user.password_hash.starts_with("$argon2id$"));
+}
+
+#[tokio::test]
+async fn an_invitation_can_only_be_accepted_once() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let token = invite(&app, INVITEE, "viewer").await;
+    app.post_accept_invitation(&accept_form(&token, "octavia"))
+        .await;
+
+    // Act
+    let response = app
+        .post_accept_invitation(&accept_form(&token, "octavia-again"))
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 401);
+    assert!(get_user(&app, "octavia-again").await.is_none());
+}
+
+#[tokio::test]
+async fn an_expired_invitation_returns_a_410() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let token = invite(&app, INVITEE, "viewer").await;
+    UserInvitations::update_many()
+        .col_expr(
+            user_invitations::Column::ExpiresAt,
+            Expr::value(OffsetDateTime::now_utc() - Duration::minutes(1)),
+        )
+        .filter(user_invitations::Column::Token.eq(&token))
+        .exec(&app.dp_pool)
+        .await
+        .unwrap();
+
+    // Act
+    let response = app
+        .post_accept_invitation(&accept_form(&token, "octavia"))
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 410);
+    assert!(get_user(&app, "octavia").await.is_none());
+}
+
+#[tokio::test]
+async fn an_unknown_invitation_returns_a_401() {
+    // Arrange
+    let app = spawn_app().await;
+    let token = Uuid::new_v4().to_string();
+
+    // Act
+    let response = app
+        .post_accept_invitation(&accept_form(&token, "octavia"))
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn usernames_must_be_valid_and_unique() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let token = invite(&app, INVITEE, "viewer").await;
+
+    // Act - Part 1 - Taken
+    let response = app
+        .post_accept_invitation(&accept_form(&token, &app.test_user.username))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert!(response.text().await.unwrap().contains(&format!(
+        "There already is a user called {}.",
+        app.test_user.username
+    )));
+
+    // Act - Part 2 - Invalid
+    let response = app
+        .post_accept_invitation(&accept_form(&token, "octavia butler"))
+        .await;
+    assert_eq!(response.status().as_u16(), 400);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("octavia butler is not a valid username."));
+
+    // Assert - The invitation can still be used
+    let response = app
+        .post_accept_invitation(&accept_form(&token, "octavia"))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn the_passwords_must_match() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let token = invite(&app, INVITEE, "viewer").await;
+
+    // Act
+    let response = app
+        .post_accept_invitation(&[
+            ("token", token.as_str()),
+            ("username", "octavia"),
+            ("password", "a-long-enough-password"),
+            ("password_check", "another-password"),
+        ])
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("You entered two different passwords - the field values must match."));
+    assert!(get_user(&app, "octavia").await.is_none());
+}
+
+#[tokio::test]
+async fn an_address_can_only_be_invited_until_it_has_joined() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let token = invite(&app, INVITEE, "viewer").await;
+    app.post_accept_invitation(&accept_form(&token, "octavia"))
+        .await;
+
+    // Act
+    app.post_invite_user(INVITEE, "editor").await;
+
+    // Assert
+    let html_page = app.get_users_html().await;
+    assert!(html_page.contains(&format!("There already is a user with {}.", INVITEE)));
+}
+
+#[tokio::test]
+async fn invalid_addresses_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+
+    // Act
+    app.post_invite_user("not-an-address", "viewer").await;
+
+    // Assert
+    let html_page = app.get_users_html().await;
+    assert!(html_page.contains("not-an-address is not a valid email address."));
+    assert!(UserInvitations::find()
+        .one(&app.dp_pool)
+        .await
+        .unwrap()
+        .is_none());
+}
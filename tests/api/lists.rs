@@ -0,0 +1,274 @@
+use std::collections::HashSet;
+
+use entity::{
+    sea_orm_active_enums::SubscriptionStatus,
+    subscriptions::{self, Entity as Subscriptions},
+};
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use uuid::Uuid;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+use zero2prod::lists::{find_list, find_membership};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, ConfirmationLinks, TestApp};
+
+/// Subscribes an address to a list, returns the links of the confirmation
+/// email.
+async fn subscribe(app: &TestApp, email: &str, list: &str) -> ConfirmationLinks {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    app.post_subscriptions(format!(
+        "name=le%20guin&email={}&list={}",
+        urlencoding::encode(email),
+        list
+    ))
+    .await
+    .error_for_status()
+    .unwrap();
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    app.get_confirmation_links(&email_request)
+}
+
+async fn subscribe_and_confirm(app: &TestApp, email: &str, list: &str) {
+    let confirmation_links = subscribe(app, email, list).await;
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+}
+
+async fn membership_status(app: &TestApp, email: &str, list: &str) -> Option<SubscriptionStatus> {
+    let subscriber = Subscriptions::find()
+        .filter(subscriptions::Column::Email.eq(email))
+        .one(&app.dp_pool)
+        .await
+        .unwrap()?;
+    let list = find_list(&app.dp_pool, list).await.unwrap().unwrap();
+    find_membership(&app.dp_pool, list.id, subscriber.id)
+        .await
+        .unwrap()
+        .map(|membership| membership.status)
+}
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_create_a_list() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.post_list("release-notes", "Release notes").await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+    assert!(find_list(&app.dp_pool, "release-notes")
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn admins_can_create_lists() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+
+    // Act - Part 1 - Create a list
+    let response = app.post_list("release-notes", "Release notes").await;
+    assert_is_redirect_to(&response, "/admin/lists");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_lists_html().await;
+    assert!(html_page.contains("The list release-notes has been created."));
+    assert!(html_page.contains("Release notes"));
+    assert!(html_page.contains("Newsletter (default)"));
+
+    // Act - Part 3 - Create it again
+    app.post_list("release-notes", "Other release notes").await;
+    let html_page = app.get_lists_html().await;
+    assert!(html_page.contains("There already is a list called release-notes."));
+
+    // Act - Part 4 - Use an invalid identifier
+    app.post_list("Release Notes", "Release notes").await;
+    let html_page = app.get_lists_html().await;
+    assert!(html_page.contains("Release Notes is not a valid list identifier."));
+}
+
+#[tokio::test]
+async fn subscribing_to_an_unknown_list_returns_a_400() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app
+        .post_subscriptions("name=le%20guin&email=ursula_le_guin%40gmail.com&list=missing".into())
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+    assert!(Subscriptions::find()
+        .one(&app.dp_pool)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn every_list_is_confirmed_separately() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    app.post_list("release-notes", "Release notes").await;
+    let email = "ursula_le_guin@gmail.com";
+    subscribe_and_confirm(&app, email, "newsletter").await;
+
+    // Act - Part 1 - Join a second list
+    let confirmation_links = subscribe(&app, email, "release-notes").await;
+    assert_eq!(
+        membership_status(&app, email, "release-notes").await,
+        Some(SubscriptionStatus::PendingConfirmation)
+    );
+    assert_eq!(
+        membership_status(&app, email, "newsletter").await,
+        Some(SubscriptionStatus::Confirmed)
+    );
+
+    // Act - Part 2 - Confirm it
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // Assert
+    assert_eq!(
+        membership_status(&app, email, "release-notes").await,
+        Some(SubscriptionStatus::Confirmed)
+    );
+}
+
+#[tokio::test]
+async fn the_confirmation_email_names_the_list() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    app.post_list("release-notes", "Release notes").await;
+
+    // Act
+    subscribe(&app, "ursula_le_guin@gmail.com", "release-notes").await;
+
+    // Assert
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert!(body["text"]
+        .as_str()
+        .unwrap()
+        .contains("confirm your subscription to Release notes."));
+}
+
+#[tokio::test]
+async fn issues_are_only_delivered_to_the_chosen_lists() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    app.post_list("release-notes", "Release notes").await;
+    app.post_list("events", "Events").await;
+    subscribe_and_confirm(&app, "newsletter-only@example.com", "newsletter").await;
+    subscribe_and_confirm(&app, "release-notes@example.com", "release-notes").await;
+    subscribe_and_confirm(&app, "both@example.com", "release-notes").await;
+    subscribe_and_confirm(&app, "both@example.com", "events").await;
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+            {"error_code": 0, "message": "OK"},
+            {"error_code": 0, "message": "OK"},
+        ])))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_newsletters([
+            ("title", "Newsletter title"),
+            ("content_txt", "Newsletter body as plain text"),
+            ("content_html", "<p>Newsletter body as HTML</p>"),
+            ("idempotency_key", &Uuid::new_v4().to_string()),
+            ("lists", "release-notes"),
+            ("lists", "events"),
+        ])
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let batch_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let batch: serde_json::Value = serde_json::from_slice(&batch_request.body).unwrap();
+    let recipients: HashSet<_> = batch
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|email| email["to"][0]["email"].as_str().unwrap().to_owned())
+        .collect();
+    assert_eq!(
+        recipients,
+        HashSet::from([
+            "release-notes@example.com".to_owned(),
+            "both@example.com".to_owned()
+        ])
+    );
+}
+
+#[tokio::test]
+async fn publishing_to_an_unknown_list_returns_a_400() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+
+    // Act
+    let response = app
+        .post_newsletters([
+            ("title", "Newsletter title"),
+            ("content_txt", "Newsletter body as plain text"),
+            ("content_html", "<p>Newsletter body as HTML</p>"),
+            ("idempotency_key", &Uuid::new_v4().to_string()),
+            ("lists", "missing"),
+        ])
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn the_newsletter_form_offers_every_list() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    app.post_list("release-notes", "Release notes").await;
+
+    // Act
+    let html_page = app.get_newsletter_html().await;
+
+    // Assert
+    assert!(html_page.contains(r#"name="lists" value="newsletter" checked"#));
+    assert!(html_page.contains(r#"name="lists" value="release-notes" "#));
+}
@@ -0,0 +1,241 @@
+use entity::audit_events::{self, Entity as AuditEvents};
+use sea_orm::{ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app};
+
+const MAX_FAILURES_PER_USERNAME: usize = 5;
+const MAX_FAILURES_PER_IP: usize = 20;
+
+#[tokio::test]
+async fn a_username_is_locked_out_after_too_many_failed_logins() {
+    // Arrange
+    let app = spawn_app().await;
+    let wrong_password = serde_json::json!({
+        "username": &app.test_user.username,
+        "password": "wrong-password"
+    });
+    for _ in 0..MAX_FAILURES_PER_USERNAME {
+        app.post_login(&wrong_password).await;
+    }
+
+    // Act
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(html_page
+        .contains("<p><i>Too many failed login attempts, please try again in 15 minutes.</i></p>"));
+}
+
+#[tokio::test]
+async fn the_lockout_is_shown_on_the_failed_login_that_triggers_it() {
+    // Arrange
+    let app = spawn_app().await;
+    let wrong_password = serde_json::json!({
+        "username": &app.test_user.username,
+        "password": "wrong-password"
+    });
+    for _ in 0..MAX_FAILURES_PER_USERNAME - 1 {
+        app.post_login(&wrong_password).await;
+        let html_page = app.get_login_html().await;
+        assert!(html_page.contains("<p><i>Authentication failed</i></p>"));
+    }
+
+    // Act
+    app.post_login(&wrong_password).await;
+
+    // Assert
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("Too many failed login attempts"));
+}
+
+#[tokio::test]
+async fn a_lockout_is_recorded_in_the_audit_log() {
+    // Arrange
+    let app = spawn_app().await;
+    let wrong_password = serde_json::json!({
+        "username": &app.test_user.username,
+        "password": "wrong-password"
+    });
+
+    // Act
+    for _ in 0..MAX_FAILURES_PER_USERNAME {
+        app.post_login_from(&wrong_password, "203.0.113.7").await;
+    }
+
+    // Assert
+    let event = AuditEvents::find()
+        .filter(audit_events::Column::Kind.eq("login_lockout"))
+        .one(&app.dp_pool)
+        .await
+        .unwrap()
+        .expect("The lockout was not recorded.");
+    assert_eq!(
+        event.username.as_deref(),
+        Some(app.test_user.username.as_str())
+    );
+    assert_eq!(event.ip_address.as_deref(), Some("203.0.113.7"));
+    assert_eq!(
+        event.details.as_deref(),
+        Some("5 failed attempts for the username")
+    );
+}
+
+#[tokio::test]
+async fn failed_logins_below_the_limit_are_not_audited() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    for _ in 0..MAX_FAILURES_PER_USERNAME - 1 {
+        app.post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": "wrong-password"
+        }))
+        .await;
+    }
+
+    // Assert
+    assert_eq!(AuditEvents::find().count(&app.dp_pool).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn a_successful_login_resets_the_failures_of_the_username() {
+    // Arrange
+    let app = spawn_app().await;
+    let wrong_password = serde_json::json!({
+        "username": &app.test_user.username,
+        "password": "wrong-password"
+    });
+    let right_password = serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password
+    });
+    for _ in 0..MAX_FAILURES_PER_USERNAME - 1 {
+        app.post_login(&wrong_password).await;
+    }
+    let response = app.post_login(&right_password).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    app.post_logout().await;
+
+    // Act
+    for _ in 0..MAX_FAILURES_PER_USERNAME - 1 {
+        app.post_login(&wrong_password).await;
+    }
+    let response = app.post_login(&right_password).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn an_address_trying_many_usernames_is_locked_out() {
+    // Arrange
+    let app = spawn_app().await;
+    for i in 0..MAX_FAILURES_PER_IP {
+        let body = serde_json::json!({
+            "username": format!("user-{}", i),
+            "password": "wrong-password"
+        });
+        app.post_login_from(&body, "203.0.113.7").await;
+    }
+    let right_password = serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password
+    });
+
+    // Act - Part 1 - Log in from the locked out address
+    let response = app.post_login_from(&right_password, "203.0.113.7").await;
+
+    // Assert - Part 1
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("Too many failed login attempts"));
+
+    // Act - Part 2 - Log in from another address
+    let response = app.post_login_from(&right_password, "198.51.100.1").await;
+
+    // Assert - Part 2
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn a_made_up_forwarded_address_does_not_lift_the_lockout() {
+    // Arrange
+    let app = spawn_app().await;
+    for i in 0..MAX_FAILURES_PER_IP {
+        let body = serde_json::json!({
+            "username": format!("user-{}", i),
+            "password": "wrong-password"
+        });
+        app.post_login_from(&body, "203.0.113.7").await;
+    }
+
+    // Act - The client puts another address in front of its own
+    let response = app
+        .post_login_from(
+            &serde_json::json!({
+                "username": &app.test_user.username,
+                "password": &app.test_user.password
+            }),
+            "198.51.100.1, 203.0.113.7",
+        )
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("Too many failed login attempts"));
+}
+
+#[tokio::test]
+async fn an_attempt_can_lock_out_the_username_and_the_address_at_once() {
+    // Arrange
+    let app = spawn_app().await;
+    for i in 0..MAX_FAILURES_PER_IP - MAX_FAILURES_PER_USERNAME {
+        let body = serde_json::json!({
+            "username": format!("user-{}", i),
+            "password": "wrong-password"
+        });
+        app.post_login_from(&body, "203.0.113.7").await;
+    }
+    let wrong_password = serde_json::json!({
+        "username": &app.test_user.username,
+        "password": "wrong-password"
+    });
+
+    // Act - Part 1 - The last attempt reaches both limits
+    for _ in 0..MAX_FAILURES_PER_USERNAME {
+        app.post_login_from(&wrong_password, "203.0.113.7").await;
+    }
+
+    // Assert - Part 1
+    let n_lockouts = AuditEvents::find()
+        .filter(audit_events::Column::Kind.eq("login_lockout"))
+        .count(&app.dp_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_lockouts, 2);
+
+    // Act - Part 2 - The address cannot move on to another username
+    let response = app
+        .post_login_from(
+            &serde_json::json!({
+                "username": "someone-else",
+                "password": "wrong-password"
+            }),
+            "203.0.113.7",
+        )
+        .await;
+
+    // Assert - Part 2
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("Too many failed login attempts"));
+}
@@ -1,8 +1,23 @@
 mod admin_dashboard;
+mod admin_subscribers;
+mod admin_subscribers_csv;
 mod change_password;
+mod consent;
 mod health_check;
 mod helpers;
+mod invitations;
+mod lists;
 mod login;
+mod login_throttle;
 mod newsletter;
+mod newsletter_history;
+mod newsletter_scheduling;
+mod password_reset;
+mod preferences;
+mod subscription_sweeper;
 mod subscriptions;
 mod subscriptions_confirm;
+mod subscriptions_data;
+mod subscriptions_unsubscribe;
+mod two_factor;
+mod users;
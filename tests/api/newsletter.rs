@@ -1,7 +1,16 @@
+use std::time::Duration;
+
+use entity::{
+    issue_delivery_queue::Entity as IssueDeliveryQueue,
+    newsletter_issues::Entity as NewsletterIssues,
+};
+use sea_orm::{EntityTrait, PaginatorTrait};
+use time::OffsetDateTime;
+use uuid::Uuid;
 use wiremock::matchers::{any, method, path};
 use wiremock::{Mock, ResponseTemplate};
 
-use crate::helpers::{assert_is_redirect_to, spawn_app, ConfirmationLinks, TestApp};
+use crate::helpers::{self, assert_is_redirect_to, spawn_app, ConfirmationLinks, TestApp};
 
 #[tokio::test]
 async fn you_must_be_logged_in_to_access_the_newsletter() {
@@ -34,11 +43,13 @@ async fn newsletter_are_not_delivered_to_unconfirmed_subscribers() {
         "title" : "Newsletter title",
         "content_txt": "Newsletter body as plain text",
         "content_html": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string(),
     });
     let response = app.post_newsletters(newsletter_request_body).await;
 
     // Assert
-    assert_eq!(response.status().as_u16(), 200);
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
     // Mock verifies on Drop that we haven't sent the newsletter email
 }
 
@@ -60,11 +71,13 @@ async fn newsletters_are_delivered_to_confirmed_subscribers() {
         "title" : "Newsletter title",
         "content_txt": "Newsletter body as plain text",
         "content_html": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string(),
     });
     let response = app.post_newsletters(newsletter_request_body).await;
 
     // Assert
-    assert_eq!(response.status().as_u16(), 200);
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
     // Mock verifies on Drop that we have sent the newsletter email
 }
 
@@ -81,13 +94,6 @@ async fn newsletters_returns_400_for_invalid_data() {
             }),
             "missing title",
         ),
-        (
-            serde_json::json!({
-                "title": "Newsletter!",
-                "content_html": "<p>Newsletter body as HTML</p>",
-            }),
-            "missing content_txt",
-        ),
         (
             serde_json::json!({
                 "title": "Newsletter!",
@@ -118,7 +124,7 @@ async fn you_must_be_logged_in_to_post_the_newsletter() {
         .redirect(reqwest::redirect::Policy::none())
         .build()
         .unwrap()
-        .post(&format!("{}/admin/newsletters", &app.address))
+        .post(format!("{}/admin/newsletters", &app.address))
         .json(&serde_json::json!({
             "title" : "Newsletter title",
             "content_txt": "Newsletter body as plain text",
@@ -132,6 +138,516 @@ async fn you_must_be_logged_in_to_post_the_newsletter() {
     assert_is_redirect_to(&response, "/login");
 }
 
+#[tokio::test]
+async fn newsletters_returns_400_for_an_invalid_idempotency_key() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let test_cases = vec![
+        ("".to_string(), "empty key"),
+        ("a".repeat(50), "too long key"),
+    ];
+    for (idempotency_key, description) in test_cases {
+        // Act
+        let response = app
+            .post_newsletters(serde_json::json!({
+                "title": "Newsletter title",
+                "content_txt": "Newsletter body as plain text",
+                "content_html": "<p>Newsletter body as HTML</p>",
+                "idempotency_key": idempotency_key,
+            }))
+            .await;
+
+        // Assert
+        assert_eq!(
+            400,
+            response.status().as_u16(),
+            "The API did not fail with 400 Bad Request when the payload had an {}.",
+            description
+        );
+    }
+}
+
+#[tokio::test]
+async fn newsletter_creation_is_idempotent() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Submit newsletter form
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "content_txt": "Newsletter body as plain text",
+        "content_html": "<p>Newsletter body as HTML</p>",
+        // We expect the idempotency key as part of the
+        // form data, not as an header
+        "idempotency_key": Uuid::new_v4().to_string()
+    });
+    let response = app.post_newsletters(newsletter_request_body.clone()).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = app.get_newsletter_html().await;
+    assert!(html_page.contains("<p><i>The newsletter issue has been published!</i></p>"));
+
+    // Act - Part 3 - Submit newsletter form **again**
+    let response = app.post_newsletters(newsletter_request_body).await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    // Act - Part 4 - Follow the redirect
+    let html_page = app.get_newsletter_html().await;
+    assert!(html_page.contains("<p><i>The newsletter issue has been published!</i></p>"));
+
+    app.dispatch_all_pending_emails().await;
+    // Mock verifies on Drop that we have sent the newsletter email **once**
+}
+
+#[tokio::test]
+async fn concurrent_form_submission_is_handled_gracefully() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        // Setting a long delay to ensure that the second request
+        // arrives before the first one completes
+        .respond_with(ResponseTemplate::new(200).set_delay(Duration::from_secs(2)))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Submit two newsletter forms concurrently
+    let newsletter_request_body = serde_json::json!({
+        "title": "Newsletter title",
+        "content_txt": "Newsletter body as plain text",
+        "content_html": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string()
+    });
+    let response1 = app.post_newsletters(newsletter_request_body.clone());
+    let response2 = app.post_newsletters(newsletter_request_body);
+    let (response1, response2) = tokio::join!(response1, response2);
+
+    assert_eq!(response1.status(), response2.status());
+    assert_eq!(
+        response1.headers().get("Location"),
+        response2.headers().get("Location")
+    );
+    assert_eq!(
+        response1.text().await.unwrap(),
+        response2.text().await.unwrap()
+    );
+
+    app.dispatch_all_pending_emails().await;
+    // Mock verifies on Drop that we have sent the newsletter email **once**
+}
+
+#[tokio::test]
+async fn publishing_does_not_wait_for_the_delivery() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    let n_sent_emails = app.email_server.received_requests().await.unwrap().len();
+
+    // Act - Part 1 - Publish the issue
+    let response = app
+        .post_newsletters(serde_json::json!({
+            "title": "Newsletter title",
+            "content_txt": "Newsletter body as plain text",
+            "content_html": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": Uuid::new_v4().to_string()
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+
+    // Assert - The delivery has been queued, not performed
+    let n_pending_tasks = IssueDeliveryQueue::find()
+        .count(&app.dp_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_pending_tasks, 1);
+    assert_eq!(
+        app.email_server.received_requests().await.unwrap().len(),
+        n_sent_emails
+    );
+
+    // Act - Part 2 - Let the worker drain the queue
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let n_pending_tasks = IssueDeliveryQueue::find()
+        .count(&app.dp_pool)
+        .await
+        .unwrap();
+    assert_eq!(n_pending_tasks, 0);
+    let issue = NewsletterIssues::find()
+        .one(&app.dp_pool)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(issue.n_recipients, 1);
+    assert_eq!(issue.n_delivered, 1);
+    assert_eq!(issue.n_failed, 0);
+    // Mock verifies on Drop that we have sent the newsletter email
+}
+
+#[tokio::test]
+async fn failed_deliveries_are_retried_later() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_newsletters(serde_json::json!({
+            "title": "Newsletter title",
+            "content_txt": "Newsletter body as plain text",
+            "content_html": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": Uuid::new_v4().to_string()
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let task = IssueDeliveryQueue::find()
+        .one(&app.dp_pool)
+        .await
+        .unwrap()
+        .expect("The failed delivery task should still be queued.");
+    assert_eq!(task.n_retries, 1);
+    assert!(task.execute_after > OffsetDateTime::now_utc());
+    // Mock verifies on Drop that the retry has not been attempted right away
+}
+
+#[tokio::test]
+async fn rejected_deliveries_are_not_retried() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(422))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.post_newsletters(serde_json::json!({
+        "title": "Newsletter title",
+        "content_txt": "Newsletter body as plain text",
+        "content_html": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string()
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let issue = NewsletterIssues::find()
+        .one(&app.dp_pool)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(issue.n_failed, 1);
+    assert!(IssueDeliveryQueue::find()
+        .one(&app.dp_pool)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn newsletter_emails_carry_an_unsubscribe_link() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.post_newsletters(serde_json::json!({
+        "title": "Newsletter title",
+        "content_txt": "Newsletter body as plain text",
+        "content_html": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string()
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    let headers = body["headers"].as_array().unwrap();
+    let header = |name: &str| {
+        headers
+            .iter()
+            .find(|h| h["name"] == name)
+            .and_then(|h| h["value"].as_str())
+            .unwrap()
+            .to_owned()
+    };
+    let list_unsubscribe = header("List-Unsubscribe");
+    let unsubscribe_url = list_unsubscribe
+        .trim_start_matches('<')
+        .trim_end_matches('>');
+    assert!(unsubscribe_url.contains("/subscriptions/unsubscribe?"));
+    assert_eq!(
+        header("List-Unsubscribe-Post"),
+        "List-Unsubscribe=One-Click"
+    );
+    assert!(body["html"].as_str().unwrap().contains(unsubscribe_url));
+    assert!(body["text"].as_str().unwrap().contains(unsubscribe_url));
+}
+
+#[tokio::test]
+async fn newsletters_are_sent_in_batches() {
+    // Arrange
+    let app = spawn_app().await;
+    for _ in 0..3 {
+        helpers::create_confirmed_subscriber(&app).await;
+    }
+    app.login().await;
+
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+            {"error_code": 0, "message": "OK"},
+            {"error_code": 0, "message": "OK"},
+            {"error_code": 0, "message": "OK"},
+        ])))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.post_newsletters(serde_json::json!({
+        "title": "Newsletter title",
+        "content_txt": "Newsletter body as plain text",
+        "content_html": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string()
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let issue = NewsletterIssues::find()
+        .one(&app.dp_pool)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(issue.n_delivered, 3);
+    // Mock verifies on Drop that the three emails went out in a single request
+}
+
+#[tokio::test]
+async fn a_rejected_recipient_does_not_fail_the_whole_batch() {
+    // Arrange
+    let app = spawn_app().await;
+    for _ in 0..2 {
+        helpers::create_confirmed_subscriber(&app).await;
+    }
+    app.login().await;
+
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+            {"error_code": 0, "message": "OK"},
+            {"error_code": 406, "message": "Inactive recipient"},
+        ])))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.post_newsletters(serde_json::json!({
+        "title": "Newsletter title",
+        "content_txt": "Newsletter body as plain text",
+        "content_html": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string()
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let issue = NewsletterIssues::find()
+        .one(&app.dp_pool)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(issue.n_delivered, 1);
+    // A rejected recipient is not retried.
+    assert_eq!(issue.n_failed, 1);
+    assert!(IssueDeliveryQueue::find()
+        .one(&app.dp_pool)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn newsletter_content_is_personalised_for_every_subscriber() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = helpers::create_confirmed_subscriber(&app).await;
+    app.login().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.post_newsletters(serde_json::json!({
+        "title": "Newsletter title",
+        "content_txt": "Hi {{name}}! Leave at {{unsubscribe_url}}",
+        "content_html": "<p>Hi {{name}}!</p>",
+        "idempotency_key": Uuid::new_v4().to_string()
+    }))
+    .await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    let unsubscribe_url = app.link_signer.unsubscribe_url(subscriber_id);
+    assert!(body["html"]
+        .as_str()
+        .unwrap()
+        .contains("<p>Hi le guin!</p>"));
+    assert!(body["text"]
+        .as_str()
+        .unwrap()
+        .contains(&format!("Hi le guin! Leave at {}", unsubscribe_url)));
+}
+
+#[tokio::test]
+async fn the_text_part_is_generated_from_the_html_when_left_empty() {
+    // Arrange
+    let app = spawn_app().await;
+    helpers::create_confirmed_subscriber(&app).await;
+    app.login().await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_newsletters(serde_json::json!({
+            "title": "Newsletter title",
+            "content_html": "<h1>Big news</h1><p>Read <a href=\"https://example.com\">more</a>.</p>",
+            "idempotency_key": Uuid::new_v4().to_string()
+        }))
+        .await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert!(body["text"]
+        .as_str()
+        .unwrap()
+        .starts_with("Big news\n\nRead more (https://example.com)."));
+}
+
+#[tokio::test]
+async fn newsletters_with_invalid_template_syntax_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let test_cases = vec![
+        (
+            serde_json::json!({
+                "title": "Newsletter!",
+                "content_txt": "Hi {{name}}",
+                "content_html": "<p>Hi {{name}</p>",
+                "idempotency_key": Uuid::new_v4().to_string()
+            }),
+            "invalid HTML content",
+        ),
+        (
+            serde_json::json!({
+                "title": "Newsletter!",
+                "content_txt": "{{#if name}}Hi",
+                "content_html": "<p>Hi {{name}}</p>",
+                "idempotency_key": Uuid::new_v4().to_string()
+            }),
+            "invalid text content",
+        ),
+    ];
+    for (invalid_body, error_message) in test_cases {
+        // Act
+        let response = app.post_newsletters(invalid_body).await;
+
+        // Assert
+        assert_eq!(
+            400,
+            response.status().as_u16(),
+            "The API did not fail with 400 Bad Request when the payload had {}.",
+            error_message
+        );
+    }
+    let n_issues = NewsletterIssues::find().count(&app.dp_pool).await.unwrap();
+    assert_eq!(n_issues, 0);
+}
+
 async fn create_unconfirmed_subscriber(app: &TestApp) -> ConfirmationLinks {
     let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
 
@@ -0,0 +1,118 @@
+use entity::newsletter_issues::Entity as NewsletterIssues;
+use sea_orm::EntityTrait;
+use uuid::Uuid;
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_the_newsletter_history() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.get_newsletter_history().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn published_issues_are_listed_in_the_history() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+
+    // Act
+    publish_issue(&app, "My first issue").await;
+
+    // Assert
+    let html_page = app.get_newsletter_history().await.text().await.unwrap();
+    assert!(html_page.contains("My first issue"));
+    assert!(html_page.contains(&app.test_user.username));
+
+    let issue = NewsletterIssues::find()
+        .one(&app.dp_pool)
+        .await
+        .unwrap()
+        .expect("The published issue has not been persisted.");
+    assert_eq!(issue.author_id, Some(app.test_user.user_id));
+    assert_eq!(issue.n_recipients, 0);
+}
+
+#[tokio::test]
+async fn a_published_issue_can_be_previewed() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let issue_id = publish_issue(&app, "My first issue").await;
+
+    // Act
+    let response = app
+        .api_client
+        .get(format!(
+            "{}/admin/newsletters/history/{}",
+            app.address, issue_id
+        ))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("Newsletter body as plain text"));
+    assert!(html_page.contains(&format!("/archive/{}", issue_id)));
+}
+
+#[tokio::test]
+async fn the_archive_serves_the_html_body_of_a_published_issue() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let issue_id = publish_issue(&app, "My first issue").await;
+    app.logout().await;
+
+    // Act
+    let response = reqwest::get(format!("{}/archive/{}", app.address, issue_id))
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        response.text().await.unwrap(),
+        "<p>Newsletter body as HTML</p>"
+    );
+}
+
+#[tokio::test]
+async fn the_archive_returns_404_for_unknown_issues() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = reqwest::get(format!("{}/archive/{}", app.address, Uuid::new_v4()))
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+async fn publish_issue(app: &TestApp, title: &str) -> Uuid {
+    let response = app
+        .post_newsletters(serde_json::json!({
+            "title": title,
+            "content_txt": "Newsletter body as plain text",
+            "content_html": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": Uuid::new_v4().to_string()
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    NewsletterIssues::find()
+        .one(&app.dp_pool)
+        .await
+        .unwrap()
+        .unwrap()
+        .newsletter_issue_id
+}
@@ -0,0 +1,243 @@
+use entity::{
+    issue_delivery_queue::Entity as IssueDeliveryQueue,
+    newsletter_issues::{self, Entity as NewsletterIssues},
+    sea_orm_active_enums::IssueStatus,
+};
+use sea_orm::{ActiveModelTrait, EntityTrait, IntoActiveModel, PaginatorTrait, Set};
+use time::{format_description::well_known::Rfc3339, Duration, OffsetDateTime};
+use uuid::Uuid;
+use wiremock::matchers::{any, method, path};
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{assert_is_redirect_to, create_confirmed_subscriber, spawn_app, TestApp};
+
+#[tokio::test]
+async fn you_must_be_logged_in_to_see_the_scheduled_issues() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.get_scheduled_newsletters().await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn scheduled_issues_are_not_delivered_before_their_time() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = schedule_issue(&app, "Next week's issue", in_one_hour()).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.release_due_issues().await;
+    app.dispatch_all_pending_emails().await;
+    let issue = get_single_issue(&app).await;
+    assert_eq!(issue.status, IssueStatus::Scheduled);
+    assert_eq!(issue.n_recipients, 0);
+    let html_page = app.get_scheduled_newsletters_html().await;
+    assert!(html_page.contains("Next week&#x27;s issue"));
+    // Mock verifies on Drop that nothing has been sent
+}
+
+#[tokio::test]
+async fn due_issues_are_released_for_delivery() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+    schedule_issue(&app, "Scheduled issue", in_one_hour()).await;
+    make_issue_due(&app).await;
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    app.release_due_issues().await;
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let issue = get_single_issue(&app).await;
+    assert_eq!(issue.status, IssueStatus::Published);
+    assert_eq!(issue.n_recipients, 1);
+    assert_eq!(issue.n_delivered, 1);
+    // Mock verifies on Drop that the issue has been sent
+}
+
+#[tokio::test]
+async fn send_at_must_be_a_valid_time_in_the_future() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let one_hour_ago = (OffsetDateTime::now_utc() - Duration::hours(1))
+        .format(&Rfc3339)
+        .unwrap();
+    let test_cases = vec![
+        (one_hour_ago.as_str(), "a time in the past"),
+        ("next tuesday", "an invalid time"),
+    ];
+
+    for (send_at, description) in test_cases {
+        // Act
+        let response = schedule_issue(&app, "Newsletter title", send_at.to_owned()).await;
+
+        // Assert
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "The API did not fail with 400 Bad Request when send_at was {}.",
+            description
+        );
+    }
+    assert_eq!(
+        NewsletterIssues::find().count(&app.dp_pool).await.unwrap(),
+        0
+    );
+}
+
+#[tokio::test]
+async fn cancelled_issues_are_never_sent() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+    schedule_issue(&app, "Scheduled issue", in_one_hour()).await;
+    let issue_id = get_single_issue(&app).await.newsletter_issue_id;
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app.post_cancel_scheduled_newsletter(issue_id).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletters/scheduled");
+    let html_page = app.get_scheduled_newsletters_html().await;
+    assert!(html_page.contains("<p><i>The scheduled newsletter issue has been cancelled.</i></p>"));
+    make_issue_due(&app).await;
+    app.release_due_issues().await;
+    app.dispatch_all_pending_emails().await;
+    assert_eq!(get_single_issue(&app).await.status, IssueStatus::Cancelled);
+    // Mock verifies on Drop that nothing has been sent
+}
+
+#[tokio::test]
+async fn released_issues_can_no_longer_be_cancelled() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    schedule_issue(&app, "Scheduled issue", in_one_hour()).await;
+    make_issue_due(&app).await;
+    app.release_due_issues().await;
+    let issue_id = get_single_issue(&app).await.newsletter_issue_id;
+
+    // Act
+    let response = app.post_cancel_scheduled_newsletter(issue_id).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletters/scheduled");
+    let html_page = app.get_scheduled_newsletters_html().await;
+    assert!(html_page.contains("<p><i>The newsletter issue is no longer scheduled.</i></p>"));
+    assert_eq!(get_single_issue(&app).await.status, IssueStatus::Published);
+}
+
+#[tokio::test]
+async fn scheduled_issues_can_be_rescheduled() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    schedule_issue(&app, "Scheduled issue", in_one_hour()).await;
+    let issue_id = get_single_issue(&app).await.newsletter_issue_id;
+    let tomorrow = OffsetDateTime::now_utc() + Duration::days(1);
+
+    // Act
+    let response = app
+        .post_reschedule_newsletter(
+            issue_id,
+            &serde_json::json!({ "send_at": tomorrow.format(&Rfc3339).unwrap() }),
+        )
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletters/scheduled");
+    let issue = get_single_issue(&app).await;
+    assert_eq!(issue.status, IssueStatus::Scheduled);
+    assert_eq!(
+        issue.send_at.unwrap().unix_timestamp(),
+        tomorrow.unix_timestamp()
+    );
+}
+
+#[tokio::test]
+async fn scheduled_issues_are_not_in_the_public_archive() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    schedule_issue(&app, "Scheduled issue", in_one_hour()).await;
+    let issue_id = get_single_issue(&app).await.newsletter_issue_id;
+
+    // Act
+    let response = reqwest::get(format!("{}/archive/{}", app.address, issue_id))
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 404);
+}
+
+fn in_one_hour() -> String {
+    (OffsetDateTime::now_utc() + Duration::hours(1))
+        .format(&Rfc3339)
+        .unwrap()
+}
+
+async fn schedule_issue(app: &TestApp, title: &str, send_at: String) -> reqwest::Response {
+    app.post_newsletters(serde_json::json!({
+        "title": title,
+        "content_txt": "Newsletter body as plain text",
+        "content_html": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string(),
+        "send_at": send_at,
+    }))
+    .await
+}
+
+async fn get_single_issue(app: &TestApp) -> newsletter_issues::Model {
+    NewsletterIssues::find()
+        .one(&app.dp_pool)
+        .await
+        .unwrap()
+        .expect("No newsletter issue has been stored.")
+}
+
+/// Moves the issue's `send_at` into the past instead of waiting for it.
+async fn make_issue_due(app: &TestApp) {
+    let mut issue = get_single_issue(app).await.into_active_model();
+    issue.send_at = Set(Some(OffsetDateTime::now_utc() - Duration::minutes(1)));
+    issue.update(&app.dp_pool).await.unwrap();
+    assert_eq!(
+        IssueDeliveryQueue::find()
+            .count(&app.dp_pool)
+            .await
+            .unwrap(),
+        0
+    );
+}
@@ -0,0 +1,251 @@
+use entity::password_reset_tokens::{self, Entity as PasswordResetTokens};
+use sea_orm::{sea_query::Expr, ColumnTrait, EntityTrait, QueryFilter};
+use time::{Duration, OffsetDateTime};
+use wiremock::{
+    matchers::{any, method, path},
+    Mock, ResponseTemplate,
+};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+const NEW_PASSWORD: &str = "a-brand-new-password";
+
+/// Asks for a reset link for the test user and returns its token.
+async fn request_reset_token(app: &TestApp) -> String {
+    let _mock_guard = Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount_as_scoped(&app.email_server)
+        .await;
+    let response = app.post_forgot_password(&app.test_user.username).await;
+    assert_is_redirect_to(&response, "/login/forgot");
+    let email_request = app.wait_for_emails(1).await.pop().unwrap();
+    let links = app.get_confirmation_links(&email_request);
+    assert_eq!(links.html.path(), "/login/reset");
+    links
+        .html
+        .query_pairs()
+        .find(|(key, _)| key == "token")
+        .unwrap()
+        .1
+        .into_owned()
+}
+
+fn reset_form(token: &str) -> Vec<(&str, &str)> {
+    vec![
+        ("token", token),
+        ("new_password", NEW_PASSWORD),
+        ("new_password_check", NEW_PASSWORD),
+    ]
+}
+
+async fn get_forgot_password_html(app: &TestApp) -> String {
+    app.api_client
+        .get(format!("{}/login/forgot", &app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn the_reset_link_is_sent_to_the_address_of_the_user() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    request_reset_token(&app).await;
+
+    // Assert
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["to"][0]["email"], app.test_user.email);
+}
+
+#[tokio::test]
+async fn unknown_usernames_get_the_same_answer() {
+    // Arrange
+    let app = spawn_app().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Ask for an unknown user
+    let response = app.post_forgot_password("nobody").await;
+    assert_is_redirect_to(&response, "/login/forgot");
+
+    // Act - Part 2 - Follow the redirect
+    let html_page = get_forgot_password_html(&app).await;
+
+    // Assert
+    assert!(html_page.contains(
+        "If there is an account with that username, a link to reset its password \
+        has been sent to its email address."
+    ));
+}
+
+#[tokio::test]
+async fn a_reset_password_can_be_used_to_log_in() {
+    // Arrange
+    let app = spawn_app().await;
+    let token = request_reset_token(&app).await;
+
+    // Act - Part 1 - Open the link
+    let response = app
+        .api_client
+        .get(format!("{}/login/reset?token={}", &app.address, token))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Act - Part 2 - Reset the password
+    let response = app.post_reset_password(&reset_form(&token)).await;
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("Your password has been reset."));
+
+    // Act - Part 3 - The old password stops working
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+
+    // Act - Part 4 - The new one works
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": NEW_PASSWORD
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn a_reset_logs_the_user_out_everywhere() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    let token = request_reset_token(&app).await;
+
+    // Act - Part 1 - Reset from another browser
+    let response = TestApp::client()
+        .post(format!("{}/login/reset", &app.address))
+        .form(&reset_form(&token))
+        .send()
+        .await
+        .unwrap();
+    assert_is_redirect_to(&response, "/login");
+
+    // Act - Part 2 - The existing session stops working
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+
+    // Act - Part 3 - A new session works
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": NEW_PASSWORD
+    }))
+    .await;
+    let response = app.get_admin_dashboard().await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn a_reset_link_can_only_be_used_once() {
+    // Arrange
+    let app = spawn_app().await;
+    let token = request_reset_token(&app).await;
+    app.post_reset_password(&reset_form(&token)).await;
+
+    // Act
+    let response = app.post_reset_password(&reset_form(&token)).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn an_expired_reset_link_returns_a_410() {
+    // Arrange
+    let app = spawn_app().await;
+    let token = request_reset_token(&app).await;
+    PasswordResetTokens::update_many()
+        .col_expr(
+            password_reset_tokens::Column::ExpiresAt,
+            Expr::value(OffsetDateTime::now_utc() - Duration::minutes(1)),
+        )
+        .filter(password_reset_tokens::Column::UserId.eq(app.test_user.user_id))
+        .exec(&app.dp_pool)
+        .await
+        .unwrap();
+
+    // Act
+    let response = app.post_reset_password(&reset_form(&token)).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 410);
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &app.test_user.username,
+            "password": &app.test_user.password
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn the_new_passwords_must_match() {
+    // Arrange
+    let app = spawn_app().await;
+    let token = request_reset_token(&app).await;
+
+    // Act
+    let response = app
+        .post_reset_password(&[
+            ("token", token.as_str()),
+            ("new_password", NEW_PASSWORD),
+            ("new_password_check", "another-password"),
+        ])
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("You entered two different new passwords - the field values must match."));
+    let response = app.post_reset_password(&reset_form(&token)).await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn reset_tokens_are_not_stored_in_plaintext() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let token = request_reset_token(&app).await;
+
+    // Assert
+    let stored = PasswordResetTokens::find()
+        .filter(password_reset_tokens::Column::UserId.eq(app.test_user.user_id))
+        .one(&app.dp_pool)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_ne!(stored.token_hash, token);
+}
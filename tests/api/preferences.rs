@@ -0,0 +1,625 @@
+use entity::{
+    consent_events::{self, Entity as ConsentEvents},
+    email_change_requests::{self, Entity as EmailChangeRequests},
+    issue_delivery_queue::{self, Entity as IssueDeliveryQueue},
+    sea_orm_active_enums::{DigestFrequency, SubscriptionStatus},
+    subscriptions::{self, Entity as Subscriptions},
+};
+use sea_orm::{
+    sea_query::Expr, ActiveModelTrait, ColumnTrait, EntityTrait, PaginatorTrait, QueryFilter,
+    QueryOrder, Set,
+};
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+use wiremock::{
+    matchers::{any, method, path},
+    Mock, ResponseTemplate,
+};
+use zero2prod::lists::{find_list, find_membership};
+
+use crate::helpers::{assert_is_redirect_to, create_confirmed_subscriber, spawn_app, TestApp};
+
+async fn get_subscriber(app: &TestApp, subscriber_id: Uuid) -> subscriptions::Model {
+    Subscriptions::find_by_id(subscriber_id)
+        .one(&app.dp_pool)
+        .await
+        .unwrap()
+        .unwrap()
+}
+
+async fn membership_status(
+    app: &TestApp,
+    subscriber_id: Uuid,
+    list: &str,
+) -> Option<SubscriptionStatus> {
+    let list = find_list(&app.dp_pool, list).await.unwrap().unwrap();
+    find_membership(&app.dp_pool, list.id, subscriber_id)
+        .await
+        .unwrap()
+        .map(|membership| membership.status)
+}
+
+/// The form as it is submitted when only the given fields are changed.
+fn preferences_form<'a>(
+    subscriber: &'a subscriptions::Model,
+    changes: &[(&'a str, &'a str)],
+) -> Vec<(&'a str, &'a str)> {
+    let mut form = vec![
+        ("name", subscriber.name.as_str()),
+        ("email", subscriber.email.as_str()),
+        ("lists", "newsletter"),
+        ("frequency", "immediately"),
+    ];
+    for (field, value) in changes {
+        form.retain(|(name, _)| name != field);
+        form.push((field, value));
+    }
+    form
+}
+
+#[tokio::test]
+async fn preferences_links_with_an_invalid_signature_are_rejected_with_a_401() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = create_confirmed_subscriber(&app).await;
+    let subscriber = get_subscriber(&app, subscriber_id).await;
+    let mut preferences_link = app.get_preferences_link(subscriber_id);
+    preferences_link.set_query(Some(&format!(
+        "subscriber_id={}&signature=deadbeef",
+        subscriber_id
+    )));
+
+    // Act
+    let get_response = reqwest::get(preferences_link.clone()).await.unwrap();
+    let post_response = reqwest::Client::new()
+        .post(preferences_link)
+        .form(&preferences_form(&subscriber, &[("name", "Ursula")]))
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(get_response.status().as_u16(), 401);
+    assert_eq!(post_response.status().as_u16(), 401);
+    assert_eq!(get_subscriber(&app, subscriber_id).await.name, "le guin");
+}
+
+#[tokio::test]
+async fn the_preferences_page_shows_the_current_preferences() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    app.post_list("release-notes", "Release notes").await;
+    let subscriber_id = create_confirmed_subscriber(&app).await;
+    let subscriber = get_subscriber(&app, subscriber_id).await;
+
+    // Act
+    let response = reqwest::get(app.get_preferences_link(subscriber_id))
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains(r#"name="name" value="le guin""#));
+    assert!(html_page.contains(&format!(r#"name="email" value="{}""#, subscriber.email)));
+    assert!(html_page.contains(r#"name="lists" value="newsletter" checked"#));
+    assert!(html_page.contains(r#"name="lists" value="release-notes" >"#));
+    assert!(html_page.contains(r#"name="frequency" value="immediately" checked"#));
+}
+
+#[tokio::test]
+async fn subscribers_can_change_their_name_and_digest_frequency() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = create_confirmed_subscriber(&app).await;
+    let subscriber = get_subscriber(&app, subscriber_id).await;
+
+    // Act
+    let response = app
+        .post_preferences(
+            subscriber_id,
+            &preferences_form(&subscriber, &[("name", "Ursula"), ("frequency", "weekly")]),
+        )
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains("Your preferences have been saved."));
+    assert!(html_page.contains(r#"name="frequency" value="weekly" checked"#));
+    let subscriber = get_subscriber(&app, subscriber_id).await;
+    assert_eq!(subscriber.name, "Ursula");
+    assert_eq!(subscriber.digest_frequency, DigestFrequency::Weekly);
+}
+
+#[tokio::test]
+async fn invalid_preferences_are_rejected_with_a_400() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = create_confirmed_subscriber(&app).await;
+    let subscriber = get_subscriber(&app, subscriber_id).await;
+    let test_cases = vec![
+        (vec![("name", "<script>")], "an invalid name"),
+        (vec![("email", "not-an-email")], "an invalid email"),
+        (vec![("lists", "missing")], "an unknown list"),
+    ];
+
+    for (changes, description) in test_cases {
+        // Act
+        let response = app
+            .post_preferences(subscriber_id, &preferences_form(&subscriber, &changes))
+            .await;
+
+        // Assert
+        assert_eq!(
+            response.status().as_u16(),
+            400,
+            "The API did not fail with 400 Bad Request when the form had {}.",
+            description
+        );
+    }
+    assert_eq!(get_subscriber(&app, subscriber_id).await, subscriber);
+}
+
+#[tokio::test]
+async fn subscribers_can_switch_lists() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    app.post_list("release-notes", "Release notes").await;
+    let subscriber_id = create_confirmed_subscriber(&app).await;
+    let subscriber = get_subscriber(&app, subscriber_id).await;
+
+    // Act
+    app.post_preferences(
+        subscriber_id,
+        &preferences_form(&subscriber, &[("lists", "release-notes")]),
+    )
+    .await
+    .error_for_status()
+    .unwrap();
+
+    // Assert
+    assert_eq!(
+        membership_status(&app, subscriber_id, "newsletter").await,
+        Some(SubscriptionStatus::Unsubscribed)
+    );
+    assert_eq!(
+        membership_status(&app, subscriber_id, "release-notes").await,
+        Some(SubscriptionStatus::Confirmed)
+    );
+    assert_eq!(
+        get_subscriber(&app, subscriber_id).await.status,
+        SubscriptionStatus::Confirmed
+    );
+}
+
+#[tokio::test]
+async fn leaving_every_list_unsubscribes() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = create_confirmed_subscriber(&app).await;
+    let subscriber = get_subscriber(&app, subscriber_id).await;
+    let mut form = preferences_form(&subscriber, &[]);
+    form.retain(|(name, _)| *name != "lists");
+
+    // Act
+    app.post_preferences(subscriber_id, &form)
+        .await
+        .error_for_status()
+        .unwrap();
+
+    // Assert
+    assert_eq!(
+        get_subscriber(&app, subscriber_id).await.status,
+        SubscriptionStatus::Unsubscribed
+    );
+    assert_eq!(
+        membership_status(&app, subscriber_id, "newsletter").await,
+        Some(SubscriptionStatus::Unsubscribed)
+    );
+    let event = ConsentEvents::find()
+        .filter(consent_events::Column::SubscriberId.eq(subscriber_id))
+        .filter(consent_events::Column::Kind.eq("unsubscribed"))
+        .one(&app.dp_pool)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(event.source.as_deref(), Some("preferences"));
+}
+
+#[tokio::test]
+async fn a_new_address_is_only_used_once_it_is_confirmed() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = create_confirmed_subscriber(&app).await;
+    let subscriber = get_subscriber(&app, subscriber_id).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Ask for the change
+    let response = app
+        .post_preferences(
+            subscriber_id,
+            &preferences_form(&subscriber, &[("email", "ursula@example.com")]),
+        )
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("We sent a confirmation link to ursula@example.com."));
+    assert_eq!(
+        get_subscriber(&app, subscriber_id).await.email,
+        subscriber.email
+    );
+
+    // Act - Part 2 - Follow the link sent to the new address
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&email_request.body).unwrap();
+    assert_eq!(body["to"][0]["email"], "ursula@example.com");
+    let links = app.get_confirmation_links(&email_request);
+    assert_eq!(links.html.path(), "/preferences/email");
+    let response = reqwest::get(links.html.clone()).await.unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        get_subscriber(&app, subscriber_id).await.email,
+        "ursula@example.com"
+    );
+    // The link can only be used once
+    let response = reqwest::get(links.html).await.unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn the_old_address_is_notified_of_the_change() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = create_confirmed_subscriber(&app).await;
+    let subscriber = get_subscriber(&app, subscriber_id).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+    app.post_preferences(
+        subscriber_id,
+        &preferences_form(&subscriber, &[("email", "ursula@example.com")]),
+    )
+    .await
+    .error_for_status()
+    .unwrap();
+    let email_request = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let links = app.get_confirmation_links(&email_request);
+
+    // Act
+    reqwest::get(links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // Assert
+    let notification = app
+        .email_server
+        .received_requests()
+        .await
+        .unwrap()
+        .pop()
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&notification.body).unwrap();
+    assert_eq!(body["to"][0]["email"], subscriber.email.as_str());
+    assert!(body["text"]
+        .as_str()
+        .unwrap()
+        .contains("to ursula@example.com instead of this address"));
+}
+
+#[tokio::test]
+async fn an_email_change_keeps_the_history_of_the_subscriber() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = create_confirmed_subscriber(&app).await;
+    let subscriber = get_subscriber(&app, subscriber_id).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    // Two changes are asked for, only the last one is confirmed.
+    for email in ["first@example.com", "second@example.com"] {
+        app.post_preferences(
+            subscriber_id,
+            &preferences_form(&subscriber, &[("email", email)]),
+        )
+        .await
+        .error_for_status()
+        .unwrap();
+    }
+    let requests = app.email_server.received_requests().await.unwrap();
+    let first_links = app.get_confirmation_links(&requests[requests.len() - 2]);
+    let second_links = app.get_confirmation_links(&requests[requests.len() - 1]);
+
+    // Act
+    reqwest::get(second_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // Assert
+    let response = reqwest::get(first_links.html).await.unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+    let subscriber = get_subscriber(&app, subscriber_id).await;
+    assert_eq!(subscriber.email, "second@example.com");
+    assert_eq!(subscriber.status, SubscriptionStatus::Confirmed);
+    let events: Vec<_> = ConsentEvents::find()
+        .filter(consent_events::Column::SubscriberId.eq(subscriber_id))
+        .order_by_asc(consent_events::Column::OccurredAt)
+        .all(&app.dp_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|event| event.kind)
+        .collect();
+    assert_eq!(
+        events,
+        [
+            "signed_up",
+            "confirmation_sent",
+            "confirmed",
+            "email_changed"
+        ]
+    );
+}
+
+#[tokio::test]
+async fn changing_to_a_subscribed_address_sends_nothing() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = create_confirmed_subscriber(&app).await;
+    let other_subscriber_id = create_confirmed_subscriber(&app).await;
+    let subscriber = get_subscriber(&app, subscriber_id).await;
+    let other_subscriber = get_subscriber(&app, other_subscriber_id).await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_preferences(
+            subscriber_id,
+            &preferences_form(&subscriber, &[("email", &other_subscriber.email)]),
+        )
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.text().await.unwrap().contains(&format!(
+        "We sent a confirmation link to {}.",
+        other_subscriber.email
+    )));
+    assert!(EmailChangeRequests::find()
+        .filter(email_change_requests::Column::SubscriberId.eq(subscriber_id))
+        .one(&app.dp_pool)
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn an_expired_email_change_link_is_rejected_with_a_410() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = create_confirmed_subscriber(&app).await;
+    let subscriber = get_subscriber(&app, subscriber_id).await;
+    let now = OffsetDateTime::now_utc();
+    email_change_requests::ActiveModel {
+        token: Set("expired-token".to_owned()),
+        subscriber_id: Set(subscriber_id),
+        new_email: Set("ursula@example.com".to_owned()),
+        created_at: Set(now - Duration::days(2)),
+        expires_at: Set(now - Duration::days(1)),
+        consumed_at: Set(None),
+    }
+    .insert(&app.dp_pool)
+    .await
+    .unwrap();
+
+    // Act
+    let response = reqwest::get(format!(
+        "{}/preferences/email?token=expired-token",
+        app.address
+    ))
+    .await
+    .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 410);
+    assert_eq!(
+        get_subscriber(&app, subscriber_id).await.email,
+        subscriber.email
+    );
+}
+
+#[tokio::test]
+async fn issues_wait_for_the_digest_of_the_subscriber() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = create_confirmed_subscriber(&app).await;
+    let subscriber = get_subscriber(&app, subscriber_id).await;
+    app.post_preferences(
+        subscriber_id,
+        &preferences_form(&subscriber, &[("frequency", "daily")]),
+    )
+    .await
+    .error_for_status()
+    .unwrap();
+    app.login().await;
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_newsletters([
+            ("title", "Newsletter title"),
+            ("content_txt", "Newsletter body as plain text"),
+            ("content_html", "<p>Newsletter body as HTML</p>"),
+            ("idempotency_key", &Uuid::new_v4().to_string()),
+        ])
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let task = IssueDeliveryQueue::find()
+        .one(&app.dp_pool)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(task.subscriber_email, subscriber.email);
+    assert!(task.execute_after > OffsetDateTime::now_utc());
+}
+
+#[tokio::test]
+async fn due_issues_are_sent_as_a_single_digest() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = create_confirmed_subscriber(&app).await;
+    let subscriber = get_subscriber(&app, subscriber_id).await;
+    app.post_preferences(
+        subscriber_id,
+        &preferences_form(&subscriber, &[("frequency", "weekly")]),
+    )
+    .await
+    .error_for_status()
+    .unwrap();
+    app.login().await;
+    for title in ["First issue", "Second issue"] {
+        let response = app
+            .post_newsletters([
+                ("title", title),
+                ("content_txt", &format!("{} as plain text", title)),
+                ("content_html", &format!("<p>{} as HTML</p>", title)),
+                ("idempotency_key", &Uuid::new_v4().to_string()),
+            ])
+            .await;
+        assert_is_redirect_to(&response, "/admin/newsletters");
+    }
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Nothing is sent before the slot
+    app.dispatch_all_pending_emails().await;
+    assert_eq!(
+        IssueDeliveryQueue::find()
+            .count(&app.dp_pool)
+            .await
+            .unwrap(),
+        2
+    );
+
+    // Act - Part 2 - The slot has come
+    IssueDeliveryQueue::update_many()
+        .col_expr(
+            issue_delivery_queue::Column::ExecuteAfter,
+            Expr::value(OffsetDateTime::now_utc() - Duration::minutes(1)),
+        )
+        .exec(&app.dp_pool)
+        .await
+        .unwrap();
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    // After the confirmation email
+    let email_requests = app.email_server.received_requests().await.unwrap();
+    assert_eq!(email_requests.len(), 2);
+    let body: serde_json::Value = serde_json::from_slice(&email_requests[1].body).unwrap();
+    assert_eq!(body["subject"], "Your weekly digest");
+    for part in [
+        body["html"].as_str().unwrap(),
+        body["text"].as_str().unwrap(),
+    ] {
+        let first = part.find("First issue as").unwrap();
+        let second = part.find("Second issue as").unwrap();
+        assert!(first < second);
+    }
+    assert_eq!(
+        IssueDeliveryQueue::find()
+            .count(&app.dp_pool)
+            .await
+            .unwrap(),
+        0
+    );
+}
+
+#[tokio::test]
+async fn subscribers_without_a_digest_get_every_issue_separately() {
+    // Arrange
+    let app = spawn_app().await;
+    create_confirmed_subscriber(&app).await;
+    app.login().await;
+    // Both issues are due at once, they go out in a batch.
+    Mock::given(path("/email/batch"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200).set_body_json(serde_json::json!([
+            { "error_code": 0 },
+            { "error_code": 0 }
+        ])))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    for title in ["First issue", "Second issue"] {
+        app.post_newsletters([
+            ("title", title),
+            ("content_txt", "Newsletter body as plain text"),
+            ("content_html", "<p>Newsletter body as HTML</p>"),
+            ("idempotency_key", &Uuid::new_v4().to_string()),
+        ])
+        .await;
+    }
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    let email_requests = app.email_server.received_requests().await.unwrap();
+    // After the confirmation email
+    let body: Vec<serde_json::Value> = serde_json::from_slice(&email_requests[1].body).unwrap();
+    let mut subjects: Vec<&str> = body
+        .iter()
+        .map(|email| email["subject"].as_str().unwrap())
+        .collect();
+    subjects.sort();
+    assert_eq!(subjects, ["First issue", "Second issue"]);
+}
@@ -0,0 +1,134 @@
+use entity::{
+    subscription_tokens::{self, Entity as SubscriptionTokens},
+    subscriptions::Entity as Subscriptions,
+};
+use sea_orm::{sea_query::Expr, ColumnTrait, EntityTrait, QueryFilter};
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+use zero2prod::subscription_sweeper::SweepOutcome;
+
+use crate::helpers::{create_confirmed_subscriber, spawn_app, TestApp};
+
+async fn create_pending_subscriber(app: &TestApp) -> Uuid {
+    let email = format!("{}@example.com", Uuid::new_v4());
+    app.post_subscriptions(format!(
+        "name=le%20guin&email={}",
+        urlencoding::encode(&email)
+    ))
+    .await
+    .error_for_status()
+    .unwrap();
+    Subscriptions::find()
+        .filter(entity::subscriptions::Column::Email.eq(email))
+        .one(&app.dp_pool)
+        .await
+        .unwrap()
+        .unwrap()
+        .id
+}
+
+async fn expire_tokens_of(app: &TestApp, subscriber_id: Uuid) {
+    SubscriptionTokens::update_many()
+        .col_expr(
+            subscription_tokens::Column::ExpiresAt,
+            Expr::value(OffsetDateTime::now_utc() - Duration::minutes(1)),
+        )
+        .filter(subscription_tokens::Column::SubscriberId.eq(subscriber_id))
+        .exec(&app.dp_pool)
+        .await
+        .unwrap();
+}
+
+#[tokio::test]
+async fn the_sweeper_purges_expired_tokens_and_abandoned_subscriptions() {
+    // Arrange
+    let app = spawn_app().await;
+    let confirmed_id = create_confirmed_subscriber(&app).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let abandoned_id = create_pending_subscriber(&app).await;
+    let pending_id = create_pending_subscriber(&app).await;
+    expire_tokens_of(&app, abandoned_id).await;
+
+    // Act
+    let outcome = app.sweep_stale_subscriptions().await;
+
+    // Assert
+    assert_eq!(
+        outcome,
+        SweepOutcome {
+            n_expired_tokens: 1,
+            n_abandoned_subscriptions: 1,
+        }
+    );
+    let remaining: Vec<_> = Subscriptions::find()
+        .all(&app.dp_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|s| s.id)
+        .collect();
+    assert!(remaining.contains(&confirmed_id));
+    assert!(remaining.contains(&pending_id));
+    assert!(!remaining.contains(&abandoned_id));
+    let remaining_token_owners: Vec<_> = SubscriptionTokens::find()
+        .all(&app.dp_pool)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|t| t.subscriber_id)
+        .collect();
+    assert!(remaining_token_owners.contains(&pending_id));
+    assert!(!remaining_token_owners.contains(&abandoned_id));
+}
+
+#[tokio::test]
+async fn a_pending_subscriber_with_one_valid_token_left_is_kept() {
+    // Arrange
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    let subscriber_id = create_pending_subscriber(&app).await;
+    expire_tokens_of(&app, subscriber_id).await;
+    // Subscribing again issues a fresh token
+    let email = Subscriptions::find_by_id(subscriber_id)
+        .one(&app.dp_pool)
+        .await
+        .unwrap()
+        .unwrap()
+        .email;
+    app.post_subscriptions(format!(
+        "name=le%20guin&email={}",
+        urlencoding::encode(&email)
+    ))
+    .await
+    .error_for_status()
+    .unwrap();
+
+    // Act
+    let outcome = app.sweep_stale_subscriptions().await;
+
+    // Assert
+    assert_eq!(
+        outcome,
+        SweepOutcome {
+            n_expired_tokens: 1,
+            n_abandoned_subscriptions: 0,
+        }
+    );
+    let saved = Subscriptions::find_by_id(subscriber_id)
+        .one(&app.dp_pool)
+        .await
+        .unwrap();
+    assert!(saved.is_some());
+}
@@ -1,11 +1,14 @@
+use entity::sea_orm_active_enums::SubscriptionStatus;
 use entity::subscriptions::Entity as Subscriptions;
-use sea_orm::{ConnectionTrait, DerivePartialModel, EntityTrait, FromQueryResult, Statement};
+use sea_orm::{
+    ConnectionTrait, DerivePartialModel, EntityTrait, FromQueryResult, PaginatorTrait, Statement,
+};
 use wiremock::{
     matchers::{method, path},
     Mock, ResponseTemplate,
 };
 
-use crate::helpers::spawn_app;
+use crate::helpers::{self, spawn_app};
 
 #[tokio::test]
 async fn subscribe_returns_a_200_for_valid_form_data() {
@@ -44,7 +47,7 @@ async fn subscribe_persists_the_new_subscriber() {
     struct SubscriptionsNameEmail {
         name: String,
         email: String,
-        status: String,
+        status: SubscriptionStatus,
     }
 
     let saved = Subscriptions::find()
@@ -56,7 +59,7 @@ async fn subscribe_persists_the_new_subscriber() {
 
     assert_eq!(saved.name, "le guin");
     assert_eq!(saved.email, "ursula_le_guin@gmail.com");
-    assert_eq!(saved.status, "pending_confirmation");
+    assert_eq!(saved.status, SubscriptionStatus::PendingConfirmation);
 }
 
 #[tokio::test]
@@ -123,6 +126,33 @@ async fn subscribe_sends_a_confirmation_email_for_valid_data() {
     // Mock asserts on drop
 }
 
+#[tokio::test]
+async fn subscribe_survives_a_transient_email_provider_failure() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(503).insert_header("Retry-After", "0"))
+        .up_to_n_times(1)
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app.post_subscriptions(body.into()).await;
+
+    // Assert
+    assert_eq!(200, response.status().as_u16());
+}
+
 #[tokio::test]
 async fn subscribe_sends_a_confirmation_email_with_a_link() {
     // Arrange
@@ -164,3 +194,176 @@ async fn subscribe_fails_if_there_is_a_fatal_database_error() {
     // Assert
     assert_eq!(response.status().as_u16(), 500);
 }
+
+#[tokio::test]
+async fn subscribing_again_while_pending_resends_the_confirmation_email() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(2)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let first_response = app.post_subscriptions(body.into()).await;
+    let second_response = app.post_subscriptions(body.into()).await;
+
+    // Assert
+    assert_eq!(first_response.status().as_u16(), 200);
+    assert_eq!(second_response.status().as_u16(), 200);
+    let n_subscribers = Subscriptions::find().count(&app.dp_pool).await.unwrap();
+    assert_eq!(n_subscribers, 1);
+    let email_requests = app.email_server.received_requests().await.unwrap();
+    let first_link = app.get_confirmation_links(&email_requests[0]).html;
+    let second_link = app.get_confirmation_links(&email_requests[1]).html;
+    assert_ne!(first_link, second_link);
+    // The fresh link confirms the subscription
+    let response = reqwest::get(second_link).await.unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = Subscriptions::find()
+        .one(&app.dp_pool)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(saved.status, SubscriptionStatus::Confirmed);
+}
+
+#[tokio::test]
+async fn addresses_differing_only_in_case_cannot_be_stored_twice() {
+    // Arrange
+    let app = spawn_app().await;
+    app.dp_pool
+        .execute_unprepared(
+            "INSERT INTO subscriptions (id, email, name, subscribed_at, status) \
+            VALUES (gen_random_uuid(), 'ursula@example.com', 'Ursula', now(), 'confirmed')",
+        )
+        .await
+        .unwrap();
+
+    // Act
+    let outcome = app
+        .dp_pool
+        .execute_unprepared(
+            "INSERT INTO subscriptions (id, email, name, subscribed_at, status) \
+            VALUES (gen_random_uuid(), 'Ursula@Example.com', 'Ursula', now(), 'confirmed')",
+        )
+        .await;
+
+    // Assert
+    assert!(outcome.is_err());
+}
+
+#[tokio::test]
+async fn subscribing_again_does_not_change_the_name_of_the_subscriber() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = helpers::create_confirmed_subscriber(&app).await;
+    let subscriber = Subscriptions::find_by_id(subscriber_id)
+        .one(&app.dp_pool)
+        .await
+        .unwrap()
+        .unwrap();
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .mount(&app.email_server)
+        .await;
+    app.login().await;
+    app.post_list("release-notes", "Release notes").await;
+
+    // Act
+    let response = app
+        .post_subscriptions(format!(
+            "name=mallory&email={}&list=release-notes",
+            urlencoding::encode(&subscriber.email)
+        ))
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = Subscriptions::find_by_id(subscriber_id)
+        .one(&app.dp_pool)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(saved.name, subscriber.name);
+}
+
+#[tokio::test]
+async fn subscribing_again_once_confirmed_gets_a_neutral_response() {
+    // Arrange
+    let app = spawn_app().await;
+    let body = "name=le%20guin&email=ursula_le_guin%40gmail.com";
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+    app.post_subscriptions(body.into()).await;
+    let email_request = &app.email_server.received_requests().await.unwrap()[0];
+    let confirmation_links = app.get_confirmation_links(email_request);
+    reqwest::get(confirmation_links.html)
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+
+    // Act
+    let response = app.post_subscriptions(body.into()).await;
+
+    // Assert
+    // Same as for a new address, and no second email is sent
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = Subscriptions::find()
+        .one(&app.dp_pool)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(saved.status, SubscriptionStatus::Confirmed);
+}
+
+#[tokio::test]
+async fn subscribing_again_after_unsubscribing_asks_for_a_new_confirmation() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = helpers::create_confirmed_subscriber(&app).await;
+    let subscriber = Subscriptions::find_by_id(subscriber_id)
+        .one(&app.dp_pool)
+        .await
+        .unwrap()
+        .unwrap();
+    reqwest::Client::new()
+        .post(app.get_unsubscribe_link(subscriber_id))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app
+        .post_subscriptions(format!(
+            "name=le%20guin&email={}",
+            urlencoding::encode(&subscriber.email)
+        ))
+        .await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let saved = Subscriptions::find_by_id(subscriber_id)
+        .one(&app.dp_pool)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(saved.status, SubscriptionStatus::PendingConfirmation);
+}
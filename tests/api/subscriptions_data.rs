@@ -0,0 +1,311 @@
+use entity::{
+    consent_events::Entity as ConsentEvents,
+    issue_delivery_queue::Entity as IssueDeliveryQueue,
+    subscription_tokens::Entity as SubscriptionTokens,
+    subscriptions::{self, Entity as Subscriptions},
+};
+use sea_orm::{EntityTrait, PaginatorTrait};
+use time::{Duration, OffsetDateTime};
+use uuid::Uuid;
+use wiremock::{
+    matchers::{method, path},
+    Mock, ResponseTemplate,
+};
+use zero2prod::subscriber_data::{erase_subscriber, is_erased};
+
+use crate::helpers::{assert_is_redirect_to, create_confirmed_subscriber, spawn_app, TestApp};
+
+async fn get_subscriber(app: &TestApp, subscriber_id: Uuid) -> subscriptions::Model {
+    Subscriptions::find_by_id(subscriber_id)
+        .one(&app.dp_pool)
+        .await
+        .unwrap()
+        .unwrap()
+}
+
+fn with_path(link: &reqwest::Url, path: &str) -> reqwest::Url {
+    let mut link = link.clone();
+    link.set_path(path);
+    link
+}
+
+#[tokio::test]
+async fn a_data_request_emails_a_link_to_the_data_management_page() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = create_confirmed_subscriber(&app).await;
+    let subscriber = get_subscriber(&app, subscriber_id).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Ask for the link
+    let response = app.post_data_request(&subscriber.email).await;
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Act - Part 2 - Follow it
+    // The confirmation of the subscriber came first.
+    let email_request = app.wait_for_emails(2).await.pop().unwrap();
+    let links = app.get_confirmation_links(&email_request);
+    assert_eq!(links.html, links.plain_text);
+    assert_eq!(links.html.path(), "/subscriptions/data/manage");
+    let response = reqwest::get(links.html).await.unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains(&subscriber.email));
+    assert!(html_page.contains("/subscriptions/data/export?"));
+    assert!(html_page.contains("/subscriptions/data/erase?"));
+}
+
+#[tokio::test]
+async fn a_data_request_for_an_unknown_address_looks_the_same_but_sends_nothing() {
+    // Arrange
+    let app = spawn_app().await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app.post_data_request("nobody@example.com").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("If the address is subscribed"));
+}
+
+#[tokio::test]
+async fn a_data_request_looks_the_same_when_the_email_cannot_be_sent() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = create_confirmed_subscriber(&app).await;
+    let subscriber = get_subscriber(&app, subscriber_id).await;
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(500))
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    let response = app.post_data_request(&subscriber.email).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response
+        .text()
+        .await
+        .unwrap()
+        .contains("If the address is subscribed"));
+}
+
+#[tokio::test]
+async fn a_data_request_with_an_invalid_address_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let response = app.post_data_request("definitely-not-an-email").await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 400);
+}
+
+#[tokio::test]
+async fn subscribers_can_download_their_data() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = create_confirmed_subscriber(&app).await;
+    let subscriber = get_subscriber(&app, subscriber_id).await;
+    let link = app.get_data_request_link(
+        subscriber_id,
+        OffsetDateTime::now_utc() + Duration::hours(1),
+    );
+
+    // Act
+    let response = reqwest::get(with_path(&link, "/subscriptions/data/export"))
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert!(response.headers()["Content-Disposition"]
+        .to_str()
+        .unwrap()
+        .starts_with("attachment"));
+    let data: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(data["subscriber"]["id"], subscriber_id.to_string());
+    assert_eq!(data["subscriber"]["email"], subscriber.email);
+    assert_eq!(data["subscriber"]["status"], "confirmed");
+    let kinds: Vec<_> = data["consent_events"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|event| event["event"].as_str().unwrap())
+        .collect();
+    assert_eq!(kinds, ["signed_up", "confirmation_sent", "confirmed"]);
+    let confirmation_links = data["confirmation_links"].as_array().unwrap();
+    assert_eq!(confirmation_links.len(), 1);
+    assert!(confirmation_links[0].get("subscription_token").is_none());
+    assert!(!confirmation_links[0]["consumed_at"].is_null());
+}
+
+#[tokio::test]
+async fn erasure_removes_everything_but_a_tombstone() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = create_confirmed_subscriber(&app).await;
+    let subscriber = get_subscriber(&app, subscriber_id).await;
+    app.login().await;
+    let response = app
+        .post_newsletters(serde_json::json!({
+            "title": "Newsletter title",
+            "content_txt": "Newsletter body as plain text",
+            "content_html": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": Uuid::new_v4().to_string()
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/admin/newsletters");
+    assert_eq!(
+        IssueDeliveryQueue::find()
+            .count(&app.dp_pool)
+            .await
+            .unwrap(),
+        1
+    );
+    let link = app.get_data_request_link(
+        subscriber_id,
+        OffsetDateTime::now_utc() + Duration::hours(1),
+    );
+
+    // Act - Part 1 - Erase
+    let response = reqwest::Client::new()
+        .post(with_path(&link, "/subscriptions/data/erase"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status().as_u16(), 200);
+
+    // Assert
+    assert!(Subscriptions::find_by_id(subscriber_id)
+        .one(&app.dp_pool)
+        .await
+        .unwrap()
+        .is_none());
+    assert_eq!(
+        SubscriptionTokens::find()
+            .count(&app.dp_pool)
+            .await
+            .unwrap(),
+        0
+    );
+    assert_eq!(ConsentEvents::find().count(&app.dp_pool).await.unwrap(), 0);
+    assert_eq!(
+        IssueDeliveryQueue::find()
+            .count(&app.dp_pool)
+            .await
+            .unwrap(),
+        0
+    );
+    assert!(is_erased(&app.dp_pool, &subscriber.email).await.unwrap());
+
+    // Act - Part 2 - The link is now dead
+    let response = reqwest::get(link).await.unwrap();
+    assert_eq!(response.status().as_u16(), 401);
+}
+
+#[tokio::test]
+async fn an_erased_subscriber_can_subscribe_again_with_a_confirmation() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = create_confirmed_subscriber(&app).await;
+    let subscriber = get_subscriber(&app, subscriber_id).await;
+    assert!(erase_subscriber(&app.dp_pool, subscriber_id).await.unwrap());
+    Mock::given(path("/email"))
+        .and(method("POST"))
+        .respond_with(ResponseTemplate::new(200))
+        .expect(1)
+        .mount(&app.email_server)
+        .await;
+
+    // Act - Part 1 - Subscribe again
+    let response = app
+        .post_subscriptions(format!(
+            "name=le%20guin&email={}",
+            urlencoding::encode(&subscriber.email)
+        ))
+        .await;
+    assert_eq!(response.status().as_u16(), 200);
+    // Nothing changes until the owner of the address confirms
+    assert!(is_erased(&app.dp_pool, &subscriber.email).await.unwrap());
+
+    // Act - Part 2 - Confirm
+    let email_requests = app.email_server.received_requests().await.unwrap();
+    reqwest::get(
+        app.get_confirmation_links(email_requests.last().unwrap())
+            .html,
+    )
+    .await
+    .unwrap()
+    .error_for_status()
+    .unwrap();
+
+    // Assert
+    assert!(!is_erased(&app.dp_pool, &subscriber.email).await.unwrap());
+}
+
+#[tokio::test]
+async fn expired_or_tampered_data_request_links_are_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = create_confirmed_subscriber(&app).await;
+    let expired_link = app.get_data_request_link(
+        subscriber_id,
+        OffsetDateTime::now_utc() - Duration::minutes(1),
+    );
+    let mut tampered_link = app.get_data_request_link(
+        subscriber_id,
+        OffsetDateTime::now_utc() + Duration::hours(1),
+    );
+    let tampered_query = tampered_link
+        .query()
+        .unwrap()
+        .replace(&subscriber_id.to_string(), &Uuid::new_v4().to_string());
+    tampered_link.set_query(Some(&tampered_query));
+    let client = reqwest::Client::new();
+
+    for link in [expired_link, tampered_link] {
+        // Act
+        let manage = client.get(link.clone()).send().await.unwrap();
+        let export = client
+            .get(with_path(&link, "/subscriptions/data/export"))
+            .send()
+            .await
+            .unwrap();
+        let erase = client
+            .post(with_path(&link, "/subscriptions/data/erase"))
+            .send()
+            .await
+            .unwrap();
+
+        // Assert
+        assert_eq!(manage.status().as_u16(), 401);
+        assert_eq!(export.status().as_u16(), 401);
+        assert_eq!(erase.status().as_u16(), 401);
+    }
+    assert!(Subscriptions::find_by_id(subscriber_id)
+        .one(&app.dp_pool)
+        .await
+        .unwrap()
+        .is_some());
+}
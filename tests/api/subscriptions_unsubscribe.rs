@@ -0,0 +1,181 @@
+use entity::sea_orm_active_enums::SubscriptionStatus;
+use entity::subscriptions::Entity as Subscriptions;
+use sea_orm::EntityTrait;
+use uuid::Uuid;
+use wiremock::matchers::any;
+use wiremock::{Mock, ResponseTemplate};
+
+use crate::helpers::{create_confirmed_subscriber, spawn_app, TestApp};
+
+#[tokio::test]
+async fn unsubscribe_links_with_an_invalid_signature_are_rejected_with_a_401() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = create_confirmed_subscriber(&app).await;
+    let mut unsubscribe_link = app.get_unsubscribe_link(subscriber_id);
+    unsubscribe_link.set_query(Some(&format!(
+        "subscriber_id={}&signature=deadbeef",
+        subscriber_id
+    )));
+
+    // Act
+    let get_response = reqwest::get(unsubscribe_link.clone()).await.unwrap();
+    let post_response = reqwest::Client::new()
+        .post(unsubscribe_link)
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(get_response.status().as_u16(), 401);
+    assert_eq!(post_response.status().as_u16(), 401);
+    assert_eq!(
+        subscriber_status(&app, subscriber_id).await,
+        SubscriptionStatus::Confirmed
+    );
+}
+
+#[tokio::test]
+async fn a_signature_for_another_subscriber_is_rejected() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = create_confirmed_subscriber(&app).await;
+    let mut unsubscribe_link = app.get_unsubscribe_link(Uuid::new_v4());
+    let signature = unsubscribe_link
+        .query_pairs()
+        .find(|(k, _)| k == "signature")
+        .unwrap()
+        .1
+        .into_owned();
+    unsubscribe_link.set_query(Some(&format!(
+        "subscriber_id={}&signature={}",
+        subscriber_id, signature
+    )));
+
+    // Act
+    let response = reqwest::Client::new()
+        .post(unsubscribe_link)
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 401);
+    assert_eq!(
+        subscriber_status(&app, subscriber_id).await,
+        SubscriptionStatus::Confirmed
+    );
+}
+
+#[tokio::test]
+async fn following_the_unsubscribe_link_asks_for_a_confirmation() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = create_confirmed_subscriber(&app).await;
+
+    // Act
+    let response = reqwest::get(app.get_unsubscribe_link(subscriber_id))
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    assert!(html_page.contains(r#"method="post""#));
+    assert_eq!(
+        subscriber_status(&app, subscriber_id).await,
+        SubscriptionStatus::Confirmed
+    );
+}
+
+#[tokio::test]
+async fn one_click_unsubscribe_marks_the_subscriber_as_unsubscribed() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = create_confirmed_subscriber(&app).await;
+
+    // Act
+    let response = reqwest::Client::new()
+        .post(app.get_unsubscribe_link(subscriber_id))
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body("List-Unsubscribe=One-Click")
+        .send()
+        .await
+        .unwrap();
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+    assert_eq!(
+        subscriber_status(&app, subscriber_id).await,
+        SubscriptionStatus::Unsubscribed
+    );
+}
+
+#[tokio::test]
+async fn unsubscribing_twice_is_not_an_error() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = create_confirmed_subscriber(&app).await;
+    let client = reqwest::Client::new();
+
+    // Act
+    for _ in 0..2 {
+        let response = client
+            .post(app.get_unsubscribe_link(subscriber_id))
+            .send()
+            .await
+            .unwrap();
+
+        // Assert
+        assert_eq!(response.status().as_u16(), 200);
+    }
+    assert_eq!(
+        subscriber_status(&app, subscriber_id).await,
+        SubscriptionStatus::Unsubscribed
+    );
+}
+
+#[tokio::test]
+async fn queued_deliveries_are_skipped_after_unsubscribing() {
+    // Arrange
+    let app = spawn_app().await;
+    let subscriber_id = create_confirmed_subscriber(&app).await;
+    app.login().await;
+    let response = app
+        .post_newsletters(serde_json::json!({
+            "title": "Newsletter title",
+            "content_txt": "Newsletter body as plain text",
+            "content_html": "<p>Newsletter body as HTML</p>",
+            "idempotency_key": Uuid::new_v4().to_string()
+        }))
+        .await;
+    assert_eq!(response.status().as_u16(), 303);
+
+    Mock::given(any())
+        .respond_with(ResponseTemplate::new(200))
+        .expect(0)
+        .mount(&app.email_server)
+        .await;
+
+    // Act
+    reqwest::Client::new()
+        .post(app.get_unsubscribe_link(subscriber_id))
+        .send()
+        .await
+        .unwrap()
+        .error_for_status()
+        .unwrap();
+    app.dispatch_all_pending_emails().await;
+
+    // Assert
+    // Mock verifies on Drop that the queued issue has not been sent
+}
+
+async fn subscriber_status(app: &TestApp, subscriber_id: Uuid) -> SubscriptionStatus {
+    Subscriptions::find_by_id(subscriber_id)
+        .one(&app.dp_pool)
+        .await
+        .unwrap()
+        .unwrap()
+        .status
+}
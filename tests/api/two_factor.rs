@@ -0,0 +1,279 @@
+use entity::users::Entity as Users;
+use sea_orm::EntityTrait;
+use secrecy::Secret;
+use time::OffsetDateTime;
+use zero2prod::authentication::{totp_code, totp_step};
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp};
+
+async fn post_admin(app: &TestApp, path: &str, code: &str) -> reqwest::Response {
+    app.api_client
+        .post(format!("{}/admin/two-factor{}", &app.address, path))
+        .form(&[("code", code)])
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn post_login_code(app: &TestApp, code: &str) -> reqwest::Response {
+    app.api_client
+        .post(format!("{}/login/two-factor", &app.address))
+        .form(&[("code", code)])
+        .send()
+        .await
+        .expect("Failed to execute request.")
+}
+
+async fn get_two_factor_settings_html(app: &TestApp) -> String {
+    app.api_client
+        .get(format!("{}/admin/two-factor", &app.address))
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap()
+}
+
+async fn stored_secret(app: &TestApp) -> Secret<String> {
+    let user = Users::find_by_id(app.test_user.user_id)
+        .one(&app.dp_pool)
+        .await
+        .unwrap()
+        .unwrap();
+    Secret::new(user.totp_secret.unwrap())
+}
+
+/// The code the authenticator shows `offset` steps from now. Every step can
+/// only be used once, so tests move on to the next one after each login.
+fn code(secret: &Secret<String>, offset: i64) -> String {
+    totp_code(secret, totp_step(OffsetDateTime::now_utc()) + offset).unwrap()
+}
+
+/// Turns two-factor authentication on for the test user, returns the secret
+/// and the recovery codes. The user is still logged in afterwards.
+async fn enrol(app: &TestApp) -> (Secret<String>, Vec<String>) {
+    app.login().await;
+    let response = post_admin(app, "/setup", "").await;
+    assert_is_redirect_to(&response, "/admin/two-factor");
+    let secret = stored_secret(app).await;
+    let response = post_admin(app, "/confirm", &code(&secret, 0)).await;
+    assert_eq!(response.status().as_u16(), 200);
+    let html_page = response.text().await.unwrap();
+    let recovery_codes = html_page
+        .split("<li><code>")
+        .skip(1)
+        .map(|s| s.split("</code>").next().unwrap().to_owned())
+        .collect();
+    (secret, recovery_codes)
+}
+
+async fn login_with_password(app: &TestApp) -> reqwest::Response {
+    app.post_login(&serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password
+    }))
+    .await
+}
+
+#[tokio::test]
+async fn the_setup_page_shows_the_key_to_scan() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+
+    // Act
+    post_admin(&app, "/setup", "").await;
+    let html_page = get_two_factor_settings_html(&app).await;
+
+    // Assert
+    assert!(html_page.contains("<svg"));
+    assert!(html_page.contains(&format!(
+        "otpauth://totp/zero2prod:{}?secret=",
+        app.test_user.username
+    )));
+    let user = Users::find_by_id(app.test_user.user_id)
+        .one(&app.dp_pool)
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(user.totp_enabled_at.is_none());
+}
+
+#[tokio::test]
+async fn a_wrong_code_does_not_turn_two_factor_authentication_on() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+    post_admin(&app, "/setup", "").await;
+
+    // Act
+    let response = post_admin(&app, "/confirm", "000000").await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/two-factor");
+    let html_page = get_two_factor_settings_html(&app).await;
+    assert!(html_page.contains("The code is not valid."));
+    app.logout().await;
+    let response = login_with_password(&app).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
+
+#[tokio::test]
+async fn enrolment_hands_out_recovery_codes() {
+    // Arrange
+    let app = spawn_app().await;
+
+    // Act
+    let (_, recovery_codes) = enrol(&app).await;
+
+    // Assert
+    assert_eq!(recovery_codes.len(), 10);
+    let html_page = get_two_factor_settings_html(&app).await;
+    assert!(html_page.contains("You have 10\n      unused recovery codes left."));
+}
+
+#[tokio::test]
+async fn logging_in_takes_a_code_after_the_password() {
+    // Arrange
+    let app = spawn_app().await;
+    let (secret, _) = enrol(&app).await;
+    app.logout().await;
+
+    // Act - Part 1 - Password
+    let response = login_with_password(&app).await;
+    assert_is_redirect_to(&response, "/login/two-factor");
+
+    // Act - Part 2 - The dashboard is still out of reach
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+
+    // Act - Part 3 - Code
+    let response = post_login_code(&app, &code(&secret, 1)).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    let response = app.get_admin_dashboard().await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn a_code_cannot_be_used_twice() {
+    // Arrange
+    let app = spawn_app().await;
+    let (secret, _) = enrol(&app).await;
+    app.logout().await;
+    let used_code = code(&secret, 1);
+    login_with_password(&app).await;
+    post_login_code(&app, &used_code).await;
+    app.logout().await;
+
+    // Act
+    login_with_password(&app).await;
+    let response = post_login_code(&app, &used_code).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login/two-factor");
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn a_recovery_code_works_once() {
+    // Arrange
+    let app = spawn_app().await;
+    let (_, recovery_codes) = enrol(&app).await;
+    app.logout().await;
+
+    // Act - Part 1 - Use it
+    login_with_password(&app).await;
+    let response = post_login_code(&app, &recovery_codes[0]).await;
+    assert_is_redirect_to(&response, "/admin/dashboard");
+    app.logout().await;
+
+    // Act - Part 2 - Use it again
+    login_with_password(&app).await;
+    let response = post_login_code(&app, &recovery_codes[0]).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login/two-factor");
+}
+
+#[tokio::test]
+async fn too_many_wrong_codes_require_the_password_again() {
+    // Arrange
+    let app = spawn_app().await;
+    let (secret, _) = enrol(&app).await;
+    app.logout().await;
+    login_with_password(&app).await;
+
+    // Act - Part 1 - Guess
+    for _ in 0..4 {
+        let response = post_login_code(&app, "000000").await;
+        assert_is_redirect_to(&response, "/login/two-factor");
+    }
+    let response = post_login_code(&app, "000000").await;
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(html_page
+        .contains("<p><i>Too many failed login attempts, please try again in 15 minutes.</i></p>"));
+
+    // Act - Part 2 - A right code is too late now
+    let response = post_login_code(&app, &code(&secret, 1)).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn wrong_codes_are_counted_across_password_logins() {
+    // Arrange
+    let app = spawn_app().await;
+    let (secret, _) = enrol(&app).await;
+    app.logout().await;
+    login_with_password(&app).await;
+    for _ in 0..3 {
+        post_login_code(&app, "000000").await;
+    }
+    let response = login_with_password(&app).await;
+    assert_is_redirect_to(&response, "/login/two-factor");
+    post_login_code(&app, "000000").await;
+
+    // Act - Part 1 - The fifth wrong code locks the user out
+    let response = post_login_code(&app, "000000").await;
+
+    // Assert - Part 1
+    assert_is_redirect_to(&response, "/login");
+    let html_page = app.get_login_html().await;
+    assert!(html_page.contains("Too many failed login attempts"));
+
+    // Act - Part 2 - Neither the password nor a sixth code get through
+    let response = login_with_password(&app).await;
+    assert_is_redirect_to(&response, "/login");
+    let response = post_login_code(&app, &code(&secret, 1)).await;
+
+    // Assert - Part 2
+    assert_is_redirect_to(&response, "/login");
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+}
+
+#[tokio::test]
+async fn two_factor_authentication_can_be_turned_off() {
+    // Arrange
+    let app = spawn_app().await;
+    let (secret, _) = enrol(&app).await;
+
+    // Act - Part 1 - Turn it off
+    let response = post_admin(&app, "/disable", &code(&secret, 1)).await;
+    assert_is_redirect_to(&response, "/admin/two-factor");
+    let html_page = get_two_factor_settings_html(&app).await;
+    assert!(html_page.contains("Two-factor authentication is now off."));
+
+    // Act - Part 2 - Log in with the password alone
+    app.logout().await;
+    let response = login_with_password(&app).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/dashboard");
+}
@@ -0,0 +1,218 @@
+use entity::{
+    sea_orm_active_enums::UserRole,
+    user_invitations::Entity as UserInvitations,
+    users::{self, Entity as Users},
+};
+use sea_orm::{ColumnTrait, EntityTrait, QueryFilter};
+use uuid::Uuid;
+
+use crate::helpers::{assert_is_redirect_to, spawn_app, TestApp, TestUser};
+
+async fn store_user(app: &TestApp, role: UserRole) -> TestUser {
+    let user = TestUser::with_role(role);
+    user.store(&app.dp_pool).await;
+    user
+}
+
+async fn get_user(app: &TestApp, username: &str) -> Option<users::Model> {
+    Users::find()
+        .filter(users::Column::Username.eq(username))
+        .one(&app.dp_pool)
+        .await
+        .unwrap()
+}
+
+fn newsletter_body() -> serde_json::Value {
+    serde_json::json!({
+        "title": "Newsletter title",
+        "content_txt": "Newsletter body as plain text",
+        "content_html": "<p>Newsletter body as HTML</p>",
+        "idempotency_key": Uuid::new_v4().to_string(),
+    })
+}
+
+#[tokio::test]
+async fn only_owners_can_manage_users() {
+    // Arrange
+    let app = spawn_app().await;
+    let editor = store_user(&app, UserRole::Editor).await;
+
+    // Act - Part 1 - Anonymous
+    let response = app.get_users().await;
+    assert_is_redirect_to(&response, "/login");
+
+    // Act - Part 2 - Editor
+    app.login_as(&editor).await;
+    let response = app.get_users().await;
+    assert_eq!(response.status().as_u16(), 403);
+    let response = app.post_invite_user("octavia@example.com", "owner").await;
+    assert_eq!(response.status().as_u16(), 403);
+    assert!(UserInvitations::find()
+        .one(&app.dp_pool)
+        .await
+        .unwrap()
+        .is_none());
+
+    // Act - Part 3 - Owner
+    app.logout().await;
+    app.login().await;
+    let response = app.get_users().await;
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn viewers_cannot_publish_newsletters() {
+    // Arrange
+    let app = spawn_app().await;
+    let viewer = store_user(&app, UserRole::Viewer).await;
+    app.login_as(&viewer).await;
+
+    // Act
+    let form_response = app.get_newsletter().await;
+    let response = app.post_newsletters(newsletter_body()).await;
+
+    // Assert
+    assert_eq!(form_response.status().as_u16(), 403);
+    assert_eq!(response.status().as_u16(), 403);
+    let html_page = app.get_admin_dashboard_html().await;
+    assert!(!html_page.contains("Send a newsletter issue"));
+    assert!(!html_page.contains(r#"href="/admin/users""#));
+}
+
+#[tokio::test]
+async fn editors_can_publish_newsletters() {
+    // Arrange
+    let app = spawn_app().await;
+    let editor = store_user(&app, UserRole::Editor).await;
+    app.login_as(&editor).await;
+
+    // Act
+    let response = app.post_newsletters(newsletter_body()).await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/admin/newsletters");
+}
+
+#[tokio::test]
+async fn a_changed_role_takes_effect_immediately() {
+    // Arrange
+    let app = spawn_app().await;
+    let editor = store_user(&app, UserRole::Editor).await;
+    app.login().await;
+
+    // Act - Part 1 - Demote the editor
+    let response = app
+        .post_user_action(editor.user_id, "role", &[("role", "viewer")])
+        .await;
+    assert_is_redirect_to(&response, "/admin/users");
+    let html_page = app.get_users_html().await;
+    assert!(html_page.contains(&format!("{} is now viewer.", editor.username)));
+
+    // Act - Part 2 - Try to publish
+    app.logout().await;
+    app.login_as(&editor).await;
+    let response = app.post_newsletters(newsletter_body()).await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 403);
+}
+
+#[tokio::test]
+async fn disabled_users_are_logged_out_and_cannot_log_in() {
+    // Arrange
+    let app = spawn_app().await;
+    let editor = store_user(&app, UserRole::Editor).await;
+    app.login_as(&editor).await;
+    let other_owner = TestApp::client();
+    let login_body = serde_json::json!({
+        "username": &app.test_user.username,
+        "password": &app.test_user.password
+    });
+    other_owner
+        .post(format!("{}/login", &app.address))
+        .form(&login_body)
+        .send()
+        .await
+        .unwrap();
+
+    // Act - Part 1 - Disable the editor while they are logged in
+    let response = other_owner
+        .post(format!(
+            "{}/admin/users/{}/disable",
+            &app.address, editor.user_id
+        ))
+        .send()
+        .await
+        .unwrap();
+    assert_is_redirect_to(&response, "/admin/users");
+
+    // Act - Part 2 - Their session stops working
+    let response = app.get_admin_dashboard().await;
+    assert_is_redirect_to(&response, "/login");
+
+    // Act - Part 3 - They cannot log in again
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": &editor.username,
+            "password": &editor.password
+        }))
+        .await;
+    assert_is_redirect_to(&response, "/login");
+
+    // Act - Part 4 - Enable them again
+    other_owner
+        .post(format!(
+            "{}/admin/users/{}/enable",
+            &app.address, editor.user_id
+        ))
+        .send()
+        .await
+        .unwrap();
+    app.login_as(&editor).await;
+    let response = app.get_admin_dashboard().await;
+
+    // Assert
+    assert_eq!(response.status().as_u16(), 200);
+}
+
+#[tokio::test]
+async fn owners_cannot_lock_themselves_out() {
+    // Arrange
+    let app = spawn_app().await;
+    app.login().await;
+
+    // Act - Part 1 - Disable
+    app.post_user_action(app.test_user.user_id, "disable", &())
+        .await;
+    let html_page = app.get_users_html().await;
+    assert!(html_page.contains("You cannot disable your own account."));
+
+    // Act - Part 2 - Demote
+    app.post_user_action(app.test_user.user_id, "role", &[("role", "viewer")])
+        .await;
+    let html_page = app.get_users_html().await;
+    assert!(html_page.contains("You cannot change your own role."));
+
+    // Assert
+    let owner = get_user(&app, &app.test_user.username).await.unwrap();
+    assert_eq!(owner.role, UserRole::Owner);
+    assert!(owner.disabled_at.is_none());
+}
+
+#[tokio::test]
+async fn a_username_must_match_exactly_to_log_in() {
+    // Arrange
+    let app = spawn_app().await;
+    let prefix: String = app.test_user.username.chars().take(8).collect();
+
+    // Act
+    let response = app
+        .post_login(&serde_json::json!({
+            "username": prefix,
+            "password": &app.test_user.password
+        }))
+        .await;
+
+    // Assert
+    assert_is_redirect_to(&response, "/login");
+}